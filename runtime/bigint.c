@@ -0,0 +1,593 @@
+/* Arbitrary-precision integers for programs compiled with `--bigint`.
+ *
+ * A value is a sign and a magnitude of 32-bit limbs, least significant
+ * first and without leading zeros. Values are never changed once built and
+ * never freed, and NULL stands for 0, which is what `mem` starts out as.
+ * The compiled program checks for everything that traps (division by zero,
+ * negative shift amounts, results too large to hold) before calling these,
+ * so they always return a result. They compute what `src/bigint.rs` does
+ * for the interpreter. */
+
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef struct {
+    int sign; /* -1, 0 or 1 */
+    size_t len;
+    uint32_t limbs[];
+} calc_big;
+
+static const calc_big zero = {0, 0};
+
+static const calc_big *arg(const calc_big *x) {
+    return x ? x : &zero;
+}
+
+static calc_big *alloc(size_t len) {
+    calc_big *x = malloc(sizeof(calc_big) + len * sizeof(uint32_t));
+    if (!x) {
+        fputs("error: out of memory\n", stderr);
+        abort();
+    }
+    x->sign = 0;
+    x->len = len;
+    memset(x->limbs, 0, len * sizeof(uint32_t));
+    return x;
+}
+
+/* drops leading zero limbs, and the sign of 0 */
+static calc_big *trim(calc_big *x, int sign) {
+    while (x->len > 0 && x->limbs[x->len - 1] == 0) {
+        x->len--;
+    }
+    x->sign = x->len == 0 ? 0 : sign;
+    return x;
+}
+
+static calc_big *with_sign(const calc_big *x, int sign) {
+    calc_big *y = alloc(x->len);
+    memcpy(y->limbs, x->limbs, x->len * sizeof(uint32_t));
+    return trim(y, sign);
+}
+
+static calc_big *from_u64(uint64_t m, int sign) {
+    calc_big *x = alloc(2);
+    x->limbs[0] = (uint32_t)m;
+    x->limbs[1] = (uint32_t)(m >> 32);
+    return trim(x, sign);
+}
+
+/* --- magnitudes --- */
+
+static int cmp_mag(const calc_big *a, const calc_big *b) {
+    if (a->len != b->len) {
+        return a->len < b->len ? -1 : 1;
+    }
+    for (size_t i = a->len; i-- > 0;) {
+        if (a->limbs[i] != b->limbs[i]) {
+            return a->limbs[i] < b->limbs[i] ? -1 : 1;
+        }
+    }
+    return 0;
+}
+
+static calc_big *add_mag(const calc_big *a, const calc_big *b, int sign) {
+    if (a->len < b->len) {
+        const calc_big *t = a;
+        a = b;
+        b = t;
+    }
+    calc_big *r = alloc(a->len + 1);
+    uint64_t carry = 0;
+    for (size_t i = 0; i < a->len; i++) {
+        carry += (uint64_t)a->limbs[i] + (i < b->len ? b->limbs[i] : 0);
+        r->limbs[i] = (uint32_t)carry;
+        carry >>= 32;
+    }
+    r->limbs[a->len] = (uint32_t)carry;
+    return trim(r, sign);
+}
+
+/* |a| - |b|, where |a| >= |b| */
+static calc_big *sub_mag(const calc_big *a, const calc_big *b, int sign) {
+    calc_big *r = alloc(a->len);
+    int64_t borrow = 0;
+    for (size_t i = 0; i < a->len; i++) {
+        int64_t t = (int64_t)a->limbs[i] - (i < b->len ? b->limbs[i] : 0) + borrow;
+        r->limbs[i] = (uint32_t)t;
+        borrow = t < 0 ? -1 : 0;
+    }
+    return trim(r, sign);
+}
+
+static calc_big *mul_mag(const calc_big *a, const calc_big *b, int sign) {
+    calc_big *r = alloc(a->len + b->len);
+    for (size_t i = 0; i < a->len; i++) {
+        uint64_t carry = 0;
+        for (size_t j = 0; j < b->len; j++) {
+            carry += (uint64_t)a->limbs[i] * b->limbs[j] + r->limbs[i + j];
+            r->limbs[i + j] = (uint32_t)carry;
+            carry >>= 32;
+        }
+        r->limbs[i + b->len] = (uint32_t)carry;
+    }
+    return trim(r, sign);
+}
+
+/* the quotient and remainder of magnitudes, by Knuth's algorithm D; both
+ * come out positive */
+static void divmod_mag(const calc_big *a, const calc_big *b, calc_big **q, calc_big **r) {
+    if (cmp_mag(a, b) < 0) {
+        *q = alloc(0);
+        *r = with_sign(a, 1);
+        return;
+    }
+    size_t n = b->len, m = a->len - n;
+    calc_big *quot = alloc(m + 1);
+    if (n == 1) {
+        uint64_t rem = 0;
+        for (size_t i = a->len; i-- > 0;) {
+            uint64_t cur = (rem << 32) | a->limbs[i];
+            if (i <= m) {
+                quot->limbs[i] = (uint32_t)(cur / b->limbs[0]);
+            }
+            rem = cur % b->limbs[0];
+        }
+        *q = trim(quot, 1);
+        *r = from_u64(rem, 1);
+        return;
+    }
+    /* shift both so that the divisor's top limb has its high bit set */
+    int s = __builtin_clz(b->limbs[n - 1]);
+    uint32_t *v = malloc(n * sizeof(uint32_t));
+    uint32_t *u = malloc((a->len + 1) * sizeof(uint32_t));
+    for (size_t i = n - 1; i > 0; i--) {
+        v[i] = (b->limbs[i] << s) | (s ? b->limbs[i - 1] >> (32 - s) : 0);
+    }
+    v[0] = b->limbs[0] << s;
+    u[a->len] = s ? a->limbs[a->len - 1] >> (32 - s) : 0;
+    for (size_t i = a->len - 1; i > 0; i--) {
+        u[i] = (a->limbs[i] << s) | (s ? a->limbs[i - 1] >> (32 - s) : 0);
+    }
+    u[0] = a->limbs[0] << s;
+    for (size_t j = m + 1; j-- > 0;) {
+        uint64_t num = ((uint64_t)u[j + n] << 32) | u[j + n - 1];
+        uint64_t qhat = num / v[n - 1], rhat = num % v[n - 1];
+        while (qhat >> 32 || qhat * v[n - 2] > ((rhat << 32) | u[j + n - 2])) {
+            qhat--;
+            rhat += v[n - 1];
+            if (rhat >> 32) {
+                break;
+            }
+        }
+        int64_t k = 0, t;
+        for (size_t i = 0; i < n; i++) {
+            uint64_t p = qhat * v[i];
+            t = (int64_t)u[i + j] - k - (int64_t)(p & 0xFFFFFFFF);
+            u[i + j] = (uint32_t)t;
+            k = (int64_t)(p >> 32) - (t >> 32);
+        }
+        t = (int64_t)u[j + n] - k;
+        u[j + n] = (uint32_t)t;
+        /* the estimate was one too large, add the divisor back */
+        if (t < 0) {
+            qhat--;
+            uint64_t carry = 0;
+            for (size_t i = 0; i < n; i++) {
+                carry += (uint64_t)u[i + j] + v[i];
+                u[i + j] = (uint32_t)carry;
+                carry >>= 32;
+            }
+            u[j + n] += (uint32_t)carry;
+        }
+        quot->limbs[j] = (uint32_t)qhat;
+    }
+    calc_big *rem = alloc(n);
+    for (size_t i = 0; i < n; i++) {
+        rem->limbs[i] = (u[i] >> s) | (s ? (uint32_t)((uint64_t)u[i + 1] << (32 - s)) : 0);
+    }
+    free(u);
+    free(v);
+    *q = trim(quot, 1);
+    *r = trim(rem, 1);
+}
+
+/* |a| >> n */
+static calc_big *shr_mag(const calc_big *a, uint64_t n) {
+    uint64_t limbs = n / 32;
+    int bits = n % 32;
+    if (limbs >= a->len) {
+        return alloc(0);
+    }
+    calc_big *r = alloc(a->len - limbs);
+    for (size_t i = 0; i < r->len; i++) {
+        uint64_t pair = a->limbs[i + limbs];
+        if (i + limbs + 1 < a->len) {
+            pair |= (uint64_t)a->limbs[i + limbs + 1] << 32;
+        }
+        r->limbs[i] = (uint32_t)(pair >> bits);
+    }
+    return trim(r, 1);
+}
+
+static int is_odd(const calc_big *x) {
+    return x->len > 0 && (x->limbs[0] & 1);
+}
+
+static int is_unit(const calc_big *x) {
+    return x->len == 1 && x->limbs[0] == 1;
+}
+
+/* --- conversions --- */
+
+calc_big *calc_big_from_i64(int64_t i) {
+    return i < 0 ? from_u64(0 - (uint64_t)i, -1) : from_u64((uint64_t)i, 1);
+}
+
+calc_big *calc_big_shl(const calc_big *x, const calc_big *n);
+
+/* truncates; NaN and infinities convert like `as` does with 64 bits */
+calc_big *calc_big_from_f64(double f) {
+    if (isnan(f)) {
+        return alloc(0);
+    }
+    if (isinf(f)) {
+        return calc_big_from_i64(f > 0 ? INT64_MAX : INT64_MIN);
+    }
+    f = trunc(f);
+    if (fabs(f) < 0x1p63) {
+        return calc_big_from_i64((int64_t)f);
+    }
+    int exp;
+    double m = frexp(fabs(f), &exp);
+    calc_big *mantissa = from_u64((uint64_t)ldexp(m, 64), f < 0 ? -1 : 1);
+    return calc_big_shl(mantissa, calc_big_from_i64(exp - 64));
+}
+
+int64_t calc_big_bits(const calc_big *x) {
+    x = arg(x);
+    if (x->len == 0) {
+        return 0;
+    }
+    return (int64_t)(x->len * 32) - __builtin_clz(x->limbs[x->len - 1]);
+}
+
+/* rounded to nearest, or infinite if too large */
+double calc_big_to_f64(const calc_big *x) {
+    x = arg(x);
+    int64_t bits = calc_big_bits(x);
+    if (bits == 0) {
+        return 0.0;
+    }
+    int64_t shift = bits > 64 ? bits - 64 : 0;
+    calc_big *top = shr_mag(x, (uint64_t)shift);
+    uint64_t m = top->limbs[0] | (top->len > 1 ? (uint64_t)top->limbs[1] << 32 : 0);
+    /* any bit shifted out makes the rounding go up at a tie */
+    for (int64_t i = 0; i < shift; i++) {
+        if (x->limbs[i / 32] >> (i % 32) & 1) {
+            m |= 1;
+            break;
+        }
+    }
+    double f = ldexp((double)m, (int)(shift > 2000 ? 2000 : shift));
+    return x->sign < 0 ? -f : f;
+}
+
+int64_t calc_big_fits_i64(const calc_big *x) {
+    x = arg(x);
+    if (x->len > 2) {
+        return 0;
+    }
+    uint64_t m = x->len == 0 ? 0 : x->limbs[0] | (x->len > 1 ? (uint64_t)x->limbs[1] << 32 : 0);
+    return x->sign < 0 ? m <= (uint64_t)1 << 63 : m <= INT64_MAX;
+}
+
+/* only for values that fit */
+int64_t calc_big_to_i64(const calc_big *x) {
+    x = arg(x);
+    uint64_t m = x->len == 0 ? 0 : x->limbs[0] | (x->len > 1 ? (uint64_t)x->limbs[1] << 32 : 0);
+    return (int64_t)(x->sign < 0 ? 0 - m : m);
+}
+
+/* text the compiled program has checked is an optional sign and decimal
+ * digits */
+calc_big *calc_big_parse(const char *text) {
+    int sign = *text == '-' ? -1 : 1;
+    text += *text == '-' || *text == '+';
+    /* every digit adds less than 4 bits */
+    calc_big *x = alloc(strlen(text) / 8 + 1);
+    for (; *text; text++) {
+        uint64_t carry = (uint64_t)(*text - '0');
+        for (size_t i = 0; i < x->len; i++) {
+            uint64_t cur = (uint64_t)x->limbs[i] * 10 + carry;
+            x->limbs[i] = (uint32_t)cur;
+            carry = cur >> 32;
+        }
+    }
+    return trim(x, sign);
+}
+
+/* --- arithmetic --- */
+
+static calc_big *add_signed(const calc_big *x, const calc_big *y, int y_sign) {
+    if (y_sign == 0) {
+        return with_sign(x, x->sign);
+    }
+    if (x->sign == 0) {
+        return with_sign(y, y_sign);
+    }
+    if (x->sign == y_sign) {
+        return add_mag(x, y, x->sign);
+    }
+    int cmp = cmp_mag(x, y);
+    if (cmp == 0) {
+        return alloc(0);
+    }
+    return cmp > 0 ? sub_mag(x, y, x->sign) : sub_mag(y, x, y_sign);
+}
+
+calc_big *calc_big_add(const calc_big *x, const calc_big *y) {
+    x = arg(x);
+    y = arg(y);
+    return add_signed(x, y, y->sign);
+}
+
+calc_big *calc_big_sub(const calc_big *x, const calc_big *y) {
+    x = arg(x);
+    y = arg(y);
+    return add_signed(x, y, -y->sign);
+}
+
+calc_big *calc_big_mul(const calc_big *x, const calc_big *y) {
+    x = arg(x);
+    y = arg(y);
+    return mul_mag(x, y, x->sign * y->sign);
+}
+
+/* truncating, like the 64-bit `/` */
+calc_big *calc_big_div(const calc_big *x, const calc_big *y) {
+    x = arg(x);
+    y = arg(y);
+    calc_big *q, *r;
+    divmod_mag(x, y, &q, &r);
+    return trim(q, x->sign * y->sign);
+}
+
+calc_big *calc_big_rem(const calc_big *x, const calc_big *y) {
+    x = arg(x);
+    y = arg(y);
+    calc_big *q, *r;
+    divmod_mag(x, y, &q, &r);
+    return trim(r, x->sign);
+}
+
+/* the remainder with the sign of the divisor, for `mod` */
+calc_big *calc_big_mod(const calc_big *x, const calc_big *y) {
+    calc_big *r = calc_big_rem(x, y);
+    y = arg(y);
+    if (r->sign != 0 && r->sign != y->sign) {
+        return calc_big_add(r, y);
+    }
+    return r;
+}
+
+calc_big *calc_big_neg(const calc_big *x) {
+    x = arg(x);
+    return with_sign(x, -x->sign);
+}
+
+calc_big *calc_big_abs(const calc_big *x) {
+    x = arg(x);
+    return with_sign(x, 1);
+}
+
+/* a negative power truncates the fraction, and exponents beyond 32 bits
+ * only come with bases 0, 1 and -1 */
+calc_big *calc_big_pow(const calc_big *base, const calc_big *exp) {
+    base = arg(base);
+    exp = arg(exp);
+    int sign = base->sign < 0 && is_odd(exp) ? -1 : 1;
+    if (exp->sign < 0 || exp->len > 1) {
+        if (is_unit(base)) {
+            return calc_big_from_i64(sign);
+        }
+        return alloc(0);
+    }
+    uint32_t e = exp->len == 0 ? 0 : exp->limbs[0];
+    calc_big *pow = calc_big_from_i64(1);
+    const calc_big *factor = base;
+    while (e != 0) {
+        if (e & 1) {
+            pow = mul_mag(pow, factor, 1);
+        }
+        e >>= 1;
+        if (e != 0) {
+            factor = mul_mag(factor, factor, 1);
+        }
+    }
+    return trim(pow, sign);
+}
+
+calc_big *calc_big_factorial(const calc_big *n) {
+    n = arg(n);
+    uint32_t last = n->len == 0 ? 0 : n->limbs[0];
+    calc_big *product = calc_big_from_i64(1);
+    for (uint64_t i = 2; i <= last; i++) {
+        calc_big *factor = from_u64(i, 1);
+        product = mul_mag(product, factor, 1);
+        free(factor);
+    }
+    return product;
+}
+
+calc_big *calc_big_gcd(const calc_big *x, const calc_big *y) {
+    calc_big *a = with_sign(arg(x), 1), *b = with_sign(arg(y), 1);
+    while (b->len != 0) {
+        calc_big *q, *r;
+        divmod_mag(a, b, &q, &r);
+        a = b;
+        b = r;
+    }
+    return a;
+}
+
+calc_big *calc_big_lcm(const calc_big *x, const calc_big *y) {
+    x = arg(x);
+    y = arg(y);
+    if (x->sign == 0 || y->sign == 0) {
+        return alloc(0);
+    }
+    calc_big *quotient = calc_big_div(x, calc_big_gcd(x, y));
+    return mul_mag(quotient, y, 1);
+}
+
+int64_t calc_big_cmp(const calc_big *x, const calc_big *y) {
+    x = arg(x);
+    y = arg(y);
+    if (x->sign != y->sign) {
+        return x->sign < y->sign ? -1 : 1;
+    }
+    return x->sign * cmp_mag(x, y);
+}
+
+int64_t calc_big_sign(const calc_big *x) {
+    return arg(x)->sign;
+}
+
+/* --- bits, in two's complement with infinitely many sign bits --- */
+
+static void to_twos(const calc_big *x, uint32_t *out, size_t n) {
+    uint64_t carry = 1;
+    for (size_t i = 0; i < n; i++) {
+        uint32_t limb = i < x->len ? x->limbs[i] : 0;
+        if (x->sign < 0) {
+            carry += (uint32_t)~limb;
+            out[i] = (uint32_t)carry;
+            carry >>= 32;
+        } else {
+            out[i] = limb;
+        }
+    }
+}
+
+static calc_big *from_twos(const uint32_t *limbs, size_t n) {
+    calc_big *r = alloc(n);
+    int negative = limbs[n - 1] >> 31;
+    uint64_t carry = 1;
+    for (size_t i = 0; i < n; i++) {
+        if (negative) {
+            carry += (uint32_t)~limbs[i];
+            r->limbs[i] = (uint32_t)carry;
+            carry >>= 32;
+        } else {
+            r->limbs[i] = limbs[i];
+        }
+    }
+    return trim(r, negative ? -1 : 1);
+}
+
+static calc_big *bitwise(const calc_big *x, const calc_big *y, char op) {
+    x = arg(x);
+    y = arg(y);
+    /* one more limb than either has holds the sign */
+    size_t n = (x->len > y->len ? x->len : y->len) + 1;
+    uint32_t *a = malloc(n * sizeof(uint32_t)), *b = malloc(n * sizeof(uint32_t));
+    to_twos(x, a, n);
+    to_twos(y, b, n);
+    for (size_t i = 0; i < n; i++) {
+        a[i] = op == '&' ? a[i] & b[i] : op == '|' ? a[i] | b[i] : a[i] ^ b[i];
+    }
+    calc_big *r = from_twos(a, n);
+    free(a);
+    free(b);
+    return r;
+}
+
+calc_big *calc_big_and(const calc_big *x, const calc_big *y) {
+    return bitwise(x, y, '&');
+}
+
+calc_big *calc_big_or(const calc_big *x, const calc_big *y) {
+    return bitwise(x, y, '|');
+}
+
+calc_big *calc_big_xor(const calc_big *x, const calc_big *y) {
+    return bitwise(x, y, '^');
+}
+
+/* `~x` is `-x - 1` */
+calc_big *calc_big_not(const calc_big *x) {
+    return calc_big_sub(calc_big_neg(x), calc_big_from_i64(1));
+}
+
+/* the amount is at most 32 bits long */
+calc_big *calc_big_shl(const calc_big *x, const calc_big *n) {
+    x = arg(x);
+    uint64_t amount = (uint64_t)calc_big_to_i64(n);
+    if (x->len == 0) {
+        return alloc(0);
+    }
+    uint64_t limbs = amount / 32;
+    int bits = amount % 32;
+    calc_big *r = alloc(x->len + limbs + 1);
+    for (size_t i = 0; i < x->len; i++) {
+        uint64_t shifted = (uint64_t)x->limbs[i] << bits;
+        r->limbs[i + limbs] |= (uint32_t)shifted;
+        r->limbs[i + limbs + 1] |= (uint32_t)(shifted >> 32);
+    }
+    return trim(r, x->sign);
+}
+
+/* rounds towards negative infinity, so only the sign is left once every
+ * bit is shifted out */
+calc_big *calc_big_shr(const calc_big *x, const calc_big *n) {
+    x = arg(x);
+    n = arg(n);
+    uint64_t amount = calc_big_fits_i64(n) ? (uint64_t)calc_big_to_i64(n) : UINT64_MAX;
+    if (x->sign >= 0) {
+        return shr_mag(x, amount);
+    }
+    /* -((|x| - 1) >> n) - 1 */
+    calc_big *one = calc_big_from_i64(1);
+    calc_big *shifted = shr_mag(calc_big_sub(calc_big_abs(x), one), amount);
+    return calc_big_sub(calc_big_neg(shifted), one);
+}
+
+/* --- output --- */
+
+/* the decimal digits of x, which the caller frees */
+char *calc_big_format(const calc_big *x) {
+    x = arg(x);
+    /* base 10^9 digits, least significant first */
+    size_t max_digits = x->len * 32 / 29 + 1;
+    uint32_t *digits = malloc(max_digits * sizeof(uint32_t));
+    size_t count = 0;
+    calc_big *rest = with_sign(x, 1);
+    do {
+        uint64_t rem = 0;
+        for (size_t i = rest->len; i-- > 0;) {
+            uint64_t cur = (rem << 32) | rest->limbs[i];
+            rest->limbs[i] = (uint32_t)(cur / 1000000000);
+            rem = cur % 1000000000;
+        }
+        trim(rest, 1);
+        digits[count++] = (uint32_t)rem;
+    } while (rest->len != 0);
+    char *text = malloc(count * 9 + 2);
+    char *end = text + sprintf(text, "%s%u", x->sign < 0 ? "-" : "", digits[count - 1]);
+    for (size_t i = count - 1; i-- > 0;) {
+        end += sprintf(end, "%09u", digits[i]);
+    }
+    free(digits);
+    free(rest);
+    return text;
+}
+
+void calc_big_print(const calc_big *x) {
+    char *text = calc_big_format(x);
+    puts(text);
+    free(text);
+}
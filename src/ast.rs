@@ -1,46 +1,1028 @@
+//! The syntax tree, and the `Visitor` and `VisitorMut` traits that walk it.
+
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
+
+use crate::builtins::Builtin;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransUnit {
     pub block: Block,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
     pub stmts: Vec<Stmt>,
 }
 
+/// Byte range of a node in the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+/// Number of a declaration, in the order `sema::Resolver` saw them.
+pub type DeclId = usize;
+
+/// A name, either where it is declared or where it is used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+    /// The declaration this name refers to, filled in by `sema`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decl: Option<DeclId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
 pub enum Stmt {
-    ExprStmt(Expr),
-    PrintStmt(Expr),
+    ExprStmt(ExprStmt),
+    PrintStmt(PrintStmt),
+    LetStmt(LetStmt),
+    AssignStmt(AssignStmt),
+    BlockStmt(BlockStmt),
+    AttrStmt(AttrStmt),
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::ExprStmt(stmt) => stmt.span,
+            Stmt::PrintStmt(stmt) => stmt.span,
+            Stmt::LetStmt(stmt) => stmt.span,
+            Stmt::AssignStmt(stmt) => stmt.span,
+            Stmt::BlockStmt(stmt) => stmt.span,
+            Stmt::AttrStmt(stmt) => stmt.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExprStmt {
+    pub expr: Expr,
+    pub span: Span,
+}
+
+/// `print x;` prints a lone value on a line of its own. With a format
+/// string, `print("{} items", n);` prints the text as it is, and `println`
+/// ends the line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrintStmt {
+    /// Written as `println`.
+    pub newline: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<FormatString>,
+    /// The lone value, or one per placeholder in `format`.
+    pub args: Vec<Expr>,
+    pub span: Span,
+}
+
+impl PrintStmt {
+    /// Whether the printed text ends the line.
+    pub fn ends_line(&self) -> bool {
+        self.newline || self.format.is_none()
+    }
+}
+
+/// A format string, split into text and `{}` placeholders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormatString {
+    pub pieces: Vec<FormatPiece>,
+    pub span: Span,
+}
+
+impl FormatString {
+    pub fn specs(&self) -> impl Iterator<Item = &FormatSpec> {
+        self.pieces.iter().filter_map(|piece| match piece {
+            FormatPiece::Spec(spec) => Some(spec),
+            FormatPiece::Text(_) => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FormatPiece {
+    /// Text without escapes; `{{` and `}}` are single braces here.
+    Text(String),
+    Spec(FormatSpec),
+}
+
+/// `{:[[fill]align][0][width][.precision][x|X|o|b|p]}`, as in Rust except
+/// for `p`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FormatSpec {
+    pub fill: char,
+    /// `None` aligns numbers right and anything else left.
+    pub align: Option<Align>,
+    /// `0` pads numbers with zeros after the sign, instead of `fill`.
+    pub zero: bool,
+    pub width: Option<u32>,
+    /// Places after the point; only for floats and complex numbers.
+    pub precision: Option<u32>,
+    /// Not `Radix::Decimal` only for ints.
+    pub radix: Radix,
+    /// `X` rather than `x`.
+    pub upper: bool,
+    /// `p`, a `complex` in polar form rather than rectangular.
+    pub polar: bool,
+    /// The placeholder, braces included.
+    pub span: Span,
 }
+
+/// The most places `{:.N}` may ask for.
+pub const MAX_PRECISION: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Align {
+    /// `<`
+    Left,
+    /// `^`, with any odd padding on the right
+    Center,
+    /// `>`
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl std::fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintLevel::Allow => write!(f, "allow"),
+            LintLevel::Warn => write!(f, "warn"),
+            LintLevel::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// `#[level(lint, ...)]` sets the level of lints for the statement that
+/// follows, `#![level(lint, ...)]` for the rest of the enclosing block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttrStmt {
+    pub inner: bool,
+    pub level: LintLevel,
+    /// Lint names; these are not variables and are never resolved.
+    pub lints: Vec<Ident>,
+    pub span: Span,
+}
+
+/// The types a value can have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    /// An exact fraction, see `rational::Rational`.
+    Rational,
+    /// A fixed-point number, see `decimal::Decimal`.
+    Decimal,
+    /// A pair of `float`s, see `complex::Complex`.
+    Complex,
+    /// Text, which `+` joins.
+    Str,
+    /// `[int; N]`, or `[int]` when the length is only known at run time.
+    Array(Option<u32>),
+    /// `[[float; C]; R]`, a matrix of `R` rows and `C` columns, see
+    /// `matrix::Matrix`. Its shape is always known.
+    Matrix(u32, u32),
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "int" => Some(Type::Int),
+            "float" => Some(Type::Float),
+            "bool" => Some(Type::Bool),
+            "rat" => Some(Type::Rational),
+            "dec" => Some(Type::Decimal),
+            "complex" => Some(Type::Complex),
+            "str" => Some(Type::Str),
+            _ => None,
+        }
+    }
+
+    pub fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Type::Int | Type::Float | Type::Rational | Type::Decimal | Type::Complex
+        )
+    }
+
+    pub fn is_array(self) -> bool {
+        matches!(self, Type::Array(_))
+    }
+
+    pub fn is_matrix(self) -> bool {
+        matches!(self, Type::Matrix(..))
+    }
+}
+
+/// The most elements `[value; count]` may ask for.
+pub const MAX_ARRAY_LEN: i64 = 1 << 24;
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Bool => write!(f, "bool"),
+            Type::Rational => write!(f, "rat"),
+            Type::Decimal => write!(f, "dec"),
+            Type::Complex => write!(f, "complex"),
+            Type::Str => write!(f, "str"),
+            Type::Array(Some(len)) => write!(f, "[int; {}]", len),
+            Type::Array(None) => write!(f, "[int]"),
+            Type::Matrix(rows, cols) => write!(f, "[[float; {}]; {}]", cols, rows),
+        }
+    }
+}
+
+/// Parameter and result types of a function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    pub params: Vec<Type>,
+    pub ret: Type,
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params: Vec<String> = self.params.iter().map(Type::to_string).collect();
+        write!(f, "fn({}) -> {}", params.join(", "), self.ret)
+    }
+}
+
+/// `let name[: ty] = expr;` declares a variable in the enclosing block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LetStmt {
+    pub name: Ident,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ty: Option<Type>,
+    pub expr: Expr,
+    pub span: Span,
+}
+
+/// `name = expr;` updates a variable declared earlier, and
+/// `name[index] = expr;` one element of an array.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignStmt {
+    pub name: Ident,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<Expr>,
+    pub expr: Expr,
+    pub span: Span,
+}
+
+/// `{ ... }` opens a new scope.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockStmt {
+    pub block: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expr {
     Primary(Box<PrimaryExpr>),
     Prefix(Box<PrefixExpr>),
+    Postfix(Box<PostfixExpr>),
     Infix(Box<InfixExpr>),
+    Cast(Box<CastExpr>),
+    Convert(Box<ConvertExpr>),
+    Index(Box<IndexExpr>),
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Primary(e) => e.span(),
+            Expr::Prefix(e) => e.span,
+            Expr::Postfix(e) => e.span,
+            Expr::Infix(e) => e.span,
+            Expr::Cast(e) => e.span,
+            Expr::Convert(e) => e.span,
+            Expr::Index(e) => e.span,
+        }
+    }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrefixExpr {
     pub op: PrefixOp,
     pub expr: Box<Expr>,
+    pub span: Span,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostfixExpr {
+    pub expr: Box<Expr>,
+    pub op: PostfixOp,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InfixExpr {
     pub lhs: Box<Expr>,
     pub op: InfixOp,
     pub rhs: Box<Expr>,
+    pub span: Span,
+}
+
+/// `expr as ty`, an explicit conversion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CastExpr {
+    pub expr: Box<Expr>,
+    pub ty: Type,
+    pub span: Span,
 }
 
+/// `expr in unit`, a quantity as a plain number of `unit`s.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConvertExpr {
+    pub expr: Box<Expr>,
+    pub unit: Unit,
+    pub span: Span,
+}
+
+/// `m/s^2`: units multiplied and divided left to right, see `units`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Unit {
+    pub factors: Vec<UnitFactor>,
+    pub span: Span,
+}
+
+/// `s^2` in a unit; after `/` the power is negative.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitFactor {
+    pub name: String,
+    pub power: i32,
+    pub span: Span,
+}
+
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, factor) in self.factors.iter().enumerate() {
+            let power = match i {
+                0 => factor.power,
+                _ if factor.power < 0 => {
+                    write!(f, "/")?;
+                    -factor.power
+                }
+                _ => {
+                    write!(f, "*")?;
+                    factor.power
+                }
+            };
+            write!(f, "{}", factor.name)?;
+            if power != 1 {
+                write!(f, "^{}", power)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `[1, 2]` in a matrix literal, one of its rows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatrixRow {
+    pub elements: Vec<Expr>,
+    pub span: Span,
+}
+
+/// `expr[index]`, an element of an array.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexExpr {
+    pub expr: Box<Expr>,
+    pub index: Box<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PrefixOp {
     Plus,
     Minus,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PostfixOp {
+    /// `n!`, the product of `1..=n`.
+    Factorial,
+    /// `x%`, `x / 100` as a `float`.
+    Percent,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InfixOp {
     Plus,
     Minus,
     Multiply,
     Divide,
+    Remainder,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    /// `@`, the product of two matrices.
+    MatMul,
+}
+
+impl PrefixOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PrefixOp::Plus => "+",
+            PrefixOp::Minus => "-",
+            PrefixOp::Not => "!",
+            PrefixOp::BitNot => "~",
+        }
+    }
+}
+
+impl PostfixOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PostfixOp::Factorial => "!",
+            PostfixOp::Percent => "%",
+        }
+    }
+}
+
+impl InfixOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            InfixOp::Plus => "+",
+            InfixOp::Minus => "-",
+            InfixOp::Multiply => "*",
+            InfixOp::Divide => "/",
+            InfixOp::Remainder => "%",
+            InfixOp::Power => "**",
+            InfixOp::BitAnd => "&",
+            InfixOp::BitOr => "|",
+            InfixOp::BitXor => "^",
+            InfixOp::ShiftLeft => "<<",
+            InfixOp::ShiftRight => ">>",
+            InfixOp::Equal => "==",
+            InfixOp::NotEqual => "!=",
+            InfixOp::Less => "<",
+            InfixOp::LessEqual => "<=",
+            InfixOp::Greater => ">",
+            InfixOp::GreaterEqual => ">=",
+            InfixOp::MatMul => "@",
+        }
+    }
+
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            InfixOp::Equal
+                | InfixOp::NotEqual
+                | InfixOp::Less
+                | InfixOp::LessEqual
+                | InfixOp::Greater
+                | InfixOp::GreaterEqual
+        )
+    }
+
+    /// `+ - * / % **`, which apply to `int`s and `float`s alike.
+    pub fn is_arithmetic(self) -> bool {
+        matches!(
+            self,
+            InfixOp::Plus
+                | InfixOp::Minus
+                | InfixOp::Multiply
+                | InfixOp::Divide
+                | InfixOp::Remainder
+                | InfixOp::Power
+        )
+    }
+}
+
+/// The base an integer literal is written in, which `fmt` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Radix {
+    Decimal,
+    /// `0xFF`
+    Hex,
+    /// `0o17`
+    Octal,
+    /// `0b1010`
+    Binary,
 }
 
+impl Radix {
+    pub fn base(self) -> u32 {
+        match self {
+            Radix::Decimal => 10,
+            Radix::Hex => 16,
+            Radix::Octal => 8,
+            Radix::Binary => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PrimaryExpr {
-    Mem, // a fixed global variable
-    Int(i64),
-    Expr(Box<Expr>),
+    Mem(Span), // a fixed global variable
+    Int(i64, Radix, Span),
+    /// An integer literal too large for 64 bits, only valid with
+    /// `Numbers::Big`.
+    BigInt(BigInt, Radix, Span),
+    /// `'a'`, the code point of the character as an `int`.
+    Char(char, Span),
+    /// `3r`, a whole number as a `rat`.
+    Rational(i64, Span),
+    /// `19.99d`: the digits without the point, and how many of them follow
+    /// it.
+    Decimal(i64, u32, Span),
+    Float(f64, Span),
+    /// `3i` or `0.5i`, a `complex` without a real part.
+    Imaginary(f64, Span),
+    /// `9.81 m/s^2`: the number as written and its unit. It is a `float`
+    /// in the SI base units.
+    Quantity(f64, Unit, Span),
+    Bool(bool, Span),
+    /// `"text"`, a `str` with the escapes replaced.
+    Str(String, Span),
+    /// `[1, 2, 3]`, an array of the listed elements.
+    Array(Vec<Expr>, Span),
+    /// `[value; count]`, an array of `count` copies of `value`.
+    Repeat(Box<Expr>, Box<Expr>, Span),
+    /// `[[1, 2], [3, 4]]`, a matrix of the listed rows.
+    Matrix(Vec<MatrixRow>, Span),
+    Ident(Ident),
+    Call(CallExpr),
+    Expr(Box<Expr>, Span), // parenthesized, the span includes the parentheses
+}
+
+impl PrimaryExpr {
+    pub fn span(&self) -> Span {
+        match self {
+            PrimaryExpr::Mem(span)
+            | PrimaryExpr::Int(_, _, span)
+            | PrimaryExpr::BigInt(_, _, span)
+            | PrimaryExpr::Char(_, span)
+            | PrimaryExpr::Rational(_, span)
+            | PrimaryExpr::Decimal(_, _, span)
+            | PrimaryExpr::Float(_, span)
+            | PrimaryExpr::Imaginary(_, span)
+            | PrimaryExpr::Quantity(_, _, span)
+            | PrimaryExpr::Bool(_, span)
+            | PrimaryExpr::Str(_, span)
+            | PrimaryExpr::Array(_, span)
+            | PrimaryExpr::Repeat(_, _, span)
+            | PrimaryExpr::Matrix(_, span)
+            | PrimaryExpr::Expr(_, span) => *span,
+            PrimaryExpr::Ident(ident) => ident.span,
+            PrimaryExpr::Call(call) => call.span,
+        }
+    }
+}
+
+/// `name(args, ...)` calls a built-in function or one the host registered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallExpr {
+    /// A function name; it is not a variable and is not passed to
+    /// `visit_ident`.
+    pub callee: Ident,
+    pub args: Vec<Expr>,
+    pub span: Span,
+    /// The signature of a host function, filled in by `sema`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<Signature>,
+    /// The built-in function called, filled in by `sema`. Its signature
+    /// depends on the argument types.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builtin: Option<Builtin>,
+}
+
+/// Read-only traversal of the AST.
+///
+/// Every method defaults to the matching `walk_*` function, which visits the
+/// node's children, so an implementation only overrides the nodes it cares
+/// about and calls `walk_*` itself to keep descending.
+pub trait Visitor {
+    fn visit_trans_unit(&mut self, tu: &TransUnit) {
+        walk_trans_unit(self, tu);
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt) {
+        walk_expr_stmt(self, stmt);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) {
+        walk_print_stmt(self, stmt);
+    }
+
+    fn visit_let_stmt(&mut self, stmt: &LetStmt) {
+        walk_let_stmt(self, stmt);
+    }
+
+    fn visit_assign_stmt(&mut self, stmt: &AssignStmt) {
+        walk_assign_stmt(self, stmt);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) {
+        walk_block_stmt(self, stmt);
+    }
+
+    fn visit_attr_stmt(&mut self, _stmt: &AttrStmt) {}
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_primary_expr(&mut self, expr: &PrimaryExpr) {
+        walk_primary_expr(self, expr);
+    }
+
+    fn visit_prefix_expr(&mut self, expr: &PrefixExpr) {
+        walk_prefix_expr(self, expr);
+    }
+
+    fn visit_postfix_expr(&mut self, expr: &PostfixExpr) {
+        walk_postfix_expr(self, expr);
+    }
+
+    fn visit_infix_expr(&mut self, expr: &InfixExpr) {
+        walk_infix_expr(self, expr);
+    }
+
+    fn visit_cast_expr(&mut self, expr: &CastExpr) {
+        walk_cast_expr(self, expr);
+    }
+
+    fn visit_convert_expr(&mut self, expr: &ConvertExpr) {
+        walk_convert_expr(self, expr);
+    }
+
+    fn visit_index_expr(&mut self, expr: &IndexExpr) {
+        walk_index_expr(self, expr);
+    }
+
+    fn visit_call_expr(&mut self, expr: &CallExpr) {
+        walk_call_expr(self, expr);
+    }
+
+    /// Called for every declaration and use of a name.
+    fn visit_ident(&mut self, _ident: &Ident) {}
+}
+
+pub fn walk_trans_unit<V: Visitor + ?Sized>(visitor: &mut V, tu: &TransUnit) {
+    visitor.visit_block(&tu.block);
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    for stmt in &block.stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::ExprStmt(stmt) => visitor.visit_expr_stmt(stmt),
+        Stmt::PrintStmt(stmt) => visitor.visit_print_stmt(stmt),
+        Stmt::LetStmt(stmt) => visitor.visit_let_stmt(stmt),
+        Stmt::AssignStmt(stmt) => visitor.visit_assign_stmt(stmt),
+        Stmt::BlockStmt(stmt) => visitor.visit_block_stmt(stmt),
+        Stmt::AttrStmt(stmt) => visitor.visit_attr_stmt(stmt),
+    }
+}
+
+pub fn walk_expr_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &ExprStmt) {
+    visitor.visit_expr(&stmt.expr);
+}
+
+pub fn walk_print_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &PrintStmt) {
+    for arg in &stmt.args {
+        visitor.visit_expr(arg);
+    }
+}
+
+pub fn walk_let_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &LetStmt) {
+    // the initializer is visited first, it cannot see the new name
+    visitor.visit_expr(&stmt.expr);
+    visitor.visit_ident(&stmt.name);
+}
+
+pub fn walk_assign_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &AssignStmt) {
+    if let Some(index) = &stmt.index {
+        visitor.visit_expr(index);
+    }
+    visitor.visit_expr(&stmt.expr);
+    visitor.visit_ident(&stmt.name);
+}
+
+pub fn walk_block_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &BlockStmt) {
+    visitor.visit_block(&stmt.block);
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Primary(e) => visitor.visit_primary_expr(e),
+        Expr::Prefix(e) => visitor.visit_prefix_expr(e),
+        Expr::Postfix(e) => visitor.visit_postfix_expr(e),
+        Expr::Infix(e) => visitor.visit_infix_expr(e),
+        Expr::Cast(e) => visitor.visit_cast_expr(e),
+        Expr::Convert(e) => visitor.visit_convert_expr(e),
+        Expr::Index(e) => visitor.visit_index_expr(e),
+    }
+}
+
+pub fn walk_primary_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &PrimaryExpr) {
+    match expr {
+        PrimaryExpr::Mem(_)
+        | PrimaryExpr::Int(..)
+        | PrimaryExpr::BigInt(..)
+        | PrimaryExpr::Char(..)
+        | PrimaryExpr::Rational(..)
+        | PrimaryExpr::Decimal(..)
+        | PrimaryExpr::Float(..)
+        | PrimaryExpr::Imaginary(..)
+        | PrimaryExpr::Quantity(..)
+        | PrimaryExpr::Bool(..)
+        | PrimaryExpr::Str(..) => {}
+        PrimaryExpr::Ident(ident) => visitor.visit_ident(ident),
+        PrimaryExpr::Call(call) => visitor.visit_call_expr(call),
+        PrimaryExpr::Array(elements, _) => {
+            for element in elements {
+                visitor.visit_expr(element);
+            }
+        }
+        PrimaryExpr::Repeat(value, count, _) => {
+            visitor.visit_expr(value);
+            visitor.visit_expr(count);
+        }
+        PrimaryExpr::Matrix(rows, _) => {
+            for element in rows.iter().flat_map(|row| row.elements.iter()) {
+                visitor.visit_expr(element);
+            }
+        }
+        PrimaryExpr::Expr(e, _) => visitor.visit_expr(e),
+    }
+}
+
+pub fn walk_prefix_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &PrefixExpr) {
+    visitor.visit_expr(&expr.expr);
+}
+
+pub fn walk_postfix_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &PostfixExpr) {
+    visitor.visit_expr(&expr.expr);
+}
+
+pub fn walk_infix_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &InfixExpr) {
+    visitor.visit_expr(&expr.lhs);
+    visitor.visit_expr(&expr.rhs);
+}
+
+pub fn walk_cast_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &CastExpr) {
+    visitor.visit_expr(&expr.expr);
+}
+
+pub fn walk_convert_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &ConvertExpr) {
+    visitor.visit_expr(&expr.expr);
+}
+
+pub fn walk_index_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &IndexExpr) {
+    visitor.visit_expr(&expr.expr);
+    visitor.visit_expr(&expr.index);
+}
+
+pub fn walk_call_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &CallExpr) {
+    for arg in &expr.args {
+        visitor.visit_expr(arg);
+    }
+}
+
+/// Like [`Visitor`], but with mutable access so passes can rewrite the tree
+/// in place. Replacing a whole expression is done from `visit_expr_mut`.
+pub trait VisitorMut {
+    fn visit_trans_unit_mut(&mut self, tu: &mut TransUnit) {
+        walk_trans_unit_mut(self, tu);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_expr_stmt_mut(&mut self, stmt: &mut ExprStmt) {
+        walk_expr_stmt_mut(self, stmt);
+    }
+
+    fn visit_print_stmt_mut(&mut self, stmt: &mut PrintStmt) {
+        walk_print_stmt_mut(self, stmt);
+    }
+
+    fn visit_let_stmt_mut(&mut self, stmt: &mut LetStmt) {
+        walk_let_stmt_mut(self, stmt);
+    }
+
+    fn visit_assign_stmt_mut(&mut self, stmt: &mut AssignStmt) {
+        walk_assign_stmt_mut(self, stmt);
+    }
+
+    fn visit_block_stmt_mut(&mut self, stmt: &mut BlockStmt) {
+        walk_block_stmt_mut(self, stmt);
+    }
+
+    fn visit_attr_stmt_mut(&mut self, _stmt: &mut AttrStmt) {}
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_primary_expr_mut(&mut self, expr: &mut PrimaryExpr) {
+        walk_primary_expr_mut(self, expr);
+    }
+
+    fn visit_prefix_expr_mut(&mut self, expr: &mut PrefixExpr) {
+        walk_prefix_expr_mut(self, expr);
+    }
+
+    fn visit_postfix_expr_mut(&mut self, expr: &mut PostfixExpr) {
+        walk_postfix_expr_mut(self, expr);
+    }
+
+    fn visit_infix_expr_mut(&mut self, expr: &mut InfixExpr) {
+        walk_infix_expr_mut(self, expr);
+    }
+
+    fn visit_cast_expr_mut(&mut self, expr: &mut CastExpr) {
+        walk_cast_expr_mut(self, expr);
+    }
+
+    fn visit_convert_expr_mut(&mut self, expr: &mut ConvertExpr) {
+        walk_convert_expr_mut(self, expr);
+    }
+
+    fn visit_index_expr_mut(&mut self, expr: &mut IndexExpr) {
+        walk_index_expr_mut(self, expr);
+    }
+
+    fn visit_call_expr_mut(&mut self, expr: &mut CallExpr) {
+        walk_call_expr_mut(self, expr);
+    }
+
+    /// Called for every declaration and use of a name.
+    fn visit_ident_mut(&mut self, _ident: &mut Ident) {}
+}
+
+pub fn walk_trans_unit_mut<V: VisitorMut + ?Sized>(visitor: &mut V, tu: &mut TransUnit) {
+    visitor.visit_block_mut(&mut tu.block);
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    for stmt in &mut block.stmts {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::ExprStmt(stmt) => visitor.visit_expr_stmt_mut(stmt),
+        Stmt::PrintStmt(stmt) => visitor.visit_print_stmt_mut(stmt),
+        Stmt::LetStmt(stmt) => visitor.visit_let_stmt_mut(stmt),
+        Stmt::AssignStmt(stmt) => visitor.visit_assign_stmt_mut(stmt),
+        Stmt::BlockStmt(stmt) => visitor.visit_block_stmt_mut(stmt),
+        Stmt::AttrStmt(stmt) => visitor.visit_attr_stmt_mut(stmt),
+    }
+}
+
+pub fn walk_expr_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut ExprStmt) {
+    visitor.visit_expr_mut(&mut stmt.expr);
+}
+
+pub fn walk_print_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut PrintStmt) {
+    for arg in &mut stmt.args {
+        visitor.visit_expr_mut(arg);
+    }
+}
+
+pub fn walk_let_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut LetStmt) {
+    // the initializer is visited first, it cannot see the new name
+    visitor.visit_expr_mut(&mut stmt.expr);
+    visitor.visit_ident_mut(&mut stmt.name);
+}
+
+pub fn walk_assign_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut AssignStmt) {
+    if let Some(index) = &mut stmt.index {
+        visitor.visit_expr_mut(index);
+    }
+    visitor.visit_expr_mut(&mut stmt.expr);
+    visitor.visit_ident_mut(&mut stmt.name);
+}
+
+pub fn walk_block_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut BlockStmt) {
+    visitor.visit_block_mut(&mut stmt.block);
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Primary(e) => visitor.visit_primary_expr_mut(e),
+        Expr::Prefix(e) => visitor.visit_prefix_expr_mut(e),
+        Expr::Postfix(e) => visitor.visit_postfix_expr_mut(e),
+        Expr::Infix(e) => visitor.visit_infix_expr_mut(e),
+        Expr::Cast(e) => visitor.visit_cast_expr_mut(e),
+        Expr::Convert(e) => visitor.visit_convert_expr_mut(e),
+        Expr::Index(e) => visitor.visit_index_expr_mut(e),
+    }
+}
+
+pub fn walk_primary_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut PrimaryExpr) {
+    match expr {
+        PrimaryExpr::Mem(_)
+        | PrimaryExpr::Int(..)
+        | PrimaryExpr::BigInt(..)
+        | PrimaryExpr::Char(..)
+        | PrimaryExpr::Rational(..)
+        | PrimaryExpr::Decimal(..)
+        | PrimaryExpr::Float(..)
+        | PrimaryExpr::Imaginary(..)
+        | PrimaryExpr::Quantity(..)
+        | PrimaryExpr::Bool(..)
+        | PrimaryExpr::Str(..) => {}
+        PrimaryExpr::Ident(ident) => visitor.visit_ident_mut(ident),
+        PrimaryExpr::Call(call) => visitor.visit_call_expr_mut(call),
+        PrimaryExpr::Array(elements, _) => {
+            for element in elements {
+                visitor.visit_expr_mut(element);
+            }
+        }
+        PrimaryExpr::Repeat(value, count, _) => {
+            visitor.visit_expr_mut(value);
+            visitor.visit_expr_mut(count);
+        }
+        PrimaryExpr::Matrix(rows, _) => {
+            for element in rows.iter_mut().flat_map(|row| row.elements.iter_mut()) {
+                visitor.visit_expr_mut(element);
+            }
+        }
+        PrimaryExpr::Expr(e, _) => visitor.visit_expr_mut(e),
+    }
+}
+
+pub fn walk_prefix_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut PrefixExpr) {
+    visitor.visit_expr_mut(&mut expr.expr);
+}
+
+pub fn walk_postfix_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut PostfixExpr) {
+    visitor.visit_expr_mut(&mut expr.expr);
+}
+
+pub fn walk_infix_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut InfixExpr) {
+    visitor.visit_expr_mut(&mut expr.lhs);
+    visitor.visit_expr_mut(&mut expr.rhs);
+}
+
+pub fn walk_cast_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut CastExpr) {
+    visitor.visit_expr_mut(&mut expr.expr);
+}
+
+pub fn walk_convert_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut ConvertExpr) {
+    visitor.visit_expr_mut(&mut expr.expr);
+}
+
+pub fn walk_index_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut IndexExpr) {
+    visitor.visit_expr_mut(&mut expr.expr);
+    visitor.visit_expr_mut(&mut expr.index);
+}
+
+pub fn walk_call_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut CallExpr) {
+    for arg in &mut expr.args {
+        visitor.visit_expr_mut(arg);
+    }
 }
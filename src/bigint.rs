@@ -0,0 +1,129 @@
+//! Integer arithmetic with `Numbers::Big`. Values that fit in 64 bits stay
+//! `Value::Int`, everything else is a `Value::BigInt`, and results are
+//! normalized back. `runtime/bigint.c` implements the same operations for
+//! compiled programs, which check for the same traps before calling them.
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+use crate::ast::{InfixOp, PrefixOp};
+use crate::builtins::Builtin;
+use crate::interpreter::Value;
+use crate::runtime::Trap;
+
+/// Exponents, factorial arguments and left shift amounts with more bits
+/// than this make results too large to hold, so they count as overflows.
+pub const MAX_AMOUNT_BITS: u64 = 32;
+
+/// `Value::Int` if `n` fits in 64 bits, else `Value::BigInt`.
+pub fn normalize(n: BigInt) -> Value {
+    match n.to_i64() {
+        Some(i) => Value::Int(i),
+        None => Value::BigInt(n),
+    }
+}
+
+/// Truncates a finite float towards zero. NaN and infinities have no
+/// integer value, see `interpreter::float_to_int`.
+pub fn from_float(f: f64) -> Value {
+    normalize(BigInt::from_f64(f.trunc()).unwrap())
+}
+
+pub fn eval_prefix(op: PrefixOp, x: &BigInt) -> Value {
+    match op {
+        PrefixOp::Minus => normalize(-x),
+        PrefixOp::BitNot => normalize(!x),
+        _ => unreachable!(),
+    }
+}
+
+pub fn eval_infix(op: InfixOp, lhs: &BigInt, rhs: &BigInt) -> Result<Value, Trap> {
+    if matches!(op, InfixOp::Divide | InfixOp::Remainder) && rhs.is_zero() {
+        return Err(Trap::DivisionByZero);
+    }
+    Ok(match op {
+        InfixOp::Plus => normalize(lhs + rhs),
+        InfixOp::Minus => normalize(lhs - rhs),
+        InfixOp::Multiply => normalize(lhs * rhs),
+        // truncating, like the 64-bit operators
+        InfixOp::Divide => normalize(lhs / rhs),
+        InfixOp::Remainder => normalize(lhs % rhs),
+        InfixOp::Power => pow(lhs, rhs)?,
+        // in two's complement with infinitely many sign bits
+        InfixOp::BitAnd => normalize(lhs & rhs),
+        InfixOp::BitOr => normalize(lhs | rhs),
+        InfixOp::BitXor => normalize(lhs ^ rhs),
+        InfixOp::ShiftLeft | InfixOp::ShiftRight if rhs.is_negative() => {
+            return Err(Trap::InvalidShift)
+        }
+        InfixOp::ShiftLeft if rhs.bits() > MAX_AMOUNT_BITS => return Err(Trap::Overflow),
+        InfixOp::ShiftLeft => normalize(lhs << rhs.to_usize().unwrap()),
+        // rounds towards negative infinity, so only the sign is left once
+        // every bit is shifted out
+        InfixOp::ShiftRight => match rhs.to_usize() {
+            Some(n) => normalize(lhs >> n),
+            None => Value::Int(if lhs.is_negative() { -1 } else { 0 }),
+        },
+        InfixOp::Equal => Value::Bool(lhs == rhs),
+        InfixOp::NotEqual => Value::Bool(lhs != rhs),
+        InfixOp::Less => Value::Bool(lhs < rhs),
+        InfixOp::LessEqual => Value::Bool(lhs <= rhs),
+        InfixOp::Greater => Value::Bool(lhs > rhs),
+        InfixOp::GreaterEqual => Value::Bool(lhs >= rhs),
+        InfixOp::MatMul => unreachable!(),
+    })
+}
+
+// a negative power truncates the fraction, like the 64-bit `**`
+fn pow(base: &BigInt, exp: &BigInt) -> Result<Value, Trap> {
+    if exp.is_negative() {
+        return match base.to_i64() {
+            Some(0) => Err(Trap::DivisionByZero),
+            Some(1) => Ok(Value::Int(1)),
+            Some(-1) if exp.is_odd() => Ok(Value::Int(-1)),
+            Some(-1) => Ok(Value::Int(1)),
+            _ => Ok(Value::Int(0)),
+        };
+    }
+    if exp.bits() > MAX_AMOUNT_BITS {
+        // only 0, 1 and -1 have powers this large that fit
+        return match base.to_i64() {
+            Some(0) => Ok(Value::Int(0)),
+            Some(1) => Ok(Value::Int(1)),
+            Some(-1) if exp.is_odd() => Ok(Value::Int(-1)),
+            Some(-1) => Ok(Value::Int(1)),
+            _ => Err(Trap::Overflow),
+        };
+    }
+    Ok(normalize(base.pow(exp.to_u32().unwrap())))
+}
+
+pub fn factorial(n: &BigInt) -> Result<Value, Trap> {
+    if n.is_negative() {
+        return Err(Trap::NegativeFactorial);
+    }
+    if n.bits() > MAX_AMOUNT_BITS {
+        return Err(Trap::Overflow);
+    }
+    let n = n.to_u32().unwrap();
+    Ok(normalize(
+        (2..=n).fold(BigInt::one(), |product, i| product * i),
+    ))
+}
+
+/// The builtins that keep `int` arguments in `int`; `y` is ignored by the
+/// functions of one argument.
+pub fn eval_builtin(builtin: Builtin, x: &BigInt, y: &BigInt) -> Result<Value, Trap> {
+    Ok(match builtin {
+        Builtin::Abs => normalize(x.abs()),
+        Builtin::Sign => normalize(x.signum()),
+        Builtin::Min => normalize(x.min(y).clone()),
+        Builtin::Max => normalize(x.max(y).clone()),
+        Builtin::Mod if y.is_zero() => return Err(Trap::DivisionByZero),
+        Builtin::Mod => normalize(x.mod_floor(y)),
+        Builtin::Gcd => normalize(x.gcd(y)),
+        Builtin::Lcm => normalize(x.lcm(y)),
+        _ => unreachable!(),
+    })
+}
@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{Signature, Type};
+use crate::typeck::coerces;
+
+/// The functions every program can call: math, and a few on `str`s,
+/// matrices and complex numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Builtin {
+    Abs,
+    Min,
+    Max,
+    Pow,
+    Sqrt,
+    Gcd,
+    Lcm,
+    Mod,
+    Sign,
+    Floor,
+    Ceil,
+    Round,
+    Log,
+    Exp,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Len,
+    Substr,
+    ToStr,
+    ParseInt,
+    Transpose,
+    Det,
+    Inv,
+    Re,
+    Im,
+    Arg,
+    Conj,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 32] = [
+        Builtin::Abs,
+        Builtin::Min,
+        Builtin::Max,
+        Builtin::Pow,
+        Builtin::Sqrt,
+        Builtin::Gcd,
+        Builtin::Lcm,
+        Builtin::Mod,
+        Builtin::Sign,
+        Builtin::Floor,
+        Builtin::Ceil,
+        Builtin::Round,
+        Builtin::Log,
+        Builtin::Exp,
+        Builtin::Sin,
+        Builtin::Cos,
+        Builtin::Tan,
+        Builtin::Asin,
+        Builtin::Acos,
+        Builtin::Atan,
+        Builtin::Atan2,
+        Builtin::Len,
+        Builtin::Substr,
+        Builtin::ToStr,
+        Builtin::ParseInt,
+        Builtin::Transpose,
+        Builtin::Det,
+        Builtin::Inv,
+        Builtin::Re,
+        Builtin::Im,
+        Builtin::Arg,
+        Builtin::Conj,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Abs => "abs",
+            Builtin::Min => "min",
+            Builtin::Max => "max",
+            Builtin::Pow => "pow",
+            Builtin::Sqrt => "sqrt",
+            Builtin::Gcd => "gcd",
+            Builtin::Lcm => "lcm",
+            Builtin::Mod => "mod",
+            Builtin::Sign => "sign",
+            Builtin::Floor => "floor",
+            Builtin::Ceil => "ceil",
+            Builtin::Round => "round",
+            Builtin::Log => "log",
+            Builtin::Exp => "exp",
+            Builtin::Sin => "sin",
+            Builtin::Cos => "cos",
+            Builtin::Tan => "tan",
+            Builtin::Asin => "asin",
+            Builtin::Acos => "acos",
+            Builtin::Atan => "atan",
+            Builtin::Atan2 => "atan2",
+            Builtin::Len => "len",
+            Builtin::Substr => "substr",
+            Builtin::ToStr => "to_str",
+            Builtin::ParseInt => "parse_int",
+            Builtin::Transpose => "transpose",
+            Builtin::Det => "det",
+            Builtin::Inv => "inv",
+            Builtin::Re => "re",
+            Builtin::Im => "im",
+            Builtin::Arg => "arg",
+            Builtin::Conj => "conj",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        Builtin::ALL.into_iter().find(|builtin| builtin.name() == name)
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            Builtin::Min
+            | Builtin::Max
+            | Builtin::Pow
+            | Builtin::Gcd
+            | Builtin::Lcm
+            | Builtin::Mod
+            | Builtin::Atan2 => 2,
+            Builtin::Substr => 3,
+            _ => 1,
+        }
+    }
+
+    /// The overload that applies to arguments of the given types, if any.
+    /// `abs`, `min`, `max`, `mod` and `sign` stay in `int` when all
+    /// arguments are; `gcd` and `lcm` only take `int`s; everything else
+    /// computes in `float`, to which `int` arguments widen. The functions
+    /// on `str`s have one signature each, which nothing widens to, except
+    /// that `len` also counts the elements of an array. The functions on
+    /// matrices take one of a shape they apply to, and those on complex
+    /// numbers, `abs` among them, one `complex`.
+    pub fn signature(self, args: &[Type]) -> Option<Signature> {
+        if self.is_matrix() {
+            return self.matrix_signature(args);
+        }
+        if self.is_complex() || args.contains(&Type::Complex) {
+            return self.complex_signature(args);
+        }
+        if let (Builtin::Len, [Type::Array(_)]) = (self, args) {
+            return Some(Signature {
+                params: vec![Type::Array(None)],
+                ret: Type::Int,
+            });
+        }
+        if let Some(sig) = self.text_signature() {
+            let exact = args.iter().zip(&sig.params).all(|(&from, &to)| from == to);
+            return exact.then_some(sig);
+        }
+        if !args.iter().all(|ty| ty.is_numeric()) {
+            return None;
+        }
+        let ints = args.iter().all(|&ty| ty == Type::Int);
+        let ty = match self {
+            Builtin::Abs | Builtin::Min | Builtin::Max | Builtin::Mod | Builtin::Sign if ints => {
+                Type::Int
+            }
+            Builtin::Gcd | Builtin::Lcm if ints => Type::Int,
+            Builtin::Gcd | Builtin::Lcm => return None,
+            _ => Type::Float,
+        };
+        Some(Signature {
+            params: vec![ty; self.arity()],
+            ret: ty,
+        })
+    }
+
+    /// Whether this is one of the functions on `str`s.
+    pub fn is_text(self) -> bool {
+        self.text_signature().is_some()
+    }
+
+    /// Whether this is one of the functions on matrices.
+    pub fn is_matrix(self) -> bool {
+        matches!(self, Builtin::Transpose | Builtin::Det | Builtin::Inv)
+    }
+
+    /// Whether this is one of the functions that only take a `complex`.
+    pub fn is_complex(self) -> bool {
+        matches!(self, Builtin::Re | Builtin::Im | Builtin::Arg | Builtin::Conj)
+    }
+
+    // `re`, `im`, `arg` and `conj` take any number that widens to
+    // `complex`, and
+    // `abs` of a `complex` is its magnitude; no other function takes one
+    fn complex_signature(self, args: &[Type]) -> Option<Signature> {
+        let &[arg] = args else {
+            return None;
+        };
+        let ret = match self {
+            Builtin::Re | Builtin::Im | Builtin::Arg | Builtin::Abs => Type::Float,
+            Builtin::Conj => Type::Complex,
+            _ => return None,
+        };
+        coerces(arg, Type::Complex).then(|| Signature {
+            params: vec![Type::Complex],
+            ret,
+        })
+    }
+
+    // `transpose` swaps rows and columns, and `det` and `inv` only take
+    // square matrices
+    fn matrix_signature(self, args: &[Type]) -> Option<Signature> {
+        let &[Type::Matrix(rows, cols)] = args else {
+            return None;
+        };
+        let ret = match self {
+            Builtin::Transpose => Type::Matrix(cols, rows),
+            Builtin::Det if rows == cols => Type::Float,
+            Builtin::Inv if rows == cols => Type::Matrix(rows, cols),
+            _ => return None,
+        };
+        Some(Signature {
+            params: args.to_vec(),
+            ret,
+        })
+    }
+
+    // `len(str) -> int` counts characters, `substr(str, start, count)`
+    // takes `count` characters from the one at `start`, and `to_str` and
+    // `parse_int` convert `int`s from and to decimal text
+    fn text_signature(self) -> Option<Signature> {
+        let (params, ret) = match self {
+            Builtin::Len => (vec![Type::Str], Type::Int),
+            Builtin::Substr => (vec![Type::Str, Type::Int, Type::Int], Type::Str),
+            Builtin::ToStr => (vec![Type::Int], Type::Str),
+            Builtin::ParseInt => (vec![Type::Str], Type::Int),
+            _ => return None,
+        };
+        Some(Signature { params, ret })
+    }
+}
+
+/// The value of the constants `pi` and `e`. They are names like any other,
+/// so a variable can hide them.
+pub fn constant(name: &str) -> Option<f64> {
+    match name {
+        "pi" => Some(std::f64::consts::PI),
+        "e" => Some(std::f64::consts::E),
+        _ => None,
+    }
+}
@@ -1,4 +1,6 @@
+use crate::ast::{Align, Radix};
 use crate::ir::*;
+use crate::runtime::{Numbers, Precision, Rounding, Trap, EXIT_TRAP};
 
 pub trait LlvmEmitter {
     fn emit_ir(&self) -> String;
@@ -7,9 +9,12 @@ impl LlvmEmitter for Context {
     fn emit_ir(&self) -> String {
         let mut llvm_ir = String::new();
 
-        // Emit print function
+        // Emit print functions, one per type
         llvm_ir.push_str(r#"
 @.str = private unnamed_addr constant [6 x i8] c"%lld\0A\00", align 1
+@.str.float = private unnamed_addr constant [7 x i8] c"%.15g\0A\00", align 1
+@.str.true = private unnamed_addr constant [6 x i8] c"true\0A\00", align 1
+@.str.false = private unnamed_addr constant [7 x i8] c"false\0A\00", align 1
 
 define dso_local void @print(i64 noundef %0) #0{
   %2 = alloca i64, align 8
@@ -19,93 +24,2440 @@ define dso_local void @print(i64 noundef %0) #0{
   ret void
 }
 
+define dso_local void @print_float(double noundef %0) {
+  %2 = call double @calc_unsigned_nan(double %0)
+  %3 = call i32 (i8*, ...) @printf(i8* noundef getelementptr inbounds ([7 x i8], [7 x i8]* @.str.float, i64 0, i64 0), double noundef %2)
+  ret void
+}
+
+; `x` with the sign of a NaN cleared, as `printf` shows it; a NaN is
+; `nan` whatever computed it, as in `interpreter::format_float`
+define double @calc_unsigned_nan(double %x) {
+  %nan = fcmp uno double %x, %x
+  %shown = select i1 %nan, double 0x7FF8000000000000, double %x
+  ret double %shown
+}
+
+define dso_local void @print_bool(i1 noundef %0) {
+  %2 = select i1 %0, i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.str.true, i64 0, i64 0), i8* getelementptr inbounds ([7 x i8], [7 x i8]* @.str.false, i64 0, i64 0)
+  %3 = call i32 (i8*, ...) @printf(i8* noundef %2)
+  ret void
+}
+
 declare i32 @printf(i8*, ...)
+
+declare i64 @llvm.fptosi.sat.i64.f64(double)
+declare i64 @llvm.fptoui.sat.i64.f64(double)
+
+; `x` truncated and taken modulo 2^64, and 0 for NaN and infinities, as in
+; `interpreter::float_to_int`
+define i64 @calc_f64_to_i64_wrap(double %x) {
+  %rem = frem double %x, 0x43F0000000000000
+  %negative = fcmp olt double %rem, 0.0
+  %negated = fneg double %rem
+  %magnitude = select i1 %negative, double %negated, double %rem
+  %units = call i64 @llvm.fptoui.sat.i64.f64(double %magnitude)
+  %units.negated = sub i64 0, %units
+  %result = select i1 %negative, i64 %units.negated, i64 %units
+  ret i64 %result
+}
+
+declare {i64, i1} @llvm.sadd.with.overflow.i64(i64, i64)
+declare {i64, i1} @llvm.ssub.with.overflow.i64(i64, i64)
+declare {i64, i1} @llvm.smul.with.overflow.i64(i64, i64)
+declare i64 @llvm.sadd.sat.i64(i64, i64)
+declare i64 @llvm.ssub.sat.i64(i64, i64)
+declare i64 @llvm.smul.fix.sat.i64(i64, i64, i32)
+
+define i64 @calc_gcd(i64 %a, i64 %b) {
+entry:
+  %a.negative = icmp slt i64 %a, 0
+  %a.negated = sub i64 0, %a
+  %a.abs = select i1 %a.negative, i64 %a.negated, i64 %a
+  %b.negative = icmp slt i64 %b, 0
+  %b.negated = sub i64 0, %b
+  %b.abs = select i1 %b.negative, i64 %b.negated, i64 %b
+  br label %loop
+loop:
+  %x = phi i64 [ %a.abs, %entry ], [ %y, %next ]
+  %y = phi i64 [ %b.abs, %entry ], [ %rem, %next ]
+  %done = icmp eq i64 %y, 0
+  br i1 %done, label %exit, label %next
+next:
+  %rem = urem i64 %x, %y
+  br label %loop
+exit:
+  ret i64 %x
+}
+
+define {i64, i1} @calc_pow_with_overflow(i64 %base, i64 %exp) {
+entry:
+  %negative = icmp slt i64 %exp, 0
+  br i1 %negative, label %reciprocal, label %loop
+reciprocal:
+  %exp.bit = and i64 %exp, 1
+  %exp.odd = icmp ne i64 %exp.bit, 0
+  %minus.one.pow = select i1 %exp.odd, i64 -1, i64 1
+  %base.one = icmp eq i64 %base, 1
+  %base.minus.one = icmp eq i64 %base, -1
+  %unit.pow = select i1 %base.minus.one, i64 %minus.one.pow, i64 0
+  %reciprocal.pow = select i1 %base.one, i64 1, i64 %unit.pow
+  %reciprocal.result = insertvalue {i64, i1} {i64 0, i1 false}, i64 %reciprocal.pow, 0
+  ret {i64, i1} %reciprocal.result
+loop:
+  %pow = phi i64 [ 1, %entry ], [ %pow.next, %step ]
+  %factor = phi i64 [ %base, %entry ], [ %square, %step ]
+  %n = phi i64 [ %exp, %entry ], [ %n.next, %step ]
+  %overflow = phi i1 [ false, %entry ], [ %overflow.next, %step ]
+  %done = icmp eq i64 %n, 0
+  br i1 %done, label %exit, label %step
+step:
+  %bit = and i64 %n, 1
+  %odd = icmp ne i64 %bit, 0
+  %product.result = call {i64, i1} @llvm.smul.with.overflow.i64(i64 %pow, i64 %factor)
+  %product = extractvalue {i64, i1} %product.result, 0
+  %product.overflow = extractvalue {i64, i1} %product.result, 1
+  %pow.next = select i1 %odd, i64 %product, i64 %pow
+  %pow.overflow = and i1 %odd, %product.overflow
+  %n.next = lshr i64 %n, 1
+  %more = icmp ne i64 %n.next, 0
+  %square.result = call {i64, i1} @llvm.smul.with.overflow.i64(i64 %factor, i64 %factor)
+  %square = extractvalue {i64, i1} %square.result, 0
+  %square.overflow.any = extractvalue {i64, i1} %square.result, 1
+  ; the square after the last bit is never used
+  %square.overflow = and i1 %more, %square.overflow.any
+  %overflow.pow = or i1 %overflow, %pow.overflow
+  %overflow.next = or i1 %overflow.pow, %square.overflow
+  br label %loop
+exit:
+  %pow.result = insertvalue {i64, i1} undef, i64 %pow, 0
+  %result = insertvalue {i64, i1} %pow.result, i1 %overflow, 1
+  ret {i64, i1} %result
+}
+
+define i64 @calc_factorial(i64 %n) {
+entry:
+  %large = icmp sgt i64 %n, 66
+  %last = select i1 %large, i64 66, i64 %n
+  br label %loop
+loop:
+  %i = phi i64 [ 2, %entry ], [ %i.next, %next ]
+  %product = phi i64 [ 1, %entry ], [ %product.next, %next ]
+  %done = icmp sgt i64 %i, %last
+  br i1 %done, label %exit, label %next
+next:
+  %product.next = mul i64 %product, %i
+  %i.next = add i64 %i, 1
+  br label %loop
+exit:
+  ret i64 %product
+}
+
+define i64 @calc_pow(i64 %base, i64 %exp) {
+  %result = call {i64, i1} @calc_pow_with_overflow(i64 %base, i64 %exp)
+  %pow = extractvalue {i64, i1} %result, 0
+  ret i64 %pow
+}
 "#);
+        llvm_ir.push_str(FORMAT_PRELUDE);
+
+        if self.numbers == Numbers::Big {
+            llvm_ir.push_str(BIGINT_DECLARATIONS);
+        }
+        let uses_rationals = self
+            .values
+            .borrow()
+            .iter()
+            .any(|(_, value)| value.ty() == IrType::Rational);
+        if uses_rationals {
+            llvm_ir.push_str(&rational_prelude());
+        }
+        let uses_decimals = self
+            .values
+            .borrow()
+            .iter()
+            .any(|(_, value)| value.ty() == IrType::Decimal);
+        if uses_decimals {
+            llvm_ir.push_str(&decimal_prelude(self.precision, uses_rationals));
+        }
+        let uses_strings = self
+            .values
+            .borrow()
+            .iter()
+            .any(|(_, value)| value.ty() == IrType::Str);
+        if uses_strings {
+            llvm_ir.push_str(STRING_PRELUDE);
+        }
+        let uses_arrays = self
+            .values
+            .borrow()
+            .iter()
+            .any(|(_, value)| value.ty() == IrType::Array);
+        if uses_arrays {
+            llvm_ir.push_str(ARRAY_PRELUDE);
+        }
+        let uses_matrices = self
+            .values
+            .borrow()
+            .iter()
+            .any(|(_, value)| value.ty() == IrType::Matrix);
+        if uses_matrices {
+            llvm_ir.push_str(MATRIX_PRELUDE);
+        }
+        let uses_complex = self
+            .values
+            .borrow()
+            .iter()
+            .any(|(_, value)| value.ty() == IrType::Complex);
+        if uses_complex {
+            llvm_ir.push_str(COMPLEX_PRELUDE);
+        }
+
+        // Emit the runtime error handler, which prints the same message as
+        // `RuntimeError::render`
+        llvm_ir.push_str(&emit_trap_handler());
+
+        // Declare the host functions the program calls, which the runtime
+        // it is linked against defines
+        for (name, sig) in &self.functions {
+            let params: Vec<&str> = sig.params.iter().map(|&ty| abi_type(ty.into())).collect();
+            llvm_ir.push_str(&format!(
+                "declare {} @{}({})\n",
+                abi_return_type(sig.ret.into()),
+                name,
+                params.join(", ")
+            ));
+        }
+        if !self.functions.is_empty() {
+            llvm_ir.push('\n');
+        }
+
+        // Emit the text the program prints
+        for (i, text) in self.strings.iter().enumerate() {
+            let (len, text) = c_string(text);
+            llvm_ir.push_str(&format!(
+                "@.text.{} = private unnamed_addr constant [{} x i8] c\"{}\", align 1\n",
+                i, len, text
+            ));
+        }
+        if !self.strings.is_empty() {
+            llvm_ir.push('\n');
+        }
 
         // Emit global variable declarations
-        for (name, id) in &self.global_variables {
-            let global_var = format!("@{} = global i64 0\n", name);
+        let mut globals: Vec<_> = self.global_variables.values().collect();
+        globals.sort_by_key(|id| self.values.borrow()[**id].name());
+        for id in globals {
+            let global = &self.values.borrow()[*id];
+            let ty = global.ty();
+            let global_var = format!(
+                "{} = global {} {}\n",
+                global.name(),
+                llvm_type(ty),
+                zero_value(ty)
+            );
             llvm_ir.push_str(&global_var);
         }
 
         // Emit function definition
-        llvm_ir.push_str("define void @main() {\n");
+        llvm_ir.push_str("define i32 @main() {\n");
 
         // Emit instructions
         for inst_id in &self.instructions {
             let value = &self.values.borrow()[*inst_id];
-            match value {
-                Value::Instruction(instruction) => {
-                    let llvm_instruction = emit_instruction(instruction, self);
-                    llvm_ir.push_str(&llvm_instruction);
-                }
-                _ => (),
+            if let Value::Instruction(instruction) = value {
+                let llvm_instruction = emit_instruction(instruction, self);
+                llvm_ir.push_str(&llvm_instruction);
             }
         }
 
         // Emit function end
-        llvm_ir.push_str("  ret void\n}\n");
+        llvm_ir.push_str("  ret i32 0\n}\n");
         llvm_ir
     }
 }
 
+// the functions of `runtime/bigint.c`, which takes `NULL` for 0
+const BIGINT_DECLARATIONS: &str = "\
+declare i8* @calc_big_from_i64(i64)
+declare i8* @calc_big_from_f64(double)
+declare double @calc_big_to_f64(i8*)
+declare i64 @calc_big_fits_i64(i8*)
+declare i64 @calc_big_to_i64(i8*)
+declare i8* @calc_big_add(i8*, i8*)
+declare i8* @calc_big_sub(i8*, i8*)
+declare i8* @calc_big_mul(i8*, i8*)
+declare i8* @calc_big_div(i8*, i8*)
+declare i8* @calc_big_rem(i8*, i8*)
+declare i8* @calc_big_mod(i8*, i8*)
+declare i8* @calc_big_pow(i8*, i8*)
+declare i8* @calc_big_and(i8*, i8*)
+declare i8* @calc_big_or(i8*, i8*)
+declare i8* @calc_big_xor(i8*, i8*)
+declare i8* @calc_big_shl(i8*, i8*)
+declare i8* @calc_big_shr(i8*, i8*)
+declare i8* @calc_big_gcd(i8*, i8*)
+declare i8* @calc_big_lcm(i8*, i8*)
+declare i8* @calc_big_neg(i8*)
+declare i8* @calc_big_not(i8*)
+declare i8* @calc_big_abs(i8*)
+declare i8* @calc_big_factorial(i8*)
+declare i64 @calc_big_cmp(i8*, i8*)
+declare i64 @calc_big_sign(i8*)
+declare i64 @calc_big_bits(i8*)
+declare void @calc_big_print(i8*)
+declare i8* @calc_big_format(i8*)
+declare i8* @calc_big_parse(i8*)
+
+define void @calc_big_fmt(i8* %x, i8* %fill, i32 %align, i64 %width) {
+  %text = call i8* @calc_big_format(i8* %x)
+  call void @calc_fmt_pad(i8* %text, i8* %fill, i32 %align, i64 %width)
+  call void @free(i8* %text)
+  ret void
+}
+
+";
+
+// what `print` with a format string calls; every `calc_*_fmt` and
+// `calc_fmt_*` function formats a value into a buffer and pads it with
+// `calc_fmt_pad`, as `interpreter::pad` does
+const FORMAT_PRELUDE: &str = r#"@.fmt.s = private unnamed_addr constant [3 x i8] c"%s\00", align 1
+@.fmt.lld = private unnamed_addr constant [5 x i8] c"%lld\00", align 1
+@.fmt.llx = private unnamed_addr constant [5 x i8] c"%llx\00", align 1
+@.fmt.llX = private unnamed_addr constant [5 x i8] c"%llX\00", align 1
+@.fmt.llo = private unnamed_addr constant [5 x i8] c"%llo\00", align 1
+@.fmt.float = private unnamed_addr constant [6 x i8] c"%.15g\00", align 1
+@.fmt.fixed = private unnamed_addr constant [5 x i8] c"%.*f\00", align 1
+@.fmt.true = private unnamed_addr constant [5 x i8] c"true\00", align 1
+@.fmt.false = private unnamed_addr constant [6 x i8] c"false\00", align 1
+@.fmt.zero = private unnamed_addr constant [2 x i8] c"0\00", align 1
+@stdout = external global i8*
+
+declare i32 @snprintf(i8*, i64, i8*, ...)
+declare i64 @strlen(i8*)
+declare i32 @puts(i8*)
+declare i32 @putchar(i32)
+declare i64 @fwrite(i8*, i64, i64, i8*)
+declare i8* @malloc(i64)
+declare void @free(i8*)
+declare void @llvm.memcpy.p0i8.p0i8.i64(i8*, i8*, i64, i1)
+
+; `len` bytes of `text`, which may hold NULs
+define void @calc_fmt_text(i8* %text, i64 %len) {
+  %stdout = load i8*, i8** @stdout
+  %written = call i64 @fwrite(i8* %text, i64 1, i64 %len, i8* %stdout)
+  ret void
+}
+
+define void @calc_fmt_repeat(i8* %text, i64 %count) {
+entry:
+  br label %loop
+loop:
+  %i = phi i64 [ 0, %entry ], [ %i.next, %body ]
+  %done = icmp sge i64 %i, %count
+  br i1 %done, label %exit, label %body
+body:
+  %printed = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.fmt.s, i64 0, i64 0), i8* %text)
+  %i.next = add i64 %i, 1
+  br label %loop
+exit:
+  ret void
+}
+
+; the number of characters in UTF-8 `text`, which are the bytes that do
+; not continue one
+define i64 @calc_utf8_len(i8* %text) {
+entry:
+  br label %loop
+loop:
+  %at = phi i8* [ %text, %entry ], [ %next, %body ]
+  %len = phi i64 [ 0, %entry ], [ %len.next, %body ]
+  %byte = load i8, i8* %at
+  %end = icmp eq i8 %byte, 0
+  br i1 %end, label %exit, label %body
+body:
+  %high = and i8 %byte, -64
+  %lead = icmp ne i8 %high, -128
+  %lead.count = zext i1 %lead to i64
+  %len.next = add i64 %len, %lead.count
+  %next = getelementptr i8, i8* %at, i64 1
+  br label %loop
+exit:
+  ret i64 %len
+}
+
+; `text` padded to `width` characters with copies of `fill`: `align` 0 puts
+; the padding after the text, 1 around it with any odd one after, 2 before
+; it, and 3 pads with zeros after a minus sign
+define void @calc_fmt_pad(i8* %text, i8* %fill, i32 %align, i64 %width) {
+entry:
+  %len = call i64 @calc_utf8_len(i8* %text)
+  %short = icmp slt i64 %len, %width
+  %missing = sub i64 %width, %len
+  %padding = select i1 %short, i64 %missing, i64 0
+  %half = udiv i64 %padding, 2
+  switch i32 %align, label %zeros [ i32 0, label %pad
+                                    i32 1, label %center
+                                    i32 2, label %right ]
+center:
+  br label %pad
+right:
+  br label %pad
+pad:
+  %before = phi i64 [ 0, %entry ], [ %half, %center ], [ %padding, %right ]
+  %after = sub i64 %padding, %before
+  call void @calc_fmt_repeat(i8* %fill, i64 %before)
+  %printed = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.fmt.s, i64 0, i64 0), i8* %text)
+  call void @calc_fmt_repeat(i8* %fill, i64 %after)
+  ret void
+zeros:
+  %first = load i8, i8* %text
+  %minus = icmp eq i8 %first, 45
+  br i1 %minus, label %sign, label %digits
+sign:
+  %sign.printed = call i32 @putchar(i32 45)
+  br label %digits
+digits:
+  %skip = zext i1 %minus to i64
+  %rest = getelementptr i8, i8* %text, i64 %skip
+  call void @calc_fmt_repeat(i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.fmt.zero, i64 0, i64 0), i64 %padding)
+  %digits.printed = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.fmt.s, i64 0, i64 0), i8* %rest)
+  ret void
+}
+
+; `format` is one of the `%ll` ones above
+define void @calc_fmt_int(i64 %x, i8* %format, i8* %fill, i32 %align, i64 %width) {
+  %buffer = alloca [24 x i8]
+  %text = getelementptr inbounds [24 x i8], [24 x i8]* %buffer, i64 0, i64 0
+  %len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 24, i8* %format, i64 %x)
+  call void @calc_fmt_pad(i8* %text, i8* %fill, i32 %align, i64 %width)
+  ret void
+}
+
+; the bits of `x` from the highest one set, as `{:b}` shows an `i64`
+define void @calc_fmt_binary(i64 %x, i8* %fill, i32 %align, i64 %width) {
+entry:
+  %buffer = alloca [65 x i8]
+  %end = getelementptr inbounds [65 x i8], [65 x i8]* %buffer, i64 0, i64 64
+  store i8 0, i8* %end
+  br label %loop
+loop:
+  %rest = phi i64 [ %x, %entry ], [ %rest.next, %loop ]
+  %at = phi i64 [ 64, %entry ], [ %at.next, %loop ]
+  %bit = and i64 %rest, 1
+  %bit.byte = trunc i64 %bit to i8
+  %digit = add i8 %bit.byte, 48
+  %at.next = sub i64 %at, 1
+  %slot = getelementptr inbounds [65 x i8], [65 x i8]* %buffer, i64 0, i64 %at.next
+  store i8 %digit, i8* %slot
+  %rest.next = lshr i64 %rest, 1
+  %more = icmp ne i64 %rest.next, 0
+  br i1 %more, label %loop, label %exit
+exit:
+  call void @calc_fmt_pad(i8* %slot, i8* %fill, i32 %align, i64 %width)
+  ret void
+}
+
+; with 15 significant digits, as `print` shows floats, or with `places`
+; after the point unless it is negative; the buffer holds the largest
+; double with `ast::MAX_PRECISION` places
+define void @calc_fmt_float(double %x, i32 %places, i8* %fill, i32 %align, i64 %width) {
+entry:
+  %buffer = alloca [400 x i8]
+  %text = getelementptr inbounds [400 x i8], [400 x i8]* %buffer, i64 0, i64 0
+  %shown = call double @calc_unsigned_nan(double %x)
+  %fixed = icmp sge i32 %places, 0
+  br i1 %fixed, label %fixed.places, label %general
+fixed.places:
+  %fixed.len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 400, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt.fixed, i64 0, i64 0), i32 %places, double %shown)
+  br label %exit
+general:
+  %general.len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 400, i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.fmt.float, i64 0, i64 0), double %shown)
+  br label %exit
+exit:
+  call void @calc_fmt_pad(i8* %text, i8* %fill, i32 %align, i64 %width)
+  ret void
+}
+
+define void @calc_fmt_bool(i1 %b, i8* %fill, i32 %align, i64 %width) {
+  %text = select i1 %b, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt.true, i64 0, i64 0), i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.fmt.false, i64 0, i64 0)
+  call void @calc_fmt_pad(i8* %text, i8* %fill, i32 %align, i64 %width)
+  ret void
+}
+
+"#;
+
+// the `calc_rat_` helpers; `rational::Rational` computes the same
+fn rational_prelude() -> String {
+    // unpacks `%a` and `%b` into 128-bit terms and their cross products
+    const OPERANDS: &str = "\
+  %a.num = extractvalue {i64, i64} %a, 0
+  %a.den = extractvalue {i64, i64} %a, 1
+  %b.num = extractvalue {i64, i64} %b, 0
+  %b.den = extractvalue {i64, i64} %b, 1
+  %an = sext i64 %a.num to i128
+  %ad = sext i64 %a.den to i128
+  %bn = sext i64 %b.num to i128
+  %bd = sext i64 %b.den to i128
+  %an.bd = mul i128 %an, %bd
+  %bn.ad = mul i128 %bn, %ad
+  %an.bn = mul i128 %an, %bn
+  %ad.bd = mul i128 %ad, %bd
+";
+    let mut prelude = String::from(RATIONAL_PRELUDE);
+    for (name, sum, num, den) in [
+        ("add", "  %sum = add i128 %an.bd, %bn.ad\n", "%sum", "%ad.bd"),
+        ("sub", "  %sum = sub i128 %an.bd, %bn.ad\n", "%sum", "%ad.bd"),
+        ("mul", "", "%an.bn", "%ad.bd"),
+        ("div", "", "%an.bd", "%bn.ad"),
+    ] {
+        prelude.push_str(&format!(
+            "define {{i64, i64}} @calc_rat_{name}({{i64, i64}} %a, {{i64, i64}} %b) {{\n{OPERANDS}{sum}  \
+             %result = call {{i64, i64}} @calc_rat_make(i128 {num}, i128 {den})\n  ret {{i64, i64}} %result\n}}\n\n"
+        ));
+    }
+    prelude.push_str(&format!(
+        "define i64 @calc_rat_cmp({{i64, i64}} %a, {{i64, i64}} %b) {{\n{OPERANDS}\
+  %less = icmp slt i128 %an.bd, %bn.ad
+  %greater = icmp sgt i128 %an.bd, %bn.ad
+  %sign = select i1 %greater, i64 1, i64 0
+  %cmp = select i1 %less, i64 -1, i64 %sign
+  ret i64 %cmp
+}}
+
+"
+    ));
+    prelude
+}
+
+// rationals are `{numerator, denominator}` in lowest terms with a positive
+// denominator; `calc_rat_make` returns a denominator of 0 for fractions
+// whose terms do not fit in 64 bits
+const RATIONAL_PRELUDE: &str = r#"@.str.rat = private unnamed_addr constant [10 x i8] c"%lld/%lld\00", align 1
+@.str.rat.int = private unnamed_addr constant [7 x i8] c"%s%llu\00", align 1
+@.str.rat.decimal = private unnamed_addr constant [14 x i8] c"%s%llu.%0*llu\00", align 1
+@.str.minus = private unnamed_addr constant [2 x i8] c"-\00", align 1
+@.str.empty = private unnamed_addr constant [1 x i8] c"\00", align 1
+
+declare i64 @llvm.cttz.i64(i64, i1)
+
+define {i64, i64} @calc_rat_make(i128 %num, i128 %den) {
+entry:
+  %num.negative = icmp slt i128 %num, 0
+  %num.negated = sub i128 0, %num
+  %num.abs = select i1 %num.negative, i128 %num.negated, i128 %num
+  %den.negative = icmp slt i128 %den, 0
+  %den.negated = sub i128 0, %den
+  %den.abs = select i1 %den.negative, i128 %den.negated, i128 %den
+  br label %loop
+loop:
+  %x = phi i128 [ %num.abs, %entry ], [ %y, %next ]
+  %y = phi i128 [ %den.abs, %entry ], [ %rem, %next ]
+  %done = icmp eq i128 %y, 0
+  br i1 %done, label %exit, label %next
+next:
+  %rem = urem i128 %x, %y
+  br label %loop
+exit:
+  ; dividing by a negative gcd makes the denominator positive
+  %gcd.negated = sub i128 0, %x
+  %gcd = select i1 %den.negative, i128 %gcd.negated, i128 %x
+  %n = sdiv i128 %num, %gcd
+  %d = sdiv i128 %den, %gcd
+  %n.trunc = trunc i128 %n to i64
+  %n.sext = sext i64 %n.trunc to i128
+  %n.fits = icmp eq i128 %n.sext, %n
+  %d.trunc = trunc i128 %d to i64
+  %d.sext = sext i64 %d.trunc to i128
+  %d.fits = icmp eq i128 %d.sext, %d
+  %fits = and i1 %n.fits, %d.fits
+  %den.result = select i1 %fits, i64 %d.trunc, i64 0
+  %result.num = insertvalue {i64, i64} undef, i64 %n.trunc, 0
+  %result = insertvalue {i64, i64} %result.num, i64 %den.result, 1
+  ret {i64, i64} %result
+}
+
+define {i64, i64} @calc_rat_neg({i64, i64} %a) {
+  %a.num = extractvalue {i64, i64} %a, 0
+  %a.den = extractvalue {i64, i64} %a, 1
+  %an = sext i64 %a.num to i128
+  %ad = sext i64 %a.den to i128
+  %num = sub i128 0, %an
+  %result = call {i64, i64} @calc_rat_make(i128 %num, i128 %ad)
+  ret {i64, i64} %result
+}
+
+define i64 @calc_rat_num({i64, i64} %a) {
+  %num = extractvalue {i64, i64} %a, 0
+  ret i64 %num
+}
+
+define i64 @calc_rat_den({i64, i64} %a) {
+  %den = extractvalue {i64, i64} %a, 1
+  ret i64 %den
+}
+
+define double @calc_rat_to_f64({i64, i64} %a) {
+  %num = extractvalue {i64, i64} %a, 0
+  %den = extractvalue {i64, i64} %a, 1
+  %x = sitofp i64 %num to double
+  %y = sitofp i64 %den to double
+  %quotient = fdiv double %x, %y
+  ret double %quotient
+}
+
+define i64 @calc_rat_to_i64({i64, i64} %a) {
+  %num = extractvalue {i64, i64} %a, 0
+  %den = extractvalue {i64, i64} %a, 1
+  %quotient = sdiv i64 %num, %den
+  ret i64 %quotient
+}
+
+; the exact value, a mantissa over a power of two
+define {i64, i64} @calc_rat_from_f64(double %x) {
+entry:
+  %bits = bitcast double %x to i64
+  %negative = icmp slt i64 %bits, 0
+  %exp.shifted = lshr i64 %bits, 52
+  %exp.field = and i64 %exp.shifted, 2047
+  %fraction = and i64 %bits, 4503599627370495
+  %special = icmp eq i64 %exp.field, 2047
+  br i1 %special, label %overflow, label %finite
+finite:
+  %subnormal = icmp eq i64 %exp.field, 0
+  %normal.mantissa = or i64 %fraction, 4503599627370496
+  %mantissa = select i1 %subnormal, i64 %fraction, i64 %normal.mantissa
+  %normal.exp = sub i64 %exp.field, 1075
+  %exp = select i1 %subnormal, i64 -1074, i64 %normal.exp
+  %zero = icmp eq i64 %mantissa, 0
+  br i1 %zero, label %exit.zero, label %nonzero
+exit.zero:
+  ret {i64, i64} {i64 0, i64 1}
+nonzero:
+  %m = zext i64 %mantissa to i128
+  %m.negated = sub i128 0, %m
+  %signed = select i1 %negative, i128 %m.negated, i128 %m
+  %whole = icmp sge i64 %exp, 0
+  br i1 %whole, label %integer, label %fractional
+integer:
+  %too.large = icmp sgt i64 %exp, 63
+  br i1 %too.large, label %overflow, label %shift
+shift:
+  %amount = zext i64 %exp to i128
+  %shifted = shl i128 %signed, %amount
+  %int.result = call {i64, i64} @calc_rat_make(i128 %shifted, i128 1)
+  ret {i64, i64} %int.result
+fractional:
+  ; trailing zero bits of the mantissa cancel against the denominator
+  %zeros = call i64 @llvm.cttz.i64(i64 %mantissa, i1 true)
+  %places = sub i64 0, %exp
+  %fewer = icmp slt i64 %zeros, %places
+  %dropped = select i1 %fewer, i64 %zeros, i64 %places
+  %den.bits = sub i64 %places, %dropped
+  %too.small = icmp sgt i64 %den.bits, 62
+  br i1 %too.small, label %overflow, label %reduce
+reduce:
+  %dropped.wide = zext i64 %dropped to i128
+  %reduced = ashr i128 %signed, %dropped.wide
+  %den.bits.wide = zext i64 %den.bits to i128
+  %den = shl i128 1, %den.bits.wide
+  %fraction.result = call {i64, i64} @calc_rat_make(i128 %reduced, i128 %den)
+  ret {i64, i64} %fraction.result
+overflow:
+  ret {i64, i64} {i64 0, i64 0}
+}
+
+; as a fraction if `digits` is negative, else rounded half away from zero
+; to `digits` places; `scale` is 10 to the power of `digits`, and `text`
+; holds 64 bytes
+define void @calc_rat_format({i64, i64} %a, i32 %digits, i64 %scale, i8* %text) {
+entry:
+  %num = extractvalue {i64, i64} %a, 0
+  %den = extractvalue {i64, i64} %a, 1
+  %as.fraction = icmp slt i32 %digits, 0
+  br i1 %as.fraction, label %fraction, label %decimal
+fraction:
+  %whole = icmp eq i64 %den, 1
+  br i1 %whole, label %integer, label %ratio
+integer:
+  %int.len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 64, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt.lld, i64 0, i64 0), i64 %num)
+  ret void
+ratio:
+  %ratio.len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 64, i8* getelementptr inbounds ([10 x i8], [10 x i8]* @.str.rat, i64 0, i64 0), i64 %num, i64 %den)
+  ret void
+decimal:
+  %n = sext i64 %num to i128
+  %d = sext i64 %den to i128
+  %s = sext i64 %scale to i128
+  %negative = icmp slt i128 %n, 0
+  %n.negated = sub i128 0, %n
+  %n.abs = select i1 %negative, i128 %n.negated, i128 %n
+  %scaled = mul i128 %n.abs, %s
+  %twice = shl i128 %scaled, 1
+  %half.up = add i128 %twice, %d
+  %d.twice = shl i128 %d, 1
+  %rounded = udiv i128 %half.up, %d.twice
+  %int.wide = udiv i128 %rounded, %s
+  %int = trunc i128 %int.wide to i64
+  %frac.wide = urem i128 %rounded, %s
+  %frac = trunc i128 %frac.wide to i64
+  ; no sign if it rounds to zero
+  %nonzero = icmp ne i128 %rounded, 0
+  %minus = and i1 %negative, %nonzero
+  %sign = select i1 %minus, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.str.minus, i64 0, i64 0), i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.empty, i64 0, i64 0)
+  %no.places = icmp eq i32 %digits, 0
+  br i1 %no.places, label %decimal.int, label %decimal.places
+decimal.int:
+  %decimal.int.len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 64, i8* getelementptr inbounds ([7 x i8], [7 x i8]* @.str.rat.int, i64 0, i64 0), i8* %sign, i64 %int)
+  ret void
+decimal.places:
+  %places.len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 64, i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.rat.decimal, i64 0, i64 0), i8* %sign, i64 %int, i32 %digits, i64 %frac)
+  ret void
+}
+
+define void @calc_rat_print({i64, i64} %a, i32 %digits, i64 %scale) {
+  %buffer = alloca [64 x i8]
+  %text = getelementptr inbounds [64 x i8], [64 x i8]* %buffer, i64 0, i64 0
+  call void @calc_rat_format({i64, i64} %a, i32 %digits, i64 %scale, i8* %text)
+  %printed = call i32 @puts(i8* %text)
+  ret void
+}
+
+define void @calc_rat_fmt({i64, i64} %a, i32 %digits, i64 %scale, i8* %fill, i32 %align, i64 %width) {
+  %buffer = alloca [64 x i8]
+  %text = getelementptr inbounds [64 x i8], [64 x i8]* %buffer, i64 0, i64 0
+  call void @calc_rat_format({i64, i64} %a, i32 %digits, i64 %scale, i8* %text)
+  call void @calc_fmt_pad(i8* %text, i8* %fill, i32 %align, i64 %width)
+  ret void
+}
+
+"#;
+
+// the `calc_dec_` helpers, with the scale of the program built in;
+// `decimal::Decimal` computes the same
+fn decimal_prelude(precision: Precision, rationals: bool) -> String {
+    let scale = precision.decimal_scale;
+    let unit = 10i64.pow(scale);
+    // the `mode` of `calc_dec_round`
+    let mode = match precision.rounding {
+        Rounding::HalfEven => 0,
+        Rounding::HalfUp => 1,
+        Rounding::Truncate => 2,
+    };
+    let mut prelude = String::from(DECIMAL_PRELUDE);
+    for (name, num, den, mode) in [
+        ("add", "add i128 %x, %y".to_string(), "1", 2),
+        ("sub", "sub i128 %x, %y".to_string(), "1", 2),
+        ("mul", "mul i128 %x, %y".to_string(), &unit.to_string(), mode),
+        ("div", format!("mul i128 %x, {unit}"), "%y", mode),
+    ] {
+        prelude.push_str(&format!(
+            "define i64 @calc_dec_{name}(i64 %a, i64 %b) {{
+  %x = sext i64 %a to i128
+  %y = sext i64 %b to i128
+  %num = {num}
+  %result = call i64 @calc_dec_round(i128 %num, i128 {den}, i32 {mode})
+  ret i64 %result
+}}
+
+"
+        ));
+    }
+    let unit_f64 = format!("0x{:016X}", (unit as f64).to_bits());
+    prelude.push_str(&format!(
+        "define i64 @calc_dec_from_i64(i64 %a) {{
+  %x = sext i64 %a to i128
+  %num = mul i128 %x, {unit}
+  %result = call i64 @calc_dec_round(i128 %num, i128 1, i32 2)
+  ret i64 %result
+}}
+
+define i64 @calc_dec_from_rat({{i64, i64}} %a) {{
+  %a.num = extractvalue {{i64, i64}} %a, 0
+  %a.den = extractvalue {{i64, i64}} %a, 1
+  %x = sext i64 %a.num to i128
+  %y = sext i64 %a.den to i128
+  %num = mul i128 %x, {unit}
+  %result = call i64 @calc_dec_round(i128 %num, i128 %y, i32 {mode})
+  ret i64 %result
+}}
+
+define i64 @calc_dec_to_i64(i64 %a) {{
+  %quotient = sdiv i64 %a, {unit}
+  ret i64 %quotient
+}}
+
+define double @calc_dec_to_f64(i64 %a) {{
+  %x = sitofp i64 %a to double
+  %quotient = fdiv double %x, {unit_f64}
+  ret double %quotient
+}}
+
+"
+    ));
+    if rationals {
+        prelude.push_str(&format!(
+            "define {{i64, i64}} @calc_dec_to_rat(i64 %a) {{
+  %x = sext i64 %a to i128
+  %result = call {{i64, i64}} @calc_rat_make(i128 %x, i128 {unit})
+  ret {{i64, i64}} %result
+}}
+
+"
+        ));
+    }
+    // `%s%llu.%0*llu` needs at least one place
+    let format = match scale {
+        0 => "  %len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 48, i8* getelementptr inbounds ([7 x i8], [7 x i8]* @.str.dec.int, i64 0, i64 0), i8* %sign, i64 %int)".to_string(),
+        _ => format!("  %len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 48, i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.dec, i64 0, i64 0), i8* %sign, i64 %int, i32 {scale}, i64 %frac)"),
+    };
+    prelude.push_str(&format!(
+        "; always with all places of the scale, into the 48 bytes of `text`
+define void @calc_dec_format(i64 %a, i8* %text) {{
+  %negative = icmp slt i64 %a, 0
+  %negated = sub i64 0, %a
+  %abs = select i1 %negative, i64 %negated, i64 %a
+  %int = udiv i64 %abs, {unit}
+  %frac = urem i64 %abs, {unit}
+  %sign = select i1 %negative, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.str.dec.minus, i64 0, i64 0), i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.dec.empty, i64 0, i64 0)
+{format}
+  ret void
+}}
+
+define void @calc_dec_print(i64 %a) {{
+  %buffer = alloca [48 x i8]
+  %text = getelementptr inbounds [48 x i8], [48 x i8]* %buffer, i64 0, i64 0
+  call void @calc_dec_format(i64 %a, i8* %text)
+  %printed = call i32 @puts(i8* %text)
+  ret void
+}}
+
+define void @calc_dec_fmt(i64 %a, i8* %fill, i32 %align, i64 %width) {{
+  %buffer = alloca [48 x i8]
+  %text = getelementptr inbounds [48 x i8], [48 x i8]* %buffer, i64 0, i64 0
+  call void @calc_dec_format(i64 %a, i8* %text)
+  call void @calc_fmt_pad(i8* %text, i8* %fill, i32 %align, i64 %width)
+  ret void
+}}
+
+"
+    ));
+    prelude
+}
+
+// decimals are their units as an `i64`, and `i64::MIN` marks results that
+// overflowed
+const DECIMAL_PRELUDE: &str = r#"@.str.dec = private unnamed_addr constant [14 x i8] c"%s%llu.%0*llu\00", align 1
+@.str.dec.int = private unnamed_addr constant [7 x i8] c"%s%llu\00", align 1
+@.str.dec.minus = private unnamed_addr constant [2 x i8] c"-\00", align 1
+@.str.dec.empty = private unnamed_addr constant [1 x i8] c"\00", align 1
+
+; `num / den` rounded to a whole number: `mode` 0 rounds half to even, 1
+; half away from zero, and 2 truncates
+define i64 @calc_dec_round(i128 %num, i128 %den, i32 %mode) {
+  %quotient = sdiv i128 %num, %den
+  %rem = srem i128 %num, %den
+  %rem.negative = icmp slt i128 %rem, 0
+  %rem.negated = sub i128 0, %rem
+  %rem.abs = select i1 %rem.negative, i128 %rem.negated, i128 %rem
+  %twice = shl i128 %rem.abs, 1
+  %den.negative = icmp slt i128 %den, 0
+  %den.negated = sub i128 0, %den
+  %den.abs = select i1 %den.negative, i128 %den.negated, i128 %den
+  %above = icmp ugt i128 %twice, %den.abs
+  %tie = icmp eq i128 %twice, %den.abs
+  %odd = trunc i128 %quotient to i1
+  %tie.odd = and i1 %tie, %odd
+  %half.even = or i1 %above, %tie.odd
+  %half.up = or i1 %above, %tie
+  %is.half.up = icmp eq i32 %mode, 1
+  %nearest = select i1 %is.half.up, i1 %half.up, i1 %half.even
+  %is.truncate = icmp eq i32 %mode, 2
+  %away = select i1 %is.truncate, i1 false, i1 %nearest
+  %num.negative = icmp slt i128 %num, 0
+  %negative = xor i1 %num.negative, %den.negative
+  %step = select i1 %negative, i128 -1, i128 1
+  %stepped = add i128 %quotient, %step
+  %units = select i1 %away, i128 %stepped, i128 %quotient
+  ; `i64::MIN` is the overflow marker, so it does not fit either
+  %too.small = icmp sle i128 %units, -9223372036854775808
+  %too.large = icmp sgt i128 %units, 9223372036854775807
+  %overflows = or i1 %too.small, %too.large
+  %truncated = trunc i128 %units to i64
+  %result = select i1 %overflows, i64 -9223372036854775808, i64 %truncated
+  ret i64 %result
+}
+
+; `x` has already been scaled and rounded
+define i64 @calc_dec_from_f64(double %x) {
+  %above.min = fcmp ogt double %x, 0xC3E0000000000000
+  %below.max = fcmp olt double %x, 0x43E0000000000000
+  %fits = and i1 %above.min, %below.max
+  %units = fptosi double %x to i64
+  %result = select i1 %fits, i64 %units, i64 -9223372036854775808
+  ret i64 %result
+}
+
+"#;
+
+// the `calc_str_` helpers, which compute what `interpreter::eval_text_builtin`
+// and the operators on `str`s do; text is never freed
+const STRING_PRELUDE: &str = r#"declare i32 @strcmp(i8*, i8*)
+
+define void @calc_str_print(i8* %text) {
+  %printed = call i32 @puts(i8* %text)
+  ret void
+}
+
+define i64 @calc_str_cmp(i8* %a, i8* %b) {
+  %cmp = call i32 @strcmp(i8* %a, i8* %b)
+  %result = sext i32 %cmp to i64
+  ret i64 %result
+}
+
+define i8* @calc_str_concat(i8* %a, i8* %b) {
+  %a.len = call i64 @strlen(i8* %a)
+  %b.len = call i64 @strlen(i8* %b)
+  %len = add i64 %a.len, %b.len
+  %size = add i64 %len, 1
+  %text = call i8* @malloc(i64 %size)
+  call void @llvm.memcpy.p0i8.p0i8.i64(i8* %text, i8* %a, i64 %a.len, i1 false)
+  %rest = getelementptr i8, i8* %text, i64 %a.len
+  %b.size = add i64 %b.len, 1
+  call void @llvm.memcpy.p0i8.p0i8.i64(i8* %rest, i8* %b, i64 %b.size, i1 false)
+  ret i8* %text
+}
+
+; the character `count` characters after the one at `text`, or its end
+define i8* @calc_utf8_skip(i8* %text, i64 %count) {
+entry:
+  br label %loop
+loop:
+  %at = phi i8* [ %text, %entry ], [ %next, %step ]
+  %left = phi i64 [ %count, %entry ], [ %left.next, %step ]
+  %byte = load i8, i8* %at
+  %high = and i8 %byte, -64
+  %lead = icmp ne i8 %high, -128
+  %none.left = icmp eq i64 %left, 0
+  %done = and i1 %lead, %none.left
+  br i1 %done, label %exit, label %step
+step:
+  %lead.count = zext i1 %lead to i64
+  %left.next = sub i64 %left, %lead.count
+  %next = getelementptr i8, i8* %at, i64 1
+  br label %loop
+exit:
+  ret i8* %at
+}
+
+; the caller has checked that the characters are there
+define i8* @calc_str_substr(i8* %text, i64 %start, i64 %count) {
+  %begin = call i8* @calc_utf8_skip(i8* %text, i64 %start)
+  %end = call i8* @calc_utf8_skip(i8* %begin, i64 %count)
+  %begin.at = ptrtoint i8* %begin to i64
+  %end.at = ptrtoint i8* %end to i64
+  %len = sub i64 %end.at, %begin.at
+  %size = add i64 %len, 1
+  %sub = call i8* @malloc(i64 %size)
+  call void @llvm.memcpy.p0i8.p0i8.i64(i8* %sub, i8* %begin, i64 %len, i1 false)
+  %nul = getelementptr i8, i8* %sub, i64 %len
+  store i8 0, i8* %nul
+  ret i8* %sub
+}
+
+define i8* @calc_str_from_i64(i64 %x) {
+  %text = call i8* @malloc(i64 24)
+  %len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 24, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt.lld, i64 0, i64 0), i64 %x)
+  ret i8* %text
+}
+
+; an optional sign and decimal digits, as the value and a status of 0 for
+; an `int`, 1 for digits beyond 64 bits and 2 for anything else; the value
+; is accumulated below zero, which reaches `i64::MIN`
+define {i64, i64} @calc_str_scan_int(i8* %text) {
+entry:
+  %first = load i8, i8* %text
+  %minus = icmp eq i8 %first, 45
+  %plus = icmp eq i8 %first, 43
+  %signed = or i1 %minus, %plus
+  %skip = zext i1 %signed to i64
+  %digits = getelementptr i8, i8* %text, i64 %skip
+  %digits.first = load i8, i8* %digits
+  %empty = icmp eq i8 %digits.first, 0
+  br i1 %empty, label %invalid, label %loop
+loop:
+  %at = phi i8* [ %digits, %entry ], [ %next, %digit ]
+  %value = phi i64 [ 0, %entry ], [ %value.next, %digit ]
+  %overflow = phi i1 [ false, %entry ], [ %overflow.next, %digit ]
+  %byte = load i8, i8* %at
+  %end = icmp eq i8 %byte, 0
+  br i1 %end, label %exit, label %check
+check:
+  %digit.byte = sub i8 %byte, 48
+  %is.digit = icmp ult i8 %digit.byte, 10
+  br i1 %is.digit, label %digit, label %invalid
+digit:
+  %digit.value = zext i8 %digit.byte to i64
+  %shifted.result = call {i64, i1} @llvm.smul.with.overflow.i64(i64 %value, i64 10)
+  %shifted = extractvalue {i64, i1} %shifted.result, 0
+  %shifted.overflow = extractvalue {i64, i1} %shifted.result, 1
+  %added.result = call {i64, i1} @llvm.ssub.with.overflow.i64(i64 %shifted, i64 %digit.value)
+  %value.next = extractvalue {i64, i1} %added.result, 0
+  %added.overflow = extractvalue {i64, i1} %added.result, 1
+  %step.overflow = or i1 %shifted.overflow, %added.overflow
+  %overflow.next = or i1 %overflow, %step.overflow
+  %next = getelementptr i8, i8* %at, i64 1
+  br label %loop
+exit:
+  ; `i64::MIN` has no positive counterpart
+  %negated = sub i64 0, %value
+  %result.value = select i1 %minus, i64 %value, i64 %negated
+  %min = icmp eq i64 %value, -9223372036854775808
+  %positive = xor i1 %minus, true
+  %too.large = and i1 %positive, %min
+  %overflows = or i1 %overflow, %too.large
+  %status = zext i1 %overflows to i64
+  %value.result = insertvalue {i64, i64} undef, i64 %result.value, 0
+  %result = insertvalue {i64, i64} %value.result, i64 %status, 1
+  ret {i64, i64} %result
+invalid:
+  ret {i64, i64} {i64 0, i64 2}
+}
+
+define i64 @calc_str_int_status(i8* %text) {
+  %scan = call {i64, i64} @calc_str_scan_int(i8* %text)
+  %status = extractvalue {i64, i64} %scan, 1
+  ret i64 %status
+}
+
+define i64 @calc_str_to_i64(i8* %text) {
+  %scan = call {i64, i64} @calc_str_scan_int(i8* %text)
+  %value = extractvalue {i64, i64} %scan, 0
+  ret i64 %value
+}
+
+"#;
+
+// the `calc_array_` helpers; arrays are `{i64, i64*}`, their length and
+// their elements, as `ir::IrType::Array` describes them
+const ARRAY_PRELUDE: &str = r#"@.str.array.open = private unnamed_addr constant [2 x i8] c"[\00", align 1
+@.str.array.separator = private unnamed_addr constant [3 x i8] c", \00", align 1
+
+define i64 @calc_array_len({i64, i64*} %array) {
+  %len = extractvalue {i64, i64*} %array, 0
+  ret i64 %len
+}
+
+define {i64, i64*} @calc_array_new(i64 %len) {
+  %size = mul i64 %len, 8
+  %bytes = call i8* @malloc(i64 %size)
+  %data = bitcast i8* %bytes to i64*
+  %array.len = insertvalue {i64, i64*} undef, i64 %len, 0
+  %array = insertvalue {i64, i64*} %array.len, i64* %data, 1
+  ret {i64, i64*} %array
+}
+
+; `array` with every element set to `value`
+define {i64, i64*} @calc_array_fill({i64, i64*} %array, i64 %value) {
+entry:
+  %len = extractvalue {i64, i64*} %array, 0
+  %data = extractvalue {i64, i64*} %array, 1
+  br label %loop
+loop:
+  %i = phi i64 [ 0, %entry ], [ %i.next, %body ]
+  %done = icmp sge i64 %i, %len
+  br i1 %done, label %exit, label %body
+body:
+  %element = getelementptr inbounds i64, i64* %data, i64 %i
+  store i64 %value, i64* %element
+  %i.next = add i64 %i, 1
+  br label %loop
+exit:
+  ret {i64, i64*} %array
+}
+
+define {i64, i64*} @calc_array_copy({i64, i64*} %array) {
+  %len = extractvalue {i64, i64*} %array, 0
+  %data = extractvalue {i64, i64*} %array, 1
+  %copy = call {i64, i64*} @calc_array_new(i64 %len)
+  %copy.data = extractvalue {i64, i64*} %copy, 1
+  %to = bitcast i64* %copy.data to i8*
+  %from = bitcast i64* %data to i8*
+  %size = mul i64 %len, 8
+  call void @llvm.memcpy.p0i8.p0i8.i64(i8* %to, i8* %from, i64 %size, i1 false)
+  ret {i64, i64*} %copy
+}
+
+; 0 when `a` and `b` have the same elements, which is all `==` asks
+define i64 @calc_array_cmp({i64, i64*} %a, {i64, i64*} %b) {
+entry:
+  %len = extractvalue {i64, i64*} %a, 0
+  %b.len = extractvalue {i64, i64*} %b, 0
+  %a.data = extractvalue {i64, i64*} %a, 1
+  %b.data = extractvalue {i64, i64*} %b, 1
+  %same.len = icmp eq i64 %len, %b.len
+  br i1 %same.len, label %loop, label %differ
+loop:
+  %i = phi i64 [ 0, %entry ], [ %i.next, %body ]
+  %done = icmp sge i64 %i, %len
+  br i1 %done, label %exit, label %body
+body:
+  %a.at = getelementptr inbounds i64, i64* %a.data, i64 %i
+  %b.at = getelementptr inbounds i64, i64* %b.data, i64 %i
+  %a.element = load i64, i64* %a.at
+  %b.element = load i64, i64* %b.at
+  %i.next = add i64 %i, 1
+  %same = icmp eq i64 %a.element, %b.element
+  br i1 %same, label %loop, label %differ
+differ:
+  ret i64 1
+exit:
+  ret i64 0
+}
+
+; `[1, 2, 3]`, as `interpreter::Value` shows arrays, in a buffer the caller
+; frees: each element takes at most 20 characters and a separator
+define i8* @calc_array_format({i64, i64*} %array) {
+entry:
+  %len = extractvalue {i64, i64*} %array, 0
+  %data = extractvalue {i64, i64*} %array, 1
+  %element.size = mul i64 %len, 22
+  %size = add i64 %element.size, 3
+  %text = call i8* @malloc(i64 %size)
+  %open = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 2, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.str.array.open, i64 0, i64 0))
+  %start = getelementptr i8, i8* %text, i64 1
+  br label %loop
+loop:
+  %i = phi i64 [ 0, %entry ], [ %i.next, %element ]
+  %at = phi i8* [ %start, %entry ], [ %at.next, %element ]
+  %done = icmp sge i64 %i, %len
+  br i1 %done, label %exit, label %body
+body:
+  %first = icmp eq i64 %i, 0
+  br i1 %first, label %element, label %separator
+separator:
+  %separated = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %at, i64 3, i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.str.array.separator, i64 0, i64 0))
+  %after.separator = getelementptr i8, i8* %at, i64 2
+  br label %element
+element:
+  %element.at = phi i8* [ %at, %body ], [ %after.separator, %separator ]
+  %slot = getelementptr inbounds i64, i64* %data, i64 %i
+  %value = load i64, i64* %slot
+  %written = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %element.at, i64 21, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt.lld, i64 0, i64 0), i64 %value)
+  %written.len = sext i32 %written to i64
+  %at.next = getelementptr i8, i8* %element.at, i64 %written.len
+  %i.next = add i64 %i, 1
+  br label %loop
+exit:
+  store i8 93, i8* %at
+  %end = getelementptr i8, i8* %at, i64 1
+  store i8 0, i8* %end
+  ret i8* %text
+}
+
+define void @calc_array_print({i64, i64*} %array) {
+  %text = call i8* @calc_array_format({i64, i64*} %array)
+  %printed = call i32 @puts(i8* %text)
+  call void @free(i8* %text)
+  ret void
+}
+
+define void @calc_array_fmt({i64, i64*} %array, i8* %fill, i32 %align, i64 %width) {
+  %text = call i8* @calc_array_format({i64, i64*} %array)
+  call void @calc_fmt_pad(i8* %text, i8* %fill, i32 %align, i64 %width)
+  call void @free(i8* %text)
+  ret void
+}
+
+"#;
+
+// the `calc_complex_` helpers; the arithmetic on complex numbers but
+// division is expanded inline by `emit_complex_*`
+const COMPLEX_PRELUDE: &str = r#"@.str.complex.plus = private unnamed_addr constant [2 x i8] c"+\00", align 1
+@.str.complex.minus = private unnamed_addr constant [2 x i8] c"-\00", align 1
+@.str.complex.angle = private unnamed_addr constant [4 x i8] c"\E2\88\A0\00", align 1
+@.str.complex.i = private unnamed_addr constant [2 x i8] c"i\00", align 1
+@.str.complex.none = private unnamed_addr constant [1 x i8] c"\00", align 1
+@.str.complex.general = private unnamed_addr constant [15 x i8] c"%.15g%s%.15g%s\00", align 1
+@.str.complex.fixed = private unnamed_addr constant [13 x i8] c"%.*f%s%.*f%s\00", align 1
+
+define {double, double} @calc_complex_new(double %re, double %im) {
+  %part = insertvalue {double, double} undef, double %re, 0
+  %z = insertvalue {double, double} %part, double %im, 1
+  ret {double, double} %z
+}
+
+define double @calc_complex_re({double, double} %z) {
+  %re = extractvalue {double, double} %z, 0
+  ret double %re
+}
+
+define double @calc_complex_im({double, double} %z) {
+  %im = extractvalue {double, double} %z, 1
+  ret double %im
+}
+
+; `a / b` by Smith's algorithm, in the order `complex::Complex` computes it
+define {double, double} @calc_complex_div({double, double} %a, {double, double} %b) {
+entry:
+  %a.re = extractvalue {double, double} %a, 0
+  %a.im = extractvalue {double, double} %a, 1
+  %b.re = extractvalue {double, double} %b, 0
+  %b.im = extractvalue {double, double} %b, 1
+  %re.negative = fcmp olt double %b.re, 0.0
+  %re.negated = fneg double %b.re
+  %re.abs = select i1 %re.negative, double %re.negated, double %b.re
+  %im.negative = fcmp olt double %b.im, 0.0
+  %im.negated = fneg double %b.im
+  %im.abs = select i1 %im.negative, double %im.negated, double %b.im
+  %re.larger = fcmp oge double %re.abs, %im.abs
+  br i1 %re.larger, label %by.re, label %by.im
+by.re:
+  %re.ratio = fdiv double %b.im, %b.re
+  %re.square = fmul double %re.ratio, %re.ratio
+  %re.den = fadd double 1.0, %re.square
+  %re.x = fdiv double %a.re, %b.re
+  %re.y = fdiv double %a.im, %b.re
+  %re.y.ratio = fmul double %re.y, %re.ratio
+  %re.num.re = fadd double %re.x, %re.y.ratio
+  %re.x.ratio = fmul double %re.x, %re.ratio
+  %re.num.im = fsub double %re.y, %re.x.ratio
+  %re.re = fdiv double %re.num.re, %re.den
+  %re.im = fdiv double %re.num.im, %re.den
+  br label %exit
+by.im:
+  %im.ratio = fdiv double %b.re, %b.im
+  %im.square = fmul double %im.ratio, %im.ratio
+  %im.den = fadd double 1.0, %im.square
+  %im.x = fdiv double %a.re, %b.im
+  %im.y = fdiv double %a.im, %b.im
+  %im.x.ratio = fmul double %im.x, %im.ratio
+  %im.num.re = fadd double %im.x.ratio, %im.y
+  %im.y.ratio = fmul double %im.y, %im.ratio
+  %im.num.im = fsub double %im.y.ratio, %im.x
+  %im.re = fdiv double %im.num.re, %im.den
+  %im.im = fdiv double %im.num.im, %im.den
+  br label %exit
+exit:
+  %re = phi double [ %re.re, %by.re ], [ %im.re, %by.im ]
+  %im = phi double [ %re.im, %by.re ], [ %im.im, %by.im ]
+  %part = insertvalue {double, double} undef, double %re, 0
+  %z = insertvalue {double, double} %part, double %im, 1
+  ret {double, double} %z
+}
+
+define {double, double} @calc_complex_conj({double, double} %z) {
+  %im = extractvalue {double, double} %z, 1
+  %negated = fneg double %im
+  %conj = insertvalue {double, double} %z, double %negated, 1
+  ret {double, double} %conj
+}
+
+; `z` as `complex::Complex::format` shows it, in a new buffer, with 15
+; significant digits, or with `places` after the point unless it is
+; negative; in polar form `z` holds the magnitude and the angle already.
+; The buffer holds two parts as large as `calc_fmt_float` does
+define i8* @calc_complex_format({double, double} %z, i1 %polar, i32 %places) {
+entry:
+  %re = extractvalue {double, double} %z, 0
+  %im = extractvalue {double, double} %z, 1
+  %re.shown = call double @calc_unsigned_nan(double %re)
+  %im.shown = call double @calc_unsigned_nan(double %im)
+  %bits = bitcast double %im.shown to i64
+  %negative = icmp slt i64 %bits, 0
+  %magnitude.bits = and i64 %bits, 9223372036854775807
+  %magnitude = bitcast i64 %magnitude.bits to double
+  %sign = select i1 %negative, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.str.complex.minus, i64 0, i64 0), i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.str.complex.plus, i64 0, i64 0)
+  %separator = select i1 %polar, i8* getelementptr inbounds ([4 x i8], [4 x i8]* @.str.complex.angle, i64 0, i64 0), i8* %sign
+  %second = select i1 %polar, double %im.shown, double %magnitude
+  %suffix = select i1 %polar, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.complex.none, i64 0, i64 0), i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.str.complex.i, i64 0, i64 0)
+  %text = call i8* @malloc(i64 808)
+  %fixed = icmp sge i32 %places, 0
+  br i1 %fixed, label %fixed.places, label %general
+fixed.places:
+  %fixed.len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 808, i8* getelementptr inbounds ([13 x i8], [13 x i8]* @.str.complex.fixed, i64 0, i64 0), i32 %places, double %re.shown, i8* %separator, i32 %places, double %second, i8* %suffix)
+  br label %exit
+general:
+  %general.len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %text, i64 808, i8* getelementptr inbounds ([15 x i8], [15 x i8]* @.str.complex.general, i64 0, i64 0), double %re.shown, i8* %separator, double %second, i8* %suffix)
+  br label %exit
+exit:
+  ret i8* %text
+}
+
+define void @calc_complex_print({double, double} %z) {
+  %text = call i8* @calc_complex_format({double, double} %z, i1 false, i32 -1)
+  %printed = call i32 @puts(i8* %text)
+  call void @free(i8* %text)
+  ret void
+}
+
+define void @calc_complex_fmt({double, double} %z, i1 %polar, i32 %places, i8* %fill, i32 %align, i64 %width) {
+  %text = call i8* @calc_complex_format({double, double} %z, i1 %polar, i32 %places)
+  call void @calc_fmt_pad(i8* %text, i8* %fill, i32 %align, i64 %width)
+  call void @free(i8* %text)
+  ret void
+}
+
+"#;
+
+// the `calc_matrix_` helpers, which compute what `matrix::Matrix` does in
+// the same order; matrices are `{i64, i64, double*}`, as
+// `ir::IrType::Matrix` describes them
+const MATRIX_PRELUDE: &str = r#"declare void @llvm.memset.p0i8.i64(i8*, i8, i64, i1)
+
+define {i64, i64, double*} @calc_matrix_new(i64 %rows, i64 %cols) {
+  %len = mul i64 %rows, %cols
+  %size = mul i64 %len, 8
+  %bytes = call i8* @malloc(i64 %size)
+  %data = bitcast i8* %bytes to double*
+  %m.rows = insertvalue {i64, i64, double*} undef, i64 %rows, 0
+  %m.cols = insertvalue {i64, i64, double*} %m.rows, i64 %cols, 1
+  %m = insertvalue {i64, i64, double*} %m.cols, double* %data, 2
+  ret {i64, i64, double*} %m
+}
+
+; a copy of the elements of `m`, which the caller frees
+define double* @calc_matrix_copy_data({i64, i64, double*} %m) {
+  %rows = extractvalue {i64, i64, double*} %m, 0
+  %cols = extractvalue {i64, i64, double*} %m, 1
+  %data = extractvalue {i64, i64, double*} %m, 2
+  %len = mul i64 %rows, %cols
+  %size = mul i64 %len, 8
+  %bytes = call i8* @malloc(i64 %size)
+  %from = bitcast double* %data to i8*
+  call void @llvm.memcpy.p0i8.p0i8.i64(i8* %bytes, i8* %from, i64 %size, i1 false)
+  %copy = bitcast i8* %bytes to double*
+  ret double* %copy
+}
+
+; `a + b`, or `a - b` if `subtract` is set
+define {i64, i64, double*} @calc_matrix_combine({i64, i64, double*} %a, {i64, i64, double*} %b, i1 %subtract) {
+entry:
+  %rows = extractvalue {i64, i64, double*} %a, 0
+  %cols = extractvalue {i64, i64, double*} %a, 1
+  %a.data = extractvalue {i64, i64, double*} %a, 2
+  %b.data = extractvalue {i64, i64, double*} %b, 2
+  %len = mul i64 %rows, %cols
+  %result = call {i64, i64, double*} @calc_matrix_new(i64 %rows, i64 %cols)
+  %data = extractvalue {i64, i64, double*} %result, 2
+  br label %loop
+loop:
+  %i = phi i64 [ 0, %entry ], [ %i.next, %body ]
+  %done = icmp sge i64 %i, %len
+  br i1 %done, label %exit, label %body
+body:
+  %a.at = getelementptr inbounds double, double* %a.data, i64 %i
+  %b.at = getelementptr inbounds double, double* %b.data, i64 %i
+  %x = load double, double* %a.at
+  %y = load double, double* %b.at
+  %sum = fadd double %x, %y
+  %difference = fsub double %x, %y
+  %z = select i1 %subtract, double %difference, double %sum
+  %at = getelementptr inbounds double, double* %data, i64 %i
+  store double %z, double* %at
+  %i.next = add i64 %i, 1
+  br label %loop
+exit:
+  ret {i64, i64, double*} %result
+}
+
+define {i64, i64, double*} @calc_matrix_add({i64, i64, double*} %a, {i64, i64, double*} %b) {
+  %sum = call {i64, i64, double*} @calc_matrix_combine({i64, i64, double*} %a, {i64, i64, double*} %b, i1 false)
+  ret {i64, i64, double*} %sum
+}
+
+define {i64, i64, double*} @calc_matrix_sub({i64, i64, double*} %a, {i64, i64, double*} %b) {
+  %difference = call {i64, i64, double*} @calc_matrix_combine({i64, i64, double*} %a, {i64, i64, double*} %b, i1 true)
+  ret {i64, i64, double*} %difference
+}
+
+define {i64, i64, double*} @calc_matrix_scale({i64, i64, double*} %m, double %factor) {
+entry:
+  %rows = extractvalue {i64, i64, double*} %m, 0
+  %cols = extractvalue {i64, i64, double*} %m, 1
+  %m.data = extractvalue {i64, i64, double*} %m, 2
+  %len = mul i64 %rows, %cols
+  %result = call {i64, i64, double*} @calc_matrix_new(i64 %rows, i64 %cols)
+  %data = extractvalue {i64, i64, double*} %result, 2
+  br label %loop
+loop:
+  %i = phi i64 [ 0, %entry ], [ %i.next, %body ]
+  %done = icmp sge i64 %i, %len
+  br i1 %done, label %exit, label %body
+body:
+  %m.at = getelementptr inbounds double, double* %m.data, i64 %i
+  %x = load double, double* %m.at
+  %y = fmul double %x, %factor
+  %at = getelementptr inbounds double, double* %data, i64 %i
+  store double %y, double* %at
+  %i.next = add i64 %i, 1
+  br label %loop
+exit:
+  ret {i64, i64, double*} %result
+}
+
+define {i64, i64, double*} @calc_matrix_mul({i64, i64, double*} %a, {i64, i64, double*} %b) {
+entry:
+  %rows = extractvalue {i64, i64, double*} %a, 0
+  %inner = extractvalue {i64, i64, double*} %a, 1
+  %cols = extractvalue {i64, i64, double*} %b, 1
+  %a.data = extractvalue {i64, i64, double*} %a, 2
+  %b.data = extractvalue {i64, i64, double*} %b, 2
+  %result = call {i64, i64, double*} @calc_matrix_new(i64 %rows, i64 %cols)
+  %data = extractvalue {i64, i64, double*} %result, 2
+  br label %rows.loop
+rows.loop:
+  %i = phi i64 [ 0, %entry ], [ %i.next, %rows.next ]
+  %rows.done = icmp sge i64 %i, %rows
+  br i1 %rows.done, label %exit, label %cols.loop
+cols.loop:
+  %j = phi i64 [ 0, %rows.loop ], [ %j.next, %cols.next ]
+  %cols.done = icmp sge i64 %j, %cols
+  br i1 %cols.done, label %rows.next, label %sum.loop
+sum.loop:
+  %k = phi i64 [ 0, %cols.loop ], [ %k.next, %sum.body ]
+  %sum = phi double [ 0.0, %cols.loop ], [ %sum.next, %sum.body ]
+  %sum.done = icmp sge i64 %k, %inner
+  br i1 %sum.done, label %cols.next, label %sum.body
+sum.body:
+  %a.row = mul i64 %i, %inner
+  %a.index = add i64 %a.row, %k
+  %a.at = getelementptr inbounds double, double* %a.data, i64 %a.index
+  %b.row = mul i64 %k, %cols
+  %b.index = add i64 %b.row, %j
+  %b.at = getelementptr inbounds double, double* %b.data, i64 %b.index
+  %x = load double, double* %a.at
+  %y = load double, double* %b.at
+  %term = fmul double %x, %y
+  %sum.next = fadd double %sum, %term
+  %k.next = add i64 %k, 1
+  br label %sum.loop
+cols.next:
+  %row = mul i64 %i, %cols
+  %index = add i64 %row, %j
+  %at = getelementptr inbounds double, double* %data, i64 %index
+  store double %sum, double* %at
+  %j.next = add i64 %j, 1
+  br label %cols.loop
+rows.next:
+  %i.next = add i64 %i, 1
+  br label %rows.loop
+exit:
+  ret {i64, i64, double*} %result
+}
+
+define {i64, i64, double*} @calc_matrix_transpose({i64, i64, double*} %m) {
+entry:
+  %rows = extractvalue {i64, i64, double*} %m, 0
+  %cols = extractvalue {i64, i64, double*} %m, 1
+  %m.data = extractvalue {i64, i64, double*} %m, 2
+  %len = mul i64 %rows, %cols
+  %result = call {i64, i64, double*} @calc_matrix_new(i64 %cols, i64 %rows)
+  %data = extractvalue {i64, i64, double*} %result, 2
+  br label %loop
+loop:
+  %index = phi i64 [ 0, %entry ], [ %index.next, %body ]
+  %done = icmp sge i64 %index, %len
+  br i1 %done, label %exit, label %body
+body:
+  %i = udiv i64 %index, %cols
+  %j = urem i64 %index, %cols
+  %m.at = getelementptr inbounds double, double* %m.data, i64 %index
+  %x = load double, double* %m.at
+  %row = mul i64 %j, %rows
+  %transposed = add i64 %row, %i
+  %at = getelementptr inbounds double, double* %data, i64 %transposed
+  store double %x, double* %at
+  %index.next = add i64 %index, 1
+  br label %loop
+exit:
+  ret {i64, i64, double*} %result
+}
+
+; the row from `k` down of the `n` by `n` elements `a` whose element in
+; column `k` is largest in magnitude, the first of them on a tie
+define i64 @calc_matrix_pivot_row(double* %a, i64 %n, i64 %k) {
+entry:
+  %start = add i64 %k, 1
+  br label %loop
+loop:
+  %i = phi i64 [ %start, %entry ], [ %i.next, %body ]
+  %p = phi i64 [ %k, %entry ], [ %p.next, %body ]
+  %done = icmp sge i64 %i, %n
+  br i1 %done, label %exit, label %body
+body:
+  %i.row = mul i64 %i, %n
+  %i.index = add i64 %i.row, %k
+  %i.at = getelementptr inbounds double, double* %a, i64 %i.index
+  %x = load double, double* %i.at
+  %p.row = mul i64 %p, %n
+  %p.index = add i64 %p.row, %k
+  %p.at = getelementptr inbounds double, double* %a, i64 %p.index
+  %y = load double, double* %p.at
+  ; the magnitudes, which clearing the sign bit gives like `f64::abs`
+  %x.bits = bitcast double %x to i64
+  %x.abs.bits = and i64 %x.bits, 9223372036854775807
+  %x.abs = bitcast i64 %x.abs.bits to double
+  %y.bits = bitcast double %y to i64
+  %y.abs.bits = and i64 %y.bits, 9223372036854775807
+  %y.abs = bitcast i64 %y.abs.bits to double
+  %larger = fcmp ogt double %x.abs, %y.abs
+  %p.next = select i1 %larger, i64 %i, i64 %p
+  %i.next = add i64 %i, 1
+  br label %loop
+exit:
+  ret i64 %p
+}
+
+define void @calc_matrix_swap_rows(double* %a, i64 %n, i64 %p, i64 %k) {
+entry:
+  %p.row = mul i64 %p, %n
+  %k.row = mul i64 %k, %n
+  br label %loop
+loop:
+  %j = phi i64 [ 0, %entry ], [ %j.next, %body ]
+  %done = icmp sge i64 %j, %n
+  br i1 %done, label %exit, label %body
+body:
+  %p.index = add i64 %p.row, %j
+  %k.index = add i64 %k.row, %j
+  %p.at = getelementptr inbounds double, double* %a, i64 %p.index
+  %k.at = getelementptr inbounds double, double* %a, i64 %k.index
+  %x = load double, double* %p.at
+  %y = load double, double* %k.at
+  store double %y, double* %p.at
+  store double %x, double* %k.at
+  %j.next = add i64 %j, 1
+  br label %loop
+exit:
+  ret void
+}
+
+; row `k` divided by `pivot`
+define void @calc_matrix_divide_row(double* %a, i64 %n, i64 %k, double %pivot) {
+entry:
+  %k.row = mul i64 %k, %n
+  br label %loop
+loop:
+  %j = phi i64 [ 0, %entry ], [ %j.next, %body ]
+  %done = icmp sge i64 %j, %n
+  br i1 %done, label %exit, label %body
+body:
+  %index = add i64 %k.row, %j
+  %at = getelementptr inbounds double, double* %a, i64 %index
+  %x = load double, double* %at
+  %y = fdiv double %x, %pivot
+  store double %y, double* %at
+  %j.next = add i64 %j, 1
+  br label %loop
+exit:
+  ret void
+}
+
+; row `i` minus `factor` times row `k`, from column `from` on
+define void @calc_matrix_subtract_row(double* %a, i64 %n, i64 %i, i64 %k, double %factor, i64 %from) {
+entry:
+  %i.row = mul i64 %i, %n
+  %k.row = mul i64 %k, %n
+  br label %loop
+loop:
+  %j = phi i64 [ %from, %entry ], [ %j.next, %body ]
+  %done = icmp sge i64 %j, %n
+  br i1 %done, label %exit, label %body
+body:
+  %i.index = add i64 %i.row, %j
+  %k.index = add i64 %k.row, %j
+  %i.at = getelementptr inbounds double, double* %a, i64 %i.index
+  %k.at = getelementptr inbounds double, double* %a, i64 %k.index
+  %x = load double, double* %i.at
+  %y = load double, double* %k.at
+  %scaled = fmul double %factor, %y
+  %z = fsub double %x, %scaled
+  store double %z, double* %i.at
+  %j.next = add i64 %j, 1
+  br label %loop
+exit:
+  ret void
+}
+
+define double @calc_matrix_det({i64, i64, double*} %m) {
+entry:
+  %n = extractvalue {i64, i64, double*} %m, 0
+  %a = call double* @calc_matrix_copy_data({i64, i64, double*} %m)
+  br label %column
+column:
+  %k = phi i64 [ 0, %entry ], [ %k.next, %rows.done ]
+  %det = phi double [ 1.0, %entry ], [ %det.next, %rows.done ]
+  %columns.done = icmp sge i64 %k, %n
+  br i1 %columns.done, label %exit, label %pivot
+pivot:
+  %p = call i64 @calc_matrix_pivot_row(double* %a, i64 %n, i64 %k)
+  %p.row = mul i64 %p, %n
+  %p.index = add i64 %p.row, %k
+  %p.at = getelementptr inbounds double, double* %a, i64 %p.index
+  %p.value = load double, double* %p.at
+  %no.pivot = fcmp oeq double %p.value, 0.0
+  br i1 %no.pivot, label %exit, label %swap
+swap:
+  call void @calc_matrix_swap_rows(double* %a, i64 %n, i64 %p, i64 %k)
+  %swapped = icmp ne i64 %p, %k
+  %negated = fneg double %det
+  %det.signed = select i1 %swapped, double %negated, double %det
+  %k.row = mul i64 %k, %n
+  %k.index = add i64 %k.row, %k
+  %k.at = getelementptr inbounds double, double* %a, i64 %k.index
+  %pivot.value = load double, double* %k.at
+  %det.next = fmul double %det.signed, %pivot.value
+  %k.next = add i64 %k, 1
+  br label %rows
+rows:
+  %i = phi i64 [ %k.next, %swap ], [ %i.next, %row ]
+  %rows.finished = icmp sge i64 %i, %n
+  br i1 %rows.finished, label %rows.done, label %row
+row:
+  %i.row = mul i64 %i, %n
+  %i.index = add i64 %i.row, %k
+  %i.at = getelementptr inbounds double, double* %a, i64 %i.index
+  %i.value = load double, double* %i.at
+  %factor = fdiv double %i.value, %pivot.value
+  call void @calc_matrix_subtract_row(double* %a, i64 %n, i64 %i, i64 %k, double %factor, i64 %k.next)
+  %i.next = add i64 %i, 1
+  br label %rows
+rows.done:
+  br label %column
+exit:
+  %result = phi double [ %det, %column ], [ 0.0, %pivot ]
+  %bytes = bitcast double* %a to i8*
+  call void @free(i8* %bytes)
+  ret double %result
+}
+
+; the inverse of `m` into the elements `inverse`, unless `m` is singular,
+; which the result tells
+define i1 @calc_matrix_invert({i64, i64, double*} %m, double* %inverse) {
+entry:
+  %n = extractvalue {i64, i64, double*} %m, 0
+  %a = call double* @calc_matrix_copy_data({i64, i64, double*} %m)
+  %len = mul i64 %n, %n
+  %stride = add i64 %n, 1
+  br label %identity
+identity:
+  %d = phi i64 [ 0, %entry ], [ %d.next, %identity.body ]
+  %identity.done = icmp sge i64 %d, %len
+  br i1 %identity.done, label %column, label %identity.body
+identity.body:
+  %d.offset = urem i64 %d, %stride
+  %diagonal = icmp eq i64 %d.offset, 0
+  %d.value = select i1 %diagonal, double 1.0, double 0.0
+  %d.at = getelementptr inbounds double, double* %inverse, i64 %d
+  store double %d.value, double* %d.at
+  %d.next = add i64 %d, 1
+  br label %identity
+column:
+  %k = phi i64 [ 0, %identity ], [ %k.next, %rows.done ]
+  %columns.done = icmp sge i64 %k, %n
+  br i1 %columns.done, label %exit, label %pivot
+pivot:
+  %p = call i64 @calc_matrix_pivot_row(double* %a, i64 %n, i64 %k)
+  %p.row = mul i64 %p, %n
+  %p.index = add i64 %p.row, %k
+  %p.at = getelementptr inbounds double, double* %a, i64 %p.index
+  %p.value = load double, double* %p.at
+  %no.pivot = fcmp oeq double %p.value, 0.0
+  br i1 %no.pivot, label %exit, label %swap
+swap:
+  call void @calc_matrix_swap_rows(double* %a, i64 %n, i64 %p, i64 %k)
+  call void @calc_matrix_swap_rows(double* %inverse, i64 %n, i64 %p, i64 %k)
+  %k.row = mul i64 %k, %n
+  %k.index = add i64 %k.row, %k
+  %k.at = getelementptr inbounds double, double* %a, i64 %k.index
+  %pivot.value = load double, double* %k.at
+  call void @calc_matrix_divide_row(double* %a, i64 %n, i64 %k, double %pivot.value)
+  call void @calc_matrix_divide_row(double* %inverse, i64 %n, i64 %k, double %pivot.value)
+  %k.next = add i64 %k, 1
+  br label %rows
+rows:
+  %i = phi i64 [ 0, %swap ], [ %i.next, %row.next ]
+  %rows.finished = icmp sge i64 %i, %n
+  br i1 %rows.finished, label %rows.done, label %row
+row:
+  %pivot.row = icmp eq i64 %i, %k
+  br i1 %pivot.row, label %row.next, label %subtract
+subtract:
+  %i.row = mul i64 %i, %n
+  %i.index = add i64 %i.row, %k
+  %i.at = getelementptr inbounds double, double* %a, i64 %i.index
+  %factor = load double, double* %i.at
+  call void @calc_matrix_subtract_row(double* %a, i64 %n, i64 %i, i64 %k, double %factor, i64 0)
+  call void @calc_matrix_subtract_row(double* %inverse, i64 %n, i64 %i, i64 %k, double %factor, i64 0)
+  br label %row.next
+row.next:
+  %i.next = add i64 %i, 1
+  br label %rows
+rows.done:
+  br label %column
+exit:
+  %singular = phi i1 [ false, %column ], [ true, %pivot ]
+  %bytes = bitcast double* %a to i8*
+  call void @free(i8* %bytes)
+  ret i1 %singular
+}
+
+; 1 if `m` has no inverse, else 0
+define i64 @calc_matrix_singular({i64, i64, double*} %m) {
+  %n = extractvalue {i64, i64, double*} %m, 0
+  %len = mul i64 %n, %n
+  %size = mul i64 %len, 8
+  %bytes = call i8* @malloc(i64 %size)
+  %inverse = bitcast i8* %bytes to double*
+  %singular = call i1 @calc_matrix_invert({i64, i64, double*} %m, double* %inverse)
+  call void @free(i8* %bytes)
+  %result = zext i1 %singular to i64
+  ret i64 %result
+}
+
+define {i64, i64, double*} @calc_matrix_inv({i64, i64, double*} %m) {
+  %n = extractvalue {i64, i64, double*} %m, 0
+  %result = call {i64, i64, double*} @calc_matrix_new(i64 %n, i64 %n)
+  %inverse = extractvalue {i64, i64, double*} %result, 2
+  %singular = call i1 @calc_matrix_invert({i64, i64, double*} %m, double* %inverse)
+  ret {i64, i64, double*} %result
+}
+
+; the grid `matrix::Matrix` shows, in a buffer the caller frees: every
+; element takes less than 32 characters, plus a separator or a bracket
+define i8* @calc_matrix_format({i64, i64, double*} %m) {
+entry:
+  %rows = extractvalue {i64, i64, double*} %m, 0
+  %cols = extractvalue {i64, i64, double*} %m, 1
+  %data = extractvalue {i64, i64, double*} %m, 2
+  %len = mul i64 %rows, %cols
+  %cell = alloca [32 x i8]
+  %cell.text = getelementptr inbounds [32 x i8], [32 x i8]* %cell, i64 0, i64 0
+  %widths.size = mul i64 %cols, 8
+  %widths.bytes = call i8* @malloc(i64 %widths.size)
+  %widths = bitcast i8* %widths.bytes to i64*
+  %row.size = mul i64 %cols, 34
+  %rows.size = mul i64 %rows, %row.size
+  %size = add i64 %rows.size, 1
+  %text = call i8* @malloc(i64 %size)
+  br label %measure
+measure:
+  %index = phi i64 [ 0, %entry ], [ %index.next, %measure.body ]
+  %measure.done = icmp sge i64 %index, %len
+  br i1 %measure.done, label %write, label %measure.body
+measure.body:
+  %x.at = getelementptr inbounds double, double* %data, i64 %index
+  %x = load double, double* %x.at
+  %x.shown = call double @calc_unsigned_nan(double %x)
+  %x.len.i32 = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %cell.text, i64 32, i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.fmt.float, i64 0, i64 0), double %x.shown)
+  %x.len = sext i32 %x.len.i32 to i64
+  %col = urem i64 %index, %cols
+  %width.at = getelementptr inbounds i64, i64* %widths, i64 %col
+  %first.row = icmp ult i64 %index, %cols
+  %width.old = load i64, i64* %width.at
+  %width = select i1 %first.row, i64 0, i64 %width.old
+  %wider = icmp sgt i64 %x.len, %width
+  %width.new = select i1 %wider, i64 %x.len, i64 %width
+  store i64 %width.new, i64* %width.at
+  %index.next = add i64 %index, 1
+  br label %measure
+write:
+  %i = phi i64 [ 0, %measure ], [ %i.next, %close ]
+  %at = phi i8* [ %text, %measure ], [ %row.end, %close ]
+  %write.done = icmp sge i64 %i, %rows
+  br i1 %write.done, label %exit, label %row
+row:
+  %later.row = icmp ugt i64 %i, 0
+  br i1 %later.row, label %newline, label %open
+newline:
+  store i8 10, i8* %at
+  %after.newline = getelementptr i8, i8* %at, i64 1
+  br label %open
+open:
+  %open.at = phi i8* [ %at, %row ], [ %after.newline, %newline ]
+  store i8 91, i8* %open.at
+  %cells.start = getelementptr i8, i8* %open.at, i64 1
+  br label %cells
+cells:
+  %j = phi i64 [ 0, %open ], [ %j.next, %cell.body ]
+  %cell.at = phi i8* [ %cells.start, %open ], [ %cell.end, %cell.body ]
+  %cells.done = icmp sge i64 %j, %cols
+  br i1 %cells.done, label %close, label %cell.body
+cell.body:
+  ; two spaces before every element but the first, which the element
+  ; overwrites otherwise
+  store i8 32, i8* %cell.at
+  %space.at = getelementptr i8, i8* %cell.at, i64 1
+  store i8 32, i8* %space.at
+  %later.col = icmp ugt i64 %j, 0
+  %gap = select i1 %later.col, i64 2, i64 0
+  %pad.at = getelementptr i8, i8* %cell.at, i64 %gap
+  %row.start = mul i64 %i, %cols
+  %y.index = add i64 %row.start, %j
+  %y.at = getelementptr inbounds double, double* %data, i64 %y.index
+  %y = load double, double* %y.at
+  %y.shown = call double @calc_unsigned_nan(double %y)
+  %y.len.i32 = call i32 (i8*, i64, i8*, ...) @snprintf(i8* %cell.text, i64 32, i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.fmt.float, i64 0, i64 0), double %y.shown)
+  %y.len = sext i32 %y.len.i32 to i64
+  %col.width.at = getelementptr inbounds i64, i64* %widths, i64 %j
+  %col.width = load i64, i64* %col.width.at
+  %padding = sub i64 %col.width, %y.len
+  call void @llvm.memset.p0i8.i64(i8* %pad.at, i8 32, i64 %padding, i1 false)
+  %value.at = getelementptr i8, i8* %pad.at, i64 %padding
+  call void @llvm.memcpy.p0i8.p0i8.i64(i8* %value.at, i8* %cell.text, i64 %y.len, i1 false)
+  %cell.end = getelementptr i8, i8* %value.at, i64 %y.len
+  %j.next = add i64 %j, 1
+  br label %cells
+close:
+  store i8 93, i8* %cell.at
+  %row.end = getelementptr i8, i8* %cell.at, i64 1
+  %i.next = add i64 %i, 1
+  br label %write
+exit:
+  store i8 0, i8* %at
+  call void @free(i8* %widths.bytes)
+  ret i8* %text
+}
+
+define void @calc_matrix_print({i64, i64, double*} %m) {
+  %text = call i8* @calc_matrix_format({i64, i64, double*} %m)
+  %printed = call i32 @puts(i8* %text)
+  call void @free(i8* %text)
+  ret void
+}
+
+define void @calc_matrix_fmt({i64, i64, double*} %m, i8* %fill, i32 %align, i64 %width) {
+  %text = call i8* @calc_matrix_format({i64, i64, double*} %m)
+  call void @calc_fmt_pad(i8* %text, i8* %fill, i32 %align, i64 %width)
+  call void @free(i8* %text)
+  ret void
+}
+
+"#;
+
+fn llvm_type(ty: IrType) -> &'static str {
+    match ty {
+        IrType::Void => "void",
+        IrType::Int => "i64",
+        IrType::BigInt => "i8*",
+        IrType::Float => "double",
+        IrType::Bool => "i1",
+        IrType::Rational => "{i64, i64}",
+        IrType::Decimal => "i64",
+        IrType::Complex => "{double, double}",
+        IrType::Str => "i8*",
+        IrType::Array => "{i64, i64*}",
+        IrType::Matrix => "{i64, i64, double*}",
+    }
+}
+
+// C passes `bool` as an `i1` that is zero-extended to a whole register
+fn abi_type(ty: IrType) -> &'static str {
+    match ty {
+        IrType::Bool => "i1 zeroext",
+        ty => llvm_type(ty),
+    }
+}
+
+// the same for results, whose attributes go before the type
+fn abi_return_type(ty: IrType) -> &'static str {
+    match ty {
+        IrType::Bool => "zeroext i1",
+        ty => llvm_type(ty),
+    }
+}
+
+fn zero_value(ty: IrType) -> &'static str {
+    match ty {
+        IrType::Int => "0",
+        IrType::BigInt => "null",
+        IrType::Float => "0.0",
+        IrType::Bool => "false",
+        IrType::Rational => "{i64 0, i64 1}",
+        IrType::Decimal => "0",
+        IrType::Complex => "{double 0.0, double 0.0}",
+        IrType::Str => "null",
+        IrType::Array => "{i64 0, i64* null}",
+        IrType::Matrix => "{i64 0, i64 0, double* null}",
+        IrType::Void => unreachable!(),
+    }
+}
 
 fn emit_instruction(instruction: &InstructionValue, context: &Context) -> String {
     match instruction {
         InstructionValue::LoadInst(load_inst) => {
             let arena = context.values.borrow();
             let src = arena.get(load_inst.source).unwrap();
-            format!("  {} = load i64, i64* {}\n", load_inst.name, emit_operand(src, context))
+            let ty = llvm_type(load_inst.ty);
+            format!(
+                "  {} = load {}, {}* {}\n",
+                load_inst.name,
+                ty,
+                ty,
+                emit_operand(src, context)
+            )
         }
         InstructionValue::StoreInst(store_inst) => {
             let arena = context.values.borrow();
             let dest = arena.get(store_inst.destination).unwrap();
             let src = arena.get(store_inst.source).unwrap();
+            let ty = llvm_type(src.ty());
             format!(
-                "  store i64 {}, i64* {}\n",
+                "  store {} {}, {}* {}\n",
+                ty,
                 emit_operand(src, context),
+                ty,
                 emit_operand(dest, context)
             )
         }
-        InstructionValue::AllocaInst(alloc_inst) => match alloc_inst.ty {
-            IrType::Int => format!("  {} = alloca i64\n", instruction.name()),
-            IrType::Void => unreachable!(),
+        InstructionValue::AllocaInst(alloc_inst) => match (alloc_inst.ty, alloc_inst.count) {
+            (IrType::Void, _) => unreachable!(),
+            (ty, Some(count)) => format!(
+                "  {} = alloca {}, i64 {}\n",
+                instruction.name(),
+                llvm_type(ty),
+                count
+            ),
+            (ty, None) => format!("  {} = alloca {}\n", instruction.name(), llvm_type(ty)),
         },
+        InstructionValue::ArrayInst(array_inst) => {
+            let arena = context.values.borrow();
+            let len = arena.get(array_inst.len).unwrap();
+            let data = arena.get(array_inst.data).unwrap();
+            format!(
+                "  {} = insertvalue {{i64, i64*}} undef, i64 {}, 0\n  {} = insertvalue {{i64, i64*}} {}, i64* {}, 1\n",
+                array_inst.aggregate,
+                emit_operand(len, context),
+                array_inst.name,
+                array_inst.aggregate,
+                emit_operand(data, context)
+            )
+        }
+        InstructionValue::ElementPtrInst(element_ptr) => {
+            let arena = context.values.borrow();
+            let array = arena.get(element_ptr.array).unwrap();
+            let index = arena.get(element_ptr.index).unwrap();
+            // the pointer to the elements comes after the lengths
+            let field = match array.ty() {
+                IrType::Matrix => 2,
+                _ => 1,
+            };
+            let ty = llvm_type(element_ptr.ty);
+            format!(
+                "  {} = extractvalue {} {}, {}\n  {} = getelementptr inbounds {}, {}* {}, i64 {}\n",
+                element_ptr.data,
+                llvm_type(array.ty()),
+                emit_operand(array, context),
+                field,
+                element_ptr.name,
+                ty,
+                ty,
+                element_ptr.data,
+                emit_operand(index, context)
+            )
+        }
+        InstructionValue::BinaryOperator(bin_op) if bin_op.ty == IrType::Complex => {
+            let arena = context.values.borrow();
+            let left = emit_operand(arena.get(bin_op.left_operand).unwrap(), context);
+            let right = emit_operand(arena.get(bin_op.right_operand).unwrap(), context);
+            emit_complex_binary(&bin_op.name, &bin_op.operation, &left, &right)
+        }
         InstructionValue::BinaryOperator(bin_op) => {
-            let operation = match bin_op.operation {
-                BinaryOp::Add => "add",
-                BinaryOp::Sub => "sub",
-                BinaryOp::Mul => "mul",
-                BinaryOp::Div => "sdiv",
+            let function = match bin_op.operation {
+                BinaryOp::Pow => Some("@calc_pow("),
+                BinaryOp::SaturatingAdd => Some("@llvm.sadd.sat.i64("),
+                BinaryOp::SaturatingSub => Some("@llvm.ssub.sat.i64("),
+                // a fixed-point multiply with scale 0 is an integer one
+                BinaryOp::SaturatingMul => Some("@llvm.smul.fix.sat.i64("),
+                _ => None,
+            };
+            let operation = match (&bin_op.operation, bin_op.ty) {
+                (BinaryOp::Add, IrType::Float) => "fadd",
+                (BinaryOp::Sub, IrType::Float) => "fsub",
+                (BinaryOp::Mul, IrType::Float) => "fmul",
+                (BinaryOp::Div, IrType::Float) => "fdiv",
+                (BinaryOp::Rem, IrType::Float) => "frem",
+                (BinaryOp::Add, _) => "add",
+                (BinaryOp::Sub, _) => "sub",
+                (BinaryOp::Mul, _) => "mul",
+                (BinaryOp::Div, _) => "sdiv",
+                (BinaryOp::Rem, _) => "srem",
+                (BinaryOp::And, _) => "and",
+                (BinaryOp::Or, _) => "or",
+                (BinaryOp::Xor, _) => "xor",
+                (BinaryOp::Shl, _) => "shl",
+                (BinaryOp::AShr, _) => "ashr",
+                _ => "call i64",
             };
             let arena = context.values.borrow();
             let left = arena.get(bin_op.left_operand).unwrap();
             let right = arena.get(bin_op.right_operand).unwrap();
+            if let Some(function) = function {
+                let scale = if function.contains("fix") { ", i32 0" } else { "" };
+                return format!(
+                    "  {} = call i64 {}i64 {}, i64 {}{})\n",
+                    instruction.name(),
+                    function,
+                    emit_operand(left, context),
+                    emit_operand(right, context),
+                    scale
+                );
+            }
             format!(
-                "  {} = {} i64 {}, {}\n",
+                "  {} = {} {} {}, {}\n",
                 instruction.name(),
                 operation,
+                llvm_type(bin_op.ty),
                 emit_operand(left, context),
                 emit_operand(right, context)
             )
         }
-        InstructionValue::PrintIntInst(print_var_inst) => {
+        InstructionValue::UnaryOperator(unary_op) => {
+            let arena = context.values.borrow();
+            let operand = emit_operand(arena.get(unary_op.operand).unwrap(), context);
+            match (&unary_op.operation, unary_op.ty) {
+                (UnaryOp::Neg, IrType::Complex) => {
+                    let z = complex_temporary(&unary_op.name);
+                    format!(
+                        "{}  {z}.re = fneg double {z}.a.re\n  {z}.im = fneg double {z}.a.im\n{}",
+                        emit_complex_parts(&format!("{z}.a"), &operand),
+                        emit_complex_from_parts(&unary_op.name, &z)
+                    )
+                }
+                (UnaryOp::Neg, IrType::Float) => {
+                    format!("  {} = fneg double {}\n", unary_op.name, operand)
+                }
+                (UnaryOp::Neg, _) => format!("  {} = sub i64 0, {}\n", unary_op.name, operand),
+                (UnaryOp::Not, IrType::Int) => format!("  {} = xor i64 {}, -1\n", unary_op.name, operand),
+                (UnaryOp::Not, _) => format!("  {} = xor i1 {}, true\n", unary_op.name, operand),
+            }
+        }
+        InstructionValue::CompareInst(cmp_inst) => {
             let arena = context.values.borrow();
-            let param_val = arena.get(print_var_inst.param).unwrap();
+            let left = arena.get(cmp_inst.left_operand).unwrap();
+            let right = arena.get(cmp_inst.right_operand).unwrap();
+            let operand_ty = left.ty();
+            if operand_ty == IrType::Complex {
+                return emit_complex_compare(
+                    &cmp_inst.name,
+                    &cmp_inst.predicate,
+                    &emit_operand(left, context),
+                    &emit_operand(right, context),
+                );
+            }
+            // float comparisons are ordered, except `!=`, which like Rust is
+            // true when either side is NaN
+            let predicate = match (&cmp_inst.predicate, operand_ty) {
+                (ComparePredicate::Eq, IrType::Float) => "fcmp oeq",
+                (ComparePredicate::Ne, IrType::Float) => "fcmp une",
+                (ComparePredicate::Lt, IrType::Float) => "fcmp olt",
+                (ComparePredicate::Le, IrType::Float) => "fcmp ole",
+                (ComparePredicate::Gt, IrType::Float) => "fcmp ogt",
+                (ComparePredicate::Ge, IrType::Float) => "fcmp oge",
+                (ComparePredicate::Eq, _) => "icmp eq",
+                (ComparePredicate::Ne, _) => "icmp ne",
+                (ComparePredicate::Lt, _) => "icmp slt",
+                (ComparePredicate::Le, _) => "icmp sle",
+                (ComparePredicate::Gt, _) => "icmp sgt",
+                (ComparePredicate::Ge, _) => "icmp sge",
+            };
+            format!(
+                "  {} = {} {} {}, {}\n",
+                cmp_inst.name,
+                predicate,
+                llvm_type(operand_ty),
+                emit_operand(left, context),
+                emit_operand(right, context)
+            )
+        }
+        InstructionValue::CastInst(cast_inst) => {
+            let arena = context.values.borrow();
+            let source = arena.get(cast_inst.source).unwrap();
+            let operand = emit_operand(source, context);
+            let name = &cast_inst.name;
+            match (source.ty(), cast_inst.ty) {
+                (IrType::Int, IrType::Float) => {
+                    format!("  {} = sitofp i64 {} to double\n", name, operand)
+                }
+                (IrType::Bool, IrType::Float) => {
+                    format!("  {} = uitofp i1 {} to double\n", name, operand)
+                }
+                (IrType::Bool, IrType::Int) => format!("  {} = zext i1 {} to i64\n", name, operand),
+                (IrType::Float, IrType::Complex) => format!(
+                    "  {} = insertvalue {{double, double}} {{double 0.0, double 0.0}}, double {}, 0\n",
+                    name, operand
+                ),
+                // saturates and maps NaN to 0, like Rust's `as`; see
+                // `Lowering::convert_checked` for the other overflow modes
+                (IrType::Float, IrType::Int) => format!(
+                    "  {} = call i64 @llvm.fptosi.sat.i64.f64(double {})\n",
+                    name, operand
+                ),
+                (IrType::Int, IrType::Bool) => format!("  {} = icmp ne i64 {}, 0\n", name, operand),
+                (IrType::Float, IrType::Bool) => {
+                    format!("  {} = fcmp une double {}, 0.0\n", name, operand)
+                }
+                (IrType::Int, IrType::BigInt) => {
+                    format!("  {} = call i8* @calc_big_from_i64(i64 {})\n", name, operand)
+                }
+                (IrType::Float, IrType::BigInt) => {
+                    format!("  {} = call i8* @calc_big_from_f64(double {})\n", name, operand)
+                }
+                (IrType::BigInt, IrType::Int) => {
+                    format!("  {} = call i64 @calc_big_to_i64(i8* {})\n", name, operand)
+                }
+                (IrType::BigInt, IrType::Float) => {
+                    format!("  {} = call double @calc_big_to_f64(i8* {})\n", name, operand)
+                }
+                (IrType::Int, IrType::Rational) => format!(
+                    "  {} = insertvalue {{i64, i64}} {{i64 0, i64 1}}, i64 {}, 0\n",
+                    name, operand
+                ),
+                (IrType::Rational, IrType::Int) => {
+                    format!("  {} = call i64 @calc_rat_to_i64({{i64, i64}} {})\n", name, operand)
+                }
+                (IrType::Rational, IrType::Float) => {
+                    format!("  {} = call double @calc_rat_to_f64({{i64, i64}} {})\n", name, operand)
+                }
+                (IrType::Int, IrType::Decimal) => {
+                    format!("  {} = call i64 @calc_dec_from_i64(i64 {})\n", name, operand)
+                }
+                (IrType::Rational, IrType::Decimal) => {
+                    format!("  {} = call i64 @calc_dec_from_rat({{i64, i64}} {})\n", name, operand)
+                }
+                (IrType::Decimal, IrType::Int) => {
+                    format!("  {} = call i64 @calc_dec_to_i64(i64 {})\n", name, operand)
+                }
+                (IrType::Decimal, IrType::Float) => {
+                    format!("  {} = call double @calc_dec_to_f64(i64 {})\n", name, operand)
+                }
+                (IrType::Decimal, IrType::Rational) => {
+                    format!("  {} = call {{i64, i64}} @calc_dec_to_rat(i64 {})\n", name, operand)
+                }
+                (IrType::Decimal, IrType::Bool) => format!("  {} = icmp ne i64 {}, 0\n", name, operand),
+                (from, to) => unreachable!("no conversion from {:?} to {:?}", from, to),
+            }
+        }
+        InstructionValue::OverflowCheckInst(check) => {
+            let arena = context.values.borrow();
+            let left = arena.get(check.left_operand).unwrap();
+            let right = arena.get(check.right_operand).unwrap();
+            let function = match check.operation {
+                BinaryOp::Add => "llvm.sadd.with.overflow.i64",
+                BinaryOp::Sub => "llvm.ssub.with.overflow.i64",
+                BinaryOp::Mul => "llvm.smul.with.overflow.i64",
+                BinaryOp::Pow => "calc_pow_with_overflow",
+                _ => unreachable!("no overflow check for {:?}", check.operation),
+            };
+            format!(
+                "  {} = call {{i64, i1}} @{}(i64 {}, i64 {})\n  {} = extractvalue {{i64, i1}} {}, 1\n",
+                check.aggregate,
+                function,
+                emit_operand(left, context),
+                emit_operand(right, context),
+                check.name,
+                check.aggregate
+            )
+        }
+        InstructionValue::SelectInst(select_inst) => {
+            let arena = context.values.borrow();
+            let condition = arena.get(select_inst.condition).unwrap();
+            let true_value = arena.get(select_inst.true_value).unwrap();
+            let false_value = arena.get(select_inst.false_value).unwrap();
+            let ty = llvm_type(select_inst.ty);
+            format!(
+                "  {} = select i1 {}, {} {}, {} {}\n",
+                select_inst.name,
+                emit_operand(condition, context),
+                ty,
+                emit_operand(true_value, context),
+                ty,
+                emit_operand(false_value, context)
+            )
+        }
+        InstructionValue::TrapIfInst(trap_if) => {
+            let arena = context.values.borrow();
+            let condition = arena.get(trap_if.condition).unwrap();
+            let message = trap_message(trap_if.trap);
+            format!(
+                "  br i1 {}, label %trap.{}, label %ok.{}\ntrap.{}:\n  call void @calc_trap(i8* {}, i64 {}, i64 {})\n  unreachable\nok.{}:\n",
+                emit_operand(condition, context),
+                trap_if.label,
+                trap_if.label,
+                trap_if.label,
+                message,
+                trap_if.line,
+                trap_if.col,
+                trap_if.label
+            )
+        }
+        InstructionValue::CallInst(call_inst) => {
+            let arena = context.values.borrow();
+            let args: Vec<String> = call_inst
+                .args
+                .iter()
+                .map(|id| {
+                    let arg = arena.get(*id).unwrap();
+                    format!("{} {}", abi_type(arg.ty()), emit_operand(arg, context))
+                })
+                .collect();
+            format!(
+                "  {} = call {} @{}({})\n",
+                call_inst.name,
+                abi_return_type(call_inst.ty),
+                call_inst.callee,
+                args.join(", ")
+            )
+        }
+        InstructionValue::PrintInst(PrintInst {
+            param,
+            format: Some(format),
+        }) => {
+            let arena = context.values.borrow();
+            let param_val = arena.get(*param).unwrap();
+            let ty = param_val.ty();
+            let spec = format.spec;
+            // the `align` of `calc_fmt_pad`
+            let align = match (spec.zero, spec.align) {
+                (true, _) => 3,
+                (_, Some(Align::Left)) => 0,
+                (_, Some(Align::Center)) => 1,
+                (_, Some(Align::Right)) => 2,
+                (_, None) if matches!(ty, IrType::Bool | IrType::Str | IrType::Array | IrType::Matrix) => 0,
+                (_, None) => 2,
+            };
+            let padding = format!(
+                "i8* {}, i32 {}, i64 {}",
+                text_pointer(format.fill, context),
+                align,
+                spec.width.unwrap_or(0)
+            );
+            let operand = emit_operand(param_val, context);
+            let call = match (ty, spec.radix) {
+                (IrType::Int, Radix::Binary) => format!("calc_fmt_binary(i64 {operand}, {padding})"),
+                (IrType::Int, radix) => {
+                    let format = match (radix, spec.upper) {
+                        (Radix::Hex, false) => "llx",
+                        (Radix::Hex, true) => "llX",
+                        (Radix::Octal, _) => "llo",
+                        _ => "lld",
+                    };
+                    format!("calc_fmt_int(i64 {operand}, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt.{format}, i64 0, i64 0), {padding})")
+                }
+                (IrType::Float, _) => {
+                    let places = spec.precision.map_or(-1, |places| places as i32);
+                    format!("calc_fmt_float(double {operand}, i32 {places}, {padding})")
+                }
+                (IrType::Bool, _) => format!("calc_fmt_bool(i1 {operand}, {padding})"),
+                (IrType::Complex, _) => {
+                    let places = spec.precision.map_or(-1, |places| places as i32);
+                    let polar = spec.polar;
+                    format!("calc_complex_fmt({{double, double}} {operand}, i1 {polar}, i32 {places}, {padding})")
+                }
+                (IrType::Rational, _) => {
+                    let (digits, scale) = rational_digits(context.precision);
+                    format!("calc_rat_fmt({{i64, i64}} {operand}, i32 {digits}, i64 {scale}, {padding})")
+                }
+                (IrType::Decimal, _) => format!("calc_dec_fmt(i64 {operand}, {padding})"),
+                (IrType::BigInt, _) => format!("calc_big_fmt(i8* {operand}, {padding})"),
+                (IrType::Str, _) => format!("calc_fmt_pad(i8* {operand}, {padding})"),
+                (IrType::Array, _) => format!("calc_array_fmt({{i64, i64*}} {operand}, {padding})"),
+                (IrType::Matrix, _) => format!("calc_matrix_fmt({{i64, i64, double*}} {operand}, {padding})"),
+                _ => unreachable!(),
+            };
+            format!("  call void @{}\n", call)
+        }
+        InstructionValue::PrintTextInst(print_inst) => format!(
+            "  call void @calc_fmt_text(i8* {}, i64 {})\n",
+            text_pointer(print_inst.text, context),
+            context.strings[print_inst.text].len()
+        ),
+        InstructionValue::PrintInst(print_inst) => {
+            let arena = context.values.borrow();
+            let param_val = arena.get(print_inst.param).unwrap();
+            if param_val.ty() == IrType::Rational {
+                let (digits, scale) = rational_digits(context.precision);
+                return format!(
+                    "  call void @calc_rat_print({{i64, i64}} {}, i32 {}, i64 {})\n",
+                    emit_operand(param_val, context),
+                    digits,
+                    scale
+                );
+            }
+            let function = match param_val.ty() {
+                IrType::BigInt => "calc_big_print",
+                IrType::Decimal => "calc_dec_print",
+                IrType::Str => "calc_str_print",
+                IrType::Array => "calc_array_print",
+                IrType::Matrix => "calc_matrix_print",
+                IrType::Complex => "calc_complex_print",
+                IrType::Float => "print_float",
+                IrType::Bool => "print_bool",
+                _ => "print",
+            };
             format!(
-                "  call void @print(i64 {})\n",
+                "  call void @{}({} {})\n",
+                function,
+                llvm_type(param_val.ty()),
                 emit_operand(param_val, context)
             )
         }
     }
 }
 
+// the prefix of the temporaries that compute the `Complex` `name` part by
+// part; `%7` has `%complex.7.re` and so on, which unlike numbers need not
+// come in order
+fn complex_temporary(name: &str) -> String {
+    format!("%complex.{}", name.trim_start_matches('%'))
+}
+
+// `prefix.re` and `prefix.im`, the parts of `operand`
+fn emit_complex_parts(prefix: &str, operand: &str) -> String {
+    format!(
+        "  {prefix}.re = extractvalue {{double, double}} {operand}, 0\n  {prefix}.im = extractvalue {{double, double}} {operand}, 1\n"
+    )
+}
+
+// `name` put together from `prefix.re` and `prefix.im`
+fn emit_complex_from_parts(name: &str, prefix: &str) -> String {
+    format!(
+        "  {prefix}.part = insertvalue {{double, double}} undef, double {prefix}.re, 0\n  {name} = insertvalue {{double, double}} {prefix}.part, double {prefix}.im, 1\n"
+    )
+}
+
+// `+ - *` on the parts of `a` and `b` in the order `complex::Complex`
+// computes them, and `/` by `calc_complex_div`
+fn emit_complex_binary(name: &str, operation: &BinaryOp, a: &str, b: &str) -> String {
+    if *operation == BinaryOp::Div {
+        return format!(
+            "  {name} = call {{double, double}} @calc_complex_div({{double, double}} {a}, {{double, double}} {b})\n"
+        );
+    }
+    let z = complex_temporary(name);
+    let steps: &[(&str, &str, &str, &str)] = match operation {
+        BinaryOp::Add => &[("re", "fadd", "a.re", "b.re"), ("im", "fadd", "a.im", "b.im")],
+        BinaryOp::Sub => &[("re", "fsub", "a.re", "b.re"), ("im", "fsub", "a.im", "b.im")],
+        BinaryOp::Mul => &[
+            ("rr", "fmul", "a.re", "b.re"),
+            ("ii", "fmul", "a.im", "b.im"),
+            ("re", "fsub", "rr", "ii"),
+            ("ri", "fmul", "a.re", "b.im"),
+            ("ir", "fmul", "a.im", "b.re"),
+            ("im", "fadd", "ri", "ir"),
+        ],
+        _ => unreachable!("no complex {:?}", operation),
+    };
+    let mut out = emit_complex_parts(&format!("{z}.a"), a);
+    out.push_str(&emit_complex_parts(&format!("{z}.b"), b));
+    for (result, operation, lhs, rhs) in steps {
+        out.push_str(&format!("  {z}.{result} = {operation} double {z}.{lhs}, {z}.{rhs}\n"));
+    }
+    out.push_str(&emit_complex_from_parts(name, &z));
+    out
+}
+
+// `==` holds if both parts are equal, `!=` if either differs, as for the
+// floats they are made of
+fn emit_complex_compare(name: &str, predicate: &ComparePredicate, a: &str, b: &str) -> String {
+    let z = complex_temporary(name);
+    let (compare, combine) = match predicate {
+        ComparePredicate::Eq => ("fcmp oeq", "and"),
+        ComparePredicate::Ne => ("fcmp une", "or"),
+        _ => unreachable!("complex numbers have no order"),
+    };
+    format!(
+        "{}{}  {z}.re = {compare} double {z}.a.re, {z}.b.re\n  {z}.im = {compare} double {z}.a.im, {z}.b.im\n  {name} = {combine} i1 {z}.re, {z}.im\n",
+        emit_complex_parts(&format!("{z}.a"), a),
+        emit_complex_parts(&format!("{z}.b"), b)
+    )
+}
+
+fn emit_trap_handler() -> String {
+    let mut out = String::new();
+    for (i, trap) in Trap::ALL.into_iter().enumerate() {
+        let (len, text) = c_string(trap.message());
+        out.push_str(&format!(
+            "@.trap.{} = private unnamed_addr constant [{} x i8] c\"{}\", align 1\n",
+            i, len, text
+        ));
+    }
+    let (len, text) = c_string("error: %s\n --> %lld:%lld\n");
+    out.push_str(&format!(
+        r#"@.str.trap = private unnamed_addr constant [{len} x i8] c"{text}", align 1
+@stderr = external global i8*
+
+declare i32 @fprintf(i8*, i8*, ...)
+
+declare i32 @fflush(i8*)
+
+declare void @exit(i32)
+
+define void @calc_trap(i8* %0, i64 %1, i64 %2) {{
+  %4 = call i32 @fflush(i8* null)
+  %5 = load i8*, i8** @stderr
+  %6 = call i32 (i8*, i8*, ...) @fprintf(i8* %5, i8* getelementptr inbounds ([{len} x i8], [{len} x i8]* @.str.trap, i64 0, i64 0), i8* %0, i64 %1, i64 %2)
+  call void @exit(i32 {EXIT_TRAP})
+  unreachable
+}}
+
+"#
+    ));
+    out
+}
+
+// a pointer to the message of `trap`, as defined by `emit_trap_handler`
+fn trap_message(trap: Trap) -> String {
+    let i = Trap::ALL.iter().position(|t| *t == trap).unwrap();
+    let (len, _) = c_string(trap.message());
+    format!(
+        "getelementptr inbounds ([{} x i8], [{} x i8]* @.trap.{}, i64 0, i64 0)",
+        len, len, i
+    )
+}
+
+// the `digits` and `scale` of `calc_rat_format`; -1 digits prints a fraction
+fn rational_digits(precision: Precision) -> (i32, i64) {
+    match precision.rational_digits {
+        Some(digits) => (digits as i32, 10i64.pow(digits)),
+        None => (-1, 1),
+    }
+}
+
+// a pointer to one of `Context::strings`, as `emit_ir` defines them
+fn text_pointer(i: usize, context: &Context) -> String {
+    let (len, _) = c_string(&context.strings[i]);
+    format!("getelementptr inbounds ([{len} x i8], [{len} x i8]* @.text.{i}, i64 0, i64 0)")
+}
+
+// the length and LLVM notation of a NUL-terminated string, escaping every
+// byte that is not printable ASCII
+fn c_string(s: &str) -> (usize, String) {
+    let mut text = String::new();
+    for b in s.bytes() {
+        match b {
+            b'"' | b'\\' | ..=0x1F | 0x7F.. => text.push_str(&format!("\\{:02X}", b)),
+            _ => text.push(b as char),
+        }
+    }
+    text.push_str("\\00");
+    (s.len() + 1, text)
+}
+
 fn emit_operand(value: &Value, context: &Context) -> String {
     match value {
         Value::Instruction(inst) => inst.name(),
-        Value::Global(global) => format!("{}", global.name()),
-        Value::Constant(constant) => format!("{}", match constant {
-            ConstantValue::Int(int) => int,
-        }),
+        Value::Global(global) => global.name(),
+        Value::Constant(constant) => match constant {
+            ConstantValue::Int(int) | ConstantValue::Decimal(int) => int.to_string(),
+            // hexadecimal is the only exact notation LLVM accepts for doubles
+            ConstantValue::Float(float) => format!("0x{:016X}", float.to_bits()),
+            ConstantValue::Complex(re, im) => {
+                format!("{{double 0x{:016X}, double 0x{:016X}}}", re.to_bits(), im.to_bits())
+            }
+            ConstantValue::Bool(b) => b.to_string(),
+            ConstantValue::Str(i) => text_pointer(*i, context),
+        },
     }
 }
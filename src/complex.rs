@@ -0,0 +1,139 @@
+//! Complex numbers, the values of `complex`: a real and an imaginary part,
+//! each a `float`. `codegen` expands the operators on them into operations
+//! on pairs of `double`s, in the same order as here, so results agree to
+//! the bit.
+
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::ast::InfixOp;
+use crate::interpreter::{format_fixed, format_float, Value};
+
+/// What separates the magnitude from the angle in polar form.
+pub const ANGLE: char = '∠';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    pub fn re(self) -> f64 {
+        self.re
+    }
+
+    pub fn im(self) -> f64 {
+        self.im
+    }
+
+    pub fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    /// The magnitude, computed by C's `hypot` without overflowing early.
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// The angle from the positive real axis, in radians from -pi to pi.
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    /// `3+4i` with the sign of the imaginary part between the parts, or in
+    /// polar form the magnitude and the angle, as `5∠0.927295218001612`.
+    /// Each part has 15 significant digits like a float, or `places` after
+    /// the point.
+    pub fn format(self, polar: bool, places: Option<u32>) -> String {
+        let part = |x: f64| match places {
+            Some(places) => format_fixed(x, places),
+            None => format_float(x),
+        };
+        if polar {
+            return format!("{}{}{}", part(self.abs()), ANGLE, part(self.arg()));
+        }
+        // a NaN part has no sign, as `format_float` shows it
+        let sign = if self.im.is_sign_negative() && !self.im.is_nan() { '-' } else { '+' };
+        format!("{}{}{}i", part(self.re), sign, part(self.im.abs()))
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    /// `self / other` by Smith's algorithm: dividing by the larger part of
+    /// `other` first keeps the intermediate results from overflowing or
+    /// underflowing where the quotient does not. Dividing by zero gives NaN
+    /// parts, as dividing zero by zero does for floats.
+    fn div(self, other: Complex) -> Complex {
+        let (a, b, c, d) = (self.re, self.im, other.re, other.im);
+        if c.abs() >= d.abs() {
+            let ratio = d / c;
+            let den = 1.0 + ratio * ratio;
+            Complex::new((a / c + b / c * ratio) / den, (b / c - a / c * ratio) / den)
+        } else {
+            let ratio = c / d;
+            let den = 1.0 + ratio * ratio;
+            Complex::new((a / d * ratio + b / d) / den, (b / d * ratio - a / d) / den)
+        }
+    }
+}
+
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(false, None))
+    }
+}
+
+/// `+ - * /` and the equality operators; complex numbers have no order.
+pub fn eval_infix(op: InfixOp, lhs: Complex, rhs: Complex) -> Value {
+    match op {
+        InfixOp::Plus => Value::Complex(lhs + rhs),
+        InfixOp::Minus => Value::Complex(lhs - rhs),
+        InfixOp::Multiply => Value::Complex(lhs * rhs),
+        InfixOp::Divide => Value::Complex(lhs / rhs),
+        InfixOp::Equal => Value::Bool(lhs == rhs),
+        InfixOp::NotEqual => Value::Bool(lhs != rhs),
+        _ => unreachable!(),
+    }
+}
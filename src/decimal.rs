@@ -0,0 +1,206 @@
+//! Fixed-point decimals, the values of `dec`. A decimal is a whole number
+//! of units of 10 to the power of minus the scale, which every `dec` in a
+//! program shares. Results with more places are rounded as `Rounding` says,
+//! computing in 128 bits first, and overflow if the units do not fit in 64
+//! bits. The `calc_dec_*` functions in the prelude of `codegen` compute the
+//! same for compiled programs.
+
+use std::fmt;
+
+use crate::ast::InfixOp;
+use crate::interpreter::Value;
+use crate::rational::Rational;
+use crate::runtime::{Rounding, Trap};
+
+/// The most decimal places a `dec` can have.
+pub const MAX_SCALE: u32 = 18;
+
+/// A number of units of `10^-scale`. `i64::MIN` is never one, so that
+/// negating cannot overflow, and compiled programs use it to mark overflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Decimal {
+    units: i64,
+    scale: u32,
+}
+
+impl Decimal {
+    /// `num / den` units, rounded to a whole number.
+    pub fn round(num: i128, den: i128, scale: u32, rounding: Rounding) -> Result<Decimal, Trap> {
+        if den == 0 {
+            return Err(Trap::DivisionByZero);
+        }
+        let (quotient, rem) = (num / den, num % den);
+        let twice = rem.unsigned_abs() * 2;
+        let away = match rounding {
+            Rounding::HalfEven => {
+                twice > den.unsigned_abs() || twice == den.unsigned_abs() && quotient % 2 != 0
+            }
+            Rounding::HalfUp => twice >= den.unsigned_abs(),
+            Rounding::Truncate => false,
+        };
+        let units = match (away, (num < 0) != (den < 0)) {
+            (false, _) => quotient,
+            (true, false) => quotient + 1,
+            (true, true) => quotient - 1,
+        };
+        match i64::try_from(units) {
+            Ok(units) if units != i64::MIN => Ok(Decimal { units, scale }),
+            _ => Err(Trap::DecimalOverflow),
+        }
+    }
+
+    /// The literal `digits` with `places` of them after the point.
+    pub fn from_literal(
+        digits: i64,
+        places: u32,
+        scale: u32,
+        rounding: Rounding,
+    ) -> Result<Decimal, Trap> {
+        match places.checked_sub(scale) {
+            Some(extra) => Decimal::round(digits as i128, 10i128.pow(extra), scale, rounding),
+            None => Decimal::round(
+                digits as i128 * 10i128.pow(scale - places),
+                1,
+                scale,
+                rounding,
+            ),
+        }
+    }
+
+    pub fn from_int(i: i64, scale: u32) -> Result<Decimal, Trap> {
+        Decimal::round(i as i128 * 10i128.pow(scale), 1, scale, Rounding::Truncate)
+    }
+
+    /// Scales the float up and rounds it, both in floating point. NaN,
+    /// infinities and values out of range overflow.
+    pub fn from_float(f: f64, scale: u32, rounding: Rounding) -> Result<Decimal, Trap> {
+        let scaled = f * unit(scale) as f64;
+        let rounded = match rounding {
+            Rounding::HalfEven => scaled.round_ties_even(),
+            Rounding::HalfUp => scaled.round(),
+            Rounding::Truncate => scaled.trunc(),
+        };
+        // ±2^63 is not a decimal either way
+        let limit = 2f64.powi(63);
+        if rounded > -limit && rounded < limit {
+            Ok(Decimal {
+                units: rounded as i64,
+                scale,
+            })
+        } else {
+            Err(Trap::DecimalOverflow)
+        }
+    }
+
+    pub fn from_rational(r: Rational, scale: u32, rounding: Rounding) -> Result<Decimal, Trap> {
+        Decimal::round(
+            r.num() as i128 * unit(scale) as i128,
+            r.den() as i128,
+            scale,
+            rounding,
+        )
+    }
+
+    pub fn units(self) -> i64 {
+        self.units
+    }
+
+    /// Divides the units by `10^scale` as floats, which is what compiled
+    /// programs do.
+    pub fn to_float(self) -> f64 {
+        self.units as f64 / unit(self.scale) as f64
+    }
+
+    /// Truncates towards zero, like `as int` does with floats.
+    pub fn to_int(self) -> i64 {
+        self.units / unit(self.scale)
+    }
+
+    pub fn to_rational(self) -> Rational {
+        // the units and `10^scale` fit, so their lowest terms do too
+        Rational::new(self.units as i128, unit(self.scale) as i128).unwrap()
+    }
+
+    pub fn checked_add(self, rhs: Decimal) -> Result<Decimal, Trap> {
+        Decimal::round(
+            self.units as i128 + rhs.units as i128,
+            1,
+            self.scale,
+            Rounding::Truncate,
+        )
+    }
+
+    pub fn checked_sub(self, rhs: Decimal) -> Result<Decimal, Trap> {
+        Decimal::round(
+            self.units as i128 - rhs.units as i128,
+            1,
+            self.scale,
+            Rounding::Truncate,
+        )
+    }
+
+    pub fn checked_mul(self, rhs: Decimal, rounding: Rounding) -> Result<Decimal, Trap> {
+        let num = self.units as i128 * rhs.units as i128;
+        Decimal::round(num, unit(self.scale) as i128, self.scale, rounding)
+    }
+
+    pub fn checked_div(self, rhs: Decimal, rounding: Rounding) -> Result<Decimal, Trap> {
+        let num = self.units as i128 * unit(self.scale) as i128;
+        Decimal::round(num, rhs.units as i128, self.scale, rounding)
+    }
+
+    pub fn negated(self) -> Decimal {
+        Decimal {
+            units: -self.units,
+            scale: self.scale,
+        }
+    }
+}
+
+/// `10^scale`, the number of units in 1.
+fn unit(scale: u32) -> i64 {
+    10i64.pow(scale)
+}
+
+/// Always with `scale` places, like `-0.50`.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.units < 0 { "-" } else { "" };
+        let abs = self.units.unsigned_abs();
+        let unit = unit(self.scale) as u64;
+        match self.scale {
+            0 => write!(f, "{}{}", sign, abs),
+            scale => write!(
+                f,
+                "{}{}.{:0width$}",
+                sign,
+                abs / unit,
+                abs % unit,
+                width = scale as usize
+            ),
+        }
+    }
+}
+
+/// `+ - * /` and the comparisons. Overflow always traps, as it does with
+/// rationals.
+pub fn eval_infix(
+    op: InfixOp,
+    lhs: Decimal,
+    rhs: Decimal,
+    rounding: Rounding,
+) -> Result<Value, Trap> {
+    Ok(match op {
+        InfixOp::Plus => Value::Decimal(lhs.checked_add(rhs)?),
+        InfixOp::Minus => Value::Decimal(lhs.checked_sub(rhs)?),
+        InfixOp::Multiply => Value::Decimal(lhs.checked_mul(rhs, rounding)?),
+        InfixOp::Divide => Value::Decimal(lhs.checked_div(rhs, rounding)?),
+        InfixOp::Equal => Value::Bool(lhs == rhs),
+        InfixOp::NotEqual => Value::Bool(lhs != rhs),
+        InfixOp::Less => Value::Bool(lhs < rhs),
+        InfixOp::LessEqual => Value::Bool(lhs <= rhs),
+        InfixOp::Greater => Value::Bool(lhs > rhs),
+        InfixOp::GreaterEqual => Value::Bool(lhs >= rhs),
+        _ => unreachable!(),
+    })
+}
@@ -0,0 +1,158 @@
+use std::fmt;
+
+use pest::error::{Error, InputLocation};
+
+use crate::ast::Span;
+use crate::parser::Rule;
+use crate::runtime::line_col;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Error => write!(f, "error"),
+            Level::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A message about a span of the source, produced by the passes after
+/// parsing.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: String,
+    pub span: Span,
+    pub notes: Vec<Note>,
+}
+
+/// Another place in the source that explains a diagnostic, such as the
+/// declaration a name clashes with.
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            level: Level::Error,
+            message: message.into(),
+            span,
+            notes: vec![],
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            level: Level::Warning,
+            message: message.into(),
+            span,
+            notes: vec![],
+        }
+    }
+
+    pub fn with_note(mut self, message: impl Into<String>, span: Span) -> Self {
+        self.notes.push(Note {
+            message: message.into(),
+            span,
+        });
+        self
+    }
+
+    pub fn from_syntax_error(err: &Error<Rule>) -> Self {
+        let span = match err.location {
+            InputLocation::Pos(pos) => Span { start: pos, end: pos },
+            InputLocation::Span((start, end)) => Span { start, end },
+        };
+        Self::error(format!("invalid syntax: {}", err.variant.message()), span)
+    }
+
+    /// Renders the message with the offending line and a caret underline,
+    /// in the same layout pest uses for syntax errors, and each note the
+    /// same way after it.
+    pub fn render(&self, src: &str) -> String {
+        let mut out = render_at(src, &self.level.to_string(), &self.message, self.span);
+        for note in &self.notes {
+            out.push('\n');
+            out.push_str(&render_at(src, "note", &note.message, note.span));
+        }
+        out
+    }
+}
+
+// `label: message` over the line of `span`, underlined
+fn render_at(src: &str, label: &str, message: &str, span: Span) -> String {
+    let start = span.start.min(src.len());
+    let (line_no, col) = line_col(src, start);
+    let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = src[start..].find('\n').map_or(src.len(), |i| start + i);
+    let line = &src[line_start..line_end];
+    let width = src[start..span.end.clamp(start, line_end)]
+        .chars()
+        .count()
+        .max(1);
+    let gutter = " ".repeat(line_no.to_string().len());
+    format!(
+        "{}: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}{}",
+        label,
+        message,
+        gutter,
+        line_no,
+        col,
+        gutter,
+        line_no,
+        line,
+        gutter,
+        " ".repeat(col - 1),
+        "^".repeat(width)
+    )
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.level == Level::Error)
+}
+
+/// Everything that is wrong with a program that failed to compile.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    /// Renders every diagnostic against the program's source.
+    pub fn render(&self, src: &str) -> String {
+        let rendered: Vec<String> = self.0.iter().map(|d| d.render(src)).collect();
+        rendered.join("\n")
+    }
+}
+
+/// One `level: message` line per diagnostic, for when the source is not at
+/// hand.
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, d) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {}", d.level, d.message)?;
+            for note in &d.notes {
+                write!(f, "\nnote: {}", note.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// A program that does not parse has nothing else wrong with it yet.
+impl From<crate::parser::Error> for Diagnostics {
+    fn from(err: crate::parser::Error) -> Self {
+        Diagnostics(vec![Diagnostic::from_syntax_error(&err)])
+    }
+}
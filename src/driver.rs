@@ -1,14 +1,335 @@
-use crate::{parser::parse, ir::*, codegen::LlvmEmitter};
-
-pub fn drive(src: &str) {
-    let tu = parse(src);
-    if tu.is_err() {
-        println!("Error: {:?}", tu.err());
-        return;
-    }
-    // interpret(&tu.unwrap());
-    let tu = tu.unwrap();
-    let mut ir_ctx = crate::ir::Context::new();
-    tu.to_ir(&mut ir_ctx);
-    println!("{}", ir_ctx.emit_ir());
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+use tempfile::TempDir;
+
+use calcc::{Options, Program, Stdout, TransUnit, EXIT_TRAP};
+
+/// Exit status when the program is rejected by the compiler.
+pub const EXIT_ERROR: i32 = 1;
+/// Exit status for bad arguments, unreadable files and failing tools.
+pub const EXIT_USAGE: i32 = 2;
+
+/// The runtime that programs compiled with `Numbers::Big` call into.
+const BIGINT_RUNTIME: &str = include_str!("../runtime/bigint.c");
+
+/// What `calcc emit` prints for a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    AstSexpr,
+    AstJson,
+    Ir,
+    Llvm,
+    Asm,
+}
+
+impl Emit {
+    pub fn from_name(name: &str) -> Option<Emit> {
+        match name {
+            "ast" | "ast-sexpr" => Some(Emit::AstSexpr),
+            "ast-json" => Some(Emit::AstJson),
+            "ir" => Some(Emit::Ir),
+            "llvm" => Some(Emit::Llvm),
+            "asm" => Some(Emit::Asm),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a file, or stdin for `-`.
+pub fn read_input(path: &str) -> Result<String, String> {
+    let src = if path == "-" {
+        let mut buf = String::new();
+        std::io::stdin().read_to_string(&mut buf).map(|_| buf)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    src.map_err(|err| format!("{}: {}", path, err))
+}
+
+/// Writes to a file, or stdout for `-`.
+pub fn write_output(path: &str, contents: &[u8]) -> Result<(), String> {
+    let result = if path == "-" {
+        std::io::stdout().write_all(contents)
+    } else {
+        std::fs::write(path, contents)
+    };
+    result.map_err(|err| format!("{}: {}", path, err))
+}
+
+/// Parses and checks a program, printing every diagnostic to stderr.
+/// Returns `None` if there were errors.
+pub fn compile(src: &str, options: Options) -> Option<Program> {
+    match Program::compile(src, options) {
+        Ok(program) => {
+            for d in program.warnings() {
+                eprintln!("{}", d.render(src));
+            }
+            Some(program)
+        }
+        Err(diagnostics) => {
+            eprintln!("{}", diagnostics.render(src));
+            None
+        }
+    }
+}
+
+/// Like `compile`, but starts from an AST serialized with `--emit=ast-json`.
+/// There is no source to quote, so diagnostics are printed without it.
+pub fn compile_ast_json(json: &str, options: Options) -> Option<Program> {
+    match Program::from_json(json, options) {
+        Ok(program) => {
+            for d in program.warnings() {
+                eprintln!("{}: {}", d.level, d.message);
+            }
+            Some(program)
+        }
+        Err(diagnostics) => {
+            eprintln!("{}", diagnostics);
+            None
+        }
+    }
+}
+
+/// The syntax tree `--emit=ast` and `--emit=ast-json` print, parsed from
+/// `text` or loaded from it as JSON, before any checks. Errors are printed
+/// the way `compile` and `compile_ast_json` print them.
+pub fn syntax_tree(text: &str, from_json: bool, emit: Emit) -> Option<String> {
+    let tree = match (from_json, emit) {
+        (true, Emit::AstJson) => TransUnit::from_json(text).map(|tu| tu.to_json()),
+        (true, _) => TransUnit::from_json(text).map(|tu| tu.to_sexpr()),
+        (false, Emit::AstJson) => calcc::syntax_tree_json(text),
+        (false, _) => calcc::syntax_tree(text),
+    };
+    match tree {
+        Ok(tree) => Some(format!("{}\n", tree)),
+        Err(diagnostics) if from_json => {
+            eprintln!("{}", diagnostics);
+            None
+        }
+        Err(diagnostics) => {
+            eprintln!("{}", diagnostics.render(text));
+            None
+        }
+    }
+}
+
+/// How `calcc run` executes a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Walk the AST; needs nothing installed.
+    Interpret,
+    /// JIT-compile the LLVM IR with `lli`, which can be overridden with the
+    /// `LLI` variable.
+    Llvm,
+}
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "interpret" => Some(Mode::Interpret),
+            "llvm" => Some(Mode::Llvm),
+            _ => None,
+        }
+    }
+}
+
+/// Executes `program`, which was compiled from `src`. Both modes print
+/// exactly the same output, and report runtime errors alike. Returns the
+/// program's exit status.
+pub fn run(program: &Program, src: &str, mode: Mode) -> Result<i32, String> {
+    match mode {
+        Mode::Interpret => match program.run(&mut Stdout) {
+            Ok(()) => Ok(0),
+            Err(err) => {
+                eprintln!("{}", err.render(src));
+                Ok(EXIT_TRAP)
+            }
+        },
+        Mode::Llvm => {
+            let ir = program.to_llvm();
+            let lli = tool("LLI", "lli");
+            let mut args = vec![];
+            let runtime = if program.needs_bigint_runtime() {
+                let dir = temp_dir()?;
+                let object = compile_bigint_runtime(dir.path())?;
+                args.push(format!("--extra-object={}", object));
+                Some(dir)
+            } else {
+                None
+            };
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let result = pipe(&lli, &args, &ir, Stdio::inherit());
+            drop(runtime);
+            match result {
+                Ok(_) => Ok(0),
+                Err(Failure::Status(status)) if status.code() == Some(EXIT_TRAP) => Ok(EXIT_TRAP),
+                Err(err) => Err(err.describe(&lli)),
+            }
+        }
+    }
+}
+
+/// Renders `program` in the form `emit` asks for. `asm` is produced by
+/// `llc`.
+pub fn emit(program: &Program, emit: Emit) -> Result<String, String> {
+    match emit {
+        Emit::AstSexpr | Emit::AstJson => unreachable!("syntax trees are emitted before any checks"),
+        Emit::Ir => Ok(program.to_ir()),
+        Emit::Llvm => Ok(program.to_llvm()),
+        Emit::Asm => {
+            let ir = program.to_llvm();
+            let llc = tool("LLC", "llc");
+            let asm = pipe(&llc, &["-filetype=asm", "-o", "-"], &ir, Stdio::piped())
+                .map_err(|err| err.describe(&llc))?;
+            String::from_utf8(asm).map_err(|err| err.to_string())
+        }
+    }
+}
+
+/// Compiles `program` to a native executable at `out` with `llc` and the system
+/// C compiler, which can be overridden with the `LLC` and `CC` variables.
+/// `runtime` are the sources, objects or libraries that define the host
+/// functions the program calls; they are handed to the C compiler as is.
+/// With `Numbers::Big`, the bundled bignum runtime is compiled in as well.
+pub fn build(program: &Program, out: &str, runtime: &[String]) -> Result<(), String> {
+    let dir = temp_dir()?;
+    let object = temp_path(dir.path(), "calc.o");
+    let ir = program.to_llvm();
+    let llc = tool("LLC", "llc");
+    let llc_args = ["-filetype=obj", "-relocation-model=pic", "-o", &object];
+    pipe(&llc, &llc_args, &ir, Stdio::null()).map_err(|err| err.describe(&llc))?;
+    let bigint = if program.needs_bigint_runtime() {
+        Some(write_bigint_runtime(dir.path())?)
+    } else {
+        None
+    };
+    let cc = tool("CC", "cc");
+    let status = Command::new(&cc)
+        .arg(&object)
+        .args(&bigint)
+        .args(runtime)
+        .args(["-lm", "-o", out])
+        .status()
+        .map_err(|err| format!("{}: {}", cc, err));
+    match status? {
+        status if status.success() => Ok(()),
+        status => Err(format!("{} failed with {}", cc, status)),
+    }
+}
+
+// a new directory only this process can use, for the files passed between
+// tools; it is removed with everything in it when dropped
+fn temp_dir() -> Result<TempDir, String> {
+    tempfile::Builder::new()
+        .prefix("calcc-")
+        .tempdir()
+        .map_err(|err| format!("cannot create a temporary directory: {}", err))
+}
+
+fn temp_path(dir: &Path, name: &str) -> String {
+    dir.join(name).to_string_lossy().into_owned()
+}
+
+fn write_bigint_runtime(dir: &Path) -> Result<String, String> {
+    let path = temp_path(dir, "bigint.c");
+    std::fs::write(&path, BIGINT_RUNTIME).map_err(|err| format!("{}: {}", path, err))?;
+    Ok(path)
+}
+
+// `lli` loads the runtime as an object, which the C compiler builds
+fn compile_bigint_runtime(dir: &Path) -> Result<String, String> {
+    let source = write_bigint_runtime(dir)?;
+    let object = temp_path(dir, "bigint.o");
+    let cc = tool("CC", "cc");
+    let status = Command::new(&cc)
+        .args(["-c", "-O2", "-fPIC", "-o", &object, &source])
+        .status()
+        .map_err(|err| format!("{}: {}", cc, err));
+    match status? {
+        status if status.success() => Ok(object),
+        status => Err(format!("{} failed with {}", cc, status)),
+    }
+}
+
+fn tool(var: &str, default: &str) -> String {
+    std::env::var(var).unwrap_or_else(|_| default.to_string())
+}
+
+enum Failure {
+    Io(std::io::Error),
+    Status(ExitStatus),
+}
+
+impl Failure {
+    fn describe(&self, program: &str) -> String {
+        match self {
+            Failure::Io(err) => format!("{}: {}", program, err),
+            Failure::Status(status) => format!("{} failed with {}", program, status),
+        }
+    }
+}
+
+// runs `program` with `input` on stdin and returns what it wrote to a piped
+// stdout
+fn pipe(program: &str, args: &[&str], input: &str, stdout: Stdio) -> Result<Vec<u8>, Failure> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(stdout)
+        .spawn()
+        .map_err(Failure::Io)?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .map_err(Failure::Io)?;
+    let output = child.wait_with_output().map_err(Failure::Io)?;
+    if !output.status.success() {
+        return Err(Failure::Status(output.status));
+    }
+    Ok(output.stdout)
+}
+
+/// Formats each file in place, or stdin to stdout when `paths` is empty or
+/// `-`. With `check`, nothing is written and the files that would change
+/// are listed instead. Returns the process exit code.
+pub fn fmt(paths: &[String], check: bool) -> i32 {
+    let stdin = ["-".to_string()];
+    let paths = if paths.is_empty() { &stdin[..] } else { paths };
+    let mut code = 0;
+    for path in paths {
+        let src = match read_input(path) {
+            Ok(src) => src,
+            Err(err) => {
+                eprintln!("calc: {}", err);
+                code = 2;
+                continue;
+            }
+        };
+        let formatted = match calcc::format(&src) {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                eprintln!("calc: {}:\n{}", path, err.render(&src));
+                code = 2;
+                continue;
+            }
+        };
+        if check {
+            if formatted != src {
+                println!("would reformat {}", path);
+                code = code.max(1);
+            }
+        } else if path == "-" {
+            std::io::stdout().write_all(formatted.as_bytes()).unwrap();
+        } else if formatted != src {
+            if let Err(err) = std::fs::write(path, formatted) {
+                eprintln!("calc: {}: {}", path, err);
+                code = 2;
+            }
+        }
+    }
+    code
 }
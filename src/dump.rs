@@ -0,0 +1,333 @@
+use serde::de::Error as _;
+
+use crate::ast::*;
+use crate::diagnostic::{Diagnostic, Diagnostics};
+use crate::formatter::{
+    format_char_literal, format_decimal_literal, format_imaginary_literal, format_int_literal, format_str_literal,
+    format_string_literal,
+};
+
+/// Serializes the AST, spans included, as pretty-printed JSON.
+pub fn to_json(tu: &TransUnit) -> String {
+    serde_json::to_string_pretty(tu).unwrap()
+}
+
+/// Reads back an AST produced by [`to_json`]. Trees the parser cannot
+/// produce, which the later passes do not expect, are an error too.
+pub fn from_json(json: &str) -> Result<TransUnit, serde_json::Error> {
+    let tu = serde_json::from_str(json)?;
+    let mut validator = Validator { error: None };
+    validator.visit_trans_unit(&tu);
+    match validator.error {
+        Some(message) => Err(serde_json::Error::custom(message)),
+        None => Ok(tu),
+    }
+}
+
+impl TransUnit {
+    /// Reads back a tree saved with `to_json`. Only checks that the parser
+    /// could have produced it; `Program::from_unit` checks the rest.
+    pub fn from_json(json: &str) -> Result<TransUnit, Diagnostics> {
+        from_json(json).map_err(|err| Diagnostics(vec![Diagnostic::error(err.to_string(), Span::default())]))
+    }
+
+    /// The tree as pretty-printed JSON, spans included.
+    pub fn to_json(&self) -> String {
+        to_json(self)
+    }
+
+    /// The tree as an S-expression, one statement per line.
+    pub fn to_sexpr(&self) -> String {
+        to_sexpr(self)
+    }
+}
+
+// finds the first thing in a tree that the parser would have rejected
+struct Validator {
+    error: Option<String>,
+}
+
+impl Validator {
+    fn reject(&mut self, message: String, span: Span) {
+        self.error
+            .get_or_insert_with(|| format!("{} at {}..{}", message, span.start, span.end));
+    }
+
+    fn check_span(&mut self, span: Span) {
+        if span.start > span.end {
+            self.reject("span ends before it starts".to_string(), span);
+        }
+    }
+
+    fn check_unit(&mut self, unit: &Unit) {
+        if unit.factors.is_empty() {
+            self.reject("unit without a name".to_string(), unit.span);
+        }
+        for factor in &unit.factors {
+            if crate::units::lookup(&factor.name).is_none() {
+                self.reject(format!("unknown unit `{}`", factor.name), factor.span);
+            }
+        }
+    }
+}
+
+impl Visitor for Validator {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        self.check_span(stmt.span());
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) {
+        if stmt.format.is_none() && stmt.args.len() != 1 {
+            self.reject("`print` takes one value without a format string".to_string(), stmt.span);
+        }
+        walk_print_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.check_span(expr.span());
+        walk_expr(self, expr);
+    }
+
+    fn visit_primary_expr(&mut self, expr: &PrimaryExpr) {
+        match expr {
+            PrimaryExpr::Matrix(rows, span) => {
+                let cols = rows.first().map_or(0, |row| row.elements.len());
+                if cols == 0 || rows.len() as i64 > MAX_ARRAY_LEN || cols as i64 > MAX_ARRAY_LEN {
+                    self.reject(
+                        format!("a matrix has between 1 and {} rows and columns", MAX_ARRAY_LEN),
+                        *span,
+                    );
+                } else if rows.iter().any(|row| row.elements.len() != cols) {
+                    self.reject("matrix rows must have the same length".to_string(), *span);
+                }
+            }
+            PrimaryExpr::Array(elements, span) if elements.len() as i64 > MAX_ARRAY_LEN => {
+                self.reject(format!("array length can be at most {}", MAX_ARRAY_LEN), *span);
+            }
+            PrimaryExpr::Quantity(_, unit, _) => self.check_unit(unit),
+            _ => {}
+        }
+        walk_primary_expr(self, expr);
+    }
+
+    fn visit_convert_expr(&mut self, expr: &ConvertExpr) {
+        self.check_unit(&expr.unit);
+        walk_convert_expr(self, expr);
+    }
+}
+
+/// Renders the AST as an S-expression, one statement per line.
+///
+/// Parentheses in the source are implied by the nesting and are not shown.
+pub fn to_sexpr(tu: &TransUnit) -> String {
+    let mut out = String::from("(trans_unit");
+    for stmt in &tu.block.stmts {
+        out.push_str("\n  ");
+        out.push_str(&stmt_to_sexpr(stmt));
+    }
+    out.push(')');
+    out
+}
+
+fn stmt_to_sexpr(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::ExprStmt(stmt) => format!("(expr_stmt {})", expr_to_sexpr(&stmt.expr)),
+        Stmt::PrintStmt(stmt) => {
+            let mut out = String::from(if stmt.newline { "(println" } else { "(print" });
+            if let Some(format) = &stmt.format {
+                out.push(' ');
+                out.push_str(&format_string_literal(format));
+            }
+            for arg in &stmt.args {
+                out.push(' ');
+                out.push_str(&expr_to_sexpr(arg));
+            }
+            out.push(')');
+            out
+        }
+        Stmt::LetStmt(stmt) => match stmt.ty {
+            Some(ty) => format!(
+                "(let {} {} {})",
+                stmt.name.name,
+                ty,
+                expr_to_sexpr(&stmt.expr)
+            ),
+            None => format!("(let {} {})", stmt.name.name, expr_to_sexpr(&stmt.expr)),
+        },
+        Stmt::AssignStmt(stmt) => match &stmt.index {
+            Some(index) => format!(
+                "(set (index {} {}) {})",
+                stmt.name.name,
+                expr_to_sexpr(index),
+                expr_to_sexpr(&stmt.expr)
+            ),
+            None => format!("(set {} {})", stmt.name.name, expr_to_sexpr(&stmt.expr)),
+        },
+        Stmt::AttrStmt(stmt) => {
+            let mut out = format!("({}{}", if stmt.inner { "#!" } else { "#" }, stmt.level);
+            for lint in &stmt.lints {
+                out.push(' ');
+                out.push_str(&lint.name);
+            }
+            out.push(')');
+            out
+        }
+        Stmt::BlockStmt(stmt) => {
+            let mut out = String::from("(block");
+            for stmt in &stmt.block.stmts {
+                out.push(' ');
+                out.push_str(&stmt_to_sexpr(stmt));
+            }
+            out.push(')');
+            out
+        }
+    }
+}
+
+fn expr_to_sexpr(expr: &Expr) -> String {
+    match expr {
+        Expr::Primary(primary) => match primary.as_ref() {
+            PrimaryExpr::Mem(_) => "mem".to_string(),
+            PrimaryExpr::Int(i, radix, _) => format_int_literal(i, *radix),
+            PrimaryExpr::BigInt(i, radix, _) => format_int_literal(i, *radix),
+            PrimaryExpr::Char(c, _) => format_char_literal(*c),
+            PrimaryExpr::Rational(i, _) => format!("{}r", i),
+            PrimaryExpr::Decimal(digits, places, _) => format_decimal_literal(*digits, *places),
+            PrimaryExpr::Float(f, _) => format!("{:?}", f),
+            PrimaryExpr::Imaginary(f, _) => format_imaginary_literal(*f),
+            PrimaryExpr::Quantity(f, unit, _) => format!("(quantity {:?} {})", f, unit),
+            PrimaryExpr::Bool(b, _) => b.to_string(),
+            PrimaryExpr::Str(text, _) => format_str_literal(text),
+            PrimaryExpr::Ident(ident) => ident.name.clone(),
+            PrimaryExpr::Call(call) => {
+                let mut out = format!("(call {}", call.callee.name);
+                for arg in &call.args {
+                    out.push(' ');
+                    out.push_str(&expr_to_sexpr(arg));
+                }
+                out.push(')');
+                out
+            }
+            PrimaryExpr::Array(elements, _) => {
+                let mut out = String::from("(array");
+                for element in elements {
+                    out.push(' ');
+                    out.push_str(&expr_to_sexpr(element));
+                }
+                out.push(')');
+                out
+            }
+            PrimaryExpr::Repeat(value, count, _) => {
+                format!("(repeat {} {})", expr_to_sexpr(value), expr_to_sexpr(count))
+            }
+            PrimaryExpr::Matrix(rows, _) => {
+                let mut out = String::from("(matrix");
+                for row in rows {
+                    let elements: Vec<String> = row.elements.iter().map(expr_to_sexpr).collect();
+                    out.push_str(&format!(" ({})", elements.join(" ")));
+                }
+                out.push(')');
+                out
+            }
+            PrimaryExpr::Expr(expr, _) => expr_to_sexpr(expr),
+        },
+        Expr::Prefix(prefix) => {
+            let op = prefix.op.symbol();
+            format!("({} {})", op, expr_to_sexpr(&prefix.expr))
+        }
+        Expr::Postfix(postfix) => {
+            let op = postfix.op.symbol();
+            format!("(postfix{} {})", op, expr_to_sexpr(&postfix.expr))
+        }
+        Expr::Infix(infix) => {
+            let op = infix.op.symbol();
+            format!(
+                "({} {} {})",
+                op,
+                expr_to_sexpr(&infix.lhs),
+                expr_to_sexpr(&infix.rhs)
+            )
+        }
+        Expr::Cast(cast) => format!("(as {} {})", cast.ty, expr_to_sexpr(&cast.expr)),
+        Expr::Convert(convert) => format!("(in {} {})", convert.unit, expr_to_sexpr(&convert.expr)),
+        Expr::Index(index) => format!(
+            "(index {} {})",
+            expr_to_sexpr(&index.expr),
+            expr_to_sexpr(&index.index)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use serde_json::{json, Value};
+
+    // the JSON of `src`, changed by `edit`, read back
+    fn load_edited(src: &str, edit: impl FnOnce(&mut Value)) -> Result<TransUnit, String> {
+        let mut json: Value = serde_json::from_str(&to_json(&parse(src).unwrap())).unwrap();
+        edit(&mut json);
+        from_json(&json.to_string()).map_err(|err| err.to_string())
+    }
+
+    fn first_stmt(json: &mut Value) -> &mut Value {
+        let stmt = &mut json["block"]["stmts"][0];
+        let (_, inner) = stmt.as_object_mut().unwrap().iter_mut().next().unwrap();
+        inner
+    }
+
+    #[test]
+    fn round_trip() {
+        let src = "let m = [[1.0, 2.0]]; print(\"{}\", 3 m in cm); println 1;";
+        assert_eq!(to_json(&from_json(&to_json(&parse(src).unwrap())).unwrap()), to_json(&parse(src).unwrap()));
+    }
+
+    #[test]
+    fn print_without_arguments() {
+        let err = load_edited("print 1;", |json| first_stmt(json)["args"] = json!([])).unwrap_err();
+        assert!(err.starts_with("`print` takes one value"), "{}", err);
+    }
+
+    #[test]
+    fn matrix_without_rows() {
+        let err = load_edited("print [[1.0]];", |json| {
+            let arg = &mut first_stmt(json)["args"][0];
+            arg["Primary"]["Matrix"][0] = json!([]);
+        })
+        .unwrap_err();
+        assert!(err.starts_with("a matrix has between 1 and"), "{}", err);
+    }
+
+    #[test]
+    fn ragged_matrix() {
+        let err = load_edited("print [[1.0, 2.0], [3.0, 4.0]];", |json| {
+            let rows = &mut first_stmt(json)["args"][0]["Primary"]["Matrix"][0];
+            rows[1]["elements"].as_array_mut().unwrap().pop();
+        })
+        .unwrap_err();
+        assert!(err.starts_with("matrix rows must have the same length"), "{}", err);
+    }
+
+    #[test]
+    fn reversed_span() {
+        let err = load_edited("print 1;", |json| first_stmt(json)["span"] = json!({"start": 5, "end": 2})).unwrap_err();
+        assert!(err.starts_with("span ends before it starts at 5..2"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_unit() {
+        let err = load_edited("print 3 m;", |json| {
+            let unit = &mut first_stmt(json)["args"][0]["Primary"]["Quantity"][1];
+            unit["factors"][0]["name"] = json!("parsec");
+        })
+        .unwrap_err();
+        assert!(err.starts_with("unknown unit `parsec`"), "{}", err);
+    }
+
+    #[test]
+    fn not_json() {
+        assert!(from_json("{\"block\": ").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt;
+
+use crate::ast::Stmt;
+use crate::diagnostic::{self, Diagnostic, Diagnostics};
+use crate::interpreter::{execute, Env, Output, Stdout, Value};
+use crate::native::IntoNative;
+use crate::parser;
+use crate::runtime::{Numbers, Overflow, Precision, RuntimeError};
+use crate::sema::Resolver;
+use crate::typeck::TypeChecker;
+use crate::units::DimensionChecker;
+
+/// Why `Engine::eval` failed.
+#[derive(Debug, Clone)]
+pub enum Error {
+    Compile(Diagnostics),
+    Runtime(RuntimeError),
+}
+
+impl Error {
+    /// Renders the error against the source passed to `eval`.
+    pub fn render(&self, src: &str) -> String {
+        match self {
+            Error::Compile(diagnostics) => diagnostics.render(src),
+            Error::Runtime(err) => err.render(src),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Compile(diagnostics) => write!(f, "{}", diagnostics),
+            Error::Runtime(err) => write!(f, "error: {}", err.trap.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Evaluates programs in-process. `mem` and variables carry over from one
+/// `eval` to the next, and an input that fails leaves them as they were.
+pub struct Engine {
+    resolver: Resolver,
+    checker: TypeChecker,
+    dimensions: DimensionChecker,
+    env: Env,
+    overflow: Overflow,
+    numbers: Numbers,
+    precision: Precision,
+    output: Box<dyn Output + Send>,
+    warnings: Vec<Diagnostic>,
+}
+
+// an embedding application may hand an engine to another thread
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<Engine>();
+};
+
+impl Engine {
+    pub fn new() -> Self {
+        Self {
+            resolver: Resolver::new(),
+            checker: TypeChecker::new(),
+            dimensions: DimensionChecker::new(),
+            env: Env::new(),
+            overflow: Overflow::default(),
+            numbers: Numbers::default(),
+            precision: Precision::default(),
+            output: Box::new(Stdout),
+            warnings: vec![],
+        }
+    }
+
+    /// Sends what `print` prints to `output` instead of stdout.
+    pub fn set_output(&mut self, output: impl Output + Send + 'static) -> &mut Self {
+        self.output = Box::new(output);
+        self
+    }
+
+    pub fn set_overflow(&mut self, overflow: Overflow) -> &mut Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn set_numbers(&mut self, numbers: Numbers) -> &mut Self {
+        self.numbers = numbers;
+        self.checker.set_numbers(numbers);
+        self
+    }
+
+    /// The scale and rounding of decimals, and how `print` shows
+    /// rationals. Set it before running anything that makes a `dec`.
+    pub fn set_precision(&mut self, precision: Precision) -> &mut Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Makes a closure callable from programs as `name`, e.g.
+    /// `engine.register_fn("clamp", |x: i64, lo: i64, hi: i64| x.max(lo).min(hi))`.
+    /// Registering a name again replaces the earlier function, and a builtin
+    /// of the same name is hidden.
+    pub fn register_fn<Args>(&mut self, name: &str, func: impl IntoNative<Args>) -> &mut Self {
+        let func = func.into_native();
+        self.resolver.declare_function(name, func.sig.clone());
+        self.env.define_function(name, func);
+        self
+    }
+
+    /// Warnings about the input last passed to `eval`.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// Runs a program and returns the value of `mem` afterwards.
+    pub fn eval(&mut self, src: &str) -> Result<Value, Error> {
+        self.eval_statements(src, |_, _| {})?;
+        Ok(self.env.get_mem())
+    }
+
+    /// Like `eval`, but calls `echo` with the value of `mem` after every
+    /// top-level expression statement, which is what a REPL shows.
+    pub fn eval_each(&mut self, src: &str, mut echo: impl FnMut(Value)) -> Result<(), Error> {
+        self.eval_statements(src, |stmt, mem| {
+            if let Stmt::ExprStmt(_) = stmt {
+                echo(mem);
+            }
+        })
+    }
+
+    // calls `each` with every top-level statement and the value of `mem`
+    // after it ran
+    fn eval_statements(&mut self, src: &str, mut each: impl FnMut(&Stmt, Value)) -> Result<(), Error> {
+        self.warnings.clear();
+        let mut tu = parser::parse(src).map_err(|err| Error::Compile(err.into()))?;
+        let (resolver, checker, dimensions, env) = (
+            self.resolver.clone(),
+            self.checker.clone(),
+            self.dimensions.clone(),
+            self.env.clone(),
+        );
+        let mut diagnostics = self.resolver.resolve(&mut tu);
+        if !diagnostic::has_errors(&diagnostics) {
+            diagnostics.extend(self.checker.check(&tu));
+        }
+        if !diagnostic::has_errors(&diagnostics) {
+            diagnostics.extend(self.dimensions.check(&tu));
+        }
+        let mut result = Ok(());
+        if diagnostic::has_errors(&diagnostics) {
+            result = Err(Error::Compile(Diagnostics(diagnostics)));
+        } else {
+            self.warnings = diagnostics;
+            for stmt in &tu.block.stmts {
+                let (overflow, numbers, precision) = (self.overflow, self.numbers, self.precision);
+                if let Err(err) = execute(&mut self.env, stmt, overflow, numbers, precision, &mut *self.output) {
+                    result = Err(Error::Runtime(err));
+                    break;
+                }
+                each(stmt, self.env.get_mem());
+            }
+        }
+        if let Err(err) = result {
+            self.resolver = resolver;
+            self.checker = checker;
+            self.dimensions = dimensions;
+            self.env = env;
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,506 @@
+use crate::ast::*;
+use crate::parser::{
+    infix_precedence, CAST_PRECEDENCE, CONVERT_PRECEDENCE, INDEX_PRECEDENCE, POSTFIX_PRECEDENCE, PREFIX_PRECEDENCE,
+};
+
+// a `// ...` comment found in the source text
+struct Comment<'a> {
+    span: Span,
+    text: &'a str,
+}
+
+/// Pretty-prints a translation unit back to canonical source.
+///
+/// `src` must be the text `tu` was parsed from; it is used to recover
+/// comments and blank lines, which the AST does not keep.
+pub fn format(tu: &TransUnit, src: &str) -> String {
+    let mut formatter = Formatter {
+        src,
+        comments: collect_comments(src),
+        next_comment: 0,
+        last_end: 0,
+        indent: 0,
+        out: String::new(),
+    };
+    formatter.format_block(&tu.block);
+    formatter.flush_comments_before(src.len());
+    formatter.out
+}
+
+struct Formatter<'a> {
+    src: &'a str,
+    comments: Vec<Comment<'a>>,
+    next_comment: usize,
+    // end of the last item written, used to detect blank lines
+    last_end: usize,
+    // nesting depth of the statement being written
+    indent: usize,
+    out: String,
+}
+
+impl<'a> Formatter<'a> {
+    fn format_block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            let span = stmt.span();
+            self.flush_comments_before(span.start);
+            self.blank_line_before(span.start);
+            if !matches!(stmt, Stmt::BlockStmt(_)) {
+                // a comment inside a statement has no place in its
+                // one-line form, so it goes on its own line above it
+                self.last_end = span.start;
+                self.flush_comments_before(span.end);
+            }
+            self.write_indent();
+            self.format_stmt(stmt);
+            self.last_end = span.end;
+            self.trailing_comment();
+            self.out.push('\n');
+        }
+    }
+
+    fn format_stmt(&mut self, stmt: &Stmt) {
+        let text = match stmt {
+            Stmt::ExprStmt(stmt) => format!("{};", format_expr(&stmt.expr)),
+            Stmt::PrintStmt(stmt) => {
+                let keyword = if stmt.newline { "println" } else { "print" };
+                match (&stmt.format, &stmt.args[..]) {
+                    // `print("text")` would be a format string, which does
+                    // not end the line
+                    (None, [arg]) if is_str_literal(strip_parens(arg)) => {
+                        format!("{} {};", keyword, format_expr(arg))
+                    }
+                    _ => {
+                        let mut args: Vec<String> =
+                            stmt.format.iter().map(format_string_literal).collect();
+                        args.extend(stmt.args.iter().map(|arg| format_expr(strip_parens(arg))));
+                        format!("{}({});", keyword, args.join(", "))
+                    }
+                }
+            }
+            Stmt::LetStmt(stmt) => match stmt.ty {
+                Some(ty) => format!(
+                    "let {}: {} = {};",
+                    stmt.name.name,
+                    ty,
+                    format_expr(&stmt.expr)
+                ),
+                None => format!("let {} = {};", stmt.name.name, format_expr(&stmt.expr)),
+            },
+            Stmt::AssignStmt(stmt) => match &stmt.index {
+                Some(index) => format!(
+                    "{}[{}] = {};",
+                    stmt.name.name,
+                    format_expr(index),
+                    format_expr(&stmt.expr)
+                ),
+                None => format!("{} = {};", stmt.name.name, format_expr(&stmt.expr)),
+            },
+            Stmt::AttrStmt(stmt) => format!(
+                "#{}[{}({})]",
+                if stmt.inner { "!" } else { "" },
+                stmt.level,
+                lint_names(stmt)
+            ),
+            Stmt::BlockStmt(stmt) => {
+                let close = stmt.span.end - 1;
+                self.out.push('{');
+                self.last_end = stmt.span.start + 1;
+                self.trailing_comment();
+                let has_comments = self
+                    .comments
+                    .get(self.next_comment)
+                    .is_some_and(|comment| comment.span.start < close);
+                if stmt.block.stmts.is_empty() && !has_comments {
+                    self.out.push('}');
+                    return;
+                }
+                self.out.push('\n');
+                self.indent += 1;
+                self.format_block(&stmt.block);
+                self.flush_comments_before(close);
+                self.indent -= 1;
+                self.write_indent();
+                "}".to_string()
+            }
+        };
+        self.out.push_str(&text);
+    }
+
+    fn write_indent(&mut self) {
+        self.out.push_str(&"    ".repeat(self.indent));
+    }
+
+    // emit every comment that starts before `pos`, one per line
+    fn flush_comments_before(&mut self, pos: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.span.start >= pos {
+                break;
+            }
+            let (span, text) = (comment.span, comment.text);
+            self.blank_line_before(span.start);
+            self.write_indent();
+            self.out.push_str(text);
+            self.out.push('\n');
+            self.last_end = span.end;
+            self.next_comment += 1;
+        }
+    }
+
+    // keep a comment on the same line as the statement it follows
+    fn trailing_comment(&mut self) {
+        if let Some(comment) = self.comments.get(self.next_comment) {
+            let gap = &self.src[self.last_end..comment.span.start];
+            if !gap.contains('\n') && gap.trim().is_empty() {
+                self.out.push(' ');
+                self.out.push_str(comment.text);
+                self.last_end = comment.span.end;
+                self.next_comment += 1;
+            }
+        }
+    }
+
+    // collapse any run of blank lines in the source into a single one
+    fn blank_line_before(&mut self, pos: usize) {
+        let gap = &self.src[self.last_end..pos];
+        if !self.out.is_empty() && gap.matches('\n').count() > 1 {
+            self.out.push('\n');
+        }
+    }
+}
+
+fn collect_comments(src: &str) -> Vec<Comment<'_>> {
+    let mut comments = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            // a `//` inside a string or char literal is not a comment
+            '"' | '\'' => {
+                while let Some((_, next)) = chars.next_if(|&(_, next)| next != '\n') {
+                    if next == '\\' {
+                        chars.next_if(|&(_, next)| next != '\n');
+                    } else if next == c {
+                        break;
+                    }
+                }
+            }
+            '/' if src[start + 1..].starts_with('/') => {
+                let len = src[start..].find('\n').unwrap_or(src.len() - start);
+                let text = src[start..start + len].trim_end();
+                comments.push(Comment {
+                    span: Span {
+                        start,
+                        end: start + text.len(),
+                    },
+                    text,
+                });
+                while chars.next_if(|&(i, _)| i < start + len).is_some() {}
+            }
+            _ => {}
+        }
+    }
+    comments
+}
+
+/// Formats an expression with normalized spacing and only the parentheses
+/// the precedence table requires.
+pub fn format_expr(expr: &Expr) -> String {
+    match strip_parens(expr) {
+        Expr::Primary(primary) => match primary.as_ref() {
+            PrimaryExpr::Mem(_) => "mem".to_string(),
+            PrimaryExpr::Int(i, radix, _) => format_int_literal(i, *radix),
+            PrimaryExpr::BigInt(i, radix, _) => format_int_literal(i, *radix),
+            PrimaryExpr::Char(c, _) => format_char_literal(*c),
+            PrimaryExpr::Rational(i, _) => format!("{}r", i),
+            PrimaryExpr::Decimal(digits, places, _) => format_decimal_literal(*digits, *places),
+            PrimaryExpr::Float(f, _) => format_float_literal(*f),
+            PrimaryExpr::Imaginary(f, _) => format_imaginary_literal(*f),
+            PrimaryExpr::Quantity(f, unit, _) => format_quantity_literal(*f, unit),
+            PrimaryExpr::Bool(b, _) => b.to_string(),
+            PrimaryExpr::Str(text, _) => format_str_literal(text),
+            PrimaryExpr::Ident(ident) => ident.name.clone(),
+            PrimaryExpr::Call(call) => {
+                let args: Vec<String> = call.args.iter().map(format_expr).collect();
+                format!("{}({})", call.callee.name, args.join(", "))
+            }
+            PrimaryExpr::Array(elements, _) => {
+                let elements: Vec<String> = elements.iter().map(format_expr).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            PrimaryExpr::Repeat(value, count, _) => {
+                format!("[{}; {}]", format_expr(value), format_expr(count))
+            }
+            PrimaryExpr::Matrix(rows, _) => {
+                let rows: Vec<String> = rows
+                    .iter()
+                    .map(|row| {
+                        let elements: Vec<String> = row.elements.iter().map(format_expr).collect();
+                        format!("[{}]", elements.join(", "))
+                    })
+                    .collect();
+                format!("[{}]", rows.join(", "))
+            }
+            PrimaryExpr::Expr(..) => unreachable!(),
+        },
+        Expr::Prefix(prefix) => {
+            let op = prefix.op.symbol();
+            let operand = strip_parens(&prefix.expr);
+            format!(
+                "{}{}",
+                op,
+                format_operand(operand, precedence(operand) < PREFIX_PRECEDENCE)
+            )
+        }
+        Expr::Postfix(postfix) => {
+            let operand = strip_parens(&postfix.expr);
+            format!(
+                "{}{}",
+                format_operand(operand, precedence(operand) < POSTFIX_PRECEDENCE),
+                postfix.op.symbol()
+            )
+        }
+        Expr::Infix(infix) => {
+            let op = infix.op.symbol();
+            let prec = infix_precedence(&infix.op);
+            let lhs = strip_parens(&infix.lhs);
+            let rhs = strip_parens(&infix.rhs);
+            // all infix operators but `**` are left-associative
+            let right = infix.op == InfixOp::Power;
+            let mut lhs_text =
+                format_operand(lhs, precedence(lhs) < prec || (right && precedence(lhs) == prec));
+            // `x% - 1` would be read as the remainder `x % -1`
+            if matches!(infix.op, InfixOp::Plus | InfixOp::Minus) && lhs_text.ends_with('%') {
+                lhs_text = format!("({})", lhs_text);
+            }
+            format!(
+                "{} {} {}",
+                lhs_text,
+                op,
+                format_operand(rhs, precedence(rhs) < prec || (!right && precedence(rhs) == prec))
+            )
+        }
+        Expr::Cast(cast) => {
+            let operand = strip_parens(&cast.expr);
+            format!(
+                "{} as {}",
+                format_operand(operand, precedence(operand) < CAST_PRECEDENCE),
+                cast.ty
+            )
+        }
+        // nothing binds looser than `in`, so its operand needs no parentheses
+        Expr::Convert(convert) => format!("{} in {}", format_expr(&convert.expr), convert.unit),
+        Expr::Index(index) => {
+            let operand = strip_parens(&index.expr);
+            format!(
+                "{}[{}]",
+                format_operand(operand, precedence(operand) < INDEX_PRECEDENCE),
+                format_expr(&index.index)
+            )
+        }
+    }
+}
+
+// `1.0` or `6.02e23`: `Debug` always keeps a fractional part or an
+// exponent, so the literal stays a float
+fn format_float_literal(f: f64) -> String {
+    format!("{:?}", f)
+}
+
+/// An integer literal in the base it was written in, like `0xFF`. Digit
+/// separators are not kept.
+pub fn format_int_literal<T>(i: &T, radix: Radix) -> String
+where
+    T: std::fmt::Display + std::fmt::UpperHex + std::fmt::Octal + std::fmt::Binary,
+{
+    match radix {
+        Radix::Decimal => i.to_string(),
+        Radix::Hex => format!("{:#X}", i).replace("0X", "0x"),
+        Radix::Octal => format!("{:#o}", i),
+        Radix::Binary => format!("{:#b}", i),
+    }
+}
+
+/// `"{:>5} items\n"`, with the text escaped and the placeholders as short
+/// as they can be.
+pub fn format_string_literal(format: &FormatString) -> String {
+    let mut out = String::from('"');
+    for piece in &format.pieces {
+        match piece {
+            FormatPiece::Text(text) => {
+                for c in text.chars() {
+                    match c {
+                        '{' => out.push_str("{{"),
+                        '}' => out.push_str("}}"),
+                        '\'' => out.push(c),
+                        _ => out.extend(c.escape_debug()),
+                    }
+                }
+            }
+            FormatPiece::Spec(spec) => out.push_str(&format_spec(spec)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A placeholder as short as it can be written, like `{:>5}`.
+pub fn format_spec(spec: &FormatSpec) -> String {
+    let mut out = String::new();
+    if let Some(align) = spec.align {
+        if spec.fill != ' ' {
+            out.extend(spec.fill.escape_debug());
+        }
+        out.push(match align {
+            Align::Left => '<',
+            Align::Center => '^',
+            Align::Right => '>',
+        });
+    }
+    if spec.zero {
+        out.push('0');
+    }
+    if let Some(width) = spec.width {
+        out.push_str(&width.to_string());
+    }
+    if let Some(precision) = spec.precision {
+        out.push_str(&format!(".{}", precision));
+    }
+    out.push_str(match (spec.radix, spec.upper) {
+        (Radix::Decimal, _) => "",
+        (Radix::Hex, false) => "x",
+        (Radix::Hex, true) => "X",
+        (Radix::Octal, _) => "o",
+        (Radix::Binary, _) => "b",
+    });
+    if spec.polar {
+        out.push('p');
+    }
+    match out.is_empty() {
+        true => "{}".to_string(),
+        false => format!("{{:{}}}", out),
+    }
+}
+
+/// `'a'`, escaping what the grammar needs escaped.
+pub fn format_char_literal(c: char) -> String {
+    format!("'{}'", c.escape_debug())
+}
+
+/// `"text"` with `"`, `\` and the characters that are not printable
+/// escaped.
+pub fn format_str_literal(text: &str) -> String {
+    format!("{:?}", text)
+}
+
+/// `3i` or `0.5i`, without a fraction when it is whole.
+pub fn format_imaginary_literal(f: f64) -> String {
+    let text = format_float_literal(f);
+    format!("{}i", text.strip_suffix(".0").unwrap_or(&text))
+}
+
+/// `9.81 m/s^2` or `3 kg`, without a fraction when it is whole.
+pub fn format_quantity_literal(f: f64, unit: &Unit) -> String {
+    let text = format_float_literal(f);
+    format!("{} {}", text.strip_suffix(".0").unwrap_or(&text), unit)
+}
+
+/// `19.99d` from its digits and places, keeping the places as written.
+pub fn format_decimal_literal(digits: i64, places: u32) -> String {
+    let places = places as usize;
+    let digits = format!("{:0>width$}", digits, width = places + 1);
+    let (int, fraction) = digits.split_at(digits.len() - places);
+    match places {
+        0 => format!("{}d", int),
+        _ => format!("{}.{}d", int, fraction),
+    }
+}
+
+fn format_operand(expr: &Expr, parens: bool) -> String {
+    if parens {
+        format!("({})", format_expr(expr))
+    } else {
+        format_expr(expr)
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Primary(_) => u8::MAX,
+        Expr::Prefix(_) => PREFIX_PRECEDENCE,
+        Expr::Postfix(_) => POSTFIX_PRECEDENCE,
+        Expr::Infix(infix) => infix_precedence(&infix.op),
+        Expr::Cast(_) => CAST_PRECEDENCE,
+        Expr::Convert(_) => CONVERT_PRECEDENCE,
+        Expr::Index(_) => INDEX_PRECEDENCE,
+    }
+}
+
+pub fn strip_parens(mut expr: &Expr) -> &Expr {
+    while let Expr::Primary(primary) = expr {
+        match primary.as_ref() {
+            PrimaryExpr::Expr(inner, _) => expr = inner,
+            _ => break,
+        }
+    }
+    expr
+}
+
+fn is_str_literal(expr: &Expr) -> bool {
+    matches!(expr, Expr::Primary(primary) if matches!(primary.as_ref(), PrimaryExpr::Str(..)))
+}
+
+fn lint_names(stmt: &AttrStmt) -> String {
+    let names: Vec<&str> = stmt.lints.iter().map(|lint| lint.name.as_str()).collect();
+    names.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn fmt(src: &str) -> String {
+        let tu = parse(src).unwrap_or_else(|err| panic!("{:?} failed to parse:\n{}", src, err));
+        format(&tu, src)
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let sources = [
+            "let  x=((1+2))*3;print x;",
+            "{let y:float=-(2.0);{print(\"{:>5} {{}}\",y);}}",
+            "#![deny(unused_mem)]\n#[allow(redundant_plus)] print +1;\n\n\n\nprint 0xff+0b1_0;",
+            "let m=[[1,2],[3,4]];print m@transpose(m); // product\n",
+            "let a=[0;3];a[1]=2;print a;let s=substr(\"abc\",1,1)+\"d\";",
+            "print 1 km+500 m in mi;print (3+4i)/2;print 1/3r;print 1.25d;",
+            "// one\nprint 1 + // two\n 2; // three\n// four",
+        ];
+        for src in sources {
+            let once = fmt(src);
+            assert_eq!(fmt(&once), once, "{:?}", src);
+        }
+    }
+
+    #[test]
+    fn comment_inside_statement_goes_above_it() {
+        assert_eq!(fmt("print 1 + // mid\n 2;\n"), "// mid\nprint(1 + 2);\n");
+        assert_eq!(
+            fmt("{\n    let x = 1 // one\n        + 2; // three\n}\n"),
+            "{\n    // one\n    let x = 1 + 2; // three\n}\n"
+        );
+    }
+
+    #[test]
+    fn comments_keep_their_places() {
+        let src = "// head\n\nprint(1); // after\n\n// tail\n";
+        assert_eq!(fmt(src), src);
+    }
+
+    #[test]
+    fn slashes_in_literals_are_not_comments() {
+        assert_eq!(fmt("print \"http://example.com\";"), "print \"http://example.com\";\n");
+        assert_eq!(
+            fmt("print(\"a\\\"//b\", '\\\"'); // c\nprint '/';"),
+            "print(\"a\\\"//b\", '\\\"'); // c\nprint('/');\n"
+        );
+    }
+}
@@ -1,70 +1,1054 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
 use crate::ast::*;
-struct Env {
-    mem: i64,
+use crate::bigint;
+use crate::builtins::Builtin;
+use crate::complex::{self, Complex};
+use crate::decimal::{self, Decimal};
+use crate::matrix::Matrix;
+use crate::native::NativeFn;
+use crate::rational::{self, Rational};
+use crate::runtime::{Numbers, Overflow, Precision, RuntimeError, Trap};
+
+/// A runtime value. Programs are type-checked first, so operators only ever
+/// see the operand types `typeck` allows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    /// An `int` outside the 64-bit range, which only `Numbers::Big` has.
+    BigInt(BigInt),
+    Float(f64),
+    Bool(bool),
+    Rational(Rational),
+    Decimal(Decimal),
+    Complex(Complex),
+    Str(String),
+    /// An array of either kind; only `typeck` knows which lengths are fixed.
+    Array(Vec<i64>),
+    Matrix(Matrix),
+}
+
+impl Value {
+    fn as_float(&self) -> f64 {
+        match *self {
+            Value::Int(i) => i as f64,
+            // rounded to nearest, or infinite if too large
+            Value::BigInt(ref n) => n.to_f64().unwrap(),
+            Value::Float(f) => f,
+            Value::Bool(b) => b as i64 as f64,
+            Value::Rational(r) => r.to_float(),
+            Value::Decimal(d) => d.to_float(),
+            Value::Complex(_) | Value::Str(_) | Value::Array(_) | Value::Matrix(_) => unreachable!(),
+        }
+    }
+
+    fn as_complex(&self) -> Complex {
+        match *self {
+            Value::Complex(z) => z,
+            _ => Complex::new(self.as_float(), 0.0),
+        }
+    }
+
+    fn as_rational(&self) -> Rational {
+        match *self {
+            Value::Int(i) => Rational::from_int(i),
+            Value::Bool(b) => Rational::from_int(b as i64),
+            Value::Rational(r) => r,
+            Value::Decimal(d) => d.to_rational(),
+            _ => unreachable!(),
+        }
+    }
+
+    // arrays only exist with 64-bit `int`s
+    fn as_int(&self) -> i64 {
+        match *self {
+            Value::Int(i) => i,
+            _ => unreachable!(),
+        }
+    }
+
+    fn as_bigint(&self) -> BigInt {
+        match self {
+            Value::Int(i) => BigInt::from(*i),
+            Value::BigInt(n) => n.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Converts like `as`, which is also how `int` widens to `float`.
+    fn convert(&self, ty: Type) -> Value {
+        match (self, ty) {
+            (Value::Int(_) | Value::BigInt(_), Type::Int)
+            | (Value::Float(_), Type::Float)
+            | (Value::Bool(_), Type::Bool)
+            | (Value::Rational(_), Type::Rational)
+            | (Value::Complex(_), Type::Complex)
+            | (Value::Str(_), Type::Str)
+            | (Value::Array(_), Type::Array(_))
+            | (Value::Matrix(_), Type::Matrix(..)) => self.clone(),
+            // text only converts through `to_str` and `parse_int`, complex
+            // numbers through `re`, `im` and `abs`, and arrays and matrices
+            // not at all
+            (Value::Str(_) | Value::Complex(_) | Value::Array(_) | Value::Matrix(_), _)
+            | (_, Type::Str | Type::Array(_) | Type::Matrix(..)) => unreachable!(),
+            (_, Type::Complex) => Value::Complex(self.as_complex()),
+            (_, Type::Float) => Value::Float(self.as_float()),
+            (Value::Int(_) | Value::Bool(_) | Value::Decimal(_), Type::Rational) => {
+                Value::Rational(self.as_rational())
+            }
+            // the scale is the interpreter's, see `Interpreter::convert`
+            (_, Type::Decimal) => unreachable!(),
+            // `rat` and big integers never meet, and floats may not fit
+            (Value::BigInt(_) | Value::Float(_), Type::Rational) => unreachable!(),
+            (Value::Rational(r), Type::Int) => Value::Int(r.to_int()),
+            (Value::Rational(r), Type::Bool) => Value::Bool(r.num() != 0),
+            (Value::Decimal(d), Type::Int) => Value::Int(d.to_int()),
+            (Value::Decimal(d), Type::Bool) => Value::Bool(d.units() != 0),
+            (Value::Float(f), Type::Int) => Value::Int(*f as i64),
+            (Value::Bool(b), Type::Int) => Value::Int(*b as i64),
+            (Value::Int(i), Type::Bool) => Value::Bool(*i != 0),
+            // never zero, since it is out of the 64-bit range
+            (Value::BigInt(_), Type::Bool) => Value::Bool(true),
+            (Value::Float(f), Type::Bool) => Value::Bool(*f != 0.0),
+        }
+    }
+
+    fn ty(&self) -> Type {
+        match self {
+            Value::Int(_) | Value::BigInt(_) => Type::Int,
+            Value::Float(_) => Type::Float,
+            Value::Bool(_) => Type::Bool,
+            Value::Rational(_) => Type::Rational,
+            Value::Decimal(_) => Type::Decimal,
+            Value::Complex(_) => Type::Complex,
+            Value::Str(_) => Type::Str,
+            Value::Array(_) => Type::Array(None),
+            Value::Matrix(m) => Type::Matrix(m.rows() as u32, m.cols() as u32),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::BigInt(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{}", format_float(*x)),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Rational(r) => write!(f, "{}", r),
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::Complex(z) => write!(f, "{}", z),
+            Value::Str(text) => write!(f, "{}", text),
+            Value::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(i64::to_string).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Value::Matrix(m) => write!(f, "{}", m),
+        }
+    }
+}
+
+/// Formats a float the way C's `printf("%.15g")` does, which is what
+/// compiled programs use, so both execution modes print the same digits.
+pub fn format_float(x: f64) -> String {
+    const PRECISION: i32 = 15;
+    // C shows the sign of a NaN, which depends on how it was computed
+    if x.is_nan() {
+        return "nan".to_string();
+    }
+    if x.is_infinite() {
+        return if x < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    if x == 0.0 {
+        return if x.is_sign_negative() { "-0" } else { "0" }.to_string();
+    }
+    // the exponent after rounding to the requested number of digits
+    let sci = format!("{:.*e}", (PRECISION - 1) as usize, x);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    if !(-4..PRECISION).contains(&exp) {
+        let mantissa = trim_fraction(mantissa);
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exp.abs())
+    } else {
+        let fixed = format!("{:.*}", (PRECISION - 1 - exp) as usize, x);
+        trim_fraction(&fixed).to_string()
+    }
+}
+
+/// Formats a float with `places` after the point, like C's `printf("%.*f")`.
+pub fn format_fixed(x: f64, places: u32) -> String {
+    if !x.is_finite() {
+        return format_float(x);
+    }
+    format!("{:.*}", places as usize, x)
+}
+
+/// Pads `text` to the width `spec` asks for. Numbers are aligned right
+/// unless `spec` says otherwise, and zero padding goes after the sign.
+fn pad(text: &str, spec: &FormatSpec, numeric: bool) -> String {
+    let len = text.chars().count();
+    let padding = match spec.width {
+        Some(width) if width as usize > len => width as usize - len,
+        _ => return text.to_string(),
+    };
+    if spec.zero {
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", text),
+        };
+        return format!("{}{}{}", sign, "0".repeat(padding), digits);
+    }
+    let align = match spec.align {
+        Some(align) => align,
+        None if numeric => Align::Right,
+        None => Align::Left,
+    };
+    let (before, after) = match align {
+        Align::Left => (0, padding),
+        Align::Center => (padding / 2, padding - padding / 2),
+        Align::Right => (padding, 0),
+    };
+    let fill = spec.fill.to_string();
+    format!("{}{}{}", fill.repeat(before), text, fill.repeat(after))
+}
+
+fn trim_fraction(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
+
+/// The values of `mem` and every variable, which outlive a single input in
+/// the REPL, and the host functions programs can call.
+#[derive(Clone)]
+pub struct Env {
+    mem: Value,
+    vars: HashMap<DeclId, Value>,
+    functions: HashMap<String, NativeFn>,
 }
 
 impl Env {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
-            mem: 0,
+            mem: Value::Int(0),
+            vars: HashMap::new(),
+            functions: HashMap::new(),
         }
     }
 
-    fn get_mem(&self) -> i64 {
-        self.mem
+    /// Makes `func` callable as `name`; `sema` must be told about it too.
+    pub fn define_function(&mut self, name: &str, func: NativeFn) {
+        self.functions.insert(name.to_string(), func);
+    }
+
+    pub fn get_mem(&self) -> Value {
+        self.mem.clone()
     }
 
-    fn set_mem(&mut self, val: i64) {
+    fn set_mem(&mut self, val: Value) {
         self.mem = val;
     }
+
+    fn get_var(&self, ident: &Ident) -> Value {
+        self.vars[&ident.decl.unwrap()].clone()
+    }
+
+    fn set_var(&mut self, ident: &Ident, val: Value) {
+        self.vars.insert(ident.decl.unwrap(), val);
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-pub fn interpret(tu: &TransUnit) {
+/// Where `print` sends its output, one call per statement: a line comes
+/// with its newline, and `print` with a format string may leave the line
+/// unfinished. Closures taking a `&str` are outputs too.
+pub trait Output {
+    fn write(&mut self, text: &str);
+}
+
+impl<F: FnMut(&str)> Output for F {
+    fn write(&mut self, text: &str) {
+        self(text)
+    }
+}
+
+/// Prints to the process's standard output.
+pub struct Stdout;
+
+impl Output for Stdout {
+    fn write(&mut self, text: &str) {
+        print!("{}", text);
+        // an unfinished line would wait for the next newline
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Runs a program; it must already have passed `sema` and `typeck`.
+/// Stops at the first runtime error.
+pub fn interpret(
+    tu: &TransUnit,
+    overflow: Overflow,
+    numbers: Numbers,
+    precision: Precision,
+    out: &mut dyn Output,
+) -> Result<(), RuntimeError> {
     let mut env = Env::new();
-    for stmt in &tu.block.stmts {
-        match stmt {
-            Stmt::ExprStmt(expr) => {
-                let ret = eval_expr(&mut env, expr);
-                env.set_mem(ret);
+    let mut interpreter = Interpreter::new(&mut env, overflow, numbers, precision, out);
+    interpreter.visit_trans_unit(tu);
+    interpreter.error.map_or(Ok(()), Err)
+}
+
+/// Runs one statement against `env`, for programs that arrive piece by
+/// piece.
+pub fn execute(
+    env: &mut Env,
+    stmt: &Stmt,
+    overflow: Overflow,
+    numbers: Numbers,
+    precision: Precision,
+    out: &mut dyn Output,
+) -> Result<(), RuntimeError> {
+    let mut interpreter = Interpreter::new(env, overflow, numbers, precision, out);
+    interpreter.visit_stmt(stmt);
+    interpreter.error.map_or(Ok(()), Err)
+}
+
+struct Interpreter<'a> {
+    env: &'a mut Env,
+    overflow: Overflow,
+    numbers: Numbers,
+    precision: Precision,
+    out: &'a mut dyn Output,
+    // once set, no further statements run
+    error: Option<RuntimeError>,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(
+        env: &'a mut Env,
+        overflow: Overflow,
+        numbers: Numbers,
+        precision: Precision,
+        out: &'a mut dyn Output,
+    ) -> Self {
+        Self {
+            env,
+            overflow,
+            numbers,
+            precision,
+            out,
+            error: None,
+        }
+    }
+
+    // whether `int` operations go through `bigint`
+    fn is_big(&self, value: &Value) -> bool {
+        self.numbers == Numbers::Big && value.ty() == Type::Int
+    }
+
+    // whether `lhs op rhs` computes in `rat`: one operand is a `rat` and the
+    // other no float, or `int`s are divided with `Numbers::Rational`
+    fn is_rational(&self, op: InfixOp, lhs: &Value, rhs: &Value) -> bool {
+        match (lhs.ty(), rhs.ty()) {
+            (Type::Rational, Type::Rational | Type::Int) | (Type::Int, Type::Rational) => true,
+            (Type::Int, Type::Int) => op == InfixOp::Divide && self.numbers == Numbers::Rational,
+            _ => false,
+        }
+    }
+
+    // `value` as a `dec` with the scale and rounding of the program
+    fn to_decimal(&self, value: &Value) -> Result<Decimal, Trap> {
+        let Precision {
+            decimal_scale: scale,
+            rounding,
+            ..
+        } = self.precision;
+        match *value {
+            Value::Int(i) => Decimal::from_int(i, scale),
+            Value::Bool(b) => Decimal::from_int(b as i64, scale),
+            Value::Float(f) => Decimal::from_float(f, scale, rounding),
+            Value::Rational(r) => Decimal::from_rational(r, scale, rounding),
+            Value::Decimal(d) => Ok(d),
+            // `dec` and big integers never meet, and complex numbers, text,
+            // arrays and matrices do not convert
+            Value::BigInt(_) | Value::Complex(_) | Value::Str(_) | Value::Array(_) | Value::Matrix(_) => {
+                unreachable!()
+            }
+        }
+    }
+
+    // `Value::convert`, plus the conversions that can fail
+    fn convert(&self, value: Value, ty: Type, span: Span) -> Result<Value, RuntimeError> {
+        let result = match (&value, ty) {
+            (_, Type::Decimal) => self.to_decimal(&value).map(Value::Decimal),
+            (&Value::Float(f), Type::Rational) => Rational::from_float(f).map(Value::Rational),
+            (&Value::Float(f), Type::Int) if self.numbers == Numbers::Big && f.is_finite() => {
+                Ok(bigint::from_float(f))
+            }
+            (&Value::Float(f), Type::Int) => float_to_int(f, self.overflow).map(Value::Int),
+            _ => Ok(value.convert(ty)),
+        };
+        result.map_err(|trap| trap.at(span))
+    }
+
+    // `lhs op rhs` where either operand is a `dec` and the other may be an
+    // `int`
+    fn eval_decimal_infix(&self, op: InfixOp, lhs: &Value, rhs: &Value) -> Result<Value, Trap> {
+        let (lhs, rhs) = (self.to_decimal(lhs)?, self.to_decimal(rhs)?);
+        decimal::eval_infix(op, lhs, rhs, self.precision.rounding)
+    }
+
+    fn format(&self, value: &Value) -> String {
+        match (value, self.precision.rational_digits) {
+            (Value::Rational(r), Some(digits)) => r.format(digits),
+            (value, _) => value.to_string(),
+        }
+    }
+
+    // `value` as a placeholder shows it; `calc_fmt_*` in the prelude of
+    // `codegen` does the same for compiled programs
+    fn format_arg(&self, value: &Value, spec: &FormatSpec) -> String {
+        let text = match (value, spec.radix, spec.precision) {
+            (Value::Int(i), Radix::Hex, _) if spec.upper => format!("{:X}", i),
+            (Value::Int(i), Radix::Hex, _) => format!("{:x}", i),
+            (Value::Int(i), Radix::Octal, _) => format!("{:o}", i),
+            (Value::Int(i), Radix::Binary, _) => format!("{:b}", i),
+            (&Value::Float(x), _, Some(places)) => format_fixed(x, places),
+            (&Value::Complex(z), _, places) => z.format(spec.polar, places),
+            _ => self.format(value),
+        };
+        pad(&text, spec, value.ty().is_numeric())
+    }
+
+    fn exec_expr_stmt(&mut self, stmt: &ExprStmt) -> Result<(), RuntimeError> {
+        let ret = self.eval_expr(&stmt.expr)?;
+        self.env.set_mem(ret);
+        Ok(())
+    }
+
+    fn exec_print_stmt(&mut self, stmt: &PrintStmt) -> Result<(), RuntimeError> {
+        let values = stmt
+            .args
+            .iter()
+            .map(|arg| self.eval_expr(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut text = match &stmt.format {
+            Some(format) => {
+                let mut values = values.iter();
+                format
+                    .pieces
+                    .iter()
+                    .map(|piece| match piece {
+                        FormatPiece::Text(text) => text.clone(),
+                        FormatPiece::Spec(spec) => self.format_arg(values.next().unwrap(), spec),
+                    })
+                    .collect()
+            }
+            None => self.format(&values[0]),
+        };
+        if stmt.ends_line() {
+            text.push('\n');
+        }
+        self.out.write(&text);
+        Ok(())
+    }
+
+    fn exec_let_stmt(&mut self, stmt: &LetStmt) -> Result<(), RuntimeError> {
+        let mut val = self.eval_expr(&stmt.expr)?;
+        if let Some(ty) = stmt.ty {
+            val = self.convert(val, ty, stmt.expr.span())?;
+        }
+        self.env.set_var(&stmt.name, val);
+        Ok(())
+    }
+
+    fn exec_assign_stmt(&mut self, stmt: &AssignStmt) -> Result<(), RuntimeError> {
+        let Some(index) = &stmt.index else {
+            let ty = self.env.get_var(&stmt.name).ty();
+            let val = self.eval_expr(&stmt.expr)?;
+            let val = self.convert(val, ty, stmt.expr.span())?;
+            self.env.set_var(&stmt.name, val);
+            return Ok(());
+        };
+        let i = self.eval_expr(index)?;
+        let val = self.eval_expr(&stmt.expr)?;
+        let Value::Array(mut elements) = self.env.get_var(&stmt.name) else {
+            unreachable!()
+        };
+        let i = element_index(&elements, &i).map_err(|trap| trap.at(index.span()))?;
+        elements[i] = val.as_int();
+        self.env.set_var(&stmt.name, Value::Array(elements));
+        Ok(())
+    }
+
+    // operands are evaluated left to right, and the first trap ends the
+    // whole expression
+    fn eval_expr(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+        match expr {
+            Expr::Primary(e) => self.eval_primary_expr(e),
+            Expr::Prefix(e) => self.eval_prefix_expr(e),
+            Expr::Postfix(e) => self.eval_postfix_expr(e),
+            Expr::Infix(e) => self.eval_infix_expr(e),
+            Expr::Cast(e) => self.eval_cast_expr(e),
+            Expr::Convert(e) => self.eval_convert_expr(e),
+            Expr::Index(e) => self.eval_index_expr(e),
+        }
+    }
+
+    fn eval_primary_expr(&mut self, expr: &PrimaryExpr) -> Result<Value, RuntimeError> {
+        Ok(match expr {
+            PrimaryExpr::Mem(_) => self.env.get_mem(),
+            PrimaryExpr::Int(i, _, _) => Value::Int(*i),
+            PrimaryExpr::BigInt(n, _, _) => Value::BigInt(n.clone()),
+            PrimaryExpr::Char(c, _) => Value::Int(*c as i64),
+            PrimaryExpr::Rational(i, _) => Value::Rational(Rational::from_int(*i)),
+            PrimaryExpr::Decimal(digits, places, span) => {
+                let Precision {
+                    decimal_scale: scale,
+                    rounding,
+                    ..
+                } = self.precision;
+                let d = Decimal::from_literal(*digits, *places, scale, rounding).map_err(|trap| trap.at(*span))?;
+                Value::Decimal(d)
+            }
+            PrimaryExpr::Float(f, _) => Value::Float(*f),
+            PrimaryExpr::Imaginary(f, _) => Value::Complex(Complex::new(0.0, *f)),
+            PrimaryExpr::Quantity(f, unit, _) => Value::Float(f * unit.scale()),
+            PrimaryExpr::Bool(b, _) => Value::Bool(*b),
+            PrimaryExpr::Str(text, _) => Value::Str(text.clone()),
+            PrimaryExpr::Array(elements, _) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| Ok(self.eval_expr(element)?.as_int()))
+                    .collect::<Result<_, RuntimeError>>()?;
+                Value::Array(elements)
+            }
+            PrimaryExpr::Repeat(value, count, span) => {
+                let value = self.eval_expr(value)?.as_int();
+                match self.eval_expr(count)?.as_int() {
+                    len @ 0..=MAX_ARRAY_LEN => Value::Array(vec![value; len as usize]),
+                    _ => return Err(Trap::ArrayLength.at(*span)),
+                }
+            }
+            PrimaryExpr::Matrix(rows, _) => {
+                let elements = rows
+                    .iter()
+                    .flat_map(|row| &row.elements)
+                    .map(|element| Ok(self.eval_expr(element)?.as_float()))
+                    .collect::<Result<_, RuntimeError>>()?;
+                Value::Matrix(Matrix::new(rows.len(), rows[0].elements.len(), elements))
+            }
+            PrimaryExpr::Ident(ident) => self.env.get_var(ident),
+            PrimaryExpr::Call(call) => self.eval_call(call)?,
+            PrimaryExpr::Expr(e, _) => self.eval_expr(e)?,
+        })
+    }
+
+    fn eval_call(&mut self, call: &CallExpr) -> Result<Value, RuntimeError> {
+        // host functions may have side effects, which must not happen after
+        // a trap in their arguments
+        let args = call
+            .args
+            .iter()
+            .map(|arg| self.eval_expr(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(builtin) = call.builtin {
+            let types: Vec<Type> = args.iter().map(|arg| arg.ty()).collect();
+            let sig = builtin.signature(&types).unwrap();
+            let args: Vec<Value> = args.iter().zip(&sig.params).map(|(arg, &ty)| arg.convert(ty)).collect();
+            let result = match args.as_slice() {
+                _ if builtin.is_text() => eval_text_builtin(builtin, &args, self.numbers),
+                [Value::Matrix(m)] => eval_matrix_builtin(builtin, m),
+                &[Value::Complex(z)] => Ok(eval_complex_builtin(builtin, z)),
+                [x] if self.is_big(x) => bigint::eval_builtin(builtin, &x.as_bigint(), &BigInt::zero()),
+                [x, y] if self.is_big(x) => bigint::eval_builtin(builtin, &x.as_bigint(), &y.as_bigint()),
+                _ => eval_builtin(builtin, &args, self.overflow),
+            };
+            return result.map_err(|trap| trap.at(call.span));
+        }
+        let sig = call.sig.as_ref().unwrap();
+        let args: Vec<Value> = args.iter().zip(&sig.params).map(|(arg, &ty)| arg.convert(ty)).collect();
+        // host functions take 64-bit integers
+        if args.iter().any(|arg| matches!(arg, Value::BigInt(_))) {
+            return Err(Trap::Overflow.at(call.span));
+        }
+        // only declared, for a runtime to define
+        let Some(func) = self.env.functions.get(&call.callee.name) else {
+            return Err(Trap::UndefinedFunction.at(call.span));
+        };
+        Ok(func.call(&args))
+    }
+
+    fn eval_prefix_expr(&mut self, expr: &PrefixExpr) -> Result<Value, RuntimeError> {
+        let rhs = self.eval_expr(&expr.expr)?;
+        let at = |trap: Trap| trap.at(expr.span);
+        Ok(match (expr.op, rhs) {
+            (PrefixOp::Plus, rhs) => rhs,
+            (op @ (PrefixOp::Minus | PrefixOp::BitNot), rhs) if self.is_big(&rhs) => {
+                bigint::eval_prefix(op, &rhs.as_bigint())
+            }
+            (PrefixOp::Minus, Value::Int(i)) => {
+                Value::Int(eval_int_arith(InfixOp::Minus, 0, i, self.overflow).map_err(at)?)
+            }
+            (PrefixOp::Minus, Value::Float(f)) => Value::Float(-f),
+            (PrefixOp::Minus, Value::Rational(r)) => Value::Rational(r.checked_neg().map_err(at)?),
+            (PrefixOp::Minus, Value::Decimal(d)) => Value::Decimal(d.negated()),
+            (PrefixOp::Minus, Value::Complex(z)) => Value::Complex(-z),
+            (PrefixOp::Not, Value::Bool(b)) => Value::Bool(!b),
+            (PrefixOp::BitNot, Value::Int(i)) => Value::Int(!i),
+            _ => unreachable!(),
+        })
+    }
+
+    fn eval_postfix_expr(&mut self, expr: &PostfixExpr) -> Result<Value, RuntimeError> {
+        let operand = self.eval_expr(&expr.expr)?;
+        let at = |trap: Trap| trap.at(expr.span);
+        Ok(match (expr.op, operand) {
+            (PostfixOp::Factorial, n) if self.is_big(&n) => bigint::factorial(&n.as_bigint()).map_err(at)?,
+            (PostfixOp::Factorial, Value::Int(n)) => Value::Int(eval_factorial(n, self.overflow).map_err(at)?),
+            // exact for `rat` and `dec`, see `TypeChecker::visit_postfix_expr`
+            (PostfixOp::Percent, value) if self.is_rational(InfixOp::Divide, &value, &Value::Int(100)) => {
+                rational::eval_infix(InfixOp::Divide, value.as_rational(), Rational::from_int(100)).map_err(at)?
+            }
+            (PostfixOp::Percent, value @ Value::Decimal(_)) => {
+                self.eval_decimal_infix(InfixOp::Divide, &value, &Value::Int(100)).map_err(at)?
+            }
+            (PostfixOp::Percent, value) => Value::Float(value.as_float() / 100.0),
+            _ => unreachable!(),
+        })
+    }
+
+    fn eval_infix_expr(&mut self, expr: &InfixExpr) -> Result<Value, RuntimeError> {
+        let lhs = self.eval_expr(&expr.lhs)?;
+        let rhs = self.eval_expr(&expr.rhs)?;
+        let at = |trap: Trap| trap.at(expr.span);
+        Ok(match (&lhs, &rhs) {
+            (Value::Matrix(lhs), Value::Matrix(rhs)) => match expr.op {
+                InfixOp::Plus => Value::Matrix(lhs.add(rhs)),
+                InfixOp::Minus => Value::Matrix(lhs.sub(rhs)),
+                InfixOp::MatMul => Value::Matrix(lhs.product(rhs)),
+                _ => unreachable!(),
             },
-            Stmt::PrintStmt(expr) => {
-                println!("{}", eval_expr(&mut env, expr));
+            // a scalar on either side of `*`
+            (Value::Matrix(m), factor) | (factor, Value::Matrix(m)) => Value::Matrix(m.scale(factor.as_float())),
+            // any other number widens to `complex`
+            (lhs, rhs) if lhs.ty() == Type::Complex || rhs.ty() == Type::Complex => {
+                complex::eval_infix(expr.op, lhs.as_complex(), rhs.as_complex())
+            }
+            (lhs, rhs) if self.is_big(lhs) && self.is_big(rhs) => {
+                bigint::eval_infix(expr.op, &lhs.as_bigint(), &rhs.as_bigint()).map_err(at)?
             }
+            (lhs, rhs) if self.is_rational(expr.op, lhs, rhs) => {
+                rational::eval_infix(expr.op, lhs.as_rational(), rhs.as_rational()).map_err(at)?
+            }
+            (lhs, rhs) if lhs.ty() == Type::Decimal || rhs.ty() == Type::Decimal => {
+                self.eval_decimal_infix(expr.op, lhs, rhs).map_err(at)?
+            }
+            (&Value::Int(lhs), &Value::Int(rhs)) => eval_int_infix(expr.op, lhs, rhs, self.overflow).map_err(at)?,
+            (Value::Array(lhs), Value::Array(rhs)) => match expr.op {
+                InfixOp::Equal => Value::Bool(lhs == rhs),
+                InfixOp::NotEqual => Value::Bool(lhs != rhs),
+                _ => unreachable!(),
+            },
+            (Value::Str(lhs), Value::Str(rhs)) => match expr.op {
+                InfixOp::Plus => Value::Str(format!("{}{}", lhs, rhs)),
+                InfixOp::Equal => Value::Bool(lhs == rhs),
+                InfixOp::NotEqual => Value::Bool(lhs != rhs),
+                _ => unreachable!(),
+            },
+            (&Value::Bool(lhs), &Value::Bool(rhs)) => match expr.op {
+                InfixOp::Equal => Value::Bool(lhs == rhs),
+                InfixOp::NotEqual | InfixOp::BitXor => Value::Bool(lhs != rhs),
+                InfixOp::BitAnd => Value::Bool(lhs & rhs),
+                InfixOp::BitOr => Value::Bool(lhs | rhs),
+                _ => unreachable!(),
+            },
+            // mixed operands widen to float
+            _ => eval_float_infix(expr.op, lhs.as_float(), rhs.as_float()),
+        })
+    }
+
+    fn eval_cast_expr(&mut self, expr: &CastExpr) -> Result<Value, RuntimeError> {
+        let value = self.eval_expr(&expr.expr)?;
+        self.convert(value, expr.ty, expr.span)
+    }
+
+    fn eval_convert_expr(&mut self, expr: &ConvertExpr) -> Result<Value, RuntimeError> {
+        let value = self.eval_expr(&expr.expr)?;
+        Ok(Value::Float(value.as_float() / expr.unit.scale()))
+    }
+
+    fn eval_index_expr(&mut self, expr: &IndexExpr) -> Result<Value, RuntimeError> {
+        let array = self.eval_expr(&expr.expr)?;
+        let i = self.eval_expr(&expr.index)?;
+        let Value::Array(elements) = array else {
+            unreachable!()
+        };
+        let i = element_index(&elements, &i).map_err(|trap| trap.at(expr.index.span()))?;
+        Ok(Value::Int(elements[i]))
+    }
+}
+
+// Statements go through the visitor so blocks are walked like everywhere
+// else; expressions are evaluated by `eval_expr`, which returns their value.
+impl Visitor for Interpreter<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = match stmt {
+            Stmt::ExprStmt(stmt) => self.exec_expr_stmt(stmt),
+            Stmt::PrintStmt(stmt) => self.exec_print_stmt(stmt),
+            Stmt::LetStmt(stmt) => self.exec_let_stmt(stmt),
+            Stmt::AssignStmt(stmt) => self.exec_assign_stmt(stmt),
+            Stmt::BlockStmt(_) | Stmt::AttrStmt(_) => {
+                walk_stmt(self, stmt);
+                Ok(())
+            }
+        };
+        if let Err(err) = result {
+            self.error = Some(err);
         }
     }
 }
 
-fn eval_expr(env: &mut Env, expr: &Expr) -> i64 {
-    match expr {
-        Expr::Primary(e)=> eval_primary(env, e),
-        Expr::Prefix(e)=> eval_prefix(env, e),
-        Expr::Infix(e)=> eval_infix(env, e),
+// the position of element `i`, which must be in `elements`
+fn element_index(elements: &[i64], i: &Value) -> Result<usize, Trap> {
+    match *i {
+        Value::Int(i) if (0..elements.len() as i64).contains(&i) => Ok(i as usize),
+        _ => Err(Trap::OutOfBounds),
     }
 }
 
-fn eval_primary(env: &mut Env, expr: &PrimaryExpr) -> i64 {
-    match expr {
-        PrimaryExpr::Mem => env.get_mem(),
-        PrimaryExpr::Int(i) => *i,
-        PrimaryExpr::Expr(e) => eval_expr(env, e),
+fn eval_int_infix(op: InfixOp, lhs: i64, rhs: i64, overflow: Overflow) -> Result<Value, Trap> {
+    Ok(match op {
+        op if op.is_arithmetic() => Value::Int(eval_int_arith(op, lhs, rhs, overflow)?),
+        InfixOp::BitAnd => Value::Int(lhs & rhs),
+        InfixOp::BitOr => Value::Int(lhs | rhs),
+        InfixOp::BitXor => Value::Int(lhs ^ rhs),
+        InfixOp::ShiftLeft | InfixOp::ShiftRight if !(0..64).contains(&rhs) => {
+            return Err(Trap::InvalidShift)
+        }
+        InfixOp::ShiftLeft => Value::Int(lhs << rhs),
+        InfixOp::ShiftRight => Value::Int(lhs >> rhs),
+        InfixOp::Equal => Value::Bool(lhs == rhs),
+        InfixOp::NotEqual => Value::Bool(lhs != rhs),
+        InfixOp::Less => Value::Bool(lhs < rhs),
+        InfixOp::LessEqual => Value::Bool(lhs <= rhs),
+        InfixOp::Greater => Value::Bool(lhs > rhs),
+        InfixOp::GreaterEqual => Value::Bool(lhs >= rhs),
+        _ => unreachable!(),
+    })
+}
+
+/// Integer `+ - * / % **`, with `overflow` deciding what happens to results
+/// out of range. The LLVM backend lowers these to match.
+pub fn eval_int_arith(op: InfixOp, lhs: i64, rhs: i64, overflow: Overflow) -> Result<i64, Trap> {
+    let divides_by_zero = match op {
+        InfixOp::Divide | InfixOp::Remainder => rhs == 0,
+        // a negative power is a division
+        InfixOp::Power => lhs == 0 && rhs < 0,
+        _ => false,
+    };
+    if divides_by_zero {
+        return Err(Trap::DivisionByZero);
+    }
+    let (wrapped, overflows) = match op {
+        InfixOp::Plus => lhs.overflowing_add(rhs),
+        InfixOp::Minus => lhs.overflowing_sub(rhs),
+        InfixOp::Multiply => lhs.overflowing_mul(rhs),
+        InfixOp::Divide => lhs.overflowing_div(rhs),
+        // `i64::MIN % -1` is 0, which is no overflow
+        InfixOp::Remainder => (lhs.wrapping_rem(rhs), false),
+        InfixOp::Power => overflowing_pow(lhs, rhs),
+        _ => unreachable!(),
+    };
+    match (overflows, overflow) {
+        (false, _) | (true, Overflow::Wrap) => Ok(wrapped),
+        (true, Overflow::Trap) => Err(Trap::Overflow),
+        (true, Overflow::Saturate) => Ok(match op {
+            InfixOp::Plus => lhs.saturating_add(rhs),
+            InfixOp::Minus => lhs.saturating_sub(rhs),
+            InfixOp::Multiply => lhs.saturating_mul(rhs),
+            InfixOp::Divide => lhs.saturating_div(rhs),
+            _ if lhs < 0 && rhs % 2 != 0 => i64::MIN,
+            _ => i64::MAX,
+        }),
     }
 }
 
-fn eval_prefix(env: &mut Env, expr: &PrefixExpr) -> i64 {
-    let rhs = eval_expr(env, &expr.expr);
-    match expr.op {
-        PrefixOp::Plus => rhs,
-        PrefixOp::Minus => -rhs,
+/// `f as int`, truncated towards zero, with `overflow` deciding what happens
+/// to NaN and to floats out of the 64-bit range. Wrapping keeps the truncated
+/// value modulo 2^64, which is 0 for NaN and infinities. This is also how
+/// `calc_f64_to_i64_wrap` computes it.
+pub fn float_to_int(f: f64, overflow: Overflow) -> Result<i64, Trap> {
+    // -2^63 is a float, and 2^63 the first one past `i64::MAX`
+    if (-9223372036854775808.0..9223372036854775808.0).contains(&f) {
+        return Ok(f as i64);
+    }
+    match overflow {
+        Overflow::Trap => Err(Trap::Overflow),
+        Overflow::Saturate => Ok(f as i64),
+        Overflow::Wrap => {
+            let rem = f % 18446744073709551616.0;
+            let units = rem.abs() as u64;
+            Ok(if rem < 0.0 { units.wrapping_neg() } else { units } as i64)
+        }
+    }
+}
+
+/// The largest `n` whose factorial fits in an `int`.
+pub const MAX_FACTORIAL: i64 = 20;
+
+/// `n!`, with `overflow` deciding what happens from `21!` on. The wrapped
+/// product is 0 from `66!` on, which has 64 factors of 2, so the loop stops
+/// there. This is also how `calc_factorial` computes it.
+pub fn eval_factorial(n: i64, overflow: Overflow) -> Result<i64, Trap> {
+    if n < 0 {
+        return Err(Trap::NegativeFactorial);
+    }
+    if n > MAX_FACTORIAL {
+        match overflow {
+            Overflow::Wrap => {}
+            Overflow::Trap => return Err(Trap::Overflow),
+            Overflow::Saturate => return Ok(i64::MAX),
+        }
+    }
+    Ok((2..=n.min(66)).fold(1i64, |product, i| product.wrapping_mul(i)))
+}
+
+/// `base ** exp` by repeated squaring, wrapped, and whether it overflowed.
+/// A negative exponent truncates the fraction, so only 1 and -1 give
+/// nonzero results. This is also how `calc_pow_with_overflow` computes it.
+pub fn overflowing_pow(base: i64, exp: i64) -> (i64, bool) {
+    if exp < 0 {
+        let pow = match base {
+            1 => 1,
+            -1 if exp % 2 != 0 => -1,
+            -1 => 1,
+            _ => 0,
+        };
+        return (pow, false);
+    }
+    let (mut pow, mut factor, mut exp, mut overflows) = (1i64, base, exp, false);
+    while exp != 0 {
+        if exp & 1 != 0 {
+            let (product, o) = pow.overflowing_mul(factor);
+            pow = product;
+            overflows |= o;
+        }
+        exp >>= 1;
+        // the square after the last bit is never used
+        if exp != 0 {
+            let (square, o) = factor.overflowing_mul(factor);
+            factor = square;
+            overflows |= o;
+        }
+    }
+    (pow, overflows)
+}
+
+/// Calls a builtin with arguments of the types its signature asks for.
+/// Integer results out of range are handled like those of the operators.
+pub fn eval_builtin(builtin: Builtin, args: &[Value], overflow: Overflow) -> Result<Value, Trap> {
+    match *args {
+        [Value::Int(x)] => eval_int_builtin(builtin, x, 0, overflow).map(Value::Int),
+        [Value::Int(x), Value::Int(y)] => eval_int_builtin(builtin, x, y, overflow).map(Value::Int),
+        [Value::Float(x)] => Ok(Value::Float(eval_float_builtin(builtin, x, 0.0))),
+        [Value::Float(x), Value::Float(y)] => Ok(Value::Float(eval_float_builtin(builtin, x, y))),
+        _ => unreachable!(),
+    }
+}
+
+/// Calls one of the builtins on text, which counts in characters, not
+/// bytes, or `len` of an array. With `Numbers::Big`, `int`s of any size go in and come out.
+pub fn eval_text_builtin(builtin: Builtin, args: &[Value], numbers: Numbers) -> Result<Value, Trap> {
+    match (builtin, args) {
+        (Builtin::Len, [Value::Str(text)]) => Ok(Value::Int(text.chars().count() as i64)),
+        (Builtin::Len, [Value::Array(elements)]) => Ok(Value::Int(elements.len() as i64)),
+        (Builtin::Substr, [Value::Str(text), start, count]) => substr(text, start, count).map(Value::Str),
+        (Builtin::ToStr, [value]) => Ok(Value::Str(value.to_string())),
+        (Builtin::ParseInt, [Value::Str(text)]) => parse_int(text, numbers),
+        _ => unreachable!(),
+    }
+}
+
+// the `count` characters of `text` from the one at `start`
+fn substr(text: &str, start: &Value, count: &Value) -> Result<String, Trap> {
+    // big integers are out of the 64-bit range, and so of any text
+    let (&Value::Int(start), &Value::Int(count)) = (start, count) else {
+        return Err(Trap::OutOfBounds);
+    };
+    let len = text.chars().count() as i64;
+    if start < 0 || count < 0 || start > len || count > len - start {
+        return Err(Trap::OutOfBounds);
+    }
+    Ok(text.chars().skip(start as usize).take(count as usize).collect())
+}
+
+// an optional sign and decimal digits, and nothing else; only
+// `Numbers::Big` has values beyond 64 bits
+fn parse_int(text: &str, numbers: Numbers) -> Result<Value, Trap> {
+    let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Trap::InvalidInteger);
+    }
+    let n: BigInt = text.parse().unwrap();
+    match n.to_i64() {
+        Some(i) => Ok(Value::Int(i)),
+        None if numbers == Numbers::Big => Ok(Value::BigInt(n)),
+        None => Err(Trap::InvalidInteger),
+    }
+}
+
+// `y` is ignored by the functions of one argument
+fn eval_int_builtin(builtin: Builtin, x: i64, y: i64, overflow: Overflow) -> Result<i64, Trap> {
+    let abs = |x: i64| {
+        if x < 0 {
+            eval_int_arith(InfixOp::Minus, 0, x, overflow)
+        } else {
+            Ok(x)
+        }
+    };
+    match builtin {
+        Builtin::Abs => abs(x),
+        Builtin::Sign => Ok(x.signum()),
+        Builtin::Min => Ok(x.min(y)),
+        Builtin::Max => Ok(x.max(y)),
+        Builtin::Mod => {
+            if y == 0 {
+                return Err(Trap::DivisionByZero);
+            }
+            // `i64::MIN % -1` overflows in Rust, though the result is 0
+            let rem = x.wrapping_rem(y);
+            Ok(if rem != 0 && (rem < 0) != (y < 0) { rem + y } else { rem })
+        }
+        Builtin::Gcd => match i64::try_from(gcd(x, y)) {
+            Ok(gcd) => Ok(gcd),
+            // only `gcd(i64::MIN, i64::MIN)` and `gcd(i64::MIN, 0)`
+            Err(_) => match overflow {
+                Overflow::Trap => Err(Trap::Overflow),
+                Overflow::Wrap => Ok(i64::MIN),
+                Overflow::Saturate => Ok(i64::MAX),
+            },
+        },
+        Builtin::Lcm => {
+            // `gcd` wraps to `i64::MIN` as a divisor, which still divides
+            let gcd = gcd(x, y) as i64;
+            let quotient = if gcd == 0 { 0 } else { x / gcd };
+            abs(eval_int_arith(InfixOp::Multiply, quotient, y, overflow)?)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn gcd(x: i64, y: i64) -> u64 {
+    let (mut x, mut y) = (x.unsigned_abs(), y.unsigned_abs());
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    x
+}
+
+fn eval_float_builtin(builtin: Builtin, x: f64, y: f64) -> f64 {
+    match builtin {
+        Builtin::Abs => x.abs(),
+        Builtin::Sign => {
+            if x > 0.0 {
+                1.0
+            } else if x < 0.0 {
+                -1.0
+            } else {
+                x
+            }
+        }
+        Builtin::Min => x.min(y),
+        Builtin::Max => x.max(y),
+        Builtin::Mod => {
+            let rem = x % y;
+            if rem != 0.0 && (rem < 0.0) != (y < 0.0) {
+                rem + y
+            } else {
+                rem
+            }
+        }
+        Builtin::Pow => x.powf(y),
+        Builtin::Sqrt => x.sqrt(),
+        Builtin::Floor => x.floor(),
+        Builtin::Ceil => x.ceil(),
+        Builtin::Round => x.round(),
+        Builtin::Log => x.ln(),
+        Builtin::Exp => x.exp(),
+        Builtin::Sin => x.sin(),
+        Builtin::Cos => x.cos(),
+        Builtin::Tan => x.tan(),
+        Builtin::Asin => x.asin(),
+        Builtin::Acos => x.acos(),
+        Builtin::Atan => x.atan(),
+        Builtin::Atan2 => x.atan2(y),
+        Builtin::Gcd
+        | Builtin::Lcm
+        | Builtin::Len
+        | Builtin::Substr
+        | Builtin::ToStr
+        | Builtin::ParseInt
+        | Builtin::Transpose
+        | Builtin::Det
+        | Builtin::Inv
+        | Builtin::Re
+        | Builtin::Im
+        | Builtin::Arg
+        | Builtin::Conj => unreachable!(),
+    }
+}
+
+fn eval_matrix_builtin(builtin: Builtin, m: &Matrix) -> Result<Value, Trap> {
+    Ok(match builtin {
+        Builtin::Transpose => Value::Matrix(m.transpose()),
+        Builtin::Det => Value::Float(m.determinant()),
+        Builtin::Inv => Value::Matrix(m.inverse()?),
+        _ => unreachable!(),
+    })
+}
+
+fn eval_complex_builtin(builtin: Builtin, z: Complex) -> Value {
+    match builtin {
+        Builtin::Re => Value::Float(z.re()),
+        Builtin::Im => Value::Float(z.im()),
+        Builtin::Abs => Value::Float(z.abs()),
+        Builtin::Arg => Value::Float(z.arg()),
+        Builtin::Conj => Value::Complex(z.conj()),
+        _ => unreachable!(),
     }
 }
 
-fn eval_infix(env: &mut Env, expr: &InfixExpr) -> i64 {
-    let lhs = eval_expr(env, &expr.lhs);
-    let rhs = eval_expr(env, &expr.rhs);
-    match expr.op {
-        InfixOp::Plus => lhs + rhs,
-        InfixOp::Minus => lhs - rhs,
-        InfixOp::Multiply => lhs * rhs,
-        InfixOp::Divide => lhs / rhs,
+fn eval_float_infix(op: InfixOp, lhs: f64, rhs: f64) -> Value {
+    match op {
+        InfixOp::Plus => Value::Float(lhs + rhs),
+        InfixOp::Minus => Value::Float(lhs - rhs),
+        InfixOp::Multiply => Value::Float(lhs * rhs),
+        InfixOp::Divide => Value::Float(lhs / rhs),
+        InfixOp::Remainder => Value::Float(lhs % rhs),
+        InfixOp::Power => Value::Float(lhs.powf(rhs)),
+        InfixOp::Equal => Value::Bool(lhs == rhs),
+        InfixOp::NotEqual => Value::Bool(lhs != rhs),
+        InfixOp::Less => Value::Bool(lhs < rhs),
+        InfixOp::LessEqual => Value::Bool(lhs <= rhs),
+        InfixOp::Greater => Value::Bool(lhs > rhs),
+        InfixOp::GreaterEqual => Value::Bool(lhs >= rhs),
+        _ => unreachable!(),
     }
 }
@@ -1,23 +1,96 @@
-use std::{cell::RefCell, fmt::format};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+};
 
 use id_arena::Arena;
 
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
 use crate::ast::*;
+use crate::bigint::MAX_AMOUNT_BITS;
+use crate::builtins::Builtin;
+use crate::decimal::Decimal;
+use crate::interpreter::MAX_FACTORIAL;
+use crate::runtime::{line_col, Numbers, Overflow, Precision, Rounding, Trap};
 
 type ValueId = id_arena::Id<Value>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 
 pub enum IrType {
     Void,
     Int,
+    /// An `int` with `Numbers::Big`: a pointer to a `calc_big` value of the
+    /// runtime in `runtime/bigint.c`.
+    BigInt,
+    Float,
+    Bool,
+    /// A `rat`: numerator and denominator as `{i64, i64}`, in lowest terms
+    /// like `rational::Rational`. The `calc_rat_` helpers return a
+    /// denominator of 0 for results that overflowed.
+    Rational,
+    /// A `dec`: its units in an `i64`, like `decimal::Decimal`. No decimal
+    /// is `i64::MIN`, which the `calc_dec_` helpers return for results
+    /// that overflowed.
+    Decimal,
+    /// A `complex`: its real and imaginary parts as `{double, double}`.
+    /// `codegen` expands the arithmetic, comparisons and negations on it
+    /// into operations on the parts.
+    Complex,
+    /// A `str`: a pointer to NUL-terminated UTF-8 text. Literals point to
+    /// `Context::strings`; the `calc_str_` helpers return new text, which
+    /// like a `BigInt` is never freed.
+    Str,
+    /// An array: its length and a pointer to its elements, as
+    /// `{i64, i64*}`. The elements of literals of known length live in an
+    /// `AllocaInst`, those of other arrays on the heap, which is never
+    /// freed either. Storing an array copies it, unless it was just made.
+    Array,
+    /// A matrix: its rows, its columns and a pointer to its elements row
+    /// after row, as `{i64, i64, double*}`. The `calc_matrix_` helpers put
+    /// every matrix on the heap; since no element is ever assigned, copies
+    /// of a matrix share them.
+    Matrix,
+}
+
+impl From<Type> for IrType {
+    fn from(ty: Type) -> Self {
+        match ty {
+            Type::Int => IrType::Int,
+            Type::Float => IrType::Float,
+            Type::Bool => IrType::Bool,
+            Type::Rational => IrType::Rational,
+            Type::Decimal => IrType::Decimal,
+            Type::Complex => IrType::Complex,
+            Type::Str => IrType::Str,
+            Type::Array(_) => IrType::Array,
+            Type::Matrix(..) => IrType::Matrix,
+        }
+    }
+}
+
+impl From<IrType> for Type {
+    fn from(ty: IrType) -> Self {
+        match ty {
+            IrType::Int | IrType::BigInt => Type::Int,
+            IrType::Float => Type::Float,
+            IrType::Bool => Type::Bool,
+            IrType::Rational => Type::Rational,
+            IrType::Decimal => Type::Decimal,
+            IrType::Complex => Type::Complex,
+            IrType::Str => Type::Str,
+            IrType::Array => Type::Array(None),
+            IrType::Matrix => unreachable!("the shape of a matrix is only known to `typeck`"),
+            IrType::Void => unreachable!("`void` has no values"),
+        }
+    }
 }
 
 pub trait ValueTrait {
     fn name(&self) -> String;
-    fn set_name(&mut self, name: String);
     fn ty(&self) -> IrType;
-    fn set_ty(&mut self, ty: IrType);
 }
 
 macro_rules! impl_value_trait {
@@ -27,17 +100,9 @@ macro_rules! impl_value_trait {
                 self.name.clone()
             }
 
-            fn set_name(&mut self, name: String) {
-                self.name = name;
-            }
-
             fn ty(&self) -> IrType {
                 self.ty.clone()
             }
-
-            fn set_ty(&mut self, ty: IrType) {
-                self.ty = ty;
-            }
         }
     };
 }
@@ -49,17 +114,9 @@ macro_rules! dummy_value_trait {
                 "".to_string()
             }
 
-            fn set_name(&mut self, name: String) {
-                
-            }
-
             fn ty(&self) -> IrType {
                 IrType::Void
             }
-
-            fn set_ty(&mut self, ty: IrType) {
-                
-            }
         }
     };
 }
@@ -73,23 +130,11 @@ macro_rules! impl_value_trait_for_enum {
                 }
             }
 
-            fn set_name(&mut self, name: String) {
-                match self {
-                    $( $enum_name::$variant(value) => value.set_name(name), )+
-                }
-            }
-
             fn ty(&self) -> IrType {
                 match self {
                     $( $enum_name::$variant(value) => value.ty(), )+
                 }
             }
-
-            fn set_ty(&mut self, ty: IrType) {
-                match self {
-                    $( $enum_name::$variant(value) => value.set_ty(ty), )+
-                }
-            }
         }
     };
 }
@@ -105,6 +150,14 @@ impl_value_trait!(GlobalValue);
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ConstantValue {
     Int(i64),
+    Float(f64),
+    Bool(bool),
+    /// The units of a `dec`.
+    Decimal(i64),
+    /// The real and imaginary parts of a `complex`.
+    Complex(f64, f64),
+    /// A `str` literal, as its index in `Context::strings`.
+    Str(usize),
 }
 
 impl ValueTrait for ConstantValue {
@@ -112,27 +165,50 @@ impl ValueTrait for ConstantValue {
         "".to_string()
     }
 
-    fn set_name(&mut self, name: String) {
-        
-    }
-
     fn ty(&self) -> IrType {
         match self {
             ConstantValue::Int(_) => IrType::Int,
+            ConstantValue::Float(_) => IrType::Float,
+            ConstantValue::Bool(_) => IrType::Bool,
+            ConstantValue::Decimal(_) => IrType::Decimal,
+            ConstantValue::Complex(..) => IrType::Complex,
+            ConstantValue::Str(_) => IrType::Str,
         }
     }
-
-    fn set_ty(&mut self, ty: IrType) {
-        
-    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BinaryOp {
     Add,
     Sub,
     Mul,
     Div,
+    Rem,
+    Pow,
+    And,
+    Or,
+    Xor,
+    Shl,
+    AShr,
+    SaturatingAdd,
+    SaturatingSub,
+    SaturatingMul,
+}
+
+#[derive(Debug, Clone)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone)]
+pub enum ComparePredicate {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
 }
 #[derive(Debug, Clone)]
 
@@ -153,22 +229,70 @@ pub struct StoreInst {
 
 dummy_value_trait!(StoreInst);
 
+/// A stack slot for a value of `ty`, or with a `count` for that many `Int`s
+/// in a row, which hold the elements of an array.
 #[derive(Debug, Clone)]
-
 pub struct AllocaInst {
     pub name: String,
     pub ty: IrType,
+    pub count: Option<usize>,
 }
 
 impl_value_trait!(AllocaInst);
 
+/// The `Array` of the `len` elements that start at `data`, an `AllocaInst`
+/// with a count. `aggregate` names the array before it has its elements.
+#[derive(Debug, Clone)]
+pub struct ArrayInst {
+    pub name: String,
+    pub aggregate: String,
+    pub ty: IrType,
+    pub len: ValueId,
+    pub data: ValueId,
+}
+
+impl_value_trait!(ArrayInst);
+
+/// A pointer to element `index` of `array`, an `Array` or a `Matrix`, which
+/// `LoadInst` and `StoreInst` take like a stack slot of `ty`. `data` names
+/// the pointer to the first element.
+#[derive(Debug, Clone)]
+pub struct ElementPtrInst {
+    pub name: String,
+    pub data: String,
+    pub ty: IrType,
+    pub array: ValueId,
+    pub index: ValueId,
+}
+
+impl_value_trait!(ElementPtrInst);
+
 
 #[derive(Debug, Clone)]
-pub struct PrintIntInst {
+pub struct PrintInst {
     pub param: ValueId,
+    /// How a placeholder shows `param`; `None` prints it on a line of its
+    /// own. A `Complex` shown in polar form is already its magnitude and
+    /// angle.
+    pub format: Option<PrintFormat>,
+}
+
+dummy_value_trait!(PrintInst);
+
+#[derive(Debug, Clone, Copy)]
+pub struct PrintFormat {
+    pub spec: FormatSpec,
+    /// `spec.fill` as text, in `Context::strings`.
+    pub fill: usize,
+}
+
+/// Prints one of `Context::strings` as it is.
+#[derive(Debug, Clone)]
+pub struct PrintTextInst {
+    pub text: usize,
 }
 
-dummy_value_trait!(PrintIntInst);
+dummy_value_trait!(PrintTextInst);
 
 #[derive(Debug, Clone)]
 
@@ -182,6 +306,97 @@ pub struct BinaryOperator {
 
 impl_value_trait!(BinaryOperator);
 
+#[derive(Debug, Clone)]
+
+pub struct UnaryOperator {
+    pub name: String,
+    pub ty: IrType,
+    pub operation: UnaryOp,
+    pub operand: ValueId,
+}
+
+impl_value_trait!(UnaryOperator);
+
+/// Compares two operands of the same type, producing a `Bool`.
+#[derive(Debug, Clone)]
+
+pub struct CompareInst {
+    pub name: String,
+    pub ty: IrType,
+    pub predicate: ComparePredicate,
+    pub left_operand: ValueId,
+    pub right_operand: ValueId,
+}
+
+impl_value_trait!(CompareInst);
+
+/// Whether the integer `operation` overflows for the two operands, as a
+/// `Bool`. `aggregate` names the intermediate result of the check.
+#[derive(Debug, Clone)]
+pub struct OverflowCheckInst {
+    pub name: String,
+    pub aggregate: String,
+    pub ty: IrType,
+    pub operation: BinaryOp,
+    pub left_operand: ValueId,
+    pub right_operand: ValueId,
+}
+
+impl_value_trait!(OverflowCheckInst);
+
+/// `true_value` if `condition` holds, else `false_value`.
+#[derive(Debug, Clone)]
+pub struct SelectInst {
+    pub name: String,
+    pub ty: IrType,
+    pub condition: ValueId,
+    pub true_value: ValueId,
+    pub false_value: ValueId,
+}
+
+impl_value_trait!(SelectInst);
+
+/// Stops the program with a runtime error at `line`:`col` if `condition`
+/// holds. `label` makes the branch targets unique.
+#[derive(Debug, Clone)]
+pub struct TrapIfInst {
+    pub condition: ValueId,
+    pub trap: Trap,
+    pub line: usize,
+    pub col: usize,
+    pub label: usize,
+}
+
+dummy_value_trait!(TrapIfInst);
+
+/// Calls `callee` with arguments already converted to its parameter types.
+/// It is declared in `Context::functions`, unless it is one of the `calc_`
+/// helpers the prelude defines.
+#[derive(Debug, Clone)]
+pub struct CallInst {
+    pub name: String,
+    pub ty: IrType,
+    pub callee: String,
+    pub args: Vec<ValueId>,
+}
+
+impl_value_trait!(CallInst);
+
+/// Converts `source` to `ty` with the semantics of `as`. Conversions from
+/// and to `BigInt` call into the runtime; a `BigInt` only becomes an `Int`
+/// once it is known to fit. A `Float` never becomes a `Rational` this way,
+/// since that conversion can overflow. Conversions to `Decimal` can too, so
+/// their results are checked for the overflow marker.
+#[derive(Debug, Clone)]
+
+pub struct CastInst {
+    pub name: String,
+    pub ty: IrType,
+    pub source: ValueId,
+}
+
+impl_value_trait!(CastInst);
+
 #[derive(Debug, Clone)]
 pub enum Value {
     Global(GlobalValue),
@@ -198,18 +413,38 @@ impl_value_trait_for_enum!(Value {
 #[derive(Debug, Clone)]
 pub enum InstructionValue {
     BinaryOperator(BinaryOperator),
+    UnaryOperator(UnaryOperator),
+    CompareInst(CompareInst),
+    CastInst(CastInst),
+    OverflowCheckInst(OverflowCheckInst),
+    SelectInst(SelectInst),
+    TrapIfInst(TrapIfInst),
+    CallInst(CallInst),
     LoadInst(LoadInst),
     StoreInst(StoreInst),
     AllocaInst(AllocaInst),
-    PrintIntInst(PrintIntInst),
+    ArrayInst(ArrayInst),
+    ElementPtrInst(ElementPtrInst),
+    PrintInst(PrintInst),
+    PrintTextInst(PrintTextInst),
 }
 
 impl_value_trait_for_enum!(InstructionValue {
     BinaryOperator(BinaryOperator),
+    UnaryOperator(UnaryOperator),
+    CompareInst(CompareInst),
+    CastInst(CastInst),
+    OverflowCheckInst(OverflowCheckInst),
+    SelectInst(SelectInst),
+    TrapIfInst(TrapIfInst),
+    CallInst(CallInst),
     LoadInst(LoadInst),
     StoreInst(StoreInst),
     AllocaInst(AllocaInst),
-    PrintIntInst(PrintIntInst),
+    ArrayInst(ArrayInst),
+    ElementPtrInst(ElementPtrInst),
+    PrintInst(PrintInst),
+    PrintTextInst(PrintTextInst),
 });
 
 #[derive(Debug, Clone)]
@@ -219,6 +454,23 @@ pub struct Context {
     pub instructions: Vec<ValueId>,
     pub next_id: usize,
     pub global_variables: std::collections::HashMap<String, ValueId>,
+    pub next_label: usize,
+    /// How integer arithmetic handles overflow.
+    pub overflow: Overflow,
+    /// Whether `int` values are `Int`s or `BigInt`s.
+    pub numbers: Numbers,
+    /// The scale and rounding of decimals, and how `print` shows
+    /// rationals.
+    pub precision: Precision,
+    /// The program's text, to turn spans into the locations runtime errors
+    /// report.
+    pub source: String,
+    /// The external functions the program calls: host functions, which must
+    /// be linked in, LLVM intrinsics and C math functions.
+    pub functions: BTreeMap<String, Signature>,
+    /// The text the program prints and its `str` literals, emitted as
+    /// constants.
+    pub strings: Vec<String>,
 }
 
 impl Context {
@@ -228,8 +480,15 @@ impl Context {
             instructions: vec![],
             next_id: 1,
             global_variables: std::collections::HashMap::new(),
+            next_label: 0,
+            overflow: Overflow::default(),
+            numbers: Numbers::default(),
+            precision: Precision::default(),
+            source: String::new(),
+            functions: BTreeMap::new(),
+            strings: vec![],
         };
-        context.create_global_variable("mem".to_string());
+        context.create_global_variable("mem".to_string(), IrType::Int);
         context
     }
 
@@ -239,151 +498,1431 @@ impl Context {
         name
     }
 
-    pub fn create_global_variable(&mut self, name: String) -> ValueId {
-        let value = Value::Global(GlobalValue {name: format!("@{}", name), ty: IrType::Int});
+    /// Appends an instruction to the function body and returns its id.
+    pub fn push_instruction(&mut self, inst: InstructionValue) -> ValueId {
+        let id = self.values.borrow_mut().alloc(Value::Instruction(inst));
+        self.instructions.push(id);
+        id
+    }
+
+    /// The index of `text` in `strings`, adding it if it is new.
+    pub fn intern_string(&mut self, text: &str) -> usize {
+        match self.strings.iter().position(|s| s == text) {
+            Some(i) => i,
+            None => {
+                self.strings.push(text.to_string());
+                self.strings.len() - 1
+            }
+        }
+    }
+
+    pub fn create_global_variable(&mut self, name: String, ty: IrType) -> ValueId {
+        let value = Value::Global(GlobalValue {name: format!("@{}", name), ty});
         let id = self.values.borrow_mut().alloc(value);
         self.global_variables.insert(name, id);
         id
     }
+
+    /// The type `int` values have.
+    pub fn int_type(&self) -> IrType {
+        match self.numbers {
+            Numbers::Fixed | Numbers::Rational => IrType::Int,
+            Numbers::Big => IrType::BigInt,
+        }
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub trait IrGenerator {
     fn to_ir(&self, context: &mut Context);
 }
 
+/// Lowers a program that has passed `sema` and `typeck`.
 impl IrGenerator for TransUnit {
     fn to_ir(&self, context: &mut Context) {
-        self.block.to_ir(context);
+        let mem_ty = context.int_type();
+        let mut lowering = Lowering {
+            context,
+            value: None,
+            variables: HashMap::new(),
+            mem_ty,
+        };
+        lowering.visit_trans_unit(self);
     }
 }
 
-impl IrGenerator for Block {
-    fn to_ir(&self, context: &mut Context) {
-        for stmt in &self.stmts {
-            stmt.to_ir(context);
+// Lowers the AST into `Context::instructions` while walking it.
+struct Lowering<'a> {
+    context: &'a mut Context,
+    // value produced by the expression visited last
+    value: Option<ValueId>,
+    // stack slot of each local variable
+    variables: HashMap<DeclId, ValueId>,
+    // type of the value last stored to `mem`, see `typeck::TypeChecker`
+    mem_ty: IrType,
+}
+
+impl Lowering<'_> {
+    fn lower_expr(&mut self, expr: &Expr) -> ValueId {
+        self.visit_expr(expr);
+        self.value.take().unwrap()
+    }
+
+    fn constant(&mut self, constant: ConstantValue) -> ValueId {
+        self.context
+            .values
+            .borrow_mut()
+            .alloc(Value::Constant(constant))
+    }
+
+    fn value_ty(&self, id: ValueId) -> IrType {
+        self.context.values.borrow()[id].ty()
+    }
+
+    // `mem` is backed by one global per type it is used with
+    fn mem_global(&mut self, ty: IrType) -> ValueId {
+        let name = match ty {
+            IrType::Float => "mem.float",
+            IrType::Bool => "mem.bool",
+            IrType::BigInt => "mem.big",
+            IrType::Rational => "mem.rat",
+            IrType::Decimal => "mem.dec",
+            IrType::Complex => "mem.complex",
+            IrType::Str => "mem.str",
+            IrType::Array => "mem.array",
+            IrType::Matrix => "mem.matrix",
+            _ => "mem",
+        };
+        match self.context.global_variables.get(name) {
+            Some(id) => *id,
+            None => self.context.create_global_variable(name.to_string(), ty),
         }
     }
-}
 
-impl IrGenerator for Stmt {
-    fn to_ir(&self, context: &mut Context) {
-        match self {
-            Stmt::ExprStmt(expr) => {
-                let tmp = expr.to_ir(context);
-                // save to mem
-                let mem = context.global_variables.get("mem").unwrap();
-                let store_inst = StoreInst {
-                    source: tmp,
-                    destination: *mem,
+    fn ir_type(&self, ty: Type) -> IrType {
+        match ty {
+            Type::Int => self.context.int_type(),
+            ty => ty.into(),
+        }
+    }
+
+    fn convert(&mut self, value: ValueId, ty: IrType) -> ValueId {
+        match (self.value_ty(value), ty) {
+            (from, to) if from == to => return value,
+            (IrType::Bool, IrType::BigInt | IrType::Rational | IrType::Decimal) => {
+                let int = self.convert(value, IrType::Int);
+                return self.convert(int, ty);
+            }
+            (IrType::BigInt, IrType::Bool) => {
+                let sign = self.big_sign(value);
+                return self.convert(sign, ty);
+            }
+            (IrType::Rational, IrType::Bool) => {
+                let num = self.call("calc_rat_num", IrType::Int, vec![value]);
+                return self.convert(num, ty);
+            }
+            // only a `Float` becomes the real part of a `Complex` directly
+            (from, IrType::Complex) if from != IrType::Float => {
+                let float = self.convert(value, IrType::Float);
+                return self.convert(float, ty);
+            }
+            _ => {}
+        }
+        let cast_inst = CastInst {
+            name: self.context.generate_local_name(),
+            ty,
+            source: value,
+        };
+        self.context
+            .push_instruction(InstructionValue::CastInst(cast_inst))
+    }
+
+    // `convert`, plus the checks for the conversions that can fail
+    fn convert_checked(&mut self, value: ValueId, ty: IrType, span: Span) -> ValueId {
+        match (self.value_ty(value), ty) {
+            (IrType::Float, IrType::Rational) => {
+                let rational = self.call("calc_rat_from_f64", ty, vec![value]);
+                self.check_rational(rational, span)
+            }
+            // scaled and rounded in floating point, as in `Decimal::from_float`
+            (IrType::Float, IrType::Decimal) => {
+                let Precision {
+                    decimal_scale: scale,
+                    rounding,
+                    ..
+                } = self.context.precision;
+                let unit = self.constant(ConstantValue::Float(10i64.pow(scale) as f64));
+                let scaled = self.binary(BinaryOp::Mul, IrType::Float, value, unit);
+                let callee = match rounding {
+                    Rounding::HalfEven => "llvm.roundeven.f64",
+                    Rounding::HalfUp => "llvm.round.f64",
+                    Rounding::Truncate => "llvm.trunc.f64",
                 };
-                let inst_value_id = context.values.borrow_mut().alloc(Value::Instruction(
-                    InstructionValue::StoreInst(store_inst),
-                ));
-                context.instructions.push(inst_value_id);
-            }
-            Stmt::PrintStmt(expr) => {
-                let value_id = expr.to_ir(context);
-                let print_var_inst = PrintIntInst {
-                    param: value_id,
+                let sig = Signature {
+                    params: vec![Type::Float],
+                    ret: Type::Float,
                 };
-                let inst_value_id = context.values.borrow_mut().alloc(Value::Instruction(
-                    InstructionValue::PrintIntInst(print_var_inst),
-                ));
-                context.instructions.push(inst_value_id);
+                let rounded = self.call_external(callee, sig, vec![scaled]);
+                let decimal = self.call("calc_dec_from_f64", ty, vec![rounded]);
+                self.check_decimal(decimal, span)
             }
+            (IrType::Int | IrType::Rational, IrType::Decimal) => {
+                let decimal = self.convert(value, ty);
+                self.check_decimal(decimal, span)
+            }
+            // like `interpreter::float_to_int`; the cast itself saturates
+            (IrType::Float, IrType::Int) => match self.context.overflow {
+                Overflow::Saturate => self.convert(value, ty),
+                Overflow::Wrap => self.call("calc_f64_to_i64_wrap", ty, vec![value]),
+                Overflow::Trap => {
+                    let min = self.constant(ConstantValue::Float(-9223372036854775808.0));
+                    let max = self.constant(ConstantValue::Float(9223372036854775808.0));
+                    let is_nan = self.compare(ComparePredicate::Ne, value, value);
+                    let too_small = self.compare(ComparePredicate::Lt, value, min);
+                    let too_large = self.compare(ComparePredicate::Ge, value, max);
+                    let outside = self.binary(BinaryOp::Or, IrType::Bool, too_small, too_large);
+                    let overflows = self.binary(BinaryOp::Or, IrType::Bool, is_nan, outside);
+                    self.trap_if(overflows, Trap::Overflow, span);
+                    self.convert(value, ty)
+                }
+            },
+            // only NaN and infinities do not fit, and `calc_big_from_f64`
+            // saturates those
+            (IrType::Float, IrType::BigInt) if self.context.overflow != Overflow::Saturate => {
+                // `x - x` is NaN exactly for them
+                let difference = self.binary(BinaryOp::Sub, IrType::Float, value, value);
+                let zero = self.constant(ConstantValue::Float(0.0));
+                let special = self.compare(ComparePredicate::Ne, difference, zero);
+                let value = if self.context.overflow == Overflow::Trap {
+                    self.trap_if(special, Trap::Overflow, span);
+                    value
+                } else {
+                    self.select(special, zero, value)
+                };
+                self.convert(value, ty)
+            }
+            _ => self.convert(value, ty),
         }
     }
-}
 
-impl Expr {
-    fn to_ir(&self, context: &mut Context) -> ValueId {
-        match self {
-            Expr::Primary(primary_expr) => primary_expr.to_ir(context),
-            Expr::Prefix(prefix_expr) => prefix_expr.to_ir(context),
-            Expr::Infix(infix_expr) => infix_expr.to_ir(context),
+    fn int_constant_value(&self, id: ValueId) -> Option<i64> {
+        match self.context.values.borrow()[id] {
+            Value::Constant(ConstantValue::Int(i)) => Some(i),
+            _ => None,
         }
     }
+
+    fn binary(&mut self, operation: BinaryOp, ty: IrType, lhs: ValueId, rhs: ValueId) -> ValueId {
+        let bin_op = BinaryOperator {
+            name: self.context.generate_local_name(),
+            ty,
+            operation,
+            left_operand: lhs,
+            right_operand: rhs,
+        };
+        self.context
+            .push_instruction(InstructionValue::BinaryOperator(bin_op))
+    }
+
+    fn compare(&mut self, predicate: ComparePredicate, lhs: ValueId, rhs: ValueId) -> ValueId {
+        let cmp_inst = CompareInst {
+            name: self.context.generate_local_name(),
+            ty: IrType::Bool,
+            predicate,
+            left_operand: lhs,
+            right_operand: rhs,
+        };
+        self.context
+            .push_instruction(InstructionValue::CompareInst(cmp_inst))
+    }
+
+    fn select(&mut self, condition: ValueId, true_value: ValueId, false_value: ValueId) -> ValueId {
+        let select_inst = SelectInst {
+            name: self.context.generate_local_name(),
+            ty: self.value_ty(true_value),
+            condition,
+            true_value,
+            false_value,
+        };
+        self.context
+            .push_instruction(InstructionValue::SelectInst(select_inst))
+    }
+
+    fn trap_if(&mut self, condition: ValueId, trap: Trap, span: Span) {
+        let (line, col) = line_col(&self.context.source, span.start);
+        let label = self.context.next_label;
+        self.context.next_label += 1;
+        self.context
+            .push_instruction(InstructionValue::TrapIfInst(TrapIfInst {
+                condition,
+                trap,
+                line,
+                col,
+                label,
+            }));
+    }
+
+    // integer `+ - * /` with the semantics of `interpreter::eval_int_arith`
+    fn int_arith(&mut self, operation: BinaryOp, lhs: ValueId, rhs: ValueId, span: Span) -> ValueId {
+        let overflow = self.context.overflow;
+        let divisor = self.int_constant_value(rhs);
+        match operation {
+            BinaryOp::Div | BinaryOp::Rem => {
+                if divisor.is_none() || divisor == Some(0) {
+                    let zero = self.constant(ConstantValue::Int(0));
+                    let is_zero = self.compare(ComparePredicate::Eq, rhs, zero);
+                    self.trap_if(is_zero, Trap::DivisionByZero, span);
+                }
+                // `sdiv` and `srem` are undefined for `i64::MIN / -1`, the
+                // one quotient that overflows
+                if divisor.is_some() && divisor != Some(-1) {
+                    return self.binary(operation, IrType::Int, lhs, rhs);
+                }
+                let one = self.constant(ConstantValue::Int(1));
+                if operation == BinaryOp::Rem {
+                    // any remainder of a division by -1 is 0, as it is by 1
+                    let minus_one = self.constant(ConstantValue::Int(-1));
+                    let is_minus_one = self.compare(ComparePredicate::Eq, rhs, minus_one);
+                    let divisor = self.select(is_minus_one, one, rhs);
+                    return self.binary(BinaryOp::Rem, IrType::Int, lhs, divisor);
+                }
+                let min = self.constant(ConstantValue::Int(i64::MIN));
+                let minus_one = self.constant(ConstantValue::Int(-1));
+                let is_min = self.compare(ComparePredicate::Eq, lhs, min);
+                let is_minus_one = self.compare(ComparePredicate::Eq, rhs, minus_one);
+                let overflows = self.binary(BinaryOp::And, IrType::Bool, is_min, is_minus_one);
+                if overflow == Overflow::Trap {
+                    self.trap_if(overflows, Trap::Overflow, span);
+                    return self.binary(BinaryOp::Div, IrType::Int, lhs, rhs);
+                }
+                // dividing by 1 instead gives the wrapped result
+                let divisor = self.select(overflows, one, rhs);
+                let quotient = self.binary(BinaryOp::Div, IrType::Int, lhs, divisor);
+                if overflow == Overflow::Wrap {
+                    return quotient;
+                }
+                let max = self.constant(ConstantValue::Int(i64::MAX));
+                self.select(overflows, max, quotient)
+            }
+            BinaryOp::Pow => {
+                let zero = self.constant(ConstantValue::Int(0));
+                // a negative power is a division, by zero if the base is 0
+                let base = self.int_constant_value(lhs);
+                let exp = self.int_constant_value(rhs);
+                if (base.is_none() || base == Some(0)) && exp.is_none_or(|exp| exp < 0) {
+                    let is_zero = self.compare(ComparePredicate::Eq, lhs, zero);
+                    let negative = self.compare(ComparePredicate::Lt, rhs, zero);
+                    let divides_by_zero = self.binary(BinaryOp::And, IrType::Bool, is_zero, negative);
+                    self.trap_if(divides_by_zero, Trap::DivisionByZero, span);
+                }
+                if overflow == Overflow::Wrap {
+                    return self.binary(BinaryOp::Pow, IrType::Int, lhs, rhs);
+                }
+                let overflows = self.overflows(BinaryOp::Pow, lhs, rhs);
+                if overflow == Overflow::Trap {
+                    self.trap_if(overflows, Trap::Overflow, span);
+                    return self.binary(BinaryOp::Pow, IrType::Int, lhs, rhs);
+                }
+                let pow = self.binary(BinaryOp::Pow, IrType::Int, lhs, rhs);
+                // odd powers of negative numbers are negative
+                let one = self.constant(ConstantValue::Int(1));
+                let negative_base = self.compare(ComparePredicate::Lt, lhs, zero);
+                let low_bit = self.binary(BinaryOp::And, IrType::Int, rhs, one);
+                let odd = self.compare(ComparePredicate::Ne, low_bit, zero);
+                let negative = self.binary(BinaryOp::And, IrType::Bool, negative_base, odd);
+                let min = self.constant(ConstantValue::Int(i64::MIN));
+                let max = self.constant(ConstantValue::Int(i64::MAX));
+                let saturated = self.select(negative, min, max);
+                self.select(overflows, saturated, pow)
+            }
+            operation => match overflow {
+                Overflow::Wrap => self.binary(operation, IrType::Int, lhs, rhs),
+                Overflow::Saturate => {
+                    let operation = match operation {
+                        BinaryOp::Add => BinaryOp::SaturatingAdd,
+                        BinaryOp::Sub => BinaryOp::SaturatingSub,
+                        _ => BinaryOp::SaturatingMul,
+                    };
+                    self.binary(operation, IrType::Int, lhs, rhs)
+                }
+                Overflow::Trap => {
+                    let overflows = self.overflows(operation.clone(), lhs, rhs);
+                    self.trap_if(overflows, Trap::Overflow, span);
+                    self.binary(operation, IrType::Int, lhs, rhs)
+                }
+            },
+        }
+    }
+
+    fn overflows(&mut self, operation: BinaryOp, lhs: ValueId, rhs: ValueId) -> ValueId {
+        let check = OverflowCheckInst {
+            aggregate: self.context.generate_local_name(),
+            name: self.context.generate_local_name(),
+            ty: IrType::Bool,
+            operation,
+            left_operand: lhs,
+            right_operand: rhs,
+        };
+        self.context
+            .push_instruction(InstructionValue::OverflowCheckInst(check))
+    }
+
+    // shifting by a negative amount or by 64 bits or more traps
+    fn shift(&mut self, operation: BinaryOp, lhs: ValueId, rhs: ValueId, span: Span) -> ValueId {
+        if !self.int_constant_value(rhs).is_some_and(|amount| (0..64).contains(&amount)) {
+            let zero = self.constant(ConstantValue::Int(0));
+            let max = self.constant(ConstantValue::Int(63));
+            let negative = self.compare(ComparePredicate::Lt, rhs, zero);
+            let too_far = self.compare(ComparePredicate::Gt, rhs, max);
+            let invalid = self.binary(BinaryOp::Or, IrType::Bool, negative, too_far);
+            self.trap_if(invalid, Trap::InvalidShift, span);
+        }
+        self.binary(operation, IrType::Int, lhs, rhs)
+    }
+
+    // `n!` like `eval_factorial`: `calc_factorial` wraps, and whether it
+    // overflowed only depends on `n`
+    fn factorial(&mut self, n: ValueId, span: Span) -> ValueId {
+        let zero = self.constant(ConstantValue::Int(0));
+        if self.int_constant_value(n).is_none_or(|n| n < 0) {
+            let negative = self.compare(ComparePredicate::Lt, n, zero);
+            self.trap_if(negative, Trap::NegativeFactorial, span);
+        }
+        let factorial = self.call("calc_factorial", IrType::Int, vec![n]);
+        if self.context.overflow == Overflow::Wrap {
+            return factorial;
+        }
+        let max_factorial = self.constant(ConstantValue::Int(MAX_FACTORIAL));
+        let overflows = self.compare(ComparePredicate::Gt, n, max_factorial);
+        if self.context.overflow == Overflow::Trap {
+            self.trap_if(overflows, Trap::Overflow, span);
+            return factorial;
+        }
+        let max = self.constant(ConstantValue::Int(i64::MAX));
+        self.select(overflows, max, factorial)
+    }
+
+    // a literal too large for an `i64`, put together from 18-digit chunks
+    fn big_literal(&mut self, n: &BigInt) -> ValueId {
+        let chunk = BigInt::from(10i64.pow(18));
+        let mut chunks = vec![];
+        let mut n = n.clone();
+        while !n.is_zero() {
+            chunks.push((&n % &chunk).to_i64().unwrap());
+            n /= &chunk;
+        }
+        let base = self.constant(ConstantValue::Int(10i64.pow(18)));
+        let base = self.convert(base, IrType::BigInt);
+        let mut value = None;
+        for chunk in chunks.into_iter().rev() {
+            let chunk = self.constant(ConstantValue::Int(chunk));
+            let chunk = self.convert(chunk, IrType::BigInt);
+            value = Some(match value {
+                Some(value) => {
+                    let shifted = self.call("calc_big_mul", IrType::BigInt, vec![value, base]);
+                    self.call("calc_big_add", IrType::BigInt, vec![shifted, chunk])
+                }
+                None => chunk,
+            });
+        }
+        value.unwrap()
+    }
+
+    fn big_sign(&mut self, value: ValueId) -> ValueId {
+        self.call("calc_big_sign", IrType::Int, vec![value])
+    }
+
+    // whether the magnitude of `value` has more than `MAX_AMOUNT_BITS` bits
+    fn big_too_large(&mut self, value: ValueId) -> ValueId {
+        let bits = self.call("calc_big_bits", IrType::Int, vec![value]);
+        let max_bits = self.constant(ConstantValue::Int(MAX_AMOUNT_BITS as i64));
+        self.compare(ComparePredicate::Gt, bits, max_bits)
+    }
+
+    // arithmetic with `Numbers::Big`, which traps like `bigint::eval_infix`;
+    // the runtime handles everything else
+    fn big_arith(&mut self, operation: BinaryOp, lhs: ValueId, rhs: ValueId, span: Span) -> ValueId {
+        let zero = self.constant(ConstantValue::Int(0));
+        match operation {
+            BinaryOp::Div | BinaryOp::Rem => {
+                let sign = self.big_sign(rhs);
+                let is_zero = self.compare(ComparePredicate::Eq, sign, zero);
+                self.trap_if(is_zero, Trap::DivisionByZero, span);
+            }
+            BinaryOp::Pow => {
+                // a negative power is a division, by zero if the base is 0
+                let base_sign = self.big_sign(lhs);
+                let exp_sign = self.big_sign(rhs);
+                let is_zero = self.compare(ComparePredicate::Eq, base_sign, zero);
+                let negative = self.compare(ComparePredicate::Lt, exp_sign, zero);
+                let divides_by_zero = self.binary(BinaryOp::And, IrType::Bool, is_zero, negative);
+                self.trap_if(divides_by_zero, Trap::DivisionByZero, span);
+                // only 0, 1 and -1 have powers this large that fit
+                let positive = self.compare(ComparePredicate::Gt, exp_sign, zero);
+                let large = self.big_too_large(rhs);
+                let one = self.constant(ConstantValue::Int(1));
+                let base_bits = self.call("calc_big_bits", IrType::Int, vec![lhs]);
+                let grows = self.compare(ComparePredicate::Gt, base_bits, one);
+                let large_positive = self.binary(BinaryOp::And, IrType::Bool, positive, large);
+                let overflows = self.binary(BinaryOp::And, IrType::Bool, large_positive, grows);
+                self.trap_if(overflows, Trap::Overflow, span);
+            }
+            BinaryOp::Shl | BinaryOp::AShr => {
+                let sign = self.big_sign(rhs);
+                let negative = self.compare(ComparePredicate::Lt, sign, zero);
+                self.trap_if(negative, Trap::InvalidShift, span);
+                if operation == BinaryOp::Shl {
+                    let overflows = self.big_too_large(rhs);
+                    self.trap_if(overflows, Trap::Overflow, span);
+                }
+            }
+            _ => {}
+        }
+        let callee = match operation {
+            BinaryOp::Add => "calc_big_add",
+            BinaryOp::Sub => "calc_big_sub",
+            BinaryOp::Mul => "calc_big_mul",
+            BinaryOp::Div => "calc_big_div",
+            BinaryOp::Rem => "calc_big_rem",
+            BinaryOp::Pow => "calc_big_pow",
+            BinaryOp::And => "calc_big_and",
+            BinaryOp::Or => "calc_big_or",
+            BinaryOp::Xor => "calc_big_xor",
+            BinaryOp::Shl => "calc_big_shl",
+            BinaryOp::AShr => "calc_big_shr",
+            _ => unreachable!(),
+        };
+        self.call(callee, IrType::BigInt, vec![lhs, rhs])
+    }
+
+    fn big_factorial(&mut self, n: ValueId, span: Span) -> ValueId {
+        let zero = self.constant(ConstantValue::Int(0));
+        let sign = self.big_sign(n);
+        let negative = self.compare(ComparePredicate::Lt, sign, zero);
+        self.trap_if(negative, Trap::NegativeFactorial, span);
+        let overflows = self.big_too_large(n);
+        self.trap_if(overflows, Trap::Overflow, span);
+        self.call("calc_big_factorial", IrType::BigInt, vec![n])
+    }
+
+    // mirrors `bigint::eval_builtin`
+    fn big_builtin(&mut self, builtin: Builtin, args: &[ValueId], span: Span) -> ValueId {
+        let zero = self.constant(ConstantValue::Int(0));
+        match builtin {
+            Builtin::Abs => self.call("calc_big_abs", IrType::BigInt, args.to_vec()),
+            Builtin::Sign => {
+                let sign = self.big_sign(args[0]);
+                self.convert(sign, IrType::BigInt)
+            }
+            Builtin::Min | Builtin::Max => {
+                let cmp = self.call("calc_big_cmp", IrType::Int, args.to_vec());
+                let less = self.compare(ComparePredicate::Lt, cmp, zero);
+                match builtin {
+                    Builtin::Min => self.select(less, args[0], args[1]),
+                    _ => self.select(less, args[1], args[0]),
+                }
+            }
+            Builtin::Mod => {
+                let sign = self.big_sign(args[1]);
+                let is_zero = self.compare(ComparePredicate::Eq, sign, zero);
+                self.trap_if(is_zero, Trap::DivisionByZero, span);
+                self.call("calc_big_mod", IrType::BigInt, args.to_vec())
+            }
+            Builtin::Gcd => self.call("calc_big_gcd", IrType::BigInt, args.to_vec()),
+            Builtin::Lcm => self.call("calc_big_lcm", IrType::BigInt, args.to_vec()),
+            _ => unreachable!(),
+        }
+    }
+
+    // exact `+ - * /`; unlike integer overflow, overflowing a `rat` always
+    // traps, as in `rational::eval_infix`
+    fn rational_arith(&mut self, operation: BinaryOp, lhs: ValueId, rhs: ValueId, span: Span) -> ValueId {
+        if operation == BinaryOp::Div {
+            let num = self.call("calc_rat_num", IrType::Int, vec![rhs]);
+            let zero = self.constant(ConstantValue::Int(0));
+            let is_zero = self.compare(ComparePredicate::Eq, num, zero);
+            self.trap_if(is_zero, Trap::DivisionByZero, span);
+        }
+        let callee = match operation {
+            BinaryOp::Add => "calc_rat_add",
+            BinaryOp::Sub => "calc_rat_sub",
+            BinaryOp::Mul => "calc_rat_mul",
+            BinaryOp::Div => "calc_rat_div",
+            _ => unreachable!(),
+        };
+        let result = self.call(callee, IrType::Rational, vec![lhs, rhs]);
+        self.check_rational(result, span)
+    }
+
+    // traps if `value` is the overflow marker of the `calc_rat_` helpers
+    fn check_rational(&mut self, value: ValueId, span: Span) -> ValueId {
+        let den = self.call("calc_rat_den", IrType::Int, vec![value]);
+        let zero = self.constant(ConstantValue::Int(0));
+        let overflows = self.compare(ComparePredicate::Eq, den, zero);
+        self.trap_if(overflows, Trap::RationalOverflow, span);
+        value
+    }
+
+    // `+ - * /` rounded as `--rounding` says, which the helpers have built
+    // in; overflow always traps, as in `decimal::eval_infix`
+    fn decimal_arith(&mut self, operation: BinaryOp, lhs: ValueId, rhs: ValueId, span: Span) -> ValueId {
+        if operation == BinaryOp::Div {
+            let zero = self.constant(ConstantValue::Decimal(0));
+            let is_zero = self.compare(ComparePredicate::Eq, rhs, zero);
+            self.trap_if(is_zero, Trap::DivisionByZero, span);
+        }
+        let callee = match operation {
+            BinaryOp::Add => "calc_dec_add",
+            BinaryOp::Sub => "calc_dec_sub",
+            BinaryOp::Mul => "calc_dec_mul",
+            BinaryOp::Div => "calc_dec_div",
+            _ => unreachable!(),
+        };
+        let result = self.call(callee, IrType::Decimal, vec![lhs, rhs]);
+        self.check_decimal(result, span)
+    }
+
+    // traps if `value` is the overflow marker of the `calc_dec_` helpers
+    fn check_decimal(&mut self, value: ValueId, span: Span) -> ValueId {
+        let marker = self.constant(ConstantValue::Decimal(i64::MIN));
+        let overflows = self.compare(ComparePredicate::Eq, value, marker);
+        self.trap_if(overflows, Trap::DecimalOverflow, span);
+        value
+    }
+
+    // host functions take 64-bit integers, so larger arguments trap like
+    // they do in the interpreter
+    fn host_call(&mut self, call: &CallExpr, args: Vec<ValueId>) -> ValueId {
+        let sig = call.sig.clone().unwrap();
+        for (&arg, &ty) in args.iter().zip(&sig.params) {
+            if ty == Type::Int && self.value_ty(arg) == IrType::BigInt {
+                let fits = self.call("calc_big_fits_i64", IrType::Int, vec![arg]);
+                let zero = self.constant(ConstantValue::Int(0));
+                let overflows = self.compare(ComparePredicate::Eq, fits, zero);
+                self.trap_if(overflows, Trap::Overflow, call.span);
+            }
+        }
+        let ty = self.ir_type(sig.ret);
+        let result = self.call_external(&call.callee.name, sig, args);
+        self.convert(result, ty)
+    }
+
+    fn call(&mut self, callee: &str, ty: IrType, args: Vec<ValueId>) -> ValueId {
+        let call_inst = CallInst {
+            name: self.context.generate_local_name(),
+            ty,
+            callee: callee.to_string(),
+            args,
+        };
+        self.context
+            .push_instruction(InstructionValue::CallInst(call_inst))
+    }
+
+    fn call_external(&mut self, callee: &str, sig: Signature, args: Vec<ValueId>) -> ValueId {
+        let args = args
+            .into_iter()
+            .zip(&sig.params)
+            .map(|(arg, &ty)| self.convert(arg, ty.into()))
+            .collect();
+        let ty = sig.ret.into();
+        self.context.functions.insert(callee.to_string(), sig);
+        self.call(callee, ty, args)
+    }
+
+    fn builtin(&mut self, builtin: Builtin, args: Vec<ValueId>, span: Span) -> ValueId {
+        if builtin.is_matrix() {
+            return self.matrix_builtin(builtin, args[0], span);
+        }
+        let types: Vec<Type> = args.iter().map(|&arg| self.value_ty(arg).into()).collect();
+        let sig = builtin.signature(&types).unwrap();
+        let args: Vec<ValueId> = args
+            .into_iter()
+            .zip(&sig.params)
+            .map(|(arg, &ty)| {
+                let ty = self.ir_type(ty);
+                self.convert(arg, ty)
+            })
+            .collect();
+        match sig.ret {
+            _ if builtin.is_text() => self.text_builtin(builtin, &args, span),
+            _ if sig.params == [Type::Complex] => self.complex_builtin(builtin, args[0]),
+            Type::Int if self.context.numbers == Numbers::Big => self.big_builtin(builtin, &args, span),
+            Type::Int => self.int_builtin(builtin, &args, span),
+            _ => self.float_builtin(builtin, &args, sig),
+        }
+    }
+
+    // mirrors `interpreter::eval_builtin`
+    fn int_builtin(&mut self, builtin: Builtin, args: &[ValueId], span: Span) -> ValueId {
+        let zero = self.constant(ConstantValue::Int(0));
+        let one = self.constant(ConstantValue::Int(1));
+        let minus_one = self.constant(ConstantValue::Int(-1));
+        match builtin {
+            Builtin::Abs => self.int_abs(args[0], span),
+            Builtin::Sign => {
+                let positive = self.compare(ComparePredicate::Gt, args[0], zero);
+                let negative = self.compare(ComparePredicate::Lt, args[0], zero);
+                let sign = self.select(negative, minus_one, zero);
+                self.select(positive, one, sign)
+            }
+            Builtin::Min | Builtin::Max => {
+                let callee = match builtin {
+                    Builtin::Min => "llvm.smin.i64",
+                    _ => "llvm.smax.i64",
+                };
+                let sig = Signature {
+                    params: vec![Type::Int; 2],
+                    ret: Type::Int,
+                };
+                self.call_external(callee, sig, args.to_vec())
+            }
+            Builtin::Mod => {
+                let rem = self.int_arith(BinaryOp::Rem, args[0], args[1], span);
+                self.floored_rem(rem, args[1])
+            }
+            Builtin::Gcd => {
+                let gcd = self.call("calc_gcd", IrType::Int, args.to_vec());
+                // a gcd of 2^63 comes back as `i64::MIN`
+                let overflows = self.compare(ComparePredicate::Lt, gcd, zero);
+                match self.context.overflow {
+                    Overflow::Wrap => gcd,
+                    Overflow::Trap => {
+                        self.trap_if(overflows, Trap::Overflow, span);
+                        gcd
+                    }
+                    Overflow::Saturate => {
+                        let max = self.constant(ConstantValue::Int(i64::MAX));
+                        self.select(overflows, max, gcd)
+                    }
+                }
+            }
+            Builtin::Lcm => {
+                let (lhs, rhs) = (args[0], args[1]);
+                let gcd = self.call("calc_gcd", IrType::Int, args.to_vec());
+                // the gcd is only 0 if both operands are, and the lcm is then 0
+                let is_zero = self.compare(ComparePredicate::Eq, gcd, zero);
+                let divisor = self.select(is_zero, one, gcd);
+                let quotient = self.binary(BinaryOp::Div, IrType::Int, lhs, divisor);
+                let product = self.int_arith(BinaryOp::Mul, quotient, rhs, span);
+                self.int_abs(product, span)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn float_builtin(&mut self, builtin: Builtin, args: &[ValueId], sig: Signature) -> ValueId {
+        let callee = match builtin {
+            Builtin::Abs => "llvm.fabs.f64",
+            Builtin::Min => "llvm.minnum.f64",
+            Builtin::Max => "llvm.maxnum.f64",
+            Builtin::Pow => "llvm.pow.f64",
+            Builtin::Sqrt => "llvm.sqrt.f64",
+            Builtin::Floor => "llvm.floor.f64",
+            Builtin::Ceil => "llvm.ceil.f64",
+            Builtin::Round => "llvm.round.f64",
+            Builtin::Log => "llvm.log.f64",
+            Builtin::Exp => "llvm.exp.f64",
+            Builtin::Sin => "llvm.sin.f64",
+            Builtin::Cos => "llvm.cos.f64",
+            // these have no intrinsics and come from the C math library
+            Builtin::Tan => "tan",
+            Builtin::Asin => "asin",
+            Builtin::Acos => "acos",
+            Builtin::Atan => "atan",
+            Builtin::Atan2 => "atan2",
+            Builtin::Sign => {
+                let zero = self.constant(ConstantValue::Float(0.0));
+                let one = self.constant(ConstantValue::Float(1.0));
+                let minus_one = self.constant(ConstantValue::Float(-1.0));
+                // zeros and NaN are their own sign
+                let positive = self.compare(ComparePredicate::Gt, args[0], zero);
+                let negative = self.compare(ComparePredicate::Lt, args[0], zero);
+                let sign = self.select(negative, minus_one, args[0]);
+                return self.select(positive, one, sign);
+            }
+            Builtin::Mod => {
+                let rem = self.binary(BinaryOp::Rem, IrType::Float, args[0], args[1]);
+                return self.floored_rem(rem, args[1]);
+            }
+            Builtin::Gcd
+            | Builtin::Lcm
+            | Builtin::Len
+            | Builtin::Substr
+            | Builtin::ToStr
+            | Builtin::ParseInt
+            | Builtin::Transpose
+            | Builtin::Det
+            | Builtin::Inv
+            | Builtin::Re
+            | Builtin::Im
+            | Builtin::Arg
+            | Builtin::Conj => unreachable!(),
+        };
+        self.call_external(callee, sig, args.to_vec())
+    }
+
+    // mirrors `complex::Complex`, which also has `abs` and `arg` from the C
+    // math library
+    fn complex_builtin(&mut self, builtin: Builtin, z: ValueId) -> ValueId {
+        let callee = match builtin {
+            Builtin::Re => return self.call("calc_complex_re", IrType::Float, vec![z]),
+            Builtin::Im => return self.call("calc_complex_im", IrType::Float, vec![z]),
+            Builtin::Conj => return self.call("calc_complex_conj", IrType::Complex, vec![z]),
+            Builtin::Abs => "hypot",
+            Builtin::Arg => "atan2",
+            _ => unreachable!(),
+        };
+        let re = self.call("calc_complex_re", IrType::Float, vec![z]);
+        let im = self.call("calc_complex_im", IrType::Float, vec![z]);
+        let args = match builtin {
+            Builtin::Abs => vec![re, im],
+            _ => vec![im, re],
+        };
+        let sig = Signature {
+            params: vec![Type::Float; 2],
+            ret: Type::Float,
+        };
+        self.call_external(callee, sig, args)
+    }
+
+    // the magnitude and angle of `z`, which `{:p}` prints
+    fn polar(&mut self, z: ValueId) -> ValueId {
+        let abs = self.complex_builtin(Builtin::Abs, z);
+        let arg = self.complex_builtin(Builtin::Arg, z);
+        self.call("calc_complex_new", IrType::Complex, vec![abs, arg])
+    }
+
+    // mirrors `matrix::Matrix`, whose shapes `typeck` checked
+    fn matrix_builtin(&mut self, builtin: Builtin, m: ValueId, span: Span) -> ValueId {
+        match builtin {
+            Builtin::Transpose => self.call("calc_matrix_transpose", IrType::Matrix, vec![m]),
+            Builtin::Det => self.call("calc_matrix_det", IrType::Float, vec![m]),
+            Builtin::Inv => {
+                let singular = self.call("calc_matrix_singular", IrType::Int, vec![m]);
+                let zero = self.constant(ConstantValue::Int(0));
+                let singular = self.compare(ComparePredicate::Ne, singular, zero);
+                self.trap_if(singular, Trap::SingularMatrix, span);
+                self.call("calc_matrix_inv", IrType::Matrix, vec![m])
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // `[[elements, ...], ...]`, on the heap like every other matrix
+    fn matrix_literal(&mut self, rows: usize, cols: usize, elements: Vec<ValueId>) -> ValueId {
+        let rows = self.constant(ConstantValue::Int(rows as i64));
+        let cols = self.constant(ConstantValue::Int(cols as i64));
+        let matrix = self.call("calc_matrix_new", IrType::Matrix, vec![rows, cols]);
+        for (i, element) in elements.into_iter().enumerate() {
+            let source = self.convert(element, IrType::Float);
+            let index = self.constant(ConstantValue::Int(i as i64));
+            let destination = self.element_ptr(matrix, IrType::Float, index);
+            self.context
+                .push_instruction(InstructionValue::StoreInst(StoreInst { source, destination }));
+        }
+        matrix
+    }
+
+    // `+` and `-` of two matrices, `@`, or `*` of a matrix and a number on
+    // either side
+    fn matrix_arith(&mut self, op: InfixOp, lhs: ValueId, rhs: ValueId) -> ValueId {
+        let callee = match op {
+            InfixOp::Plus => "calc_matrix_add",
+            InfixOp::Minus => "calc_matrix_sub",
+            InfixOp::MatMul => "calc_matrix_mul",
+            InfixOp::Multiply => {
+                let (matrix, factor) = match self.value_ty(lhs) {
+                    IrType::Matrix => (lhs, rhs),
+                    _ => (rhs, lhs),
+                };
+                let factor = self.convert(factor, IrType::Float);
+                return self.call("calc_matrix_scale", IrType::Matrix, vec![matrix, factor]);
+            }
+            _ => unreachable!(),
+        };
+        self.call(callee, IrType::Matrix, vec![lhs, rhs])
+    }
+
+    // mirrors `interpreter::eval_text_builtin`
+    fn text_builtin(&mut self, builtin: Builtin, args: &[ValueId], span: Span) -> ValueId {
+        let zero = self.constant(ConstantValue::Int(0));
+        let int_type = self.context.int_type();
+        match builtin {
+            Builtin::Len if self.value_ty(args[0]) == IrType::Array => {
+                self.call("calc_array_len", IrType::Int, args.to_vec())
+            }
+            Builtin::Len => {
+                let len = self.call("calc_utf8_len", IrType::Int, args.to_vec());
+                self.convert(len, int_type)
+            }
+            Builtin::Substr => {
+                let start = self.text_index(args[1], span);
+                let count = self.text_index(args[2], span);
+                let len = self.call("calc_utf8_len", IrType::Int, vec![args[0]]);
+                let rest = self.binary(BinaryOp::Sub, IrType::Int, len, start);
+                let checks = [
+                    self.compare(ComparePredicate::Lt, start, zero),
+                    self.compare(ComparePredicate::Lt, count, zero),
+                    self.compare(ComparePredicate::Gt, start, len),
+                    self.compare(ComparePredicate::Gt, count, rest),
+                ];
+                let outside = checks
+                    .into_iter()
+                    .reduce(|lhs, rhs| self.binary(BinaryOp::Or, IrType::Bool, lhs, rhs))
+                    .unwrap();
+                self.trap_if(outside, Trap::OutOfBounds, span);
+                self.call("calc_str_substr", IrType::Str, vec![args[0], start, count])
+            }
+            Builtin::ToStr if int_type == IrType::BigInt => {
+                self.call("calc_big_format", IrType::Str, args.to_vec())
+            }
+            Builtin::ToStr => self.call("calc_str_from_i64", IrType::Str, args.to_vec()),
+            Builtin::ParseInt => {
+                // 0 for an `int`, 1 for one beyond 64 bits, 2 for no number
+                let status = self.call("calc_str_int_status", IrType::Int, args.to_vec());
+                let invalid = match int_type {
+                    IrType::BigInt => {
+                        let two = self.constant(ConstantValue::Int(2));
+                        self.compare(ComparePredicate::Eq, status, two)
+                    }
+                    _ => self.compare(ComparePredicate::Ne, status, zero),
+                };
+                self.trap_if(invalid, Trap::InvalidInteger, span);
+                match int_type {
+                    IrType::BigInt => self.call("calc_big_parse", IrType::BigInt, args.to_vec()),
+                    _ => self.call("calc_str_to_i64", IrType::Int, args.to_vec()),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // an `int` that counts characters; a big integer is beyond any text
+    fn text_index(&mut self, value: ValueId, span: Span) -> ValueId {
+        if self.value_ty(value) != IrType::BigInt {
+            return value;
+        }
+        let fits = self.call("calc_big_fits_i64", IrType::Int, vec![value]);
+        let zero = self.constant(ConstantValue::Int(0));
+        let outside = self.compare(ComparePredicate::Eq, fits, zero);
+        self.trap_if(outside, Trap::OutOfBounds, span);
+        self.convert(value, IrType::Int)
+    }
+
+    fn int_abs(&mut self, value: ValueId, span: Span) -> ValueId {
+        let zero = self.constant(ConstantValue::Int(0));
+        let negated = self.int_arith(BinaryOp::Sub, zero, value, span);
+        let negative = self.compare(ComparePredicate::Lt, value, zero);
+        self.select(negative, negated, value)
+    }
+
+    // `srem` and `frem` give the remainder the sign of the dividend; `mod`
+    // gives it the sign of the divisor
+    fn floored_rem(&mut self, rem: ValueId, divisor: ValueId) -> ValueId {
+        let ty = self.value_ty(rem);
+        let zero = match ty {
+            IrType::Float => self.constant(ConstantValue::Float(0.0)),
+            _ => self.constant(ConstantValue::Int(0)),
+        };
+        let nonzero = self.compare(ComparePredicate::Ne, rem, zero);
+        let rem_negative = self.compare(ComparePredicate::Lt, rem, zero);
+        let divisor_negative = self.compare(ComparePredicate::Lt, divisor, zero);
+        let signs_differ = self.compare(ComparePredicate::Ne, rem_negative, divisor_negative);
+        let adjust = self.binary(BinaryOp::And, IrType::Bool, nonzero, signs_differ);
+        let adjusted = self.binary(BinaryOp::Add, ty, rem, divisor);
+        self.select(adjust, adjusted, rem)
+    }
+
+    // `[elements, ...]`, whose length is known, so they go on the stack
+    fn array_literal(&mut self, elements: Vec<ValueId>) -> ValueId {
+        let len = elements.len();
+        let array = self.stack_array(len);
+        for (i, element) in elements.into_iter().enumerate() {
+            let index = self.constant(ConstantValue::Int(i as i64));
+            let destination = self.element_ptr(array, IrType::Int, index);
+            self.context
+                .push_instruction(InstructionValue::StoreInst(StoreInst {
+                    source: element,
+                    destination,
+                }));
+        }
+        array
+    }
+
+    // an array of `len` elements on the stack, which are not yet set
+    fn stack_array(&mut self, len: usize) -> ValueId {
+        let alloca_inst = AllocaInst {
+            name: self.context.generate_local_name(),
+            ty: IrType::Int,
+            count: Some(len),
+        };
+        let data = self
+            .context
+            .push_instruction(InstructionValue::AllocaInst(alloca_inst));
+        let len = self.constant(ConstantValue::Int(len as i64));
+        let array_inst = ArrayInst {
+            aggregate: self.context.generate_local_name(),
+            name: self.context.generate_local_name(),
+            ty: IrType::Array,
+            len,
+            data,
+        };
+        self.context
+            .push_instruction(InstructionValue::ArrayInst(array_inst))
+    }
+
+    // `[value; count]`: on the stack if `count` is known, else on the heap
+    // once it is checked like `interpreter` does
+    fn repeat(&mut self, value: ValueId, count: ValueId, span: Span) -> ValueId {
+        let array = match self.int_constant_value(count) {
+            Some(len @ 0..=MAX_ARRAY_LEN) => self.stack_array(len as usize),
+            _ => {
+                let zero = self.constant(ConstantValue::Int(0));
+                let max = self.constant(ConstantValue::Int(MAX_ARRAY_LEN));
+                let negative = self.compare(ComparePredicate::Lt, count, zero);
+                let too_long = self.compare(ComparePredicate::Gt, count, max);
+                let invalid = self.binary(BinaryOp::Or, IrType::Bool, negative, too_long);
+                self.trap_if(invalid, Trap::ArrayLength, span);
+                self.call("calc_array_new", IrType::Array, vec![count])
+            }
+        };
+        self.call("calc_array_fill", IrType::Array, vec![array, value])
+    }
+
+    fn element_ptr(&mut self, array: ValueId, ty: IrType, index: ValueId) -> ValueId {
+        let element_ptr = ElementPtrInst {
+            data: self.context.generate_local_name(),
+            name: self.context.generate_local_name(),
+            ty,
+            array,
+            index,
+        };
+        self.context
+            .push_instruction(InstructionValue::ElementPtrInst(element_ptr))
+    }
+
+    // `element_ptr`, once `index` is known to be inside `array`
+    fn checked_element_ptr(&mut self, array: ValueId, index: ValueId, span: Span) -> ValueId {
+        let len = self.call("calc_array_len", IrType::Int, vec![array]);
+        let zero = self.constant(ConstantValue::Int(0));
+        let negative = self.compare(ComparePredicate::Lt, index, zero);
+        let past_end = self.compare(ComparePredicate::Ge, index, len);
+        let outside = self.binary(BinaryOp::Or, IrType::Bool, negative, past_end);
+        self.trap_if(outside, Trap::OutOfBounds, span);
+        self.element_ptr(array, IrType::Int, index)
+    }
+
+    // an array that nothing else refers to: one that was just made, or else
+    // a copy
+    fn owned_array(&mut self, array: ValueId) -> ValueId {
+        let fresh = match &self.context.values.borrow()[array] {
+            Value::Instruction(InstructionValue::ArrayInst(_)) => true,
+            Value::Instruction(InstructionValue::CallInst(call)) => call.callee == "calc_array_fill",
+            _ => false,
+        };
+        if fresh {
+            return array;
+        }
+        self.call("calc_array_copy", IrType::Array, vec![array])
+    }
+
+    fn store_variable(&mut self, ident: &Ident, source: ValueId, span: Span) {
+        let destination = self.variables[&ident.decl.unwrap()];
+        let mut source = self.convert_checked(source, self.value_ty(destination), span);
+        if self.value_ty(source) == IrType::Array {
+            source = self.owned_array(source);
+        }
+        self.context
+            .push_instruction(InstructionValue::StoreInst(StoreInst {
+                source,
+                destination,
+            }));
+    }
 }
 
-impl PrimaryExpr {
-    fn to_ir(&self, context: &mut Context) -> ValueId {
-        match self {
-            PrimaryExpr::Mem => {
-                // context.global_variables["mem"]
+impl Visitor for Lowering<'_> {
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt) {
+        let mut tmp = self.lower_expr(&stmt.expr);
+        self.mem_ty = self.value_ty(tmp);
+        if self.mem_ty == IrType::Array {
+            tmp = self.owned_array(tmp);
+        }
+        // save to mem
+        let mem = self.mem_global(self.mem_ty);
+        let store_inst = StoreInst {
+            source: tmp,
+            destination: mem,
+        };
+        self.context
+            .push_instruction(InstructionValue::StoreInst(store_inst));
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) {
+        let values: Vec<ValueId> = stmt.args.iter().map(|arg| self.lower_expr(arg)).collect();
+        let Some(format) = &stmt.format else {
+            let print_inst = PrintInst {
+                param: values[0],
+                format: None,
+            };
+            self.context
+                .push_instruction(InstructionValue::PrintInst(print_inst));
+            return;
+        };
+        let mut pieces = format.pieces.clone();
+        if stmt.newline {
+            match pieces.last_mut() {
+                Some(FormatPiece::Text(text)) => text.push('\n'),
+                _ => pieces.push(FormatPiece::Text("\n".to_string())),
+            }
+        }
+        let mut values = values.into_iter();
+        for piece in pieces {
+            let inst = match piece {
+                FormatPiece::Text(text) => InstructionValue::PrintTextInst(PrintTextInst {
+                    text: self.context.intern_string(&text),
+                }),
+                FormatPiece::Spec(spec) => InstructionValue::PrintInst(PrintInst {
+                    param: match values.next().unwrap() {
+                        z if spec.polar => self.polar(z),
+                        value => value,
+                    },
+                    format: Some(PrintFormat {
+                        spec,
+                        fill: self.context.intern_string(&spec.fill.to_string()),
+                    }),
+                }),
+            };
+            self.context.push_instruction(inst);
+        }
+    }
+
+    fn visit_let_stmt(&mut self, stmt: &LetStmt) {
+        let value_id = self.lower_expr(&stmt.expr);
+        let ty = match stmt.ty {
+            Some(ty) => self.ir_type(ty),
+            None => self.value_ty(value_id),
+        };
+        let alloca_inst = AllocaInst {
+            name: format!("%{}.{}", stmt.name.name, stmt.name.decl.unwrap()),
+            ty,
+            count: None,
+        };
+        let slot = self
+            .context
+            .push_instruction(InstructionValue::AllocaInst(alloca_inst));
+        self.variables.insert(stmt.name.decl.unwrap(), slot);
+        self.store_variable(&stmt.name, value_id, stmt.expr.span());
+    }
+
+    fn visit_assign_stmt(&mut self, stmt: &AssignStmt) {
+        let Some(index) = &stmt.index else {
+            let value_id = self.lower_expr(&stmt.expr);
+            self.store_variable(&stmt.name, value_id, stmt.expr.span());
+            return;
+        };
+        let index_id = self.lower_expr(index);
+        let value_id = self.lower_expr(&stmt.expr);
+        let slot = self.variables[&stmt.name.decl.unwrap()];
+        let load_inst = LoadInst {
+            name: self.context.generate_local_name(),
+            ty: IrType::Array,
+            source: slot,
+        };
+        let array = self
+            .context
+            .push_instruction(InstructionValue::LoadInst(load_inst));
+        let destination = self.checked_element_ptr(array, index_id, index.span());
+        self.context
+            .push_instruction(InstructionValue::StoreInst(StoreInst {
+                source: value_id,
+                destination,
+            }));
+    }
+
+    fn visit_primary_expr(&mut self, expr: &PrimaryExpr) {
+        let id = match expr {
+            PrimaryExpr::Mem(_) => {
                 // generate a load instruction
-                let name =  context.generate_local_name();
-                let mem = context.global_variables.get("mem").unwrap();
+                let mem = self.mem_global(self.mem_ty);
                 let load_inst = LoadInst {
-                    name: name,
-                    ty: IrType::Int,
-                    source: *mem,
+                    name: self.context.generate_local_name(),
+                    ty: self.mem_ty,
+                    source: mem,
                 };
-                let inst_value_id = context.values.borrow_mut().alloc(Value::Instruction(
-                    InstructionValue::LoadInst(load_inst),
-                ));
-                context.instructions.push(inst_value_id);
-                inst_value_id
-            }
-            PrimaryExpr::Int(i) => {
-                let value = Value::Constant(ConstantValue::Int(*i));
-                let id = context.values.borrow_mut().alloc(value);
-                id
-            }
-            PrimaryExpr::Expr(expr) => expr.to_ir(context),
-        }
-    }
-}
-
-impl PrefixExpr {
-    fn to_ir(&self, context: &mut Context) -> ValueId {
-        let expr_value_id = self.expr.to_ir(context);
-        match self.op {
-            PrefixOp::Plus => expr_value_id,
-            PrefixOp::Minus => {
-                let zero = context
-                    .values
-                    .borrow_mut()
-                    .alloc(Value::Constant(ConstantValue::Int(0)));
-                let bin_op = BinaryOperator {
-                    name: context.generate_local_name(),
-                    ty: IrType::Int,
-                    operation: BinaryOp::Sub,
-                    left_operand: zero,
-                    right_operand: expr_value_id,
+                self.context
+                    .push_instruction(InstructionValue::LoadInst(load_inst))
+            }
+            PrimaryExpr::Int(i, _, _) => {
+                let constant = self.constant(ConstantValue::Int(*i));
+                self.convert(constant, self.context.int_type())
+            }
+            PrimaryExpr::BigInt(n, _, _) => self.big_literal(n),
+            PrimaryExpr::Char(c, _) => {
+                let constant = self.constant(ConstantValue::Int(*c as i64));
+                self.convert(constant, self.context.int_type())
+            }
+            PrimaryExpr::Rational(i, _) => {
+                let constant = self.constant(ConstantValue::Int(*i));
+                self.convert(constant, IrType::Rational)
+            }
+            // scaled and rounded here, so only a literal out of range for
+            // the scale is left to trap
+            PrimaryExpr::Decimal(digits, places, span) => {
+                let Precision {
+                    decimal_scale: scale,
+                    rounding,
+                    ..
+                } = self.context.precision;
+                match Decimal::from_literal(*digits, *places, scale, rounding) {
+                    Ok(d) => self.constant(ConstantValue::Decimal(d.units())),
+                    Err(trap) => {
+                        let always = self.constant(ConstantValue::Bool(true));
+                        self.trap_if(always, trap, *span);
+                        self.constant(ConstantValue::Decimal(0))
+                    }
+                }
+            }
+            PrimaryExpr::Float(f, _) => self.constant(ConstantValue::Float(*f)),
+            PrimaryExpr::Imaginary(f, _) => self.constant(ConstantValue::Complex(0.0, *f)),
+            PrimaryExpr::Quantity(f, unit, _) => self.constant(ConstantValue::Float(f * unit.scale())),
+            PrimaryExpr::Bool(b, _) => self.constant(ConstantValue::Bool(*b)),
+            PrimaryExpr::Str(text, _) => {
+                let text = self.context.intern_string(text);
+                self.constant(ConstantValue::Str(text))
+            }
+            PrimaryExpr::Ident(ident) => {
+                let slot = self.variables[&ident.decl.unwrap()];
+                let load_inst = LoadInst {
+                    name: self.context.generate_local_name(),
+                    ty: self.value_ty(slot),
+                    source: slot,
                 };
-                let id = context
-                    .values
-                    .borrow_mut()
-                    .alloc(Value::Instruction(InstructionValue::BinaryOperator(bin_op)));
-                context.instructions.push(id);
-                id
+                self.context
+                    .push_instruction(InstructionValue::LoadInst(load_inst))
             }
-        }
+            PrimaryExpr::Call(call) => {
+                let args: Vec<ValueId> = call.args.iter().map(|arg| self.lower_expr(arg)).collect();
+                match call.builtin {
+                    Some(builtin) => self.builtin(builtin, args, call.span),
+                    None => self.host_call(call, args),
+                }
+            }
+            PrimaryExpr::Array(elements, _) => {
+                let elements = elements.iter().map(|element| self.lower_expr(element)).collect();
+                self.array_literal(elements)
+            }
+            PrimaryExpr::Repeat(value, count, span) => {
+                let value = self.lower_expr(value);
+                let count = self.lower_expr(count);
+                self.repeat(value, count, *span)
+            }
+            PrimaryExpr::Matrix(rows, _) => {
+                let elements = rows
+                    .iter()
+                    .flat_map(|row| &row.elements)
+                    .map(|element| self.lower_expr(element))
+                    .collect();
+                self.matrix_literal(rows.len(), rows[0].elements.len(), elements)
+            }
+            PrimaryExpr::Expr(expr, _) => self.lower_expr(expr),
+        };
+        self.value = Some(id);
     }
-}
 
-impl InfixExpr {
-    fn to_ir(&self, context: &mut Context) -> ValueId {
-        let lhs_value_id = self.lhs.to_ir(context);
-        let rhs_value_id = self.rhs.to_ir(context);
-        let bin_op = match self.op {
-            InfixOp::Plus => BinaryOp::Add,
-            InfixOp::Minus => BinaryOp::Sub,
-            InfixOp::Multiply => BinaryOp::Mul,
-            InfixOp::Divide => BinaryOp::Div,
+    fn visit_prefix_expr(&mut self, expr: &PrefixExpr) {
+        let expr_value_id = self.lower_expr(&expr.expr);
+        let ty = self.value_ty(expr_value_id);
+        let id = match (expr.op, ty) {
+            (PrefixOp::Plus, _) => expr_value_id,
+            (PrefixOp::Minus, IrType::BigInt) => self.call("calc_big_neg", ty, vec![expr_value_id]),
+            (PrefixOp::BitNot, IrType::BigInt) => self.call("calc_big_not", ty, vec![expr_value_id]),
+            // no decimal is `i64::MIN`, so this cannot overflow
+            (PrefixOp::Minus, IrType::Decimal) => {
+                let zero = self.constant(ConstantValue::Decimal(0));
+                self.binary(BinaryOp::Sub, ty, zero, expr_value_id)
+            }
+            (PrefixOp::Minus, IrType::Rational) => {
+                let negated = self.call("calc_rat_neg", ty, vec![expr_value_id]);
+                self.check_rational(negated, expr.span)
+            }
+            (PrefixOp::Minus, IrType::Int) => {
+                let zero = self.constant(ConstantValue::Int(0));
+                self.int_arith(BinaryOp::Sub, zero, expr_value_id, expr.span)
+            }
+            (PrefixOp::Minus, _) | (PrefixOp::Not, _) | (PrefixOp::BitNot, _) => {
+                let unary_op = UnaryOperator {
+                    name: self.context.generate_local_name(),
+                    ty,
+                    operation: match expr.op {
+                        PrefixOp::Minus => UnaryOp::Neg,
+                        _ => UnaryOp::Not,
+                    },
+                    operand: expr_value_id,
+                };
+                self.context
+                    .push_instruction(InstructionValue::UnaryOperator(unary_op))
+            }
         };
+        self.value = Some(id);
+    }
 
-        let bin_inst = BinaryOperator {
-            name: context.generate_local_name(),
+    fn visit_postfix_expr(&mut self, expr: &PostfixExpr) {
+        let operand = self.lower_expr(&expr.expr);
+        let id = match expr.op {
+            PostfixOp::Factorial if self.value_ty(operand) == IrType::BigInt => {
+                self.big_factorial(operand, expr.span)
+            }
+            PostfixOp::Factorial => self.factorial(operand, expr.span),
+            // exact for `rat` and `dec`, like in the interpreter
+            PostfixOp::Percent if self.value_ty(operand) == IrType::Decimal => {
+                let hundred = self.constant(ConstantValue::Int(100));
+                let hundred = self.convert_checked(hundred, IrType::Decimal, expr.span);
+                self.decimal_arith(BinaryOp::Div, operand, hundred, expr.span)
+            }
+            PostfixOp::Percent
+                if self.value_ty(operand) == IrType::Rational
+                    || self.value_ty(operand) == IrType::Int && self.context.numbers == Numbers::Rational =>
+            {
+                let operand = self.convert(operand, IrType::Rational);
+                let hundred = self.constant(ConstantValue::Int(100));
+                let hundred = self.convert(hundred, IrType::Rational);
+                self.rational_arith(BinaryOp::Div, operand, hundred, expr.span)
+            }
+            PostfixOp::Percent => {
+                let operand = self.convert(operand, IrType::Float);
+                let hundred = self.constant(ConstantValue::Float(100.0));
+                self.binary(BinaryOp::Div, IrType::Float, operand, hundred)
+            }
+        };
+        self.value = Some(id);
+    }
+
+    fn visit_infix_expr(&mut self, expr: &InfixExpr) {
+        let mut lhs_value_id = self.lower_expr(&expr.lhs);
+        let mut rhs_value_id = self.lower_expr(&expr.rhs);
+        if self.value_ty(lhs_value_id) == IrType::Matrix || self.value_ty(rhs_value_id) == IrType::Matrix {
+            self.value = Some(self.matrix_arith(expr.op, lhs_value_id, rhs_value_id));
+            return;
+        }
+        // mixed operands widen to `complex` if either is one, else to `dec`
+        // if either is one, which only meets `int`s, else to float if either
+        // is one, else to `rat`
+        let mut ty = self.value_ty(lhs_value_id);
+        let rhs_ty = self.value_ty(rhs_value_id);
+        if ty != rhs_ty {
+            ty = match (ty, rhs_ty) {
+                (IrType::Complex, _) | (_, IrType::Complex) => IrType::Complex,
+                (IrType::Decimal, _) | (_, IrType::Decimal) => IrType::Decimal,
+                (IrType::Float, _) | (_, IrType::Float) => IrType::Float,
+                _ => IrType::Rational,
+            };
+        }
+        if ty == IrType::Int && expr.op == InfixOp::Divide && self.context.numbers == Numbers::Rational {
+            ty = IrType::Rational;
+        }
+        lhs_value_id = self.convert_checked(lhs_value_id, ty, expr.span);
+        rhs_value_id = self.convert_checked(rhs_value_id, ty, expr.span);
+        let predicate = match expr.op {
+            InfixOp::Equal => Some(ComparePredicate::Eq),
+            InfixOp::NotEqual => Some(ComparePredicate::Ne),
+            InfixOp::Less => Some(ComparePredicate::Lt),
+            InfixOp::LessEqual => Some(ComparePredicate::Le),
+            InfixOp::Greater => Some(ComparePredicate::Gt),
+            InfixOp::GreaterEqual => Some(ComparePredicate::Ge),
+            _ => None,
+        };
+        let id = match predicate {
+            Some(predicate) if matches!(ty, IrType::BigInt | IrType::Rational | IrType::Str | IrType::Array) => {
+                let callee = match ty {
+                    IrType::BigInt => "calc_big_cmp",
+                    IrType::Rational => "calc_rat_cmp",
+                    IrType::Str => "calc_str_cmp",
+                    _ => "calc_array_cmp",
+                };
+                let cmp = self.call(callee, IrType::Int, vec![lhs_value_id, rhs_value_id]);
+                let zero = self.constant(ConstantValue::Int(0));
+                self.compare(predicate, cmp, zero)
+            }
+            Some(predicate) => self.compare(predicate, lhs_value_id, rhs_value_id),
+            None => {
+                let bin_op = match expr.op {
+                    InfixOp::Plus => BinaryOp::Add,
+                    InfixOp::Minus => BinaryOp::Sub,
+                    InfixOp::Multiply => BinaryOp::Mul,
+                    InfixOp::Divide => BinaryOp::Div,
+                    InfixOp::Remainder => BinaryOp::Rem,
+                    InfixOp::Power => BinaryOp::Pow,
+                    InfixOp::BitAnd => BinaryOp::And,
+                    InfixOp::BitOr => BinaryOp::Or,
+                    InfixOp::BitXor => BinaryOp::Xor,
+                    InfixOp::ShiftLeft => BinaryOp::Shl,
+                    InfixOp::ShiftRight => BinaryOp::AShr,
+                    _ => unreachable!(),
+                };
+                match (bin_op, ty) {
+                    (bin_op, IrType::BigInt) => self.big_arith(bin_op, lhs_value_id, rhs_value_id, expr.span),
+                    (bin_op, IrType::Rational) => {
+                        self.rational_arith(bin_op, lhs_value_id, rhs_value_id, expr.span)
+                    }
+                    (bin_op, IrType::Decimal) => {
+                        self.decimal_arith(bin_op, lhs_value_id, rhs_value_id, expr.span)
+                    }
+                    (bin_op @ (BinaryOp::Shl | BinaryOp::AShr), _) => {
+                        self.shift(bin_op, lhs_value_id, rhs_value_id, expr.span)
+                    }
+                    (bin_op @ (BinaryOp::And | BinaryOp::Or | BinaryOp::Xor), _) => {
+                        self.binary(bin_op, ty, lhs_value_id, rhs_value_id)
+                    }
+                    (BinaryOp::Pow, IrType::Float) => {
+                        let sig = Signature {
+                            params: vec![Type::Float; 2],
+                            ret: Type::Float,
+                        };
+                        self.call_external("llvm.pow.f64", sig, vec![lhs_value_id, rhs_value_id])
+                    }
+                    (BinaryOp::Add, IrType::Str) => {
+                        self.call("calc_str_concat", ty, vec![lhs_value_id, rhs_value_id])
+                    }
+                    (bin_op, IrType::Int) => self.int_arith(bin_op, lhs_value_id, rhs_value_id, expr.span),
+                    (bin_op, _) => self.binary(bin_op, ty, lhs_value_id, rhs_value_id),
+                }
+            }
+        };
+        self.value = Some(id);
+    }
+
+    fn visit_cast_expr(&mut self, expr: &CastExpr) {
+        let value_id = self.lower_expr(&expr.expr);
+        let ty = self.ir_type(expr.ty);
+        self.value = Some(self.convert_checked(value_id, ty, expr.span));
+    }
+
+    fn visit_convert_expr(&mut self, expr: &ConvertExpr) {
+        // a number of units is the quantity divided by the size of one,
+        // which is known while compiling
+        let value_id = self.lower_expr(&expr.expr);
+        let value_id = self.convert(value_id, IrType::Float);
+        let scale = self.constant(ConstantValue::Float(expr.unit.scale()));
+        self.value = Some(self.binary(BinaryOp::Div, IrType::Float, value_id, scale));
+    }
+
+    fn visit_index_expr(&mut self, expr: &IndexExpr) {
+        let array = self.lower_expr(&expr.expr);
+        let index = self.lower_expr(&expr.index);
+        let source = self.checked_element_ptr(array, index, expr.index.span());
+        let load_inst = LoadInst {
+            name: self.context.generate_local_name(),
             ty: IrType::Int,
-            operation: bin_op,
-            left_operand: lhs_value_id,
-            right_operand: rhs_value_id,
-        };
-        let id = context.values.borrow_mut().alloc(Value::Instruction(
-            InstructionValue::BinaryOperator(bin_inst),
-        ));
-        context.instructions.push(id);
-        id
+            source,
+        };
+        self.value = Some(
+            self.context
+                .push_instruction(InstructionValue::LoadInst(load_inst)),
+        );
     }
 }
@@ -0,0 +1,151 @@
+use crate::formatter::format_spec;
+use crate::ir::*;
+
+/// Prints the IR in a compact, LLVM-like notation, for `--emit=ir`.
+pub trait IrPrinter {
+    fn print_ir(&self) -> String;
+}
+
+impl IrPrinter for Context {
+    fn print_ir(&self) -> String {
+        let mut out = String::new();
+        let arena = self.values.borrow();
+        let mut globals: Vec<_> = self.global_variables.values().collect();
+        globals.sort_by_key(|id| arena[**id].name());
+        for id in globals {
+            let global = &arena[*id];
+            out.push_str(&format!(
+                "global {}: {}\n",
+                global.name(),
+                type_name(global.ty())
+            ));
+        }
+        for id in &self.instructions {
+            if let Value::Instruction(inst) = &arena[*id] {
+                out.push_str("  ");
+                out.push_str(&print_instruction(inst, self));
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+fn type_name(ty: IrType) -> &'static str {
+    match ty {
+        IrType::Void => "void",
+        IrType::Int => "int",
+        IrType::BigInt => "bigint",
+        IrType::Float => "float",
+        IrType::Bool => "bool",
+        IrType::Rational => "rat",
+        IrType::Decimal => "dec",
+        IrType::Str => "str",
+        IrType::Array => "array",
+        IrType::Matrix => "matrix",
+        IrType::Complex => "complex",
+    }
+}
+
+fn print_instruction(inst: &InstructionValue, context: &Context) -> String {
+    let operand = |id| print_operand(&context.values.borrow()[id], context);
+    let ty = type_name(inst.ty());
+    match inst {
+        InstructionValue::BinaryOperator(op) => format!(
+            "{} = {} {} {}, {}",
+            op.name,
+            format!("{:?}", op.operation).to_lowercase(),
+            ty,
+            operand(op.left_operand),
+            operand(op.right_operand)
+        ),
+        InstructionValue::UnaryOperator(op) => format!(
+            "{} = {} {} {}",
+            op.name,
+            format!("{:?}", op.operation).to_lowercase(),
+            ty,
+            operand(op.operand)
+        ),
+        InstructionValue::CompareInst(cmp) => format!(
+            "{} = cmp {} {}, {}",
+            cmp.name,
+            format!("{:?}", cmp.predicate).to_lowercase(),
+            operand(cmp.left_operand),
+            operand(cmp.right_operand)
+        ),
+        InstructionValue::CastInst(cast) => {
+            format!("{} = cast {} to {}", cast.name, operand(cast.source), ty)
+        }
+        InstructionValue::LoadInst(load) => {
+            format!("{} = load {} {}", load.name, ty, operand(load.source))
+        }
+        InstructionValue::StoreInst(store) => format!(
+            "store {}, {}",
+            operand(store.source),
+            operand(store.destination)
+        ),
+        InstructionValue::AllocaInst(alloca) => match alloca.count {
+            Some(count) => format!("{} = alloca {} x {}", alloca.name, ty, count),
+            None => format!("{} = alloca {}", alloca.name, ty),
+        },
+        InstructionValue::ArrayInst(array) => format!(
+            "{} = array {}, {}",
+            array.name,
+            operand(array.len),
+            operand(array.data)
+        ),
+        InstructionValue::ElementPtrInst(element_ptr) => format!(
+            "{} = element {}, {}",
+            element_ptr.name,
+            operand(element_ptr.array),
+            operand(element_ptr.index)
+        ),
+        InstructionValue::OverflowCheckInst(check) => format!(
+            "{} = overflows {} {}, {}",
+            check.name,
+            format!("{:?}", check.operation).to_lowercase(),
+            operand(check.left_operand),
+            operand(check.right_operand)
+        ),
+        InstructionValue::SelectInst(select) => format!(
+            "{} = select {}, {}, {}",
+            select.name,
+            operand(select.condition),
+            operand(select.true_value),
+            operand(select.false_value)
+        ),
+        InstructionValue::TrapIfInst(trap_if) => format!(
+            "trap_if {}, \"{}\" at {}:{}",
+            operand(trap_if.condition),
+            trap_if.trap.message(),
+            trap_if.line,
+            trap_if.col
+        ),
+        InstructionValue::CallInst(call) => {
+            let args: Vec<String> = call.args.iter().map(|id| operand(*id)).collect();
+            format!(
+                "{} = call {} {}({})",
+                call.name,
+                ty,
+                call.callee,
+                args.join(", ")
+            )
+        }
+        InstructionValue::PrintInst(print) => match &print.format {
+            Some(format) => format!("print {} {}", operand(print.param), format_spec(&format.spec)),
+            None => format!("print {}", operand(print.param)),
+        },
+        InstructionValue::PrintTextInst(print) => format!("print {:?}", context.strings[print.text]),
+    }
+}
+
+fn print_operand(value: &Value, context: &Context) -> String {
+    match value {
+        Value::Constant(ConstantValue::Int(i) | ConstantValue::Decimal(i)) => i.to_string(),
+        Value::Constant(ConstantValue::Float(f)) => format!("{:?}", f),
+        Value::Constant(ConstantValue::Bool(b)) => b.to_string(),
+        Value::Constant(ConstantValue::Complex(re, im)) => format!("({:?}, {:?})", re, im),
+        Value::Constant(ConstantValue::Str(i)) => format!("{:?}", context.strings[*i]),
+        value => value.name(),
+    }
+}
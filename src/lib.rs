@@ -0,0 +1,78 @@
+//! The calc compiler as a library: parse and check programs, evaluate them
+//! in-process with an [`Engine`], or compile them to LLVM IR as a
+//! [`Program`]. The [`ast`] module holds the syntax tree and the visitors
+//! that walk it.
+
+pub mod ast;
+pub(crate) mod bigint;
+pub(crate) mod builtins;
+pub(crate) mod codegen;
+pub(crate) mod complex;
+pub(crate) mod decimal;
+pub(crate) mod diagnostic;
+pub(crate) mod dump;
+pub(crate) mod formatter;
+pub(crate) mod interpreter;
+pub(crate) mod ir;
+pub(crate) mod irprint;
+pub(crate) mod lint;
+pub(crate) mod matrix;
+pub(crate) mod native;
+pub(crate) mod parser;
+pub(crate) mod rational;
+pub(crate) mod runtime;
+pub(crate) mod sema;
+pub(crate) mod typeck;
+pub(crate) mod units;
+mod engine;
+mod program;
+
+#[macro_use]
+extern crate pest_derive;
+
+pub use ast::{LintLevel, Span, TransUnit};
+pub use diagnostic::{Diagnostic, Diagnostics, Level, Note};
+pub use engine::{Engine, Error};
+pub use interpreter::{Output, Stdout, Value};
+pub use native::{IntoNative, NativeType};
+pub use program::{Options, Program};
+pub use runtime::{Numbers, Overflow, Precision, Rounding, RuntimeError, Trap, EXIT_TRAP};
+
+/// Parses a program and checks its names, types and units.
+pub fn parse(src: &str) -> Result<TransUnit, Diagnostics> {
+    let mut tu = parser::parse(src)?;
+    let mut diagnostics = sema::analyze_with_functions(&mut tu, &Default::default());
+    if !diagnostic::has_errors(&diagnostics) {
+        diagnostics.extend(typeck::check(&tu, Numbers::default()));
+    }
+    if !diagnostic::has_errors(&diagnostics) {
+        diagnostics.extend(units::check(&tu));
+    }
+    if diagnostic::has_errors(&diagnostics) {
+        return Err(Diagnostics(diagnostics));
+    }
+    Ok(tu)
+}
+
+/// Compiles a program to a textual LLVM module whose `main` runs it.
+pub fn compile_to_llvm(src: &str, options: Options) -> Result<String, Diagnostics> {
+    Ok(Program::compile(src, options)?.to_llvm())
+}
+
+/// Lays a program out the way `calcc fmt` does. Only its syntax has to be
+/// valid.
+pub fn format(src: &str) -> Result<String, Diagnostics> {
+    let tu = parser::parse(src)?;
+    Ok(formatter::format(&tu, src))
+}
+
+/// The syntax tree of a program as an S-expression, before any checks.
+pub fn syntax_tree(src: &str) -> Result<String, Diagnostics> {
+    Ok(parser::parse(src)?.to_sexpr())
+}
+
+/// The syntax tree of a program as JSON, before any checks, which
+/// `TransUnit::from_json` reads back.
+pub fn syntax_tree_json(src: &str) -> Result<String, Diagnostics> {
+    Ok(parser::parse(src)?.to_json())
+}
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::formatter::{format_expr, strip_parens};
+use crate::typeck::TypeChecker;
+
+/// A check for code that is valid but most likely not what was meant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    DivisionByZero,
+    UnusedMem,
+    RedundantPlus,
+    IdenticalOperands,
+    UnreachableCode,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 5] = [
+        Lint::DivisionByZero,
+        Lint::UnusedMem,
+        Lint::RedundantPlus,
+        Lint::IdenticalOperands,
+        Lint::UnreachableCode,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::DivisionByZero => "division_by_zero",
+            Lint::UnusedMem => "unused_mem",
+            Lint::RedundantPlus => "redundant_plus",
+            Lint::IdenticalOperands => "identical_operands",
+            Lint::UnreachableCode => "unreachable_code",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+/// Lint levels given on the command line. Lints not mentioned warn.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<Lint, LintLevel>,
+}
+
+impl LintConfig {
+    pub fn set(&mut self, lint: Lint, level: LintLevel) {
+        self.levels.insert(lint, level);
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        self.levels.get(&lint).copied().unwrap_or(LintLevel::Warn)
+    }
+}
+
+/// Lints a program that has passed name resolution and type checking.
+/// Denied lints are reported as errors.
+pub fn check(tu: &TransUnit, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut linter = Linter::new(config);
+    linter.visit_trans_unit(tu);
+    if let Some((span, level)) = linter.unread_mem.take() {
+        linter.report(
+            Lint::UnusedMem,
+            level,
+            "value of this expression is never read",
+            span,
+        );
+    }
+    linter.diagnostics
+}
+
+struct Linter {
+    // levels in effect, innermost block or attributed statement last
+    levels: Vec<HashMap<Lint, LintLevel>>,
+    // levels set by `#[...]` for the statement that follows
+    next: Vec<(Lint, LintLevel)>,
+    // kept in step with the statements linted so far, for operand types
+    checker: TypeChecker,
+    // the expression statement that last wrote `mem`, until `mem` is read
+    unread_mem: Option<(Span, LintLevel)>,
+    // an earlier statement always fails at runtime
+    diverged: bool,
+    reported_unreachable: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    fn new(config: &LintConfig) -> Self {
+        let levels = Lint::ALL
+            .into_iter()
+            .map(|lint| (lint, config.level(lint)))
+            .collect();
+        Self {
+            levels: vec![levels],
+            next: vec![],
+            checker: TypeChecker::new(),
+            unread_mem: None,
+            diverged: false,
+            reported_unreachable: false,
+            diagnostics: vec![],
+        }
+    }
+
+    fn level(&self, lint: Lint) -> LintLevel {
+        self.levels.last().unwrap()[&lint]
+    }
+
+    fn lint(&mut self, lint: Lint, message: impl Into<String>, span: Span) {
+        self.report(lint, self.level(lint), message, span);
+    }
+
+    fn report(&mut self, lint: Lint, level: LintLevel, message: impl Into<String>, span: Span) {
+        let message = format!("{} [{}]", message.into(), lint.name());
+        match level {
+            LintLevel::Allow => {}
+            LintLevel::Warn => self.diagnostics.push(Diagnostic::warning(message, span)),
+            LintLevel::Deny => self.diagnostics.push(Diagnostic::error(message, span)),
+        }
+    }
+
+    fn apply_attribute(&mut self, attr: &AttrStmt) {
+        for name in &attr.lints {
+            match Lint::from_name(&name.name) {
+                Some(lint) if attr.inner => {
+                    self.levels.last_mut().unwrap().insert(lint, attr.level);
+                }
+                Some(lint) => self.next.push((lint, attr.level)),
+                None => self.diagnostics.push(Diagnostic::warning(
+                    format!("unknown lint `{}`", name.name),
+                    name.span,
+                )),
+            }
+        }
+    }
+}
+
+impl Visitor for Linter {
+    fn visit_block(&mut self, block: &Block) {
+        self.levels.push(self.levels.last().unwrap().clone());
+        for stmt in &block.stmts {
+            if let Stmt::AttrStmt(attr) = stmt {
+                self.apply_attribute(attr);
+                continue;
+            }
+            let mut levels = self.levels.last().unwrap().clone();
+            levels.extend(self.next.drain(..));
+            self.levels.push(levels);
+            if self.diverged && !self.reported_unreachable {
+                self.reported_unreachable = true;
+                self.lint(Lint::UnreachableCode, "unreachable statement", stmt.span());
+            }
+            self.visit_stmt(stmt);
+            if !matches!(stmt, Stmt::BlockStmt(_)) {
+                self.checker.visit_stmt(stmt);
+            }
+            self.levels.pop();
+        }
+        // a trailing `#[...]` has nothing to apply to
+        self.next.clear();
+        self.levels.pop();
+    }
+
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt) {
+        self.visit_expr(&stmt.expr);
+        if let Some((span, level)) = self.unread_mem.take() {
+            self.report(
+                Lint::UnusedMem,
+                level,
+                "value of this expression is overwritten before it is read",
+                span,
+            );
+        }
+        self.unread_mem = Some((stmt.span, self.level(Lint::UnusedMem)));
+    }
+
+    fn visit_primary_expr(&mut self, expr: &PrimaryExpr) {
+        if let PrimaryExpr::Mem(_) = expr {
+            self.unread_mem = None;
+        }
+        walk_primary_expr(self, expr);
+    }
+
+    fn visit_prefix_expr(&mut self, expr: &PrefixExpr) {
+        if expr.op == PrefixOp::Plus {
+            self.lint(Lint::RedundantPlus, "unary `+` has no effect", expr.span);
+        }
+        walk_prefix_expr(self, expr);
+    }
+
+    fn visit_infix_expr(&mut self, expr: &InfixExpr) {
+        walk_infix_expr(self, expr);
+        if matches!(expr.op, InfixOp::Divide | InfixOp::Remainder) {
+            let zero = match strip_parens(&expr.rhs) {
+                Expr::Primary(primary) => match primary.as_ref() {
+                    PrimaryExpr::Int(0, _, _) | PrimaryExpr::Char('\0', _) => Some(Type::Int),
+                    PrimaryExpr::Rational(0, _) => Some(Type::Rational),
+                    PrimaryExpr::Decimal(0, _, _) => Some(Type::Decimal),
+                    PrimaryExpr::Float(f, _) if *f == 0.0 => Some(Type::Float),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(ty) = zero {
+                let lhs = self.checker.type_of(&expr.lhs);
+                if ty != Type::Float && matches!(lhs, Some(Type::Int | Type::Rational | Type::Decimal)) {
+                    self.diverged = true;
+                    self.lint(
+                        Lint::DivisionByZero,
+                        "this division by zero always fails",
+                        expr.span,
+                    );
+                } else {
+                    self.lint(
+                        Lint::DivisionByZero,
+                        "division by zero yields infinity or NaN",
+                        expr.span,
+                    );
+                }
+                return;
+            }
+        }
+        // only exact numbers: with floats, `x - x` and `x / x` are NaN for
+        // infinities and NaN; and a call may return something else the
+        // second time
+        let exact = matches!(
+            self.checker.type_of(&expr.lhs),
+            Some(Type::Int | Type::Rational | Type::Decimal)
+        );
+        if matches!(expr.op, InfixOp::Minus | InfixOp::Divide)
+            && exact
+            && !has_call(&expr.lhs)
+            && format_expr(strip_parens(&expr.lhs)) == format_expr(strip_parens(&expr.rhs))
+        {
+            let message = if expr.op == InfixOp::Minus {
+                "both operands of `-` are the same, so this is always zero"
+            } else {
+                "both operands of `/` are the same, so this is always one or fails"
+            };
+            self.lint(Lint::IdenticalOperands, message, expr.span);
+        }
+    }
+}
+
+// whether `expr` calls a function anywhere
+fn has_call(expr: &Expr) -> bool {
+    struct Calls(bool);
+
+    impl Visitor for Calls {
+        fn visit_call_expr(&mut self, _expr: &CallExpr) {
+            self.0 = true;
+        }
+    }
+
+    let mut calls = Calls(false);
+    calls.visit_expr(expr);
+    calls.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(src: &str, config: &LintConfig) -> Vec<Diagnostic> {
+        let tu = crate::parse(src).unwrap_or_else(|err| panic!("{:?} failed to check:\n{}", src, err.render(src)));
+        check(&tu, config)
+    }
+
+    fn warnings(src: &str) -> Vec<String> {
+        lint(src, &LintConfig::default()).into_iter().map(|d| d.message).collect()
+    }
+
+    // `level: message` for each diagnostic
+    fn lints(src: &str, config: &LintConfig) -> Vec<String> {
+        lint(src, config)
+            .into_iter()
+            .map(|d| format!("{}: {}", d.level, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn every_lint_warns_by_default() {
+        let config = LintConfig::default();
+        assert_eq!(
+            lints("print 1 / 0;", &config),
+            ["warning: this division by zero always fails [division_by_zero]"]
+        );
+        assert_eq!(
+            lints("1; 2; print(mem);", &config),
+            ["warning: value of this expression is overwritten before it is read [unused_mem]"]
+        );
+        assert_eq!(lints("print +1;", &config), ["warning: unary `+` has no effect [redundant_plus]"]);
+        assert_eq!(
+            lints("let x = 0; print 1 / 0; print 2;", &config),
+            [
+                "warning: this division by zero always fails [division_by_zero]",
+                "warning: unreachable statement [unreachable_code]"
+            ]
+        );
+    }
+
+    #[test]
+    fn config_sets_levels() {
+        let mut config = LintConfig::default();
+        config.set(Lint::RedundantPlus, LintLevel::Deny);
+        config.set(Lint::UnusedMem, LintLevel::Allow);
+        assert_eq!(lints("print +1;", &config), ["error: unary `+` has no effect [redundant_plus]"]);
+        assert!(lints("1; 2; print(mem);", &config).is_empty());
+    }
+
+    #[test]
+    fn attributes_set_levels_for_a_statement_or_a_block() {
+        let config = LintConfig::default();
+        assert_eq!(
+            lints("#[allow(division_by_zero)] print 1 / 0; print 1 % 0;", &config),
+            [
+                "warning: unreachable statement [unreachable_code]",
+                "warning: this division by zero always fails [division_by_zero]"
+            ]
+        );
+        assert_eq!(
+            lints("{ #![deny(redundant_plus)] print +1; } print +2;", &config),
+            [
+                "error: unary `+` has no effect [redundant_plus]",
+                "warning: unary `+` has no effect [redundant_plus]"
+            ]
+        );
+        let mut config = LintConfig::default();
+        config.set(Lint::RedundantPlus, LintLevel::Deny);
+        assert!(lints("#[allow(redundant_plus)] print +1;", &config).is_empty());
+    }
+
+    #[test]
+    fn identical_operands_on_exact_numbers() {
+        assert_eq!(
+            warnings("let x = 3; print x - x;"),
+            ["both operands of `-` are the same, so this is always zero [identical_operands]"]
+        );
+        assert_eq!(
+            warnings("let r = 1r; print (r) / r;"),
+            ["both operands of `/` are the same, so this is always one or fails [identical_operands]"]
+        );
+    }
+
+    #[test]
+    fn identical_float_operands_are_fine() {
+        assert!(warnings("let x = 1.5; print x - x;").is_empty());
+        assert!(warnings("let x = 1.5; print x / x;").is_empty());
+    }
+
+    #[test]
+    fn identical_operands_with_calls_are_fine() {
+        assert!(warnings("let x = 3; print abs(x) - abs(x);").is_empty());
+        assert!(warnings("print parse_int(\"4\") / parse_int(\"4\");").is_empty());
+    }
+}
@@ -1,31 +1,283 @@
-mod ir;
-mod ast;
-mod parser;
 mod driver;
-mod interpreter;
-mod codegen;
+mod repl;
 
-#[macro_use]
-extern crate pest_derive;
-use std::env;
+use std::{env, process};
 
-use driver::drive;
+use std::path::Path;
+
+use calcc::{LintLevel, Numbers, Overflow, Precision, Rounding};
+use driver::{compile, compile_ast_json, fmt, read_input, write_output, Emit, Mode, EXIT_ERROR, EXIT_USAGE};
+
+const USAGE: &str = "\
+Usage: calcc run [--mode=interpret|llvm] [OPTIONS] FILE
+       calcc build [OPTIONS] FILE [--runtime FILE...] [-o OUT]
+       calcc emit [--emit=ast|ast-json|ir|llvm|asm] [OPTIONS] FILE [-o OUT]
+       calcc fmt [--check] [FILE...]
+       calcc repl [--numbers=MODE]
+
+FILE may be `-` for stdin. Options:
+  -e SRC            compile SRC instead of a file
+  --load-ast FILE   compile an AST saved with --emit=ast-json
+  -A|-W|-D LINT     allow, warn about or deny a lint
+  --overflow=MODE   make integer overflow wrap, trap (the default) or saturate
+  --numbers=MODE    keep `int` in 64 bits (fixed, the default), give it
+                    arbitrary precision (big), or make `int / int` an exact
+                    `rat` (rational); big programs are linked against a
+                    bundled runtime when compiled
+  --bigint          the same as --numbers=big
+  --rational-digits=N
+                    print rationals rounded to N decimal places instead of
+                    as fractions
+  --decimal-scale=N give every `dec` N decimal places (2 by default)
+  --rounding=MODE   round `dec` results half to even (half-even, the
+                    default), half away from zero (half-up) or towards zero
+                    (truncate)
+  --extern SIG      let the program call a host function, declared like
+                    `clamp(int, int, int) -> int` (build and emit only)
+  --runtime FILE    link the C source, object or library that defines the
+                    host functions into the executable (build only)
+  -o OUT            write to OUT instead of stdout (or FILE's name for build)
+
+Exit status is 0 on success, 1 if the program has errors, 2 for bad
+arguments, unreadable files and failing tools, and 3 if `run` stopped
+with a runtime error.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Subcommand {
+    Run,
+    Build,
+    Emit,
+}
+
+enum Input {
+    File(String),
+    Expr(String),
+    AstJson(String),
+}
+
+struct Options {
+    input: Input,
+    output: Option<String>,
+    emit: Emit,
+    mode: Mode,
+    compiler: calcc::Options,
+    runtime: Vec<String>,
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let mut i = 1;
-    while i < args.len() {
-        match args[i].as_str() {
-            "-h" | "--help" => print!("Usage: calc -e EXPR\n"),
-            "-v" | "--version" => print!("calc 0.1.0\n"),
-            "-e" | "--expr" => {
-                i += 1;
-                drive(args[i].as_str());
-            }
-            _ => {
-                print!("calc: Unrecognized option '{}'\n", args[i])
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = match args.first().map(String::as_str) {
+        Some("fmt") => {
+            let check = args[1..].iter().any(|arg| arg == "--check");
+            let paths: Vec<String> = args[1..]
+                .iter()
+                .filter(|arg| *arg != "--check")
+                .cloned()
+                .collect();
+            process::exit(fmt(&paths, check));
+        }
+        Some("repl") => {
+            let numbers = match &args[1..] {
+                [] => Numbers::Fixed,
+                [arg] if arg == "--bigint" => Numbers::Big,
+                [arg] if arg.starts_with("--numbers=") => parse_numbers(arg).unwrap_or_else(|err| usage_error(&err)),
+                [arg, ..] => usage_error(&format!("Unrecognized option '{}'", arg)),
+            };
+            process::exit(repl::run(numbers))
+        }
+        Some("run") => Subcommand::Run,
+        Some("build") => Subcommand::Build,
+        Some("emit") => Subcommand::Emit,
+        Some("-h" | "--help") => {
+            println!("{}", USAGE);
+            return;
+        }
+        Some("-v" | "--version") => {
+            println!("calcc 0.1.0");
+            return;
+        }
+        Some(arg) => usage_error(&format!("Unknown command '{}'", arg)),
+        None => usage_error("Missing command"),
+    };
+    let options = parse_options(command, &args[1..]).unwrap_or_else(|err| usage_error(&err));
+    process::exit(execute(command, options));
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("calc: {}\n\n{}", message, USAGE);
+    process::exit(EXIT_USAGE);
+}
+
+fn parse_numbers(arg: &str) -> Result<Numbers, String> {
+    Numbers::from_name(&arg["--numbers=".len()..]).ok_or_else(|| format!("Unknown number mode '{}'", arg))
+}
+
+fn parse_options(command: Subcommand, args: &[String]) -> Result<Options, String> {
+    let mut input = None;
+    let mut output = None;
+    let mut emit = Emit::Llvm;
+    let mut mode = Mode::Interpret;
+    let mut compiler = calcc::Options::new();
+    let mut precision = Precision::default();
+    let mut runtime = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |what: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("'{}' expects {}", arg, what))
+        };
+        match arg.as_str() {
+            "-e" | "--expr" => input = Some(Input::Expr(value("an expression")?)),
+            "--load-ast" => input = Some(Input::AstJson(value("a file")?)),
+            "-o" if command != Subcommand::Run => output = Some(value("a file")?),
+            // the interpreter cannot call into a runtime that is only linked
+            "--extern" if command != Subcommand::Run => {
+                let decl = value("a function signature")?;
+                compiler
+                    .declare_function(&decl)
+                    .map_err(|_| format!("Invalid function signature '{}'", decl))?;
+            }
+            "--runtime" if command == Subcommand::Build => runtime.push(value("a file")?),
+            "--bigint" => {
+                compiler.set_numbers(Numbers::Big);
             }
+            "-A" | "-W" | "-D" => {
+                let level = match arg.as_str() {
+                    "-A" => LintLevel::Allow,
+                    "-W" => LintLevel::Warn,
+                    _ => LintLevel::Deny,
+                };
+                let name = value("a lint name")?;
+                compiler
+                    .set_lint(&name, level)
+                    .map_err(|_| format!("Unknown lint '{}'", name))?;
+            }
+            arg if command == Subcommand::Emit && arg.starts_with("--emit=") => {
+                emit = Emit::from_name(&arg["--emit=".len()..])
+                    .ok_or_else(|| format!("Unknown emit kind '{}'", arg))?;
+            }
+            arg if command == Subcommand::Run && arg.starts_with("--mode=") => {
+                mode = Mode::from_name(&arg["--mode=".len()..])
+                    .ok_or_else(|| format!("Unknown mode '{}'", arg))?;
+            }
+            arg if arg.starts_with("--numbers=") => {
+                compiler.set_numbers(parse_numbers(arg)?);
+            }
+            arg if arg.starts_with("--rational-digits=") => {
+                let digits = arg["--rational-digits=".len()..]
+                    .parse()
+                    .ok()
+                    .filter(|&digits| digits <= Precision::MAX_RATIONAL_DIGITS)
+                    .ok_or_else(|| {
+                        format!("'{}' expects a number of digits up to {}", arg, Precision::MAX_RATIONAL_DIGITS)
+                    })?;
+                precision.rational_digits = Some(digits);
+            }
+            arg if arg.starts_with("--decimal-scale=") => {
+                precision.decimal_scale = arg["--decimal-scale=".len()..]
+                    .parse()
+                    .ok()
+                    .filter(|&scale| scale <= Precision::MAX_DECIMAL_SCALE)
+                    .ok_or_else(|| {
+                        format!("'{}' expects a number of places up to {}", arg, Precision::MAX_DECIMAL_SCALE)
+                    })?;
+            }
+            arg if arg.starts_with("--rounding=") => {
+                precision.rounding = Rounding::from_name(&arg["--rounding=".len()..])
+                    .ok_or_else(|| format!("Unknown rounding mode '{}'", arg))?;
+            }
+            arg if arg.starts_with("--overflow=") => {
+                let overflow = Overflow::from_name(&arg["--overflow=".len()..])
+                    .ok_or_else(|| format!("Unknown overflow behavior '{}'", arg))?;
+                compiler.set_overflow(overflow);
+            }
+            arg if arg == "-" || !arg.starts_with('-') => {
+                if input.is_some() {
+                    return Err(format!("Unexpected argument '{}'", arg));
+                }
+                input = Some(Input::File(arg.to_string()));
+            }
+            arg => return Err(format!("Unrecognized option '{}'", arg)),
+        }
+    }
+    let input = input.ok_or("Missing input file")?;
+    compiler.set_precision(precision);
+    Ok(Options {
+        input,
+        output,
+        emit,
+        mode,
+        compiler,
+        runtime,
+    })
+}
+
+fn execute(command: Subcommand, options: Options) -> i32 {
+    let text = match &options.input {
+        Input::Expr(src) => Ok(src.clone()),
+        Input::File(path) | Input::AstJson(path) => read_input(path),
+    };
+    let text = match text {
+        Ok(text) => text,
+        Err(err) => return tool_error(&err),
+    };
+    let from_json = matches!(options.input, Input::AstJson(_));
+    // the tree as parsed, whether or not it passes the checks
+    if command == Subcommand::Emit && matches!(options.emit, Emit::AstSexpr | Emit::AstJson) {
+        let Some(tree) = driver::syntax_tree(&text, from_json, options.emit) else {
+            return EXIT_ERROR;
+        };
+        let out = options.output.as_deref().unwrap_or("-");
+        return write_output(out, tree.as_bytes()).map_or_else(|err| tool_error(&err), |()| 0);
+    }
+    let (src, program) = if from_json {
+        (String::new(), compile_ast_json(&text, options.compiler))
+    } else {
+        let program = compile(&text, options.compiler);
+        (text, program)
+    };
+    let Some(program) = program else {
+        return EXIT_ERROR;
+    };
+    let result = match command {
+        Subcommand::Run => match driver::run(&program, &src, options.mode) {
+            Ok(code) => return code,
+            Err(err) => Err(err),
+        },
+        Subcommand::Emit => driver::emit(&program, options.emit).and_then(|text| {
+            write_output(options.output.as_deref().unwrap_or("-"), text.as_bytes())
+        }),
+        Subcommand::Build => {
+            let output = options.output.unwrap_or_else(|| match &options.input {
+                Input::File(path) if path != "-" => Path::new(path)
+                    .file_stem()
+                    .map_or("a.out".to_string(), |stem| stem.to_string_lossy().into_owned()),
+                _ => "a.out".to_string(),
+            });
+            if let Input::File(path) | Input::AstJson(path) = &options.input {
+                if same_file(path, &output) {
+                    return tool_error(&format!("{}: refusing to overwrite the input; name the executable with -o", output));
+                }
+            }
+            driver::build(&program, &output, &options.runtime)
         }
-        i += 1;
+    };
+    match result {
+        Ok(()) => 0,
+        Err(err) => tool_error(&err),
     }
 }
+
+// whether `a` and `b` name the same file, which need not exist yet
+fn same_file(a: &str, b: &str) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => Path::new(a) == Path::new(b),
+    }
+}
+
+fn tool_error(message: &str) -> i32 {
+    eprintln!("calc: {}", message);
+    EXIT_USAGE
+}
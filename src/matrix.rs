@@ -0,0 +1,193 @@
+//! Matrices of floats, the values of `[[float; C]; R]`. `typeck` checks
+//! shapes, so every operation here gets operands that fit together. The
+//! `calc_matrix_*` functions in the prelude of `codegen` compute the same
+//! for compiled programs, in the same order, so results agree to the bit.
+
+use std::fmt;
+
+use crate::interpreter::format_float;
+use crate::runtime::Trap;
+
+/// A matrix with its elements row after row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    elements: Vec<f64>,
+}
+
+impl Matrix {
+    /// A matrix of `rows` rows of `cols` elements each, given row after
+    /// row.
+    pub fn new(rows: usize, cols: usize, elements: Vec<f64>) -> Matrix {
+        assert_eq!(elements.len(), rows * cols);
+        Matrix { rows, cols, elements }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn get(&self, i: usize, j: usize) -> f64 {
+        self.elements[i * self.cols + j]
+    }
+
+    /// `self + other`, element by element.
+    pub fn add(&self, other: &Matrix) -> Matrix {
+        self.zip_with(other, |a, b| a + b)
+    }
+
+    /// `self - other`, element by element.
+    pub fn sub(&self, other: &Matrix) -> Matrix {
+        self.zip_with(other, |a, b| a - b)
+    }
+
+    fn zip_with(&self, other: &Matrix, f: impl Fn(f64, f64) -> f64) -> Matrix {
+        let elements = self.elements.iter().zip(&other.elements).map(|(&a, &b)| f(a, b)).collect();
+        Matrix::new(self.rows, self.cols, elements)
+    }
+
+    /// Every element times `factor`.
+    pub fn scale(&self, factor: f64) -> Matrix {
+        let elements = self.elements.iter().map(|&x| x * factor).collect();
+        Matrix::new(self.rows, self.cols, elements)
+    }
+
+    /// `self @ other`; `self` has as many columns as `other` has rows.
+    pub fn product(&self, other: &Matrix) -> Matrix {
+        let mut elements = Vec::with_capacity(self.rows * other.cols);
+        for i in 0..self.rows {
+            for j in 0..other.cols {
+                let mut sum = 0.0;
+                for k in 0..self.cols {
+                    sum += self.get(i, k) * other.get(k, j);
+                }
+                elements.push(sum);
+            }
+        }
+        Matrix::new(self.rows, other.cols, elements)
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let mut elements = Vec::with_capacity(self.elements.len());
+        for j in 0..self.cols {
+            for i in 0..self.rows {
+                elements.push(self.get(i, j));
+            }
+        }
+        Matrix::new(self.cols, self.rows, elements)
+    }
+
+    /// The determinant of a square matrix, by Gaussian elimination with
+    /// partial pivoting: the product of the pivots, negated for every swap
+    /// of rows. It is 0 as soon as a column has no pivot left.
+    pub fn determinant(&self) -> f64 {
+        let n = self.rows;
+        let mut a = self.elements.clone();
+        let mut det = 1.0;
+        for k in 0..n {
+            let p = pivot_row(&a, n, k);
+            if a[p * n + k] == 0.0 {
+                return 0.0;
+            }
+            if p != k {
+                swap_rows(&mut a, n, p, k);
+                det = -det;
+            }
+            let pivot = a[k * n + k];
+            det *= pivot;
+            for i in k + 1..n {
+                let factor = a[i * n + k] / pivot;
+                for j in k + 1..n {
+                    a[i * n + j] -= factor * a[k * n + j];
+                }
+            }
+        }
+        det
+    }
+
+    /// The inverse of a square matrix, by Gauss-Jordan elimination with
+    /// partial pivoting. A column without a pivot makes the matrix
+    /// singular.
+    pub fn inverse(&self) -> Result<Matrix, Trap> {
+        let n = self.rows;
+        let mut a = self.elements.clone();
+        let mut inverse = vec![0.0; n * n];
+        for i in 0..n {
+            inverse[i * n + i] = 1.0;
+        }
+        for k in 0..n {
+            let p = pivot_row(&a, n, k);
+            if a[p * n + k] == 0.0 {
+                return Err(Trap::SingularMatrix);
+            }
+            swap_rows(&mut a, n, p, k);
+            swap_rows(&mut inverse, n, p, k);
+            let pivot = a[k * n + k];
+            for j in 0..n {
+                a[k * n + j] /= pivot;
+                inverse[k * n + j] /= pivot;
+            }
+            for i in (0..n).filter(|&i| i != k) {
+                let factor = a[i * n + k];
+                for j in 0..n {
+                    a[i * n + j] -= factor * a[k * n + j];
+                    inverse[i * n + j] -= factor * inverse[k * n + j];
+                }
+            }
+        }
+        Ok(Matrix::new(n, n, inverse))
+    }
+}
+
+// the row from `k` down whose element in column `k` is largest in
+// magnitude, the first of them on a tie
+fn pivot_row(a: &[f64], n: usize, k: usize) -> usize {
+    let mut p = k;
+    for i in k + 1..n {
+        if a[i * n + k].abs() > a[p * n + k].abs() {
+            p = i;
+        }
+    }
+    p
+}
+
+fn swap_rows(a: &mut [f64], n: usize, p: usize, k: usize) {
+    for j in 0..n {
+        a.swap(p * n + j, k * n + j);
+    }
+}
+
+/// One row per line in brackets, each column aligned right to its widest
+/// element:
+///
+/// ```text
+/// [1  -2.5]
+/// [3     4]
+/// ```
+impl fmt::Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cells: Vec<String> = self.elements.iter().map(|&x| format_float(x)).collect();
+        let widths: Vec<usize> = (0..self.cols)
+            .map(|j| (0..self.rows).map(|i| cells[i * self.cols + j].len()).max().unwrap())
+            .collect();
+        for i in 0..self.rows {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "[")?;
+            for (j, width) in widths.iter().enumerate() {
+                if j > 0 {
+                    write!(f, "  ")?;
+                }
+                write!(f, "{:>width$}", cells[i * self.cols + j], width = width)?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::ast::{Signature, Type};
+use crate::interpreter::Value;
+
+/// A Rust type that calc values convert to and from at a native call.
+pub trait NativeType: Sized {
+    const TYPE: Type;
+
+    /// `value` has already been converted to `Self::TYPE`.
+    fn from_value(value: Value) -> Self;
+
+    fn into_value(self) -> Value;
+}
+
+impl NativeType for i64 {
+    const TYPE: Type = Type::Int;
+
+    fn from_value(value: Value) -> Self {
+        match value {
+            Value::Int(i) => i,
+            _ => unreachable!(),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        Value::Int(self)
+    }
+}
+
+impl NativeType for f64 {
+    const TYPE: Type = Type::Float;
+
+    fn from_value(value: Value) -> Self {
+        match value {
+            Value::Float(f) => f,
+            _ => unreachable!(),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl NativeType for bool {
+    const TYPE: Type = Type::Bool;
+
+    fn from_value(value: Value) -> Self {
+        match value {
+            Value::Bool(b) => b,
+            _ => unreachable!(),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+// `Send + Sync` so that an `Engine` can move to another thread
+type Func = Arc<dyn Fn(&[Value]) -> Value + Send + Sync>;
+
+/// A host function callable from calc, together with its signature.
+#[derive(Clone)]
+pub struct NativeFn {
+    pub sig: Signature,
+    func: Func,
+}
+
+impl NativeFn {
+    /// Calls the function with arguments that match `sig`.
+    pub fn call(&self, args: &[Value]) -> Value {
+        (self.func)(args)
+    }
+}
+
+impl fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NativeFn({})", self.sig)
+    }
+}
+
+/// Closures taking and returning `i64`, `f64` or `bool`, with up to six
+/// parameters. `Args` is the tuple of parameter types; it only exists to
+/// tell the implementations apart.
+pub trait IntoNative<Args> {
+    fn into_native(self) -> NativeFn;
+}
+
+macro_rules! impl_into_native {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoNative<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: NativeType,
+            $($arg: NativeType),*
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn into_native(self) -> NativeFn {
+                NativeFn {
+                    sig: Signature {
+                        params: vec![$($arg::TYPE),*],
+                        ret: R::TYPE,
+                    },
+                    func: Arc::new(move |args: &[Value]| {
+                        let mut args = args.iter();
+                        self($($arg::from_value(args.next().unwrap().clone())),*).into_value()
+                    }),
+                }
+            }
+        }
+    };
+}
+
+impl_into_native!();
+impl_into_native!(A);
+impl_into_native!(A, B);
+impl_into_native!(A, B, C);
+impl_into_native!(A, B, C, D);
+impl_into_native!(A, B, C, D, E);
+impl_into_native!(A, B, C, D, E, G);
@@ -1,4 +1,6 @@
 use crate::ast::*;
+use crate::units;
+use num_bigint::BigInt;
 use pest::iterators::Pair;
 use pest::{pratt_parser::PrattParser, Parser};
 
@@ -11,35 +13,83 @@ lazy_static::lazy_static! {
         use pest::pratt_parser::{Assoc::*, Op};
         // Precedence is defined lowest to highest
         PrattParser::new()
-        .op(Op::postfix(Rule::postfix_op))
+        // `1 km + 500 m in mi` converts the sum
+        .op(Op::postfix(Rule::convert_op))
+        .op(Op::infix(Rule::cmp_op, Left))
+        .op(Op::infix(Rule::or_op, Left))
+        .op(Op::infix(Rule::xor_op, Left))
+        .op(Op::infix(Rule::and_op, Left))
+        .op(Op::infix(Rule::shift_op, Left))
         .op(Op::infix(Rule::add_op, Left))
         .op(Op::infix(Rule::mul_op, Left))
+        .op(Op::postfix(Rule::cast_op))
         .op(Op::prefix(Rule::prefix_op))
+        // `-x ** 2` is `-(x ** 2)`, as in mathematics
+        .op(Op::infix(Rule::pow_op, Right))
+        // `2 ** 3!` is `2 ** 6` and `-3!` is `-6`
+        .op(Op::postfix(Rule::postfix_op))
+        // `-a[0]` is `-(a[0])` and `a[0]!` is `(a[0])!`
+        .op(Op::postfix(Rule::index_op))
     };
 }
 
-// grammar = { trans_unit ~ EOI }
-pub fn parse(src: &str) -> Result<TransUnit, pest::error::Error<Rule>> {
+/// Binding power of an infix operator; must agree with `PRATT_PARSER`.
+pub fn infix_precedence(op: &InfixOp) -> u8 {
+    match op {
+        InfixOp::BitOr => 2,
+        InfixOp::BitXor => 3,
+        InfixOp::BitAnd => 4,
+        InfixOp::ShiftLeft | InfixOp::ShiftRight => 5,
+        InfixOp::Plus | InfixOp::Minus => 6,
+        InfixOp::Multiply | InfixOp::Divide | InfixOp::Remainder | InfixOp::MatMul => 7,
+        InfixOp::Power => POWER_PRECEDENCE,
+        _ => 1,
+    }
+}
+
+/// Binding power of `in`, looser than any infix operator.
+pub const CONVERT_PRECEDENCE: u8 = 0;
+
+/// Binding power of `as`, tighter than any infix operator but `**`.
+pub const CAST_PRECEDENCE: u8 = 8;
+
+/// Binding power of the prefix operators.
+pub const PREFIX_PRECEDENCE: u8 = 9;
+
+/// Binding power of `**`, the only right-associative operator.
+pub const POWER_PRECEDENCE: u8 = 10;
+
+/// Binding power of `!` and `%` after an operand.
+pub const POSTFIX_PRECEDENCE: u8 = 11;
+
+/// Binding power of `[index]` after an operand, the tightest of all.
+pub const INDEX_PRECEDENCE: u8 = 12;
+
+/// A syntax error. Boxed, as pest's errors are large.
+pub type Error = Box<pest::error::Error<Rule>>;
+
+// grammar = { SOI ~ trans_unit ~ EOI }
+pub fn parse(src: &str) -> Result<TransUnit, Error> {
     let mut grammar_pairs = CalcParser::parse(Rule::grammar, src)?;
     let tu = parse_grammar(grammar_pairs.next().unwrap())?;
     Ok(tu)
 }
 
-pub fn parse_grammar(pair: Pair<Rule>) -> Result<TransUnit, pest::error::Error<Rule>> {
+pub fn parse_grammar(pair: Pair<Rule>) -> Result<TransUnit, Error> {
     let tu = pair.into_inner().next().unwrap();
     parse_trans_unit(tu)
 }
 
 // trans_unit = { block }
-pub fn parse_trans_unit(pair: Pair<Rule>) -> Result<TransUnit, pest::error::Error<Rule>> {
+pub fn parse_trans_unit(pair: Pair<Rule>) -> Result<TransUnit, Error> {
     let block = pair.into_inner().next().unwrap();
     Ok(TransUnit {
         block: parse_block(block)?,
     })
 }
 
-// block = { "{" ~ stmts ~ "}" }
-fn parse_block(pair: Pair<Rule>) -> Result<Block, pest::error::Error<Rule>> {
+// block = { stmt* }
+fn parse_block(pair: Pair<Rule>) -> Result<Block, Error> {
     let inner = pair.into_inner();
 
     let mut statements = Vec::new();
@@ -49,84 +99,800 @@ fn parse_block(pair: Pair<Rule>) -> Result<Block, pest::error::Error<Rule>> {
     Ok(Block { stmts: statements })
 }
 
-// stmt = { expr_stmt | print_stmt }
-fn parse_statement(pair: Pair<Rule>) -> Result<Stmt, pest::error::Error<Rule>> {
+// stmt = { attribute | block_stmt | assign_stmt | expr_stmt | let_stmt | print_stmt }
+fn parse_statement(pair: Pair<Rule>) -> Result<Stmt, Error> {
     let inner = pair.into_inner().next().unwrap();
     match inner.as_rule() {
+        Rule::attribute => parse_attribute(inner),
+        Rule::block_stmt => parse_block_statement(inner),
+        Rule::assign_stmt => parse_assign_statement(inner),
         Rule::expr_stmt => parse_expr_statement(inner),
+        Rule::let_stmt => parse_let_statement(inner),
         Rule::print_stmt => parse_print_statement(inner),
         _ => unreachable!(),
     }
 }
 
-// expr_stmt = { expr ~ ";" }
-fn parse_expr_statement(pair: Pair<Rule>) -> Result<Stmt, pest::error::Error<Rule>> {
+// attribute = { "#" ~ INNER? ~ "[" ~ LINT_LEVEL ~ "(" ~ IDENT ~ ("," ~ IDENT)* ~ ")" ~ "]" }
+fn parse_attribute(pair: Pair<Rule>) -> Result<Stmt, Error> {
+    let span = to_span(&pair);
+    let mut inner = pair.into_inner().peekable();
+    let is_inner = inner.next_if(|p| p.as_rule() == Rule::INNER).is_some();
+    let level = match inner.next().unwrap().as_str() {
+        "allow" => LintLevel::Allow,
+        "warn" => LintLevel::Warn,
+        "deny" => LintLevel::Deny,
+        _ => unreachable!(),
+    };
+    Ok(Stmt::AttrStmt(AttrStmt {
+        inner: is_inner,
+        level,
+        lints: inner.map(parse_ident).collect(),
+        span,
+    }))
+}
+
+// block_stmt = { "{" ~ block ~ "}" }
+fn parse_block_statement(pair: Pair<Rule>) -> Result<Stmt, Error> {
+    let span = to_span(&pair);
     let inner = pair.into_inner().next().unwrap();
-    let expr = parse_expr(inner)?;
-    Ok(Stmt::ExprStmt(expr))
+    let block = parse_block(inner)?;
+    Ok(Stmt::BlockStmt(BlockStmt { block, span }))
+}
+
+// let_stmt = { "let" ~ IDENT ~ (":" ~ (TYPE | matrix_type | array_type))? ~ "=" ~ expr ~ ";" }
+fn parse_let_statement(pair: Pair<Rule>) -> Result<Stmt, Error> {
+    let span = to_span(&pair);
+    let mut inner = pair.into_inner();
+    let name = parse_ident(inner.next().unwrap());
+    let mut next = inner.next().unwrap();
+    let mut ty = None;
+    if next.as_rule() == Rule::TYPE {
+        ty = Some(parse_type(next));
+        next = inner.next().unwrap();
+    } else if next.as_rule() == Rule::array_type {
+        ty = Some(parse_array_type(next)?);
+        next = inner.next().unwrap();
+    } else if next.as_rule() == Rule::matrix_type {
+        ty = Some(parse_matrix_type(next)?);
+        next = inner.next().unwrap();
+    }
+    let expr = parse_expr(next)?;
+    Ok(Stmt::LetStmt(LetStmt {
+        name,
+        ty,
+        expr,
+        span,
+    }))
+}
+
+// assign_stmt = { IDENT ~ ("[" ~ expr ~ "]")? ~ "=" ~ expr ~ ";" }
+fn parse_assign_statement(pair: Pair<Rule>) -> Result<Stmt, Error> {
+    let span = to_span(&pair);
+    let mut inner = pair.into_inner();
+    let name = parse_ident(inner.next().unwrap());
+    let mut exprs = inner.map(parse_expr).collect::<Result<Vec<_>, _>>()?;
+    let expr = exprs.pop().unwrap();
+    Ok(Stmt::AssignStmt(AssignStmt {
+        name,
+        index: exprs.pop(),
+        expr,
+        span,
+    }))
 }
 
-// print_stmt = { "print" ~ expr ~ ";" }
-fn parse_print_statement(pair: Pair<Rule>) -> Result<Stmt, pest::error::Error<Rule>> {
+// expr_stmt = { expr ~ ";" }
+fn parse_expr_statement(pair: Pair<Rule>) -> Result<Stmt, Error> {
+    let span = to_span(&pair);
     let inner = pair.into_inner().next().unwrap();
     let expr = parse_expr(inner)?;
-    Ok(Stmt::PrintStmt(expr))
+    Ok(Stmt::ExprStmt(ExprStmt { expr, span }))
 }
 
-fn parse_expr(pair: Pair<Rule>) -> Result<Expr, pest::error::Error<Rule>> {
+// print_stmt = { PRINT ~ (format_args | expr) ~ ";" }
+// format_args = { "(" ~ STRING ~ ("," ~ expr)* ~ ")" }
+fn parse_print_statement(pair: Pair<Rule>) -> Result<Stmt, Error> {
+    let span = to_span(&pair);
+    let mut inner = pair.into_inner();
+    let newline = inner.next().unwrap().as_str() == "println";
+    let body = inner.next().unwrap();
+    let (format, args) = match body.as_rule() {
+        Rule::format_args => {
+            let mut inner = body.into_inner();
+            let format = parse_format_string(inner.next().unwrap())?;
+            let args = inner.map(parse_expr).collect::<Result<_, _>>()?;
+            (Some(format), args)
+        }
+        _ => (None, vec![parse_expr(body)?]),
+    };
+    Ok(Stmt::PrintStmt(PrintStmt {
+        newline,
+        format,
+        args,
+        span,
+    }))
+}
+
+// Pest lets implicit whitespace and comments leak into the edges of a rule
+// that starts or ends with a repetition, so trim them off the span.
+fn to_span(pair: &Pair<Rule>) -> Span {
+    let span = pair.as_span();
+    let mut text = span.as_str();
+    loop {
+        let trimmed = text.trim_start();
+        text = match trimmed.strip_prefix("//") {
+            Some(comment) => comment.find('\n').map_or("", |i| &comment[i..]),
+            None => break,
+        };
+    }
+    let start = span.end() - text.trim_start().len();
+    let end = match pair.as_rule() {
+        Rule::expr => pair
+            .clone()
+            .into_inner()
+            .flatten()
+            .map(|p| p.as_span().end())
+            .max()
+            .unwrap_or(span.end()),
+        // the lookahead after the operator may have skipped whitespace
+        Rule::postfix_op => start + 1,
+        _ => span.end(),
+    };
+    Span { start, end }
+}
+
+fn parse_expr(pair: Pair<Rule>) -> Result<Expr, Error> {
     let inner = pair.into_inner();
-    let expr = PRATT_PARSER
-        .map_primary(|x| Expr::Primary(Box::new(parse_primary_expr(x).unwrap())))
+    // literals can be invalid, so every step passes on the first error
+    PRATT_PARSER
+        .map_primary(|x| Ok(Expr::Primary(Box::new(parse_primary_expr(x)?))))
         .map_infix(|lhs, op, rhs| {
-            Expr::Infix(Box::new(InfixExpr {
+            let (lhs, rhs) = (lhs?, rhs?);
+            let span = lhs.span().to(rhs.span());
+            Ok(Expr::Infix(Box::new(InfixExpr {
                 lhs: Box::new(lhs),
-                op: parse_infix(op).unwrap(),
+                op: parse_infix(op)?,
                 rhs: Box::new(rhs),
-            }))
+                span,
+            })))
+        })
+        .map_postfix(|lhs, op| {
+            let lhs = lhs?;
+            let span = lhs.span().to(to_span(&op));
+            Ok(match op.as_rule() {
+                Rule::cast_op => Expr::Cast(Box::new(CastExpr {
+                    expr: Box::new(lhs),
+                    ty: parse_type(op.into_inner().next().unwrap()),
+                    span,
+                })),
+                Rule::convert_op => Expr::Convert(Box::new(ConvertExpr {
+                    expr: Box::new(lhs),
+                    unit: parse_unit(op.into_inner().nth(1).unwrap())?,
+                    span,
+                })),
+                Rule::index_op => Expr::Index(Box::new(IndexExpr {
+                    expr: Box::new(lhs),
+                    index: Box::new(parse_expr(op.into_inner().next().unwrap())?),
+                    span,
+                })),
+                _ => Expr::Postfix(Box::new(PostfixExpr {
+                    expr: Box::new(lhs),
+                    op: parse_postfix(op)?,
+                    span,
+                })),
+            })
         })
         .map_prefix(|op, rhs| {
-            Expr::Prefix(Box::new(PrefixExpr {
-                op: parse_prefix(op).unwrap(),
+            let rhs = rhs?;
+            let span = to_span(&op).to(rhs.span());
+            Ok(Expr::Prefix(Box::new(PrefixExpr {
+                op: parse_prefix(op)?,
                 expr: Box::new(rhs),
-            }))
+                span,
+            })))
         })
-        .parse(inner.into_iter());
-    Ok(expr)
+        .parse(inner.into_iter())
 }
 
-// prefix_op = { "+" | "-" }
-fn parse_prefix(op: Pair<Rule>) -> Result<PrefixOp, pest::error::Error<Rule>> {
+// prefix_op = { "+" | "-" | "!" | "~" }
+fn parse_prefix(op: Pair<Rule>) -> Result<PrefixOp, Error> {
     match op.as_str() {
         "+" => Ok(PrefixOp::Plus),
         "-" => Ok(PrefixOp::Minus),
+        "!" => Ok(PrefixOp::Not),
+        "~" => Ok(PrefixOp::BitNot),
         _ => unreachable!(),
     }
 }
 
-// infix_op = { "+" | "-" | "*" | "/" }
-fn parse_infix(op: Pair<Rule>) -> Result<InfixOp, pest::error::Error<Rule>> {
+// postfix_op = { "!" | "%" }
+fn parse_postfix(op: Pair<Rule>) -> Result<PostfixOp, Error> {
+    match op.as_str().trim_end() {
+        "!" => Ok(PostfixOp::Factorial),
+        "%" => Ok(PostfixOp::Percent),
+        _ => unreachable!(),
+    }
+}
+
+// infix_op = _{ pow_op | shift_op | cmp_op | or_op | xor_op | and_op | mul_op | add_op }
+fn parse_infix(op: Pair<Rule>) -> Result<InfixOp, Error> {
     match op.as_str() {
         "+" => Ok(InfixOp::Plus),
         "-" => Ok(InfixOp::Minus),
         "*" => Ok(InfixOp::Multiply),
         "/" => Ok(InfixOp::Divide),
+        "%" => Ok(InfixOp::Remainder),
+        "@" => Ok(InfixOp::MatMul),
+        "**" => Ok(InfixOp::Power),
+        "&" => Ok(InfixOp::BitAnd),
+        "|" => Ok(InfixOp::BitOr),
+        "^" => Ok(InfixOp::BitXor),
+        "<<" => Ok(InfixOp::ShiftLeft),
+        ">>" => Ok(InfixOp::ShiftRight),
+        "==" => Ok(InfixOp::Equal),
+        "!=" => Ok(InfixOp::NotEqual),
+        "<" => Ok(InfixOp::Less),
+        "<=" => Ok(InfixOp::LessEqual),
+        ">" => Ok(InfixOp::Greater),
+        ">=" => Ok(InfixOp::GreaterEqual),
         _ => unreachable!(),
     }
 }
 
-// primary_expr = { INT | "(" ~ expr ~ ")" }
-fn parse_primary_expr(pair: Pair<Rule>) -> Result<PrimaryExpr, pest::error::Error<Rule>> {
+// primary_expr = { MEM | BOOL | CHAR | STRING | IMAGINARY | DECIMAL | quantity | FLOAT | RATIONAL | INT | call_expr | IDENT | matrix_expr | repeat_expr | array_expr | "(" ~ expr ~ ")" }
+fn parse_primary_expr(pair: Pair<Rule>) -> Result<PrimaryExpr, Error> {
+    let span = to_span(&pair);
     let inner = pair.into_inner().next().unwrap();
     match inner.as_rule() {
-        Rule::MEM => Ok(PrimaryExpr::Mem),
+        Rule::MEM => Ok(PrimaryExpr::Mem(span)),
+        Rule::BOOL => Ok(PrimaryExpr::Bool(inner.as_str() == "true", span)),
+        Rule::CHAR => parse_char(inner),
+        Rule::STRING => parse_string(inner),
+        Rule::IMAGINARY => parse_imaginary(inner),
+        Rule::DECIMAL => parse_decimal(inner),
+        Rule::quantity => parse_quantity(inner),
+        Rule::FLOAT => parse_float(inner),
+        Rule::RATIONAL => parse_rational(inner),
         Rule::INT => parse_int(inner),
-        Rule::expr => Ok(PrimaryExpr::Expr(Box::new(parse_expr(inner)?))),
+        Rule::call_expr => Ok(PrimaryExpr::Call(parse_call_expr(inner)?)),
+        Rule::IDENT => Ok(PrimaryExpr::Ident(parse_ident(inner))),
+        Rule::repeat_expr => {
+            let mut inner = inner.into_inner();
+            let value = parse_expr(inner.next().unwrap())?;
+            let count = parse_expr(inner.next().unwrap())?;
+            Ok(PrimaryExpr::Repeat(Box::new(value), Box::new(count), span))
+        }
+        Rule::array_expr => {
+            let elements = inner.into_inner().map(parse_expr).collect::<Result<_, _>>()?;
+            Ok(PrimaryExpr::Array(elements, span))
+        }
+        Rule::matrix_expr => {
+            let rows = inner.into_inner().map(parse_matrix_row).collect::<Result<_, _>>()?;
+            Ok(PrimaryExpr::Matrix(rows, span))
+        }
+        Rule::expr => Ok(PrimaryExpr::Expr(Box::new(parse_expr(inner)?), span)),
+        _ => unreachable!(),
+    }
+}
+
+// call_expr = { IDENT ~ "(" ~ (expr ~ ("," ~ expr)*)? ~ ")" }
+fn parse_call_expr(pair: Pair<Rule>) -> Result<CallExpr, Error> {
+    let span = to_span(&pair);
+    let mut inner = pair.into_inner();
+    let callee = parse_ident(inner.next().unwrap());
+    let args = inner.map(parse_expr).collect::<Result<_, _>>()?;
+    Ok(CallExpr {
+        callee,
+        args,
+        span,
+        sig: None,
+        builtin: None,
+    })
+}
+
+// INT = @{ "0" ~ ("x" | "o" | "b") ~ IDENT_CHAR* | DIGITS }
+fn parse_int(pair: Pair<Rule>) -> Result<PrimaryExpr, Error> {
+    let span = to_span(&pair);
+    let literal = pair.as_str();
+    let (radix, name, prefix) = match literal.get(..2) {
+        Some("0x") => (Radix::Hex, "hexadecimal", 2),
+        Some("0o") => (Radix::Octal, "octal", 2),
+        Some("0b") => (Radix::Binary, "binary", 2),
+        _ => (Radix::Decimal, "decimal", 0),
+    };
+    if let Some((i, c)) = literal[prefix..]
+        .char_indices()
+        .find(|&(_, c)| c != '_' && !c.is_digit(radix.base()))
+    {
+        let at = prefix + i;
+        return Err(custom_error(
+            format!("invalid digit `{}` in {} literal", c, name),
+            pair.as_span().get(at..at + c.len_utf8()).unwrap(),
+        ));
+    }
+    let digits = literal[prefix..].replace('_', "");
+    if digits.is_empty() {
+        return Err(custom_error(
+            format!("{} literal has no digits", name),
+            pair.as_span(),
+        ));
+    }
+    // `typeck` rejects literals beyond 64 bits unless integers are unbounded
+    match i64::from_str_radix(&digits, radix.base()) {
+        Ok(int) => Ok(PrimaryExpr::Int(int, radix, span)),
+        Err(_) => Ok(PrimaryExpr::BigInt(
+            BigInt::parse_bytes(digits.as_bytes(), radix.base()).unwrap(),
+            radix,
+            span,
+        )),
+    }
+}
+
+// FLOAT = @{ DIGITS ~ ("." ~ DIGITS ~ EXPONENT? | EXPONENT) }
+fn parse_float(pair: Pair<Rule>) -> Result<PrimaryExpr, Error> {
+    let span = to_span(&pair);
+    // too small a float rounds to zero, too large a one is an error
+    match pair.as_str().replace('_', "").parse::<f64>().unwrap() {
+        f if f.is_finite() => Ok(PrimaryExpr::Float(f, span)),
+        _ => Err(custom_error(
+            "float literal is out of range for `float`".to_string(),
+            pair.as_span(),
+        )),
+    }
+}
+
+// IMAGINARY = @{ DIGITS ~ ("." ~ DIGITS)? ~ EXPONENT? ~ "i" ~ !IDENT_CHAR }
+fn parse_imaginary(pair: Pair<Rule>) -> Result<PrimaryExpr, Error> {
+    let span = to_span(&pair);
+    let literal = pair.as_str().trim_end_matches('i').replace('_', "");
+    match literal.parse::<f64>().unwrap() {
+        f if f.is_finite() => Ok(PrimaryExpr::Imaginary(f, span)),
+        _ => Err(custom_error(
+            "imaginary literal is out of range for `complex`".to_string(),
+            pair.as_span(),
+        )),
+    }
+}
+
+// quantity = ${ MAGNITUDE ~ (" " | "\t")+ ~ UNIT }
+fn parse_quantity(pair: Pair<Rule>) -> Result<PrimaryExpr, Error> {
+    let span = to_span(&pair);
+    let pest_span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let magnitude = inner.next().unwrap().as_str().replace('_', "").parse::<f64>().unwrap();
+    let unit = parse_unit(inner.next().unwrap())?;
+    // the value in base units has to be a float too
+    match magnitude * unit.scale() {
+        f if f.is_finite() => Ok(PrimaryExpr::Quantity(magnitude, unit, span)),
+        _ => Err(custom_error(
+            "quantity is out of range for `float`".to_string(),
+            pest_span,
+        )),
+    }
+}
+
+// UNIT = ${ UNIT_FACTOR ~ (UNIT_OP ~ UNIT_FACTOR)* }
+fn parse_unit(pair: Pair<Rule>) -> Result<Unit, Error> {
+    let span = to_span(&pair);
+    let mut factors = vec![];
+    let mut sign = 1;
+    for part in pair.into_inner() {
+        match part.as_rule() {
+            Rule::UNIT_OP => sign = if part.as_str() == "/" { -1 } else { 1 },
+            _ => factors.push(parse_unit_factor(part, sign)?),
+        }
+    }
+    Ok(Unit { factors, span })
+}
+
+// UNIT_FACTOR = ${ UNIT_NAME ~ ("^" ~ UNIT_POWER)? }
+fn parse_unit_factor(pair: Pair<Rule>, sign: i32) -> Result<UnitFactor, Error> {
+    let span = to_span(&pair);
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap();
+    if units::lookup(name.as_str()).is_none() {
+        return Err(custom_error(
+            format!("unknown unit `{}`", name.as_str()),
+            name.as_span(),
+        ));
+    }
+    let power = match inner.next() {
+        Some(power) => power
+            .as_str()
+            .parse::<i32>()
+            .ok()
+            .and_then(|power| power.checked_mul(sign))
+            .ok_or_else(|| custom_error("unit power is out of range".to_string(), power.as_span()))?,
+        None => sign,
+    };
+    Ok(UnitFactor {
+        name: name.as_str().to_string(),
+        power,
+        span,
+    })
+}
+
+// DECIMAL = @{ DIGITS ~ ("." ~ DIGITS)? ~ "d" ~ !IDENT_CHAR }
+fn parse_decimal(pair: Pair<Rule>) -> Result<PrimaryExpr, Error> {
+    let span = to_span(&pair);
+    let literal = pair.as_str().trim_end_matches('d').replace('_', "");
+    let (int, fraction) = literal.split_once('.').unwrap_or((&literal, ""));
+    match format!("{}{}", int, fraction).parse::<i64>() {
+        Ok(digits) => Ok(PrimaryExpr::Decimal(digits, fraction.len() as u32, span)),
+        Err(_) => Err(custom_error(
+            "decimal literal is out of range for `dec`".to_string(),
+            pair.as_span(),
+        )),
+    }
+}
+
+// RATIONAL = @{ DIGITS ~ "r" ~ !IDENT_CHAR }
+fn parse_rational(pair: Pair<Rule>) -> Result<PrimaryExpr, Error> {
+    let span = to_span(&pair);
+    let digits = pair.as_str().trim_end_matches('r').replace('_', "");
+    match digits.parse::<i64>() {
+        Ok(int) => Ok(PrimaryExpr::Rational(int, span)),
+        Err(_) => Err(custom_error(
+            "rational literal is out of range for `rat`".to_string(),
+            pair.as_span(),
+        )),
+    }
+}
+
+// CHAR = @{ "'" ~ ("\\" ~ ANY | !("'" | NEWLINE) ~ ANY)* ~ "'" }
+fn parse_char(pair: Pair<Rule>) -> Result<PrimaryExpr, Error> {
+    let span = to_span(&pair);
+    match unescape(&pair)?[..] {
+        [(_, c)] => Ok(PrimaryExpr::Char(c, span)),
+        [] => Err(custom_error(
+            "empty character literal".to_string(),
+            pair.as_span(),
+        )),
+        _ => Err(custom_error(
+            "character literal holds more than one character".to_string(),
+            pair.as_span(),
+        )),
+    }
+}
+
+// STRING = @{ "\"" ~ ("\\" ~ ANY | !("\"" | NEWLINE) ~ ANY)* ~ "\"" }
+fn parse_string(pair: Pair<Rule>) -> Result<PrimaryExpr, Error> {
+    let span = to_span(&pair);
+    let chars = unescape(&pair)?;
+    // compiled programs keep text NUL-terminated
+    if let Some(i) = chars.iter().position(|&(_, c)| c == '\0') {
+        let at = chars[i].0;
+        let end = chars.get(i + 1).map_or(pair.as_str().len() - 1, |&(at, _)| at);
+        return Err(custom_error(
+            format!("a `str` cannot hold `{}`", &pair.as_str()[at..end]),
+            pair.as_span().get(at..end).unwrap(),
+        ));
+    }
+    Ok(PrimaryExpr::Str(chars.into_iter().map(|(_, c)| c).collect(), span))
+}
+
+// STRING = @{ "\"" ~ ("\\" ~ ANY | !("\"" | NEWLINE) ~ ANY)* ~ "\"" }
+fn parse_format_string(pair: Pair<Rule>) -> Result<FormatString, Error> {
+    let chars = unescape(&pair)?;
+    // where the character at `i` ends, relative to the literal
+    let end_of = |i: usize| chars.get(i + 1).map_or(pair.as_str().len() - 1, |&(at, _)| at);
+    let error = |message: &str, from: usize, to: usize| {
+        custom_error(message.to_string(), pair.as_span().get(from..to).unwrap())
+    };
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (at, c) = chars[i];
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        match c {
+            '{' | '}' if next == Some(c) => {
+                text.push(c);
+                i += 2;
+            }
+            '}' => {
+                return Err(error(
+                    "unmatched `}` in format string, `}}` prints one",
+                    at,
+                    end_of(i),
+                ))
+            }
+            '{' => {
+                let close = match chars[i..].iter().position(|&(_, c)| c == '}') {
+                    Some(n) => i + n,
+                    None => {
+                        return Err(error(
+                            "unclosed `{` in format string, `{{` prints one",
+                            at,
+                            end_of(i),
+                        ))
+                    }
+                };
+                let start = pair.as_span().start();
+                let span = Span {
+                    start: start + at,
+                    end: start + end_of(close),
+                };
+                let spec = parse_format_spec(&chars[i + 1..close], span)
+                    .map_err(|(message, j)| error(&message, chars[j + i + 1].0, end_of(j + i + 1)))?;
+                if !text.is_empty() {
+                    pieces.push(FormatPiece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(FormatPiece::Spec(spec));
+                i = close + 1;
+            }
+            _ => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(FormatPiece::Text(text));
+    }
+    Ok(FormatString {
+        pieces,
+        span: to_span(&pair),
+    })
+}
+
+// `spec` is what the braces hold; an error has the index of the character
+// at fault
+fn parse_format_spec(spec: &[(usize, char)], span: Span) -> Result<FormatSpec, (String, usize)> {
+    let mut format = FormatSpec {
+        fill: ' ',
+        align: None,
+        zero: false,
+        width: None,
+        precision: None,
+        radix: Radix::Decimal,
+        upper: false,
+        polar: false,
+        span,
+    };
+    let chars: Vec<char> = spec.iter().map(|&(_, c)| c).collect();
+    if chars.is_empty() {
+        return Ok(format);
+    }
+    if chars[0] != ':' {
+        return Err(("expected `:` or `}` in placeholder".to_string(), 0));
+    }
+    let align = |c: Option<&char>| match c {
+        Some('<') => Some(Align::Left),
+        Some('^') => Some(Align::Center),
+        Some('>') => Some(Align::Right),
+        _ => None,
+    };
+    let mut i = 1;
+    if let Some(a) = align(chars.get(i + 1)) {
+        format.fill = chars[i];
+        format.align = Some(a);
+        i += 2;
+    } else if let Some(a) = align(chars.get(i)) {
+        format.align = Some(a);
+        i += 1;
+    }
+    if chars.get(i) == Some(&'0') {
+        format.zero = true;
+        i += 1;
+    }
+    // a run of digits, and where it ends
+    let number = |from: usize| {
+        let len = chars[from..].iter().take_while(|c| c.is_ascii_digit()).count();
+        let digits: String = chars[from..from + len].iter().collect();
+        (digits.parse::<u32>().ok(), from + len)
+    };
+    if chars.get(i).is_some_and(char::is_ascii_digit) {
+        let (width, end) = number(i);
+        format.width = Some(width.ok_or(("width is too large".to_string(), i))?);
+        i = end;
+    }
+    if chars.get(i) == Some(&'.') {
+        let (precision, end) = number(i + 1);
+        if end == i + 1 {
+            return Err(("expected a number of places after `.`".to_string(), i));
+        }
+        match precision.filter(|&places| places <= MAX_PRECISION) {
+            Some(places) => format.precision = Some(places),
+            None => {
+                let message = format!("precision can be at most {} places", MAX_PRECISION);
+                return Err((message, i + 1));
+            }
+        }
+        i = end;
+    }
+    match chars.get(i) {
+        Some('x') => format.radix = Radix::Hex,
+        Some('X') => (format.radix, format.upper) = (Radix::Hex, true),
+        Some('o') => format.radix = Radix::Octal,
+        Some('b') => format.radix = Radix::Binary,
+        Some('p') => format.polar = true,
+        Some(_) => {}
+        None => return Ok(format),
+    }
+    match chars.get(i) {
+        Some('x' | 'X' | 'o' | 'b' | 'p') if i + 1 == chars.len() => Ok(format),
+        Some('x' | 'X' | 'o' | 'b' | 'p') => Err(("expected `}` after the format".to_string(), i + 1)),
+        Some(c) => Err((
+            format!("unknown format `{}`, expected `x`, `X`, `o`, `b` or `p`", c),
+            i,
+        )),
+        None => unreachable!(),
+    }
+}
+
+/// The characters between the quotes of `pair`, with escapes replaced, and
+/// where each starts in the literal.
+fn unescape(pair: &Pair<Rule>) -> Result<Vec<(usize, char)>, Error> {
+    let literal = pair.as_str();
+    let body = &literal[1..literal.len() - 1];
+    let mut chars = Vec::new();
+    let mut rest = body;
+    while let Some(c) = rest.chars().next() {
+        let at = 1 + body.len() - rest.len();
+        if c != '\\' {
+            chars.push((at, c));
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        // the grammar never ends a literal on a backslash
+        let escape = rest[1..].chars().next().unwrap();
+        let mut len = 1 + escape.len_utf8();
+        let unescaped = match escape {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' | '\'' | '"' => Some(escape),
+            // `\u{1F600}`, with one to six hex digits
+            'u' => {
+                let hex = rest[2..]
+                    .strip_prefix('{')
+                    .and_then(|hex| hex.split_once('}'))
+                    .map(|(hex, _)| hex);
+                len = hex.map_or(len, |hex| hex.len() + 4);
+                hex.filter(|hex| (1..=6).contains(&hex.len()))
+                    .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+                    .and_then(|hex| char::from_u32(u32::from_str_radix(hex, 16).unwrap()))
+            }
+            _ => None,
+        };
+        match unescaped {
+            Some(c) => chars.push((at, c)),
+            None => {
+                let kind = match literal.starts_with('"') {
+                    true => "string",
+                    false => "character",
+                };
+                return Err(custom_error(
+                    format!("invalid escape `{}` in {} literal", &rest[..len], kind),
+                    pair.as_span().get(at..at + len).unwrap(),
+                ));
+            }
+        }
+        rest = &rest[len..];
+    }
+    Ok(chars)
+}
+
+fn custom_error(message: String, span: pest::Span) -> Error {
+    Box::new(pest::error::Error::new_from_span(
+        pest::error::ErrorVariant::CustomError { message },
+        span,
+    ))
+}
+
+// IDENT = @{ !KEYWORD ~ (ASCII_ALPHA | "_") ~ IDENT_CHAR* }
+fn parse_ident(pair: Pair<Rule>) -> Ident {
+    Ident {
+        name: pair.as_str().to_string(),
+        span: to_span(&pair),
+        decl: None,
+    }
+}
+
+// matrix_row = { "[" ~ (expr ~ ("," ~ expr)*)? ~ "]" }
+fn parse_matrix_row(pair: Pair<Rule>) -> Result<MatrixRow, Error> {
+    let span = to_span(&pair);
+    let elements = pair.into_inner().map(parse_expr).collect::<Result<_, _>>()?;
+    Ok(MatrixRow { elements, span })
+}
+
+// array_type = { "[" ~ TYPE ~ (";" ~ INT)? ~ "]" }
+fn parse_array_type(pair: Pair<Rule>) -> Result<Type, Error> {
+    let mut inner = pair.into_inner();
+    let element = inner.next().unwrap();
+    if element.as_str() != "int" {
+        return Err(custom_error(
+            format!("arrays hold `int`s, not `{}`s", element.as_str()),
+            element.as_span(),
+        ));
+    }
+    let Some(len) = inner.next() else {
+        return Ok(Type::Array(None));
+    };
+    let span = len.as_span();
+    match parse_int(len)? {
+        PrimaryExpr::Int(len, _, _) if len <= MAX_ARRAY_LEN => Ok(Type::Array(Some(len as u32))),
+        _ => Err(custom_error(
+            format!("array length can be at most {}", MAX_ARRAY_LEN),
+            span,
+        )),
+    }
+}
+
+// matrix_type = { "[" ~ "[" ~ TYPE ~ ";" ~ INT ~ "]" ~ ";" ~ INT ~ "]" }
+fn parse_matrix_type(pair: Pair<Rule>) -> Result<Type, Error> {
+    let mut inner = pair.into_inner();
+    let element = inner.next().unwrap();
+    if element.as_str() != "float" {
+        return Err(custom_error(
+            format!("matrices hold `float`s, not `{}`s", element.as_str()),
+            element.as_span(),
+        ));
+    }
+    let cols = parse_matrix_len(inner.next().unwrap())?;
+    let rows = parse_matrix_len(inner.next().unwrap())?;
+    Ok(Type::Matrix(rows, cols))
+}
+
+fn parse_matrix_len(pair: Pair<Rule>) -> Result<u32, Error> {
+    let span = pair.as_span();
+    match parse_int(pair)? {
+        PrimaryExpr::Int(len, _, _) if (1..=MAX_ARRAY_LEN).contains(&len) => Ok(len as u32),
+        _ => Err(custom_error(
+            format!("a matrix has between 1 and {} rows and columns", MAX_ARRAY_LEN),
+            span,
+        )),
+    }
+}
+
+// TYPE = @{ ("int" | "float" | "bool" | "rat" | "dec" | "complex" | "str") ~ !IDENT_CHAR }
+fn parse_type(pair: Pair<Rule>) -> Type {
+    match pair.as_str() {
+        "int" => Type::Int,
+        "float" => Type::Float,
+        "bool" => Type::Bool,
+        "rat" => Type::Rational,
+        "dec" => Type::Decimal,
+        "complex" => Type::Complex,
+        "str" => Type::Str,
         _ => unreachable!(),
     }
 }
 
-// INT = { ASCII_DIGIT+ }
-fn parse_int(pair: Pair<Rule>) -> Result<PrimaryExpr, pest::error::Error<Rule>> {
-    let int = pair.as_str().parse::<i64>().unwrap();
-    Ok(PrimaryExpr::Int(int))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_stmt(src: &str) -> Stmt {
+        let tu = parse(src).unwrap_or_else(|err| panic!("{:?} failed to parse:\n{}", src, err));
+        tu.block.stmts.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn leading_whitespace_before_first_statement() {
+        assert!(matches!(first_stmt("\nprint 1;"), Stmt::PrintStmt(_)));
+        assert!(matches!(first_stmt("  \t let x = 1;"), Stmt::LetStmt(_)));
+        assert!(matches!(first_stmt("\n\n{ 1; }"), Stmt::BlockStmt(_)));
+    }
+
+    #[test]
+    fn leading_comments_before_first_statement() {
+        assert!(matches!(first_stmt("// compute\nprint 1;"), Stmt::PrintStmt(_)));
+        assert!(matches!(first_stmt("// a\n// b\n#[allow(unused_mem)] 1;"), Stmt::AttrStmt(_)));
+        assert!(matches!(first_stmt("// a\nx = 1;"), Stmt::AssignStmt(_)));
+    }
+
+    #[test]
+    fn blank_and_comment_only_programs() {
+        assert!(parse("").unwrap().block.stmts.is_empty());
+        assert!(parse("  \n// nothing here\n").unwrap().block.stmts.is_empty());
+    }
+
+    #[test]
+    fn spans_skip_leading_whitespace() {
+        let stmt = first_stmt("\n  print 1;");
+        assert_eq!(stmt.span().start, 3);
+    }
 }
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use crate::ast::{LintLevel, Signature, TransUnit, Type};
+use crate::codegen::LlvmEmitter;
+use crate::diagnostic::{self, Diagnostic, Diagnostics};
+use crate::interpreter::{interpret, Output};
+use crate::ir::{Context, IrGenerator};
+use crate::irprint::IrPrinter;
+use crate::lint::{self, Lint, LintConfig};
+use crate::runtime::{Numbers, Overflow, Precision, RuntimeError};
+use crate::{parser, sema, typeck, units};
+
+/// How a `Program` is checked and compiled.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    overflow: Overflow,
+    numbers: Numbers,
+    precision: Precision,
+    lints: LintConfig,
+    functions: HashMap<String, Signature>,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_overflow(&mut self, overflow: Overflow) -> &mut Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// With `Numbers::Big`, the LLVM module calls the `calc_big_*`
+    /// functions of `runtime/bigint.c`, so it has to be linked against that
+    /// too.
+    pub fn set_numbers(&mut self, numbers: Numbers) -> &mut Self {
+        self.numbers = numbers;
+        self
+    }
+
+    /// The scale and rounding of decimals, and how `print` shows
+    /// rationals.
+    pub fn set_precision(&mut self, precision: Precision) -> &mut Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Allows, warns about or denies the lint called `name`, such as
+    /// `unused_mem`. Lints that are denied make compilation fail.
+    pub fn set_lint(&mut self, name: &str, level: LintLevel) -> Result<&mut Self, String> {
+        let lint = Lint::from_name(name).ok_or_else(|| format!("unknown lint `{}`", name))?;
+        self.lints.set(lint, level);
+        Ok(self)
+    }
+
+    /// Lets programs call a host function, declared like
+    /// `clamp(int, int, int) -> int`. It is only declared, so the LLVM
+    /// module has to be linked against a runtime that defines it.
+    pub fn declare_function(&mut self, decl: &str) -> Result<&mut Self, String> {
+        let (name, sig) = parse_extern(decl).ok_or_else(|| format!("invalid function signature `{}`", decl))?;
+        self.functions.insert(name, sig);
+        Ok(self)
+    }
+}
+
+// parses a declaration such as `clamp(int, int, int) -> int`
+fn parse_extern(decl: &str) -> Option<(String, Signature)> {
+    let (name, rest) = decl.split_once('(')?;
+    let (params, ret) = rest.split_once(')')?;
+    let name = name.trim();
+    let mut chars = name.chars();
+    if !chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+    let params = match params.trim() {
+        "" => vec![],
+        params => params
+            .split(',')
+            .map(|param| host_type(param.trim()))
+            .collect::<Option<_>>()?,
+    };
+    let ret = host_type(ret.trim().strip_prefix("->")?.trim())?;
+    Some((name.to_string(), Signature { params, ret }))
+}
+
+// host functions exchange plain C scalars, which `rat` and `dec` are not
+fn host_type(name: &str) -> Option<Type> {
+    Type::from_name(name).filter(|ty| matches!(ty, Type::Int | Type::Float | Type::Bool))
+}
+
+/// A program that passed every check, ready to run or to be compiled.
+#[derive(Debug, Clone)]
+pub struct Program {
+    tu: TransUnit,
+    src: String,
+    options: Options,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Program {
+    /// Parses a program and checks its names, types, units and lints. The
+    /// error holds the warnings as well as the errors, in source order.
+    pub fn compile(src: &str, options: Options) -> Result<Program, Diagnostics> {
+        let tu = parser::parse(src)?;
+        Self::check(tu, src, &[0], options)
+    }
+
+    /// Compiles inputs that ran one after another in an `Engine`, such as
+    /// the lines of a REPL session, as one program. As in the engine, a
+    /// top-level `let` may replace a variable of an earlier input. The
+    /// inputs are joined with a newline after each that lacks one, which is
+    /// what spans point into.
+    pub fn compile_inputs(inputs: &[impl AsRef<str>], options: Options) -> Result<Program, Diagnostics> {
+        let mut src = String::new();
+        let mut starts = vec![];
+        for input in inputs {
+            starts.push(src.len());
+            src.push_str(input.as_ref());
+            if !src.ends_with('\n') {
+                src.push('\n');
+            }
+        }
+        let tu = parser::parse(&src)?;
+        Self::check(tu, &src, &starts, options)
+    }
+
+    /// Like `compile`, but starts from an AST serialized with `to_json`.
+    /// There is no source to quote, so its diagnostics are best shown with
+    /// `Display`.
+    pub fn from_json(json: &str, options: Options) -> Result<Program, Diagnostics> {
+        Self::from_unit(TransUnit::from_json(json)?, options)
+    }
+
+    /// Like `compile`, but starts from a syntax tree, such as one
+    /// `calcc::parse` returned and a `VisitorMut` then rewrote. Its
+    /// diagnostics are best shown with `Display` as well.
+    pub fn from_unit(tu: TransUnit, options: Options) -> Result<Program, Diagnostics> {
+        Self::check(tu, "", &[0], options)
+    }
+
+    // `starts` are the offsets of the inputs `src` is made of
+    fn check(mut tu: TransUnit, src: &str, starts: &[usize], options: Options) -> Result<Program, Diagnostics> {
+        let mut diagnostics = sema::analyze_inputs(&mut tu, starts, &options.functions);
+        if !diagnostic::has_errors(&diagnostics) {
+            diagnostics.extend(typeck::check(&tu, options.numbers));
+        }
+        if !diagnostic::has_errors(&diagnostics) {
+            diagnostics.extend(units::check(&tu));
+        }
+        if !diagnostic::has_errors(&diagnostics) {
+            diagnostics.extend(lint::check(&tu, &options.lints));
+        }
+        if diagnostic::has_errors(&diagnostics) {
+            return Err(Diagnostics(diagnostics));
+        }
+        Ok(Program {
+            tu,
+            src: src.to_string(),
+            options,
+            warnings: diagnostics,
+        })
+    }
+
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// Whether the LLVM module calls the `calc_big_*` functions of
+    /// `runtime/bigint.c`.
+    pub fn needs_bigint_runtime(&self) -> bool {
+        self.options.numbers == Numbers::Big
+    }
+
+    /// Interprets the program, sending what `print` prints to `out`. Stops
+    /// at the first runtime error.
+    ///
+    /// Calling a host function is a `Trap::UndefinedFunction` error: only a
+    /// linked runtime defines those. An `Engine` can call closures instead.
+    pub fn run(&self, out: &mut dyn Output) -> Result<(), RuntimeError> {
+        let Options { overflow, numbers, precision, .. } = self.options;
+        interpret(&self.tu, overflow, numbers, precision, out)
+    }
+
+    /// The checked syntax tree as an S-expression.
+    pub fn to_sexpr(&self) -> String {
+        self.tu.to_sexpr()
+    }
+
+    /// The checked syntax tree as JSON, which `from_json` reads back.
+    pub fn to_json(&self) -> String {
+        self.tu.to_json()
+    }
+
+    /// The compiler's own IR, as text.
+    pub fn to_ir(&self) -> String {
+        self.lower().print_ir()
+    }
+
+    /// A textual LLVM module whose `main` runs the program.
+    pub fn to_llvm(&self) -> String {
+        self.lower().emit_ir()
+    }
+
+    fn lower(&self) -> Context {
+        let mut context = Context::new();
+        context.overflow = self.options.overflow;
+        context.numbers = self.options.numbers;
+        context.precision = self.options.precision;
+        context.source = self.src.clone();
+        self.tu.to_ir(&mut context);
+        context
+    }
+}
@@ -0,0 +1,195 @@
+//! Exact fractions, the values of `rat`. Numerator and denominator are 64
+//! bits each, and every operation computes in 128 bits before reducing, so
+//! results overflow only if their lowest terms do not fit. The `calc_rat_*`
+//! functions in the prelude of `codegen` compute the same for compiled
+//! programs.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::ast::InfixOp;
+use crate::interpreter::Value;
+use crate::runtime::Trap;
+
+/// The most decimal places a rational prints with.
+pub const MAX_DIGITS: u32 = 18;
+
+/// A fraction in lowest terms with a positive denominator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rational {
+    num: i64,
+    den: i64,
+}
+
+impl Rational {
+    /// `num / den` in lowest terms. Overflows if that does not fit in 64
+    /// bits.
+    pub fn new(num: i128, den: i128) -> Result<Rational, Trap> {
+        if den == 0 {
+            return Err(Trap::DivisionByZero);
+        }
+        let (mut x, mut y) = (num.unsigned_abs(), den.unsigned_abs());
+        while y != 0 {
+            (x, y) = (y, x % y);
+        }
+        // dividing by a negative gcd makes the denominator positive
+        let gcd = if den < 0 { -(x as i128) } else { x as i128 };
+        match (i64::try_from(num / gcd), i64::try_from(den / gcd)) {
+            (Ok(num), Ok(den)) => Ok(Rational { num, den }),
+            _ => Err(Trap::RationalOverflow),
+        }
+    }
+
+    pub fn from_int(i: i64) -> Rational {
+        Rational { num: i, den: 1 }
+    }
+
+    /// The exact value of a float, which is a fraction whose denominator is
+    /// a power of two. NaN, infinities and values whose terms do not fit
+    /// overflow.
+    pub fn from_float(f: f64) -> Result<Rational, Trap> {
+        let bits = f.to_bits();
+        let exp = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = bits & ((1 << 52) - 1);
+        if exp == 0x7ff {
+            return Err(Trap::RationalOverflow);
+        }
+        let (mantissa, exp) = match exp {
+            0 => (fraction, -1074),
+            _ => (fraction | 1 << 52, exp - 1075),
+        };
+        let sign = if f.is_sign_negative() { -1 } else { 1 };
+        if mantissa == 0 {
+            return Ok(Rational::from_int(0));
+        }
+        if exp >= 0 {
+            if exp > 63 {
+                return Err(Trap::RationalOverflow);
+            }
+            return Rational::new(sign * ((mantissa as i128) << exp), 1);
+        }
+        let shift = (mantissa.trailing_zeros() as i64).min(-exp);
+        let den_bits = -exp - shift;
+        if den_bits > 62 {
+            return Err(Trap::RationalOverflow);
+        }
+        Rational::new(sign * (mantissa >> shift) as i128, 1 << den_bits)
+    }
+
+    pub fn num(self) -> i64 {
+        self.num
+    }
+
+    pub fn den(self) -> i64 {
+        self.den
+    }
+
+    /// Divides the terms as floats, which is what compiled programs do.
+    pub fn to_float(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// Truncates towards zero, like `as int` does with floats.
+    pub fn to_int(self) -> i64 {
+        self.num / self.den
+    }
+
+    pub fn checked_add(self, rhs: Rational) -> Result<Rational, Trap> {
+        let (a, b, c, d) = self.widen(rhs);
+        Rational::new(a * d + c * b, b * d)
+    }
+
+    pub fn checked_sub(self, rhs: Rational) -> Result<Rational, Trap> {
+        let (a, b, c, d) = self.widen(rhs);
+        Rational::new(a * d - c * b, b * d)
+    }
+
+    pub fn checked_mul(self, rhs: Rational) -> Result<Rational, Trap> {
+        let (a, b, c, d) = self.widen(rhs);
+        Rational::new(a * c, b * d)
+    }
+
+    pub fn checked_div(self, rhs: Rational) -> Result<Rational, Trap> {
+        let (a, b, c, d) = self.widen(rhs);
+        Rational::new(a * d, b * c)
+    }
+
+    pub fn checked_neg(self) -> Result<Rational, Trap> {
+        Rational::new(-(self.num as i128), self.den as i128)
+    }
+
+    fn widen(self, rhs: Rational) -> (i128, i128, i128, i128) {
+        (
+            self.num as i128,
+            self.den as i128,
+            rhs.num as i128,
+            rhs.den as i128,
+        )
+    }
+
+    /// The value rounded half away from zero to `digits` decimal places,
+    /// which `calc_rat_print` prints the same way.
+    pub fn format(self, digits: u32) -> String {
+        let scale = 10u128.pow(digits);
+        let den = self.den as u128;
+        let rounded = (self.num.unsigned_abs() as u128 * scale * 2 + den) / (den * 2);
+        // no sign if it rounds to zero
+        let sign = if self.num < 0 && rounded != 0 {
+            "-"
+        } else {
+            ""
+        };
+        let (int, fraction) = (rounded / scale, rounded % scale);
+        match digits {
+            0 => format!("{}{}", sign, int),
+            _ => format!(
+                "{}{}.{:0width$}",
+                sign,
+                int,
+                fraction,
+                width = digits as usize
+            ),
+        }
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b, c, d) = self.widen(*other);
+        (a * d).cmp(&(c * b))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// `7/2`, or just `3` for whole numbers.
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.den {
+            1 => write!(f, "{}", self.num),
+            den => write!(f, "{}/{}", self.num, den),
+        }
+    }
+}
+
+/// `+ - * /` and the comparisons. Overflow always traps: a rational has no
+/// sensible value to wrap or saturate to.
+pub fn eval_infix(op: InfixOp, lhs: Rational, rhs: Rational) -> Result<Value, Trap> {
+    Ok(match op {
+        InfixOp::Plus => Value::Rational(lhs.checked_add(rhs)?),
+        InfixOp::Minus => Value::Rational(lhs.checked_sub(rhs)?),
+        InfixOp::Multiply => Value::Rational(lhs.checked_mul(rhs)?),
+        InfixOp::Divide => Value::Rational(lhs.checked_div(rhs)?),
+        InfixOp::Equal => Value::Bool(lhs == rhs),
+        InfixOp::NotEqual => Value::Bool(lhs != rhs),
+        InfixOp::Less => Value::Bool(lhs < rhs),
+        InfixOp::LessEqual => Value::Bool(lhs <= rhs),
+        InfixOp::Greater => Value::Bool(lhs > rhs),
+        InfixOp::GreaterEqual => Value::Bool(lhs >= rhs),
+        _ => unreachable!(),
+    })
+}
@@ -0,0 +1,298 @@
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use calcc::{syntax_tree, Engine, Numbers, Options, Program};
+
+const HELP: &str = "\
+Enter statements to run them; the value of an expression statement is
+printed. The trailing `;` may be left out.
+
+  :ast [SRC]   show the syntax tree of SRC, or of the last input
+  :ir          show the IR of this session
+  :llvm        show the LLVM IR of this session
+  :load FILE   run a script in this session
+  :reset       forget `mem` and all variables
+  :help        show this text
+  :quit        leave (as does Ctrl-D)";
+
+/// A session: everything that was entered and accepted so far.
+pub struct Repl {
+    engine: Engine,
+    numbers: Numbers,
+    // the accepted inputs, in order, for `:ir` and `:llvm`
+    history: Vec<String>,
+    last: Option<String>,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self::with_numbers(Numbers::default())
+    }
+
+    pub fn with_numbers(numbers: Numbers) -> Self {
+        let mut engine = Engine::new();
+        engine.set_numbers(numbers);
+        Self {
+            engine,
+            numbers,
+            history: vec![],
+            last: None,
+        }
+    }
+
+    /// Checks and runs one input. With `echo`, the value of every top-level
+    /// expression statement is printed. An input with errors, including
+    /// runtime errors, leaves the session as it was.
+    pub fn eval(&mut self, src: &str, echo: bool) {
+        let result = self.engine.eval_each(src, |mem| {
+            if echo {
+                println!("{}", mem);
+            }
+        });
+        match result {
+            Ok(()) => {
+                for d in self.engine.warnings() {
+                    eprintln!("{}", d.render(src));
+                }
+                self.history.push(src.to_string());
+                self.last = Some(src.to_string());
+            }
+            Err(err) => eprintln!("{}", err.render(src)),
+        }
+    }
+
+    /// Runs a `:command`. Returns `false` for `:quit`.
+    pub fn command(&mut self, line: &str) -> bool {
+        let (name, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+        match name {
+            ":ast" if arg.is_empty() => match &self.last {
+                Some(src) => show_syntax_tree(src),
+                None => eprintln!("nothing entered yet"),
+            },
+            ":ast" => show_syntax_tree(&with_semicolon(arg)),
+            ":ir" => {
+                if let Some(program) = self.compile() {
+                    print!("{}", program.to_ir());
+                }
+            }
+            ":llvm" => {
+                if let Some(program) = self.compile() {
+                    print!("{}", program.to_llvm());
+                }
+            }
+            ":load" => match std::fs::read_to_string(arg) {
+                Ok(src) => self.eval(&src, false),
+                Err(err) => eprintln!("calc: {}: {}", arg, err),
+            },
+            ":reset" => *self = Repl::with_numbers(self.numbers),
+            ":help" => println!("{}", HELP),
+            ":quit" | ":q" => return false,
+            _ => eprintln!("unknown command `{}`; try :help", name),
+        }
+        true
+    }
+
+    // the whole session as one program
+    fn compile(&self) -> Option<Program> {
+        let mut options = Options::new();
+        options.set_numbers(self.numbers);
+        match Program::compile_inputs(&self.history, options) {
+            Ok(program) => Some(program),
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        }
+    }
+}
+
+fn show_syntax_tree(src: &str) {
+    match syntax_tree(src) {
+        Ok(tree) => println!("{}", tree),
+        Err(err) => eprintln!("{}", err.render(src)),
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads inputs from the terminal until end of file. Returns the exit code.
+pub fn run(numbers: Numbers) -> i32 {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            eprintln!("calc: {}", err);
+            return 2;
+        }
+    };
+    let mut repl = Repl::with_numbers(numbers);
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() { "calc> " } else { "...   " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if input.is_empty() && line.trim_start().starts_with(':') {
+                    let _ = editor.add_history_entry(line.as_str());
+                    if !repl.command(line.trim()) {
+                        return 0;
+                    }
+                    continue;
+                }
+                input.push_str(&line);
+                input.push('\n');
+                // keep reading while a block is open
+                if open_braces(&input) > 0 {
+                    continue;
+                }
+                let _ = editor.add_history_entry(input.trim_end());
+                if !input.trim().is_empty() {
+                    repl.eval(&with_semicolon(&input), true);
+                }
+                input.clear();
+            }
+            // Ctrl-C abandons the current input
+            Err(ReadlineError::Interrupted) => input.clear(),
+            Err(ReadlineError::Eof) => return 0,
+            Err(err) => {
+                eprintln!("calc: {}", err);
+                return 2;
+            }
+        }
+    }
+}
+
+fn open_braces(src: &str) -> i32 {
+    code_only(src)
+        .chars()
+        .map(|c| match c {
+            '{' => 1,
+            '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+// `src` with comments and the insides of string and char literals blanked
+// out, so braces in them are not counted; byte offsets are kept
+fn code_only(src: &str) -> String {
+    let blank = |c: char| " ".repeat(c.len_utf8());
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                out.push(c);
+                while let Some(next) = chars.next_if(|&next| next != '\n') {
+                    if next == c {
+                        out.push(c);
+                        break;
+                    }
+                    out.push_str(&blank(next));
+                    if next == '\\' {
+                        if let Some(escaped) = chars.next_if(|&next| next != '\n') {
+                            out.push_str(&blank(escaped));
+                        }
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                out.push(' ');
+                while let Some(next) = chars.next_if(|&next| next != '\n') {
+                    out.push_str(&blank(next));
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// lets the last statement of an input go without its `;`
+fn with_semicolon(src: &str) -> String {
+    let code = code_only(src);
+    let code = code.trim_end();
+    if code.is_empty() || code.ends_with(';') || code.ends_with('}') {
+        src.to_string()
+    } else {
+        // before a trailing comment, if there is one
+        format!("{};{}", &src[..code.len()], &src[code.len()..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braces_in_literals_and_comments_are_not_counted() {
+        assert_eq!(open_braces("println(\"{{\");\n"), 0);
+        assert_eq!(open_braces("print '{';\n"), 0);
+        assert_eq!(open_braces("{ print \"}\\\"}\"; // }\n"), 1);
+        assert_eq!(open_braces("{\n}\n"), 0);
+    }
+
+    // the value of `expr` in the session, or the error it gives
+    fn value(repl: &mut Repl, expr: &str) -> String {
+        match repl.engine.eval(&format!("{};", expr)) {
+            Ok(value) => value.to_string(),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn later_inputs_replace_top_level_variables() {
+        let mut repl = Repl::new();
+        repl.eval("let x = 3;", false);
+        repl.eval("let x = x + 1;", false);
+        assert_eq!(value(&mut repl, "x"), "4");
+        repl.eval("let x = \"four\";", false);
+        assert_eq!(value(&mut repl, "x"), "four");
+        assert_eq!(repl.history.len(), 3);
+        // the whole session still compiles as one program
+        assert!(repl.compile().is_some());
+        // within one input it is still an error
+        repl.eval("let y = 1; let y = 2;", false);
+        assert_eq!(repl.history.len(), 3);
+        assert_eq!(value(&mut repl, "y"), "error: undefined variable `y`");
+    }
+
+    #[test]
+    fn failed_inputs_roll_back() {
+        let mut repl = Repl::new();
+        repl.eval("let y = 1;", false);
+        repl.eval("y = 2; let z = 0; print 1 / z;", false);
+        assert_eq!(value(&mut repl, "y"), "1");
+        assert_eq!(value(&mut repl, "z"), "error: undefined variable `z`");
+        assert_eq!(repl.history, ["let y = 1;"]);
+        assert_eq!(repl.last.as_deref(), Some("let y = 1;"));
+    }
+
+    #[test]
+    fn reset_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("script.calc");
+        std::fs::write(&script, "let w = 5;\nw * 2;\n").unwrap();
+        let mut repl = Repl::new();
+        repl.eval("let v = 1;", false);
+        assert!(repl.command(&format!(":load {}", script.display())));
+        assert_eq!(value(&mut repl, "w + v"), "6");
+        assert_eq!(repl.history.len(), 2);
+        assert!(repl.command(":reset"));
+        assert!(repl.history.is_empty());
+        assert_eq!(value(&mut repl, "v"), "error: undefined variable `v`");
+        assert!(!repl.command(":quit"));
+    }
+
+    #[test]
+    fn semicolon_goes_after_the_code() {
+        assert_eq!(with_semicolon("print \"a//b\"\n"), "print \"a//b\";\n");
+        assert_eq!(with_semicolon("1 + 2 // sum\n"), "1 + 2; // sum\n");
+        assert_eq!(with_semicolon("print ';'\n"), "print ';';\n");
+        assert_eq!(with_semicolon("mem;\n"), "mem;\n");
+        assert_eq!(with_semicolon("// only a comment\n"), "// only a comment\n");
+    }
+}
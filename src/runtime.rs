@@ -0,0 +1,201 @@
+use crate::ast::Span;
+
+/// Exit status of a program stopped by a runtime error, in both execution
+/// modes.
+pub const EXIT_TRAP: i32 = 3;
+
+/// What integer arithmetic does when the result does not fit in an `int`.
+/// Division by zero has no sensible result and always traps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Wrap around in two's complement.
+    Wrap,
+    /// Stop the program with a runtime error.
+    #[default]
+    Trap,
+    /// Clamp to the nearest representable value.
+    Saturate,
+}
+
+impl Overflow {
+    pub fn from_name(name: &str) -> Option<Overflow> {
+        match name {
+            "wrap" => Some(Overflow::Wrap),
+            "trap" => Some(Overflow::Trap),
+            "saturate" => Some(Overflow::Saturate),
+            _ => None,
+        }
+    }
+}
+
+/// How `int` values are represented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Numbers {
+    /// 64 bits, with `Overflow` deciding what happens to results out of
+    /// range.
+    #[default]
+    Fixed,
+    /// Arbitrary precision, so only results too large to hold in memory
+    /// overflow. Compiled programs call into the runtime in
+    /// `runtime/bigint.c`.
+    Big,
+    /// 64 bits like `Fixed`, but dividing two `int`s gives the exact `rat`.
+    Rational,
+}
+
+impl Numbers {
+    pub fn from_name(name: &str) -> Option<Numbers> {
+        match name {
+            "fixed" => Some(Numbers::Fixed),
+            "big" => Some(Numbers::Big),
+            "rational" => Some(Numbers::Rational),
+            _ => None,
+        }
+    }
+}
+
+/// How `dec` results with more decimal places than the scale are rounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// To the nearest, and ties to the neighbor whose last digit is even.
+    #[default]
+    HalfEven,
+    /// To the nearest, and ties away from zero.
+    HalfUp,
+    /// Towards zero.
+    Truncate,
+}
+
+impl Rounding {
+    pub fn from_name(name: &str) -> Option<Rounding> {
+        match name {
+            "half-even" => Some(Rounding::HalfEven),
+            "half-up" => Some(Rounding::HalfUp),
+            "truncate" => Some(Rounding::Truncate),
+            _ => None,
+        }
+    }
+}
+
+/// How exact numbers are kept and printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precision {
+    /// Rationals print as fractions like `7/2`, or with `Some(n)` rounded
+    /// half away from zero to `n` decimal places, at most
+    /// `MAX_RATIONAL_DIGITS`.
+    pub rational_digits: Option<u32>,
+    /// The number of decimal places every `dec` has, at most
+    /// `MAX_DECIMAL_SCALE`.
+    pub decimal_scale: u32,
+    pub rounding: Rounding,
+}
+
+impl Precision {
+    pub const MAX_RATIONAL_DIGITS: u32 = crate::rational::MAX_DIGITS;
+    pub const MAX_DECIMAL_SCALE: u32 = crate::decimal::MAX_SCALE;
+}
+
+impl Default for Precision {
+    /// Rationals as fractions, and decimals with the cents of money.
+    fn default() -> Self {
+        Self {
+            rational_digits: None,
+            decimal_scale: 2,
+            rounding: Rounding::default(),
+        }
+    }
+}
+
+/// Why a program stopped at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trap {
+    DivisionByZero,
+    Overflow,
+    /// A shift by a negative amount or by 64 bits or more.
+    InvalidShift,
+    NegativeFactorial,
+    /// A `rat` whose terms do not fit in 64 bits, or a float that is not a
+    /// number or too large or small to convert to one.
+    RationalOverflow,
+    /// A `dec` out of the range its scale leaves, or a conversion to one
+    /// from a float that is not a number.
+    DecimalOverflow,
+    /// A `substr` that reaches outside its text, or an index outside its
+    /// array.
+    OutOfBounds,
+    /// `parse_int` of text that is not an optional sign and decimal
+    /// digits, or out of the range of `int`.
+    InvalidInteger,
+    /// `[value; count]` with a negative count, or one beyond
+    /// `ast::MAX_ARRAY_LEN`.
+    ArrayLength,
+    /// `inv` of a matrix without an inverse.
+    SingularMatrix,
+    /// A call to a host function that was declared with
+    /// `Options::declare_function` but that nothing defines, which only
+    /// happens when interpreting a `Program`.
+    UndefinedFunction,
+}
+
+impl Trap {
+    pub const ALL: [Trap; 11] = [
+        Trap::DivisionByZero,
+        Trap::Overflow,
+        Trap::InvalidShift,
+        Trap::NegativeFactorial,
+        Trap::RationalOverflow,
+        Trap::DecimalOverflow,
+        Trap::OutOfBounds,
+        Trap::InvalidInteger,
+        Trap::ArrayLength,
+        Trap::SingularMatrix,
+        Trap::UndefinedFunction,
+    ];
+
+    pub fn message(self) -> &'static str {
+        match self {
+            Trap::DivisionByZero => "division by zero",
+            Trap::Overflow => "integer overflow",
+            Trap::InvalidShift => "shift amount out of range",
+            Trap::NegativeFactorial => "factorial of a negative number",
+            Trap::RationalOverflow => "rational overflow",
+            Trap::DecimalOverflow => "decimal overflow",
+            Trap::OutOfBounds => "index out of bounds",
+            Trap::InvalidInteger => "invalid integer",
+            Trap::ArrayLength => "array length out of range",
+            Trap::SingularMatrix => "singular matrix",
+            Trap::UndefinedFunction => "host function without a definition",
+        }
+    }
+
+    /// The error for this trap in the expression at `span`.
+    pub fn at(self, span: Span) -> RuntimeError {
+        RuntimeError { trap: self, span }
+    }
+}
+
+/// A trap together with the expression that caused it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeError {
+    pub trap: Trap,
+    pub span: Span,
+}
+
+impl RuntimeError {
+    /// Both execution modes print runtime errors in this form, which is the
+    /// head of a rendered `Diagnostic`; compiled programs have no source
+    /// text to quote.
+    pub fn render(&self, src: &str) -> String {
+        let (line, col) = line_col(src, self.span.start);
+        format!("error: {}\n --> {}:{}", self.trap.message(), line, col)
+    }
+}
+
+/// The 1-based line and column of a byte offset into `src`.
+pub fn line_col(src: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(src.len());
+    let line_start = src[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = src[..line_start].matches('\n').count() + 1;
+    let col = src[line_start..offset].chars().count() + 1;
+    (line, col)
+}
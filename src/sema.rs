@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::builtins::{self, Builtin};
+use crate::diagnostic::{Diagnostic, Level};
+
+/// A variable declared by a `let`.
+#[derive(Debug, Clone)]
+pub struct Decl {
+    pub name: String,
+    pub span: Span,
+}
+
+/// Every declaration seen so far, indexed by `DeclId`.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    decls: Vec<Decl>,
+}
+
+impl SymbolTable {
+    pub fn get(&self, id: DeclId) -> &Decl {
+        &self.decls[id]
+    }
+
+    fn push(&mut self, decl: Decl) -> DeclId {
+        self.decls.push(decl);
+        self.decls.len() - 1
+    }
+
+    fn len(&self) -> usize {
+        self.decls.len()
+    }
+}
+
+/// Runs name resolution over a whole program that may call the given host
+/// functions.
+pub fn analyze_with_functions(tu: &mut TransUnit, functions: &HashMap<String, Signature>) -> Vec<Diagnostic> {
+    let mut resolver = Resolver::new();
+    for (name, sig) in functions {
+        resolver.declare_function(name, sig.clone());
+    }
+    resolver.resolve(tu)
+}
+
+/// Like `analyze_with_functions`, for a program made of inputs that a
+/// `Resolver` was fed one at a time, starting at the byte offsets `starts`:
+/// a top-level `let` may replace a variable of an earlier input.
+pub fn analyze_inputs(
+    tu: &mut TransUnit,
+    starts: &[usize],
+    functions: &HashMap<String, Signature>,
+) -> Vec<Diagnostic> {
+    let mut resolver = Resolver::new();
+    for (name, sig) in functions {
+        resolver.declare_function(name, sig.clone());
+    }
+    let mut diagnostics = vec![];
+    let mut stmts = std::mem::take(&mut tu.block.stmts).into_iter().peekable();
+    while let Some(first) = stmts.next() {
+        let input = starts.partition_point(|&start| start <= first.span().start);
+        let mut block = Block { stmts: vec![first] };
+        let same_input = |stmt: &Stmt| starts.partition_point(|&start| start <= stmt.span().start) == input;
+        while let Some(stmt) = stmts.next_if(same_input) {
+            block.stmts.push(stmt);
+        }
+        let mut input = TransUnit { block };
+        diagnostics.extend(resolver.resolve(&mut input));
+        tu.block.stmts.append(&mut input.block.stmts);
+    }
+    diagnostics
+}
+
+/// Resolves every name to its declaration and records the result in
+/// `Ident::decl`, and every call to a builtin or to the signature of a
+/// function declared with `declare_function`. Names of constants that no
+/// variable hides are replaced by their values.
+///
+/// The outermost scope outlives a single `resolve` call, so inputs can be
+/// fed one at a time and still see each other's variables. A top-level
+/// `let` replaces a variable of an earlier input instead of clashing with
+/// it.
+#[derive(Clone)]
+pub struct Resolver {
+    symbols: SymbolTable,
+    // the first declaration of the input being resolved; earlier ones are
+    // in sources that are gone, so notes do not point at them
+    first_decl: DeclId,
+    // innermost scope last
+    scopes: Vec<HashMap<String, DeclId>>,
+    functions: HashMap<String, Signature>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            symbols: SymbolTable::default(),
+            first_decl: 0,
+            scopes: vec![HashMap::new()],
+            functions: HashMap::new(),
+            diagnostics: vec![],
+        }
+    }
+
+    pub fn resolve(&mut self, tu: &mut TransUnit) -> Vec<Diagnostic> {
+        self.first_decl = self.symbols.len();
+        self.visit_trans_unit_mut(tu);
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Makes `name` callable. Functions live in their own namespace, so a
+    /// variable of the same name does not hide them, but they hide a builtin
+    /// of the same name.
+    pub fn declare_function(&mut self, name: &str, sig: Signature) {
+        self.functions.insert(name.to_string(), sig);
+    }
+
+    fn declare(&mut self, ident: &mut Ident) {
+        let clash = match self.scopes.last().unwrap().get(&ident.name) {
+            // a top-level variable of an earlier input, which this one replaces
+            Some(&earlier) if self.scopes.len() == 1 && earlier < self.first_decl => None,
+            Some(&earlier) => {
+                let message = format!("`{}` is already declared in this scope", ident.name);
+                Some((Diagnostic::error(message, ident.span), earlier))
+            }
+            None => self.find(&ident.name).map(|earlier| {
+                let message = format!("`{}` shadows a variable from an outer scope", ident.name);
+                (Diagnostic::warning(message, ident.span), earlier)
+            }),
+        };
+        if let Some((mut diagnostic, earlier)) = clash {
+            if earlier >= self.first_decl {
+                let decl = self.symbols.get(earlier);
+                let note = match diagnostic.level {
+                    Level::Error => format!("`{}` is first declared here", decl.name),
+                    Level::Warning => format!("the outer `{}` is declared here", decl.name),
+                };
+                diagnostic = diagnostic.with_note(note, decl.span);
+            }
+            self.diagnostics.push(diagnostic);
+        }
+        let id = self.symbols.push(Decl {
+            name: ident.name.clone(),
+            span: ident.span,
+        });
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(ident.name.clone(), id);
+        ident.decl = Some(id);
+    }
+
+    fn find(&self, name: &str) -> Option<DeclId> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VisitorMut for Resolver {
+    fn visit_block_stmt_mut(&mut self, stmt: &mut BlockStmt) {
+        self.scopes.push(HashMap::new());
+        walk_block_stmt_mut(self, stmt);
+        self.scopes.pop();
+    }
+
+    fn visit_let_stmt_mut(&mut self, stmt: &mut LetStmt) {
+        self.visit_expr_mut(&mut stmt.expr);
+        self.declare(&mut stmt.name);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Primary(primary) = expr {
+            if let PrimaryExpr::Ident(ident) = primary.as_ref() {
+                if let (None, Some(value)) = (self.find(&ident.name), builtins::constant(&ident.name)) {
+                    **primary = PrimaryExpr::Float(value, ident.span);
+                    return;
+                }
+            }
+        }
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_call_expr_mut(&mut self, expr: &mut CallExpr) {
+        walk_call_expr_mut(self, expr);
+        let name = &expr.callee.name;
+        let arity = if let Some(sig) = self.functions.get(name) {
+            expr.sig = Some(sig.clone());
+            sig.params.len()
+        } else if let Some(builtin) = Builtin::from_name(name) {
+            expr.builtin = Some(builtin);
+            builtin.arity()
+        } else {
+            self.diagnostics.push(Diagnostic::error(
+                format!("undefined function `{}`", name),
+                expr.callee.span,
+            ));
+            return;
+        };
+        if arity != expr.args.len() {
+            self.diagnostics.push(Diagnostic::error(
+                format!(
+                    "`{}` takes {} argument{} but {} {} supplied",
+                    name,
+                    arity,
+                    if arity == 1 { "" } else { "s" },
+                    expr.args.len(),
+                    if expr.args.len() == 1 { "was" } else { "were" }
+                ),
+                expr.span,
+            ));
+            expr.sig = None;
+            expr.builtin = None;
+        }
+    }
+
+    fn visit_ident_mut(&mut self, ident: &mut Ident) {
+        ident.decl = self.find(&ident.name);
+        if ident.decl.is_none() {
+            self.diagnostics.push(Diagnostic::error(
+                format!("undefined variable `{}`", ident.name),
+                ident.span,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(resolver: &mut Resolver, src: &str) -> Vec<Diagnostic> {
+        let mut tu = crate::parser::parse(src).unwrap();
+        resolver.resolve(&mut tu)
+    }
+
+    #[test]
+    fn clashes_point_at_the_earlier_declaration() {
+        let src = "let x = 1;\nlet x = 2;\n{ let x = 3; }";
+        let diagnostics = resolve(&mut Resolver::new(), src);
+        let rendered: Vec<String> = diagnostics.iter().map(|d| d.render(src)).collect();
+        assert_eq!(
+            rendered,
+            [
+                "error: `x` is already declared in this scope\n --> 2:5\n  |\n2 | let x = 2;\n  |     ^\n\
+                 note: `x` is first declared here\n --> 1:5\n  |\n1 | let x = 1;\n  |     ^",
+                "warning: `x` shadows a variable from an outer scope\n --> 3:7\n  |\n3 | { let x = 3; }\n  |       ^\n\
+                 note: the outer `x` is declared here\n --> 2:5\n  |\n2 | let x = 2;\n  |     ^",
+            ]
+        );
+    }
+
+    #[test]
+    fn notes_stay_within_one_input() {
+        let mut resolver = Resolver::new();
+        assert!(resolve(&mut resolver, "let x = 1;").is_empty());
+        let diagnostics = resolve(&mut resolver, "{ let x = 2; }");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].notes.is_empty());
+        assert_eq!(resolver.symbols.get(1).name, "x");
+    }
+}
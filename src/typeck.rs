@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::formatter::format_int_literal;
+use crate::runtime::Numbers;
+
+/// Type-checks a program whose names have been resolved by `sema`.
+pub fn check(tu: &TransUnit, numbers: Numbers) -> Vec<Diagnostic> {
+    let mut checker = TypeChecker::new();
+    checker.set_numbers(numbers);
+    checker.check(tu)
+}
+
+/// Whether a value of type `from` may be used where `to` is expected
+/// without an `as` conversion. Numbers widen implicitly: `int` to `rat`,
+/// `dec` and `float`, `rat` to `float`, and all but `dec` to `complex`. An
+/// array of any length is an array whose length is only known at run time.
+pub fn coerces(from: Type, to: Type) -> bool {
+    from == to
+        || matches!(
+            (from, to),
+            (Type::Int, Type::Float | Type::Rational | Type::Decimal)
+                | (Type::Rational, Type::Float)
+                | (Type::Int | Type::Rational | Type::Float, Type::Complex)
+                | (Type::Array(_), Type::Array(None))
+        )
+}
+
+/// Whether numbers of types `lhs` and `rhs` may meet in an operator.
+/// Decimals are exact to the cent, so they only mix with `int`s.
+fn mixes(lhs: Type, rhs: Type) -> bool {
+    (lhs == Type::Decimal) == (rhs == Type::Decimal) || lhs == Type::Int || rhs == Type::Int
+}
+
+/// Infers the type of every expression and reports operations on operands
+/// they do not apply to.
+///
+/// `mem` has no fixed type: since scripts run straight through, it always
+/// has the type of the expression statement executed last. Like
+/// `sema::Resolver`, the checker can be fed one input at a time.
+#[derive(Clone)]
+pub struct TypeChecker {
+    vars: HashMap<DeclId, Type>,
+    mem: Type,
+    // type of the expression visited last, `None` once it had an error
+    ty: Option<Type>,
+    // type of the operand of the percentage visited last
+    percent_of: Option<Type>,
+    diagnostics: Vec<Diagnostic>,
+    numbers: Numbers,
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        Self {
+            vars: HashMap::new(),
+            mem: Type::Int,
+            ty: None,
+            percent_of: None,
+            diagnostics: vec![],
+            numbers: Numbers::default(),
+        }
+    }
+
+    /// Integer literals beyond 64 bits are only allowed with `Numbers::Big`,
+    /// which in turn has no `rat`. With `Numbers::Rational`, `int / int` is
+    /// a `rat`.
+    pub fn set_numbers(&mut self, numbers: Numbers) {
+        self.numbers = numbers;
+    }
+
+    pub fn check(&mut self, tu: &TransUnit) -> Vec<Diagnostic> {
+        self.visit_trans_unit(tu);
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    pub fn type_of(&mut self, expr: &Expr) -> Option<Type> {
+        self.visit_expr(expr);
+        self.ty.take()
+    }
+
+    fn type_of_call(&mut self, call: &CallExpr) -> Option<Type> {
+        let args: Vec<_> = call.args.iter().map(|arg| self.type_of(arg)).collect();
+        if let Some(builtin) = call.builtin {
+            let args: Vec<Type> = args.into_iter().collect::<Option<_>>()?;
+            let sig = builtin.signature(&args);
+            if sig.is_none() {
+                let args: Vec<String> = args.iter().map(|ty| format!("`{}`", ty)).collect();
+                self.diagnostics.push(Diagnostic::error(
+                    format!("cannot apply `{}` to {}", builtin.name(), args.join(" and ")),
+                    call.span,
+                ));
+            }
+            return sig.map(|sig| sig.ret);
+        }
+        let sig = call.sig.as_ref()?;
+        for ((arg, found), &expected) in call.args.iter().zip(args).zip(&sig.params) {
+            self.expect(expected, found, arg.span());
+        }
+        Some(sig.ret)
+    }
+
+    // `rat`, `dec` and arrays are made of 64-bit numbers, which do not mix
+    // with unbounded `int`s
+    fn allow_type(&mut self, ty: Type, span: Span) -> bool {
+        if matches!(ty, Type::Rational | Type::Decimal | Type::Array(_)) && self.numbers == Numbers::Big {
+            self.diagnostics.push(Diagnostic::error(
+                format!("`{}` is not available with arbitrary-precision integers", ty),
+                span,
+            ));
+            return false;
+        }
+        true
+    }
+
+    // the type of `lhs op rhs` for the arithmetic operators: the wider of
+    // the two operands, and `rat`, `dec` and `complex` only have `+ - * /`
+    fn arithmetic_type(&self, op: InfixOp, lhs: Type, rhs: Type) -> Option<Type> {
+        let exact = matches!(
+            op,
+            InfixOp::Plus | InfixOp::Minus | InfixOp::Multiply | InfixOp::Divide
+        );
+        if lhs == Type::Complex || rhs == Type::Complex {
+            exact.then_some(Type::Complex)
+        } else if lhs == Type::Decimal || rhs == Type::Decimal {
+            exact.then_some(Type::Decimal)
+        } else if lhs == Type::Float || rhs == Type::Float {
+            Some(Type::Float)
+        } else if lhs == Type::Rational || rhs == Type::Rational {
+            exact.then_some(Type::Rational)
+        } else if op == InfixOp::Divide && self.numbers == Numbers::Rational {
+            Some(Type::Rational)
+        } else {
+            Some(Type::Int)
+        }
+    }
+
+    // what a placeholder asks of the value it formats
+    fn check_spec(&mut self, spec: &FormatSpec, ty: Type) {
+        let radix = match spec.radix {
+            Radix::Decimal => None,
+            Radix::Hex => Some("hex"),
+            Radix::Octal => Some("octal"),
+            Radix::Binary => Some("binary"),
+        };
+        let message = if let Some(radix) = radix.filter(|_| ty != Type::Int) {
+            format!("{} formatting needs an `int`, found `{}`", radix, ty)
+        } else if let Some(radix) = radix.filter(|_| self.numbers == Numbers::Big) {
+            format!("{} formatting is not available with arbitrary-precision integers", radix)
+        } else if spec.precision.is_some() && !matches!(ty, Type::Float | Type::Complex) {
+            format!("a precision needs a `float` or a `complex`, found `{}`", ty)
+        } else if spec.polar && ty != Type::Complex {
+            format!("polar formatting needs a `complex`, found `{}`", ty)
+        } else if spec.zero && !ty.is_numeric() {
+            format!("zero padding needs a number, found `{}`", ty)
+        } else if spec.width.is_some() && ty.is_matrix() {
+            format!("a width cannot pad the rows of `{}`", ty)
+        } else {
+            return;
+        };
+        self.diagnostics.push(Diagnostic::error(message, spec.span));
+    }
+
+    // the type of `lhs @ rhs`, for which `lhs` has as many columns as `rhs`
+    // has rows
+    fn product_type(&mut self, lhs: Type, rhs: Type, span: Span) -> Option<Type> {
+        let (Type::Matrix(rows, inner), Type::Matrix(rhs_rows, cols)) = (lhs, rhs) else {
+            unreachable!()
+        };
+        if inner != rhs_rows {
+            self.diagnostics.push(Diagnostic::error(
+                format!(
+                    "cannot multiply `{}` by `{}`: the columns of the first must be as many as the rows of the second",
+                    lhs, rhs
+                ),
+                span,
+            ));
+            return None;
+        }
+        Some(Type::Matrix(rows, cols))
+    }
+
+    // the type of `array[index]`; an index that is a literal must be inside
+    // an array whose length is known
+    fn element_type(&mut self, array: Type, index: &Expr, index_ty: Option<Type>, span: Span) -> Option<Type> {
+        let Type::Array(len) = array else {
+            self.diagnostics.push(Diagnostic::error(
+                format!("cannot index into a value of type `{}`", array),
+                span,
+            ));
+            return None;
+        };
+        self.expect(Type::Int, index_ty, index.span());
+        if let (Some(len), Expr::Primary(primary)) = (len, index) {
+            if let PrimaryExpr::Int(i, _, span) = primary.as_ref() {
+                if *i >= len as i64 {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("index {} is out of bounds for `{}`", i, array),
+                        *span,
+                    ));
+                }
+            }
+        }
+        Some(Type::Int)
+    }
+
+    fn expect(&mut self, expected: Type, found: Option<Type>, span: Span) {
+        if let Some(found) = found {
+            if !coerces(found, expected) {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "mismatched types: expected `{}`, found `{}`",
+                        expected, found
+                    ),
+                    span,
+                ));
+            }
+        }
+    }
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Visitor for TypeChecker {
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt) {
+        if let Some(ty) = self.type_of(&stmt.expr) {
+            self.mem = ty;
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) {
+        let types: Vec<_> = stmt.args.iter().map(|arg| self.type_of(arg)).collect();
+        let Some(format) = &stmt.format else {
+            return;
+        };
+        let specs: Vec<&FormatSpec> = format.specs().collect();
+        for spec in specs.iter().skip(stmt.args.len()) {
+            self.diagnostics.push(Diagnostic::error(
+                "placeholder has no argument to format",
+                spec.span,
+            ));
+        }
+        for arg in stmt.args.iter().skip(specs.len()) {
+            self.diagnostics.push(Diagnostic::error(
+                "argument has no placeholder in the format string",
+                arg.span(),
+            ));
+        }
+        for (spec, ty) in specs.into_iter().zip(types) {
+            if let Some(ty) = ty {
+                self.check_spec(spec, ty);
+            }
+        }
+    }
+
+    fn visit_let_stmt(&mut self, stmt: &LetStmt) {
+        let found = self.type_of(&stmt.expr);
+        if let Some(ty) = stmt.ty {
+            if !self.allow_type(ty, stmt.span) {
+                return;
+            }
+            self.expect(ty, found, stmt.expr.span());
+        }
+        if let (Some(decl), Some(ty)) = (stmt.name.decl, stmt.ty.or(found)) {
+            self.vars.insert(decl, ty);
+        }
+    }
+
+    fn visit_assign_stmt(&mut self, stmt: &AssignStmt) {
+        let var = stmt.name.decl.and_then(|decl| self.vars.get(&decl).copied());
+        let ty = match &stmt.index {
+            Some(index) => {
+                let index_ty = self.type_of(index);
+                var.and_then(|var| self.element_type(var, index, index_ty, stmt.name.span))
+            }
+            None => var,
+        };
+        let found = self.type_of(&stmt.expr);
+        if let Some(ty) = ty {
+            self.expect(ty, found, stmt.expr.span());
+        }
+    }
+
+    fn visit_primary_expr(&mut self, expr: &PrimaryExpr) {
+        self.ty = match expr {
+            PrimaryExpr::Mem(_) => Some(self.mem),
+            PrimaryExpr::Int(..) | PrimaryExpr::Char(..) => Some(Type::Int),
+            PrimaryExpr::BigInt(_, radix, span) if self.numbers == Numbers::Fixed => {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "integer literal is out of range for `int`, whose largest value is {}",
+                        format_int_literal(&i64::MAX, *radix)
+                    ),
+                    *span,
+                ));
+                None
+            }
+            PrimaryExpr::BigInt(..) => Some(Type::Int),
+            PrimaryExpr::Rational(_, span) => self
+                .allow_type(Type::Rational, *span)
+                .then_some(Type::Rational),
+            PrimaryExpr::Decimal(_, _, span) => self
+                .allow_type(Type::Decimal, *span)
+                .then_some(Type::Decimal),
+            PrimaryExpr::Float(..) | PrimaryExpr::Quantity(..) => Some(Type::Float),
+            PrimaryExpr::Imaginary(..) => Some(Type::Complex),
+            PrimaryExpr::Bool(..) => Some(Type::Bool),
+            PrimaryExpr::Str(..) => Some(Type::Str),
+            PrimaryExpr::Array(elements, span) => {
+                for element in elements {
+                    let found = self.type_of(element);
+                    self.expect(Type::Int, found, element.span());
+                }
+                let ty = Type::Array(Some(elements.len() as u32));
+                self.allow_type(ty, *span).then_some(ty)
+            }
+            PrimaryExpr::Repeat(value, count, span) => {
+                let found = self.type_of(value);
+                self.expect(Type::Int, found, value.span());
+                let found = self.type_of(count);
+                self.expect(Type::Int, found, count.span());
+                // a literal count is known, anything else is only at run time
+                let len = match count.as_ref() {
+                    Expr::Primary(primary) => match primary.as_ref() {
+                        &PrimaryExpr::Int(len, _, _) if len <= MAX_ARRAY_LEN => Some(len as u32),
+                        PrimaryExpr::Int(_, _, span) | PrimaryExpr::BigInt(_, _, span) => {
+                            self.diagnostics.push(Diagnostic::error(
+                                format!("array length can be at most {}", MAX_ARRAY_LEN),
+                                *span,
+                            ));
+                            return;
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let ty = Type::Array(len);
+                self.allow_type(ty, *span).then_some(ty)
+            }
+            PrimaryExpr::Matrix(rows, _) => {
+                let cols = rows[0].elements.len();
+                let mut shaped = true;
+                for row in rows {
+                    for element in &row.elements {
+                        let found = self.type_of(element);
+                        self.expect(Type::Float, found, element.span());
+                    }
+                    let message = if row.elements.is_empty() {
+                        "a matrix row needs at least one element".to_string()
+                    } else if cols > 0 && row.elements.len() != cols {
+                        format!(
+                            "matrix rows must have the same length, but this one has {} and the first {}",
+                            row.elements.len(),
+                            cols
+                        )
+                    } else {
+                        continue;
+                    };
+                    self.diagnostics.push(Diagnostic::error(message, row.span));
+                    shaped = false;
+                }
+                shaped.then_some(Type::Matrix(rows.len() as u32, cols as u32))
+            }
+            PrimaryExpr::Ident(ident) => ident.decl.and_then(|decl| self.vars.get(&decl).copied()),
+            PrimaryExpr::Call(call) => self.type_of_call(call),
+            PrimaryExpr::Expr(e, _) => self.type_of(e),
+        };
+    }
+
+    fn visit_prefix_expr(&mut self, expr: &PrefixExpr) {
+        let operand = self.type_of(&expr.expr);
+        self.ty = operand.and_then(|ty| {
+            let ok = match expr.op {
+                PrefixOp::Plus | PrefixOp::Minus => ty.is_numeric(),
+                PrefixOp::Not => ty == Type::Bool,
+                PrefixOp::BitNot => ty == Type::Int,
+            };
+            if !ok {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("cannot apply unary `{}` to `{}`", expr.op.symbol(), ty),
+                    expr.span,
+                ));
+                return None;
+            }
+            Some(ty)
+        });
+    }
+
+    fn visit_postfix_expr(&mut self, expr: &PostfixExpr) {
+        let operand = self.type_of(&expr.expr);
+        if expr.op == PostfixOp::Percent {
+            self.percent_of = operand;
+        }
+        self.ty = operand.and_then(|ty| {
+            let result = match expr.op {
+                PostfixOp::Factorial if ty == Type::Int => Some(Type::Int),
+                // `x / 100`, except that an `int` gives a `float` unless
+                // it would give a `rat`
+                PostfixOp::Percent if ty == Type::Int => match self.arithmetic_type(InfixOp::Divide, ty, ty) {
+                    Some(Type::Int) => Some(Type::Float),
+                    result => result,
+                },
+                PostfixOp::Percent if ty.is_numeric() && ty != Type::Complex => Some(ty),
+                _ => None,
+            };
+            if result.is_none() {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("cannot apply postfix `{}` to `{}`", expr.op.symbol(), ty),
+                    expr.span,
+                ));
+            }
+            result
+        });
+    }
+
+    fn visit_infix_expr(&mut self, expr: &InfixExpr) {
+        let lhs = self.type_of(&expr.lhs);
+        let lhs_percent_of = self.percent_of.take();
+        let rhs = self.type_of(&expr.rhs);
+        let rhs_percent_of = self.percent_of.take();
+        let (Some(mut lhs), Some(mut rhs)) = (lhs, rhs) else {
+            return;
+        };
+        // the `float` percentage of an `int` has two decimal places, so it
+        // converts to a `dec` it meets exactly, as in `19.99d * 15%`
+        let int_percent = |expr: &Expr, percent_of| {
+            matches!(expr, Expr::Postfix(postfix) if postfix.op == PostfixOp::Percent) && percent_of == Some(Type::Int)
+        };
+        if rhs == Type::Decimal && lhs == Type::Float && int_percent(&expr.lhs, lhs_percent_of) {
+            lhs = Type::Decimal;
+        }
+        if lhs == Type::Decimal && rhs == Type::Float && int_percent(&expr.rhs, rhs_percent_of) {
+            rhs = Type::Decimal;
+        }
+        let numeric = lhs.is_numeric() && rhs.is_numeric() && mixes(lhs, rhs);
+        let arithmetic = (numeric && expr.op.is_arithmetic())
+            .then(|| self.arithmetic_type(expr.op, lhs, rhs))
+            .flatten();
+        self.ty = match expr.op {
+            InfixOp::Equal | InfixOp::NotEqual
+                if numeric || lhs == rhs && !lhs.is_matrix() || lhs.is_array() && rhs.is_array() =>
+            {
+                Some(Type::Bool)
+            }
+            // complex numbers are only equal or not
+            op if op.is_comparison() && numeric && lhs != Type::Complex && rhs != Type::Complex => {
+                Some(Type::Bool)
+            }
+            // on `bool`s these are logical operators that evaluate both sides
+            InfixOp::BitAnd | InfixOp::BitOr | InfixOp::BitXor
+                if lhs == rhs && matches!(lhs, Type::Int | Type::Bool) =>
+            {
+                Some(lhs)
+            }
+            InfixOp::ShiftLeft | InfixOp::ShiftRight if lhs == Type::Int && rhs == Type::Int => {
+                Some(Type::Int)
+            }
+            InfixOp::Plus if lhs == Type::Str && rhs == Type::Str => Some(Type::Str),
+            // matrices add element by element and scale by a number
+            InfixOp::Plus | InfixOp::Minus if lhs.is_matrix() && lhs == rhs => Some(lhs),
+            InfixOp::Multiply if lhs.is_matrix() && coerces(rhs, Type::Float) => Some(lhs),
+            InfixOp::Multiply if rhs.is_matrix() && coerces(lhs, Type::Float) => Some(rhs),
+            InfixOp::MatMul if lhs.is_matrix() && rhs.is_matrix() => self.product_type(lhs, rhs, expr.span),
+            _ if arithmetic.is_some() => arithmetic,
+            _ => {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "cannot apply `{}` to `{}` and `{}`",
+                        expr.op.symbol(),
+                        lhs,
+                        rhs
+                    ),
+                    expr.span,
+                ));
+                None
+            }
+        };
+    }
+
+    fn visit_cast_expr(&mut self, expr: &CastExpr) {
+        // every type but `str`, arrays, matrices and `complex` converts to
+        // every other explicitly; text goes through `to_str` and
+        // `parse_int`, and every number becomes a `complex`, which only `re`,
+        // `im` and `abs` take apart
+        let operand = self.type_of(&expr.expr);
+        let scalar = |ty: Type| ty != Type::Str && !ty.is_array() && !ty.is_matrix() && ty != Type::Complex;
+        let converts = |ty: Type| {
+            ty == expr.ty || scalar(ty) && scalar(expr.ty) || ty.is_numeric() && expr.ty == Type::Complex
+        };
+        if let Some(ty) = operand.filter(|&ty| !converts(ty)) {
+            self.diagnostics.push(Diagnostic::error(
+                format!("cannot convert `{}` to `{}` with `as`", ty, expr.ty),
+                expr.span,
+            ));
+            return;
+        }
+        if self.allow_type(expr.ty, expr.span) {
+            self.ty = operand.map(|_| expr.ty);
+        }
+    }
+
+    fn visit_convert_expr(&mut self, expr: &ConvertExpr) {
+        // quantities are floats, and whatever widens to one may be a
+        // quantity in turn
+        let operand = self.type_of(&expr.expr);
+        if let Some(ty) = operand.filter(|&ty| !coerces(ty, Type::Float)) {
+            self.diagnostics.push(Diagnostic::error(
+                format!("`in` needs an `int`, a `rat` or a `float`, found `{}`", ty),
+                expr.span,
+            ));
+            return;
+        }
+        self.ty = operand.map(|_| Type::Float);
+    }
+
+    fn visit_index_expr(&mut self, expr: &IndexExpr) {
+        let array = self.type_of(&expr.expr);
+        let index = self.type_of(&expr.index);
+        self.ty = array.and_then(|array| self.element_type(array, &expr.index, index, expr.expr.span()));
+    }
+}
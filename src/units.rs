@@ -0,0 +1,532 @@
+//! Units of measure. A quantity such as `9.81 m/s^2` is a `float` in the SI
+//! base units at run time, so `3 km` is `3000`; its dimension only exists
+//! while checking, where `DimensionChecker` follows it through every
+//! expression and rejects adding or comparing quantities that measure
+//! different things. `expr in unit` turns a quantity back into a plain
+//! number of `unit`s.
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt;
+
+use crate::ast::*;
+use crate::builtins::Builtin;
+use crate::diagnostic::Diagnostic;
+use crate::formatter::strip_parens;
+
+/// The base units, in the order `Dimension` keeps their powers.
+const BASE_UNITS: [&str; 7] = ["kg", "m", "s", "A", "K", "mol", "cd"];
+
+/// The powers of the SI base units a quantity is measured in; all zero
+/// for a plain number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dimension([i32; 7]);
+
+impl Dimension {
+    pub const NONE: Dimension = Dimension([0; 7]);
+
+    pub fn is_none(self) -> bool {
+        self == Dimension::NONE
+    }
+
+    /// The dimension of a product, or `None` if a power is out of range.
+    pub fn checked_mul(self, rhs: Dimension) -> Option<Dimension> {
+        self.zip(rhs, i32::checked_add)
+    }
+
+    pub fn checked_div(self, rhs: Dimension) -> Option<Dimension> {
+        self.zip(rhs, i32::checked_sub)
+    }
+
+    pub fn checked_pow(self, exp: i64) -> Option<Dimension> {
+        let exp = i32::try_from(exp).ok()?;
+        self.zip(self, |power, _| power.checked_mul(exp))
+    }
+
+    /// The dimension of a square root, if every power is even.
+    pub fn sqrt(self) -> Option<Dimension> {
+        self.zip(self, |power, _| (power % 2 == 0).then_some(power / 2))
+    }
+
+    fn zip(self, rhs: Dimension, f: impl Fn(i32, i32) -> Option<i32>) -> Option<Dimension> {
+        let mut powers = [0; 7];
+        for (i, power) in powers.iter_mut().enumerate() {
+            *power = f(self.0[i], rhs.0[i])?;
+        }
+        Some(Dimension(powers))
+    }
+}
+
+/// `kg*m/s^2`, or `1` for a plain number.
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the base units with a power of the sign of `sign`, as `s^2`
+        let factors = |sign: i32| -> Vec<String> {
+            BASE_UNITS
+                .iter()
+                .zip(self.0)
+                .filter(|&(_, power)| power * sign > 0)
+                .map(|(name, power)| match power * sign {
+                    1 => name.to_string(),
+                    power => format!("{}^{}", name, power),
+                })
+                .collect()
+        };
+        let (above, below) = (factors(1), factors(-1));
+        if self.is_none() {
+            write!(f, "1")
+        } else if above.is_empty() {
+            // `s^-1` rather than `1/s`
+            let powers: Vec<String> = BASE_UNITS
+                .iter()
+                .zip(self.0)
+                .filter(|&(_, power)| power < 0)
+                .map(|(name, power)| format!("{}^{}", name, power))
+                .collect();
+            write!(f, "{}", powers.join("*"))
+        } else {
+            write!(f, "{}", above.join("*"))?;
+            for unit in below {
+                write!(f, "/{}", unit)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// powers of kg, m, s, A, K, mol and cd
+const MASS: [i32; 7] = [1, 0, 0, 0, 0, 0, 0];
+const LENGTH: [i32; 7] = [0, 1, 0, 0, 0, 0, 0];
+const TIME: [i32; 7] = [0, 0, 1, 0, 0, 0, 0];
+const CURRENT: [i32; 7] = [0, 0, 0, 1, 0, 0, 0];
+const TEMPERATURE: [i32; 7] = [0, 0, 0, 0, 1, 0, 0];
+const AMOUNT: [i32; 7] = [0, 0, 0, 0, 0, 1, 0];
+const LUMINOSITY: [i32; 7] = [0, 0, 0, 0, 0, 0, 1];
+const PLAIN: [i32; 7] = [0; 7];
+const AREA: [i32; 7] = [0, 2, 0, 0, 0, 0, 0];
+const VOLUME: [i32; 7] = [0, 3, 0, 0, 0, 0, 0];
+const FREQUENCY: [i32; 7] = [0, 0, -1, 0, 0, 0, 0];
+const SPEED: [i32; 7] = [0, 1, -1, 0, 0, 0, 0];
+const FORCE: [i32; 7] = [1, 1, -2, 0, 0, 0, 0];
+const ENERGY: [i32; 7] = [1, 2, -2, 0, 0, 0, 0];
+const POWER: [i32; 7] = [1, 2, -3, 0, 0, 0, 0];
+const PRESSURE: [i32; 7] = [1, -1, -2, 0, 0, 0, 0];
+const CHARGE: [i32; 7] = [0, 0, 1, 1, 0, 0, 0];
+const VOLTAGE: [i32; 7] = [1, 2, -3, -1, 0, 0, 0];
+const RESISTANCE: [i32; 7] = [1, 2, -3, -2, 0, 0, 0];
+
+/// Every unit a quantity can be written in: its name, how many base units
+/// one of it is, and what it measures. Temperatures are only kelvin, since
+/// degrees Celsius and Fahrenheit do not start at zero.
+pub const UNITS: [(&str, f64, [i32; 7]); 57] = [
+    ("m", 1.0, LENGTH),
+    ("km", 1e3, LENGTH),
+    ("cm", 1e-2, LENGTH),
+    ("mm", 1e-3, LENGTH),
+    ("um", 1e-6, LENGTH),
+    ("nm", 1e-9, LENGTH),
+    ("inch", 0.0254, LENGTH),
+    ("ft", 0.3048, LENGTH),
+    ("yd", 0.9144, LENGTH),
+    ("mi", 1609.344, LENGTH),
+    ("nmi", 1852.0, LENGTH),
+    ("kg", 1.0, MASS),
+    ("g", 1e-3, MASS),
+    ("mg", 1e-6, MASS),
+    ("t", 1e3, MASS),
+    ("lb", 0.45359237, MASS),
+    ("oz", 0.028349523125, MASS),
+    ("s", 1.0, TIME),
+    ("ms", 1e-3, TIME),
+    ("us", 1e-6, TIME),
+    ("ns", 1e-9, TIME),
+    ("min", 60.0, TIME),
+    ("h", 3600.0, TIME),
+    ("day", 86400.0, TIME),
+    ("A", 1.0, CURRENT),
+    ("mA", 1e-3, CURRENT),
+    ("K", 1.0, TEMPERATURE),
+    ("mol", 1.0, AMOUNT),
+    ("cd", 1.0, LUMINOSITY),
+    ("rad", 1.0, PLAIN),
+    ("deg", PI / 180.0, PLAIN),
+    ("ha", 1e4, AREA),
+    ("L", 1e-3, VOLUME),
+    ("mL", 1e-6, VOLUME),
+    ("gal", 3.785411784e-3, VOLUME),
+    ("Hz", 1.0, FREQUENCY),
+    ("kHz", 1e3, FREQUENCY),
+    ("MHz", 1e6, FREQUENCY),
+    ("kn", 1852.0 / 3600.0, SPEED),
+    ("N", 1.0, FORCE),
+    ("kN", 1e3, FORCE),
+    ("lbf", 4.4482216152605, FORCE),
+    ("J", 1.0, ENERGY),
+    ("kJ", 1e3, ENERGY),
+    ("cal", 4.184, ENERGY),
+    ("kcal", 4184.0, ENERGY),
+    ("Wh", 3600.0, ENERGY),
+    ("kWh", 3.6e6, ENERGY),
+    ("W", 1.0, POWER),
+    ("kW", 1e3, POWER),
+    ("Pa", 1.0, PRESSURE),
+    ("kPa", 1e3, PRESSURE),
+    ("bar", 1e5, PRESSURE),
+    ("psi", 6894.757293168361, PRESSURE),
+    ("C", 1.0, CHARGE),
+    ("V", 1.0, VOLTAGE),
+    ("ohm", 1.0, RESISTANCE),
+];
+
+/// How many base units one `name` is, and what it measures.
+pub fn lookup(name: &str) -> Option<(f64, Dimension)> {
+    UNITS
+        .iter()
+        .find(|&&(unit, ..)| unit == name)
+        .map(|&(_, scale, powers)| (scale, Dimension(powers)))
+}
+
+impl Unit {
+    /// How many base units one of this unit is. Every name must be known,
+    /// as the parser and `DimensionChecker` make sure.
+    pub fn scale(&self) -> f64 {
+        self.factors.iter().fold(1.0, |scale, factor| {
+            scale * lookup(&factor.name).unwrap().0.powi(factor.power)
+        })
+    }
+}
+
+/// Checks the dimensions of a program that has passed type checking.
+pub fn check(tu: &TransUnit) -> Vec<Diagnostic> {
+    DimensionChecker::new().check(tu)
+}
+
+/// Infers what every expression measures and reports operations on
+/// quantities that do not fit together: `+`, `-`, `%` and the comparisons
+/// need operands of one dimension, functions but a few want plain numbers,
+/// and a variable keeps the dimension it was declared with.
+///
+/// Like `typeck::TypeChecker`, the checker can be fed one input at a time.
+#[derive(Clone, Default)]
+pub struct DimensionChecker {
+    vars: HashMap<DeclId, Dimension>,
+    mem: Dimension,
+    // dimension of the expression visited last, `None` once it had an
+    // error, so that one mistake is reported once
+    dim: Option<Dimension>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DimensionChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check(&mut self, tu: &TransUnit) -> Vec<Diagnostic> {
+        self.visit_trans_unit(tu);
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn dimension_of(&mut self, expr: &Expr) -> Option<Dimension> {
+        self.visit_expr(expr);
+        self.dim.take()
+    }
+
+    fn unit_dimension(&mut self, unit: &Unit) -> Option<Dimension> {
+        let mut dim = Dimension::NONE;
+        for factor in &unit.factors {
+            let Some((_, base)) = lookup(&factor.name) else {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("unknown unit `{}`", factor.name),
+                    factor.span,
+                ));
+                return None;
+            };
+            dim = base
+                .checked_pow(factor.power.into())
+                .and_then(|base| dim.checked_mul(base))
+                .or_else(|| self.out_of_range(unit.span))?;
+        }
+        Some(dim)
+    }
+
+    fn out_of_range(&mut self, span: Span) -> Option<Dimension> {
+        self.diagnostics.push(Diagnostic::error(
+            "the powers of the units of this quantity are out of range",
+            span,
+        ));
+        None
+    }
+
+    // an operand that must be a plain number, like the argument of `sin`
+    fn expect_plain(&mut self, what: &str, dim: Option<Dimension>, span: Span) {
+        if let Some(dim) = dim.filter(|dim| !dim.is_none()) {
+            self.diagnostics.push(Diagnostic::error(
+                format!("{} needs a plain number, found {}", what, describe(dim)),
+                span,
+            ));
+        }
+    }
+
+    // operands that must measure the same thing, like those of `+`
+    fn expect_same(&mut self, what: &str, lhs: Dimension, rhs: Dimension, span: Span) -> Option<Dimension> {
+        if lhs != rhs {
+            self.diagnostics.push(Diagnostic::error(
+                format!("cannot apply {} to {} and {}", what, describe(lhs), describe(rhs)),
+                span,
+            ));
+            return None;
+        }
+        Some(lhs)
+    }
+
+    // elements of an array or matrix all measure what the first one does
+    fn elements_dimension<'a>(&mut self, elements: impl Iterator<Item = &'a Expr>, kind: &str) -> Option<Dimension> {
+        let mut first = None;
+        let mut valid = true;
+        for element in elements {
+            let Some(dim) = self.dimension_of(element) else {
+                valid = false;
+                continue;
+            };
+            match first {
+                None => first = Some(dim),
+                Some(first) if first != dim => {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("cannot mix {} and {} in one {}", describe(first), describe(dim), kind),
+                        element.span(),
+                    ));
+                    valid = false;
+                }
+                Some(_) => {}
+            }
+        }
+        valid.then(|| first.unwrap_or_default())
+    }
+
+    fn call_dimension(&mut self, call: &CallExpr) -> Option<Dimension> {
+        let args: Vec<Option<Dimension>> = call.args.iter().map(|arg| self.dimension_of(arg)).collect();
+        let what = format!("`{}`", call.callee.name);
+        let Some(builtin) = call.builtin else {
+            for (arg, &dim) in call.args.iter().zip(&args) {
+                self.expect_plain(&what, dim, arg.span());
+            }
+            return Some(Dimension::NONE);
+        };
+        match builtin {
+            // the result measures what the argument does
+            Builtin::Abs
+            | Builtin::Floor
+            | Builtin::Ceil
+            | Builtin::Round
+            | Builtin::Re
+            | Builtin::Im
+            | Builtin::Conj
+            | Builtin::Transpose => args[0],
+            Builtin::Min | Builtin::Max | Builtin::Mod => {
+                self.expect_same(&what, args[0]?, args[1]?, call.span)
+            }
+            Builtin::Atan2 => self
+                .expect_same(&what, args[0]?, args[1]?, call.span)
+                .map(|_| Dimension::NONE),
+            Builtin::Sqrt => {
+                let dim = args[0]?;
+                let root = dim.sqrt();
+                if root.is_none() {
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("`sqrt` needs even powers of units, found {}", describe(dim)),
+                        call.span,
+                    ));
+                }
+                root
+            }
+            // only the sign or the angle are left, or no number at all
+            Builtin::Sign | Builtin::Arg | Builtin::Len | Builtin::ToStr => Some(Dimension::NONE),
+            _ => {
+                for (arg, &dim) in call.args.iter().zip(&args) {
+                    self.expect_plain(&what, dim, arg.span());
+                }
+                Some(Dimension::NONE)
+            }
+        }
+    }
+
+    // `x ** n` with a quantity `x` needs `n` to be a whole number known
+    // while checking
+    fn power_dimension(&mut self, expr: &InfixExpr, lhs: Dimension) -> Option<Dimension> {
+        if lhs.is_none() {
+            return Some(lhs);
+        }
+        let Some(exp) = int_literal(&expr.rhs) else {
+            self.diagnostics.push(Diagnostic::error(
+                format!("raising {} to a power needs an integer literal exponent", describe(lhs)),
+                expr.rhs.span(),
+            ));
+            return None;
+        };
+        lhs.checked_pow(exp).or_else(|| self.out_of_range(expr.span))
+    }
+}
+
+/// `a plain number` or `a quantity in `m/s``, for messages.
+fn describe(dim: Dimension) -> String {
+    if dim.is_none() {
+        "a plain number".to_string()
+    } else {
+        format!("a quantity in `{}`", dim)
+    }
+}
+
+// `2`, `-2` or `(2)`
+fn int_literal(expr: &Expr) -> Option<i64> {
+    match strip_parens(expr) {
+        Expr::Primary(primary) => match primary.as_ref() {
+            PrimaryExpr::Int(i, ..) => Some(*i),
+            _ => None,
+        },
+        Expr::Prefix(prefix) if prefix.op == PrefixOp::Minus => int_literal(&prefix.expr)?.checked_neg(),
+        _ => None,
+    }
+}
+
+impl Visitor for DimensionChecker {
+    fn visit_expr_stmt(&mut self, stmt: &ExprStmt) {
+        if let Some(dim) = self.dimension_of(&stmt.expr) {
+            self.mem = dim;
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) {
+        for arg in &stmt.args {
+            self.dimension_of(arg);
+        }
+    }
+
+    fn visit_let_stmt(&mut self, stmt: &LetStmt) {
+        if let Some(dim) = self.dimension_of(&stmt.expr) {
+            self.vars.insert(stmt.name.decl.unwrap(), dim);
+        }
+    }
+
+    fn visit_assign_stmt(&mut self, stmt: &AssignStmt) {
+        if let Some(index) = &stmt.index {
+            let dim = self.dimension_of(index);
+            self.expect_plain("an index", dim, index.span());
+        }
+        let dim = self.dimension_of(&stmt.expr);
+        let var = self.vars.get(&stmt.name.decl.unwrap()).copied();
+        if let (Some(var), Some(dim)) = (var, dim) {
+            if var != dim {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "cannot assign {} to `{}`, which holds {}",
+                        describe(dim),
+                        stmt.name.name,
+                        describe(var)
+                    ),
+                    stmt.expr.span(),
+                ));
+            }
+        }
+    }
+
+    fn visit_primary_expr(&mut self, expr: &PrimaryExpr) {
+        self.dim = match expr {
+            PrimaryExpr::Quantity(_, unit, _) => self.unit_dimension(unit),
+            PrimaryExpr::Mem(_) => Some(self.mem),
+            PrimaryExpr::Ident(ident) => self.vars.get(&ident.decl.unwrap()).copied(),
+            PrimaryExpr::Call(call) => self.call_dimension(call),
+            PrimaryExpr::Array(elements, _) => self.elements_dimension(elements.iter(), "array"),
+            PrimaryExpr::Repeat(value, count, _) => {
+                let dim = self.dimension_of(value);
+                let count_dim = self.dimension_of(count);
+                self.expect_plain("the length of an array", count_dim, count.span());
+                dim
+            }
+            PrimaryExpr::Matrix(rows, _) => {
+                self.elements_dimension(rows.iter().flat_map(|row| row.elements.iter()), "matrix")
+            }
+            PrimaryExpr::Expr(expr, _) => self.dimension_of(expr),
+            _ => Some(Dimension::NONE),
+        };
+    }
+
+    fn visit_prefix_expr(&mut self, expr: &PrefixExpr) {
+        let dim = self.dimension_of(&expr.expr);
+        self.dim = match expr.op {
+            PrefixOp::Plus | PrefixOp::Minus => dim,
+            PrefixOp::Not | PrefixOp::BitNot => {
+                self.expect_plain(&format!("`{}`", expr.op.symbol()), dim, expr.span);
+                Some(Dimension::NONE)
+            }
+        };
+    }
+
+    fn visit_postfix_expr(&mut self, expr: &PostfixExpr) {
+        let dim = self.dimension_of(&expr.expr);
+        self.dim = match expr.op {
+            PostfixOp::Percent => dim,
+            PostfixOp::Factorial => {
+                self.expect_plain("`!`", dim, expr.span);
+                Some(Dimension::NONE)
+            }
+        };
+    }
+
+    fn visit_infix_expr(&mut self, expr: &InfixExpr) {
+        let lhs = self.dimension_of(&expr.lhs);
+        let rhs = self.dimension_of(&expr.rhs);
+        let (Some(lhs), Some(rhs)) = (lhs, rhs) else {
+            return;
+        };
+        let what = format!("`{}`", expr.op.symbol());
+        self.dim = match expr.op {
+            InfixOp::Plus | InfixOp::Minus | InfixOp::Remainder => self.expect_same(&what, lhs, rhs, expr.span),
+            op if op.is_comparison() => self
+                .expect_same(&what, lhs, rhs, expr.span)
+                .map(|_| Dimension::NONE),
+            InfixOp::Multiply | InfixOp::MatMul => lhs.checked_mul(rhs).or_else(|| self.out_of_range(expr.span)),
+            InfixOp::Divide => lhs.checked_div(rhs).or_else(|| self.out_of_range(expr.span)),
+            InfixOp::Power => {
+                self.expect_plain("an exponent", Some(rhs), expr.rhs.span());
+                self.power_dimension(expr, lhs)
+            }
+            // bitwise operators and shifts
+            _ => {
+                self.expect_plain(&what, Some(lhs), expr.lhs.span());
+                self.expect_plain(&what, Some(rhs), expr.rhs.span());
+                Some(Dimension::NONE)
+            }
+        };
+    }
+
+    fn visit_cast_expr(&mut self, expr: &CastExpr) {
+        // `as` changes the type of a quantity but not what it measures
+        self.dim = self.dimension_of(&expr.expr);
+    }
+
+    fn visit_convert_expr(&mut self, expr: &ConvertExpr) {
+        let dim = self.dimension_of(&expr.expr);
+        let unit = self.unit_dimension(&expr.unit);
+        if let (Some(dim), Some(unit)) = (dim, unit) {
+            if dim != unit {
+                let measures = match unit.is_none() {
+                    true => "is for plain numbers".to_string(),
+                    false => format!("measures `{}`", unit),
+                };
+                self.diagnostics.push(Diagnostic::error(
+                    format!("cannot convert {} to `{}`, which {}", describe(dim), expr.unit, measures),
+                    expr.span,
+                ));
+            }
+        }
+        self.dim = Some(Dimension::NONE);
+    }
+
+    fn visit_index_expr(&mut self, expr: &IndexExpr) {
+        let dim = self.dimension_of(&expr.expr);
+        let index = self.dimension_of(&expr.index);
+        self.expect_plain("an index", index, expr.index.span());
+        self.dim = dim;
+    }
+}
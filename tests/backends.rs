@@ -0,0 +1,65 @@
+// Both backends print the same text for the same program.
+
+use std::process::{Command, Output};
+
+fn run(mode: &str, args: &[&str], src: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_calcc"))
+        .args(["run", &format!("--mode={}", mode)])
+        .args(args)
+        .args(["-e", src])
+        .output()
+        .unwrap()
+}
+
+fn assert_prints(src: &str, expected: &str) {
+    assert_prints_with(&[], src, expected);
+}
+
+fn assert_prints_with(args: &[&str], src: &str, expected: &str) {
+    for mode in ["interpret", "llvm"] {
+        let output = run(mode, args, src);
+        assert!(output.status.success(), "{} in {} mode:\n{:?}", src, mode, output);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected, "{} in {} mode", src, mode);
+    }
+}
+
+#[test]
+fn nan_prints_without_a_sign() {
+    assert_prints("let z = 0.0; print z / z;", "nan\n");
+    assert_prints("let z = 0.0; print -(z / z);", "nan\n");
+    assert_prints("print 5 % 0.0;", "nan\n");
+    assert_prints("let x = 0; print x / 0.0;", "nan\n");
+    assert_prints("let z = 0.0; println(\"{} {:.2} {:>5}\", z / z, -(z / z), z / z);", "nan nan   nan\n");
+    assert_prints("let z = 0.0; print [[z / z, -(z / z)]];", "[nan  nan]\n");
+}
+
+#[test]
+fn complex_nan_parts_print_without_a_sign() {
+    assert_prints("print (1 + 2i) / 0;", "nan+nani\n");
+    assert_prints("print -((1 + 2i) / 0);", "nan+nani\n");
+    assert_prints("println(\"{:p} {:.1}\", -((1 + 2i) / 0), -((1 + 2i) / 0));", "nan∠nan nan+nani\n");
+}
+
+#[test]
+fn complex_division_neither_overflows_nor_underflows() {
+    assert_prints("print (1 + 1i) / (1e-200 + 1e-200i);", "1e+200+0i\n");
+    assert_prints("print (1e308 + 1e308i) / (1e308 + 1e308i);", "1+0i\n");
+    assert_prints("print (1e-308 + 2e-308i) / (2e-308i);", "1-0.5i\n");
+    assert_prints("let z = 3 + 4i; print z / (1 - 2i);", "-1+2i\n");
+}
+
+#[test]
+fn float_to_int_follows_the_overflow_mode() {
+    let src = "let z = 0.0; print 1e30 as int; print -1.9e19 as int; print (z / z) as int; print -2.9 as int;";
+    assert_prints_with(
+        &["--overflow=saturate"],
+        src,
+        "9223372036854775807\n-9223372036854775808\n0\n-2\n",
+    );
+    assert_prints_with(&["--overflow=wrap"], src, "5076964154930102272\n-553255926290448384\n0\n-2\n");
+    assert_prints_with(
+        &["--bigint", "--overflow=wrap"],
+        src,
+        "1000000000000000019884624838656\n-19000000000000000000\n0\n-2\n",
+    );
+}
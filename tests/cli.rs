@@ -0,0 +1,75 @@
+// The command line around the compiler: files in and out.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn calcc() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_calcc"))
+}
+
+// a fresh directory for one test
+fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn build_does_not_overwrite_its_input() {
+    let dir = dir("build_does_not_overwrite_its_input");
+    let src = dir.join("prog");
+    fs::write(&src, "print 1;\n").unwrap();
+    // without `-o`, the executable goes to the current directory
+    let output = calcc().current_dir(&dir).args(["build", "prog"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("refusing to overwrite the input"));
+    let output = calcc().arg("build").arg(&src).arg("-o").arg(dir.join(".").join("prog")).output().unwrap();
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert_eq!(fs::read_to_string(&src).unwrap(), "print 1;\n");
+}
+
+fn load_ast(name: &str, json: &str) -> std::process::Output {
+    let path = dir(name).join("ast.json");
+    fs::write(&path, json).unwrap();
+    calcc().args(["run", "--load-ast"]).arg(&path).output().unwrap()
+}
+
+#[test]
+fn load_ast_rejects_trees_the_parser_cannot_produce() {
+    let output = load_ast(
+        "load_ast_rejects_trees_the_parser_cannot_produce",
+        r#"{"block":{"stmts":[{"PrintStmt":{"newline":false,"args":[],"span":{"start":0,"end":8}}}]}}"#,
+    );
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error: `print` takes one value"));
+}
+
+#[test]
+fn load_ast_reports_errors_without_source() {
+    let output = load_ast(
+        "load_ast_reports_errors_without_source",
+        r#"{"block":{"stmts":[{"PrintStmt":{"newline":false,"args":[{"Infix":{"lhs":{"Primary":{"Int":[1,"Decimal",{"start":6,"end":7}]}},"op":"Plus","rhs":{"Primary":{"Bool":[true,{"start":10,"end":14}]}},"span":{"start":6,"end":14}}}],"span":{"start":0,"end":15}}}]}}"#,
+    );
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "error: cannot apply `+` to `int` and `bool`\n");
+}
+
+#[test]
+fn emit_ast_dumps_the_tree_before_any_checks() {
+    let emit = |args: &[&str]| calcc().arg("emit").args(args).output().unwrap();
+    let output = emit(&["--emit=ast", "-e", "print pi * 2;"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "(trans_unit\n  (print (* pi 2)))\n");
+    let output = emit(&["--emit=ast", "-D", "unused_mem", "-e", "print undefined_var;"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "(trans_unit\n  (print undefined_var))\n");
+    let json = dir("emit_ast_dumps_the_tree_before_any_checks").join("ast.json");
+    let output = emit(&["--emit=ast-json", "-e", "print x + true;", "-o", json.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = emit(&["--emit=ast", "--load-ast", json.to_str().unwrap()]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "(trans_unit\n  (print (+ x true)))\n");
+    // syntax errors still stop it
+    let output = emit(&["--emit=ast", "-e", "print (;"]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+}
@@ -0,0 +1,84 @@
+// Every program in tests/examples prints what the `.out` file next to it
+// holds and exits with the same status in both execution modes, and
+// formatting it once is enough. A first line `// args: ...` passes options
+// to `calcc run`, and a program whose first line starts with `// trap` is
+// expected to stop with a runtime error.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn examples() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/examples");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "calc"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no examples in {}", dir.display());
+    paths
+}
+
+fn run(mode: &str, path: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_calcc"))
+        .args(["run", &format!("--mode={}", mode)])
+        .args(args)
+        .arg(path)
+        .output()
+        .unwrap()
+}
+
+// `calcc fmt` of stdin
+fn fmt(src: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_calcc"))
+        .arg("fmt")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(src.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn examples_behave_the_same_in_both_modes() {
+    for path in &examples() {
+        let src = fs::read_to_string(path).unwrap();
+        let first = src.lines().next().unwrap_or("");
+        let args: Vec<&str> = first.strip_prefix("// args:").map_or(vec![], |args| args.split_whitespace().collect());
+        let expected = if first.starts_with("// trap") { 3 } else { 0 };
+        let interpreted = run("interpret", path, &args);
+        let compiled = run("llvm", path, &args);
+        let name = path.file_name().unwrap().to_string_lossy();
+        assert_eq!(interpreted.status.code(), Some(expected), "{} interpreted:\n{:?}", name, interpreted);
+        assert_eq!(compiled.status.code(), Some(expected), "{} compiled:\n{:?}", name, compiled);
+        let golden = fs::read(path.with_extension("out")).unwrap_or_else(|err| panic!("{}.out: {}", name, err));
+        assert_eq!(
+            String::from_utf8_lossy(&interpreted.stdout),
+            String::from_utf8_lossy(&golden),
+            "{} interpreted",
+            name
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&compiled.stdout),
+            String::from_utf8_lossy(&golden),
+            "{} compiled",
+            name
+        );
+        assert_eq!(interpreted.stdout, golden, "{} interpreted", name);
+        assert_eq!(compiled.stdout, golden, "{} compiled", name);
+    }
+}
+
+#[test]
+fn formatting_examples_is_idempotent() {
+    for path in &examples() {
+        let src = fs::read_to_string(path).unwrap();
+        let once = fmt(&src);
+        assert_eq!(fmt(&once), once, "{}", path.display());
+    }
+}
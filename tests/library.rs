@@ -0,0 +1,120 @@
+// The library as a program embedding it uses it.
+
+use calcc::ast::{self, Ident, PrimaryExpr, Visitor, VisitorMut};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use calcc::{Engine, Error, Numbers, Options, Program, Trap, Value};
+
+#[test]
+fn running_a_program_that_calls_a_declared_function_is_an_error() {
+    let mut options = Options::new();
+    options.declare_function("clamp(int, int, int) -> int").unwrap();
+    let src = "print 1;\nprint clamp(5, 0, 3);\n";
+    let program = Program::compile(src, options).unwrap();
+    let mut printed = String::new();
+    let err = program.run(&mut |text: &str| printed.push_str(text)).unwrap_err();
+    assert_eq!(err.trap, Trap::UndefinedFunction);
+    assert_eq!(printed, "1\n");
+    assert_eq!(err.render(src), "error: host function without a definition\n --> 2:7");
+}
+
+// doubles every integer literal
+struct Doubler;
+
+impl VisitorMut for Doubler {
+    fn visit_primary_expr_mut(&mut self, expr: &mut PrimaryExpr) {
+        if let PrimaryExpr::Int(value, ..) = expr {
+            *value *= 2;
+        }
+        ast::walk_primary_expr_mut(self, expr);
+    }
+}
+
+// the names a program uses, in order
+struct Names(Vec<String>);
+
+impl Visitor for Names {
+    fn visit_ident(&mut self, ident: &Ident) {
+        self.0.push(ident.name.clone());
+    }
+}
+
+#[test]
+fn a_parsed_program_can_be_walked_rewritten_and_run() {
+    let mut tu = calcc::parse("let x = 3;\nprint x + 1;\n").unwrap();
+    let mut names = Names(vec![]);
+    names.visit_trans_unit(&tu);
+    assert_eq!(names.0, ["x", "x"]);
+    Doubler.visit_trans_unit_mut(&mut tu);
+    let program = Program::from_unit(tu, Options::new()).unwrap();
+    let mut printed = String::new();
+    program.run(&mut |text: &str| printed.push_str(text)).unwrap();
+    assert_eq!(printed, "8\n");
+}
+
+#[test]
+fn a_rewritten_program_is_checked_again() {
+    let mut tu = calcc::parse("let x = 3;\nprint x;\n").unwrap();
+    // renames the uses of `x` but not its declaration
+    struct Rename;
+    impl VisitorMut for Rename {
+        fn visit_primary_expr_mut(&mut self, expr: &mut PrimaryExpr) {
+            if let PrimaryExpr::Ident(ident) = expr {
+                ident.name = "y".to_string();
+            }
+        }
+    }
+    Rename.visit_trans_unit_mut(&mut tu);
+    let err = Program::from_unit(tu, Options::new()).unwrap_err();
+    assert!(err.to_string().contains("`y`"), "{}", err);
+}
+
+#[test]
+fn an_engine_can_run_on_another_thread() {
+    let printed = Arc::new(Mutex::new(String::new()));
+    let mut engine = Engine::new();
+    let sink = Arc::clone(&printed);
+    engine.set_output(move |text: &str| sink.lock().unwrap().push_str(text));
+    let scale = 10;
+    engine.register_fn("scale", move |x: i64| x * scale);
+    let value = thread::spawn(move || engine.eval("print scale(4); scale(5);").unwrap()).join().unwrap();
+    assert!(matches!(value, Value::Int(50)), "{:?}", value);
+    assert_eq!(*printed.lock().unwrap(), "40\n");
+}
+
+#[test]
+fn calling_a_registered_function_with_the_wrong_number_of_arguments_is_an_error() {
+    let mut engine = Engine::new();
+    engine.register_fn("clamp", |x: i64, lo: i64, hi: i64| x.max(lo).min(hi));
+    let err = engine.eval("clamp(5, 0);").unwrap_err();
+    let Error::Compile(diagnostics) = &err else { panic!("{:?}", err) };
+    assert!(diagnostics.to_string().contains("`clamp` takes 3 arguments but 2 were supplied"), "{}", err);
+    assert!(matches!(engine.eval("clamp(5, 0, 3);").unwrap(), Value::Int(3)));
+}
+
+#[test]
+fn int_arguments_widen_to_float_parameters() {
+    let mut engine = Engine::new();
+    engine.register_fn("half", |x: f64| x / 2.0);
+    let value = engine.eval("half(3);").unwrap();
+    assert!(matches!(value, Value::Float(f) if f == 1.5), "{:?}", value);
+}
+
+#[test]
+fn a_big_integer_overflows_an_i64_parameter() {
+    let mut engine = Engine::new();
+    engine.set_numbers(Numbers::Big).register_fn("twice", |x: i64| x.wrapping_mul(2));
+    assert!(matches!(engine.eval("twice(2 ** 40);").unwrap(), Value::Int(2199023255552)));
+    let err = engine.eval("twice(2 ** 70);").unwrap_err();
+    let Error::Runtime(err) = &err else { panic!("{:?}", err) };
+    assert_eq!(err.trap, Trap::Overflow);
+}
+
+#[test]
+fn a_registered_function_hides_the_builtin_of_the_same_name() {
+    let mut engine = Engine::new();
+    assert!(matches!(engine.eval("abs(-2);").unwrap(), Value::Int(2)));
+    engine.register_fn("abs", |x: i64| x + 100);
+    assert!(matches!(engine.eval("abs(-2);").unwrap(), Value::Int(98)));
+}
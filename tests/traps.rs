@@ -0,0 +1,86 @@
+// A trap ends a program with status 3 and the same message, whichever
+// backend runs it.
+
+use std::process::{Command, Output};
+
+fn run(mode: &str, src: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_calcc"))
+        .args(["run", &format!("--mode={}", mode), "-e", src])
+        .output()
+        .unwrap()
+}
+
+// the message and location of the error, without warnings before it
+fn error(output: &Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let start = stderr.find("error:").unwrap_or_else(|| panic!("no error in:\n{}", stderr));
+    stderr[start..].to_string()
+}
+
+fn assert_trap(src: &str, message: &str) {
+    for mode in ["interpret", "llvm"] {
+        let output = run(mode, src);
+        assert_eq!(output.status.code(), Some(3), "{} in {} mode:\n{:?}", src, mode, output);
+        assert!(error(&output).starts_with(message), "{} in {} mode:\n{:?}", src, mode, output);
+    }
+    assert_eq!(error(&run("interpret", src)), error(&run("llvm", src)), "{}", src);
+}
+
+#[test]
+fn trap_under_not() {
+    assert_trap("let x = 0; print !(1/x > 0);", "error: division by zero");
+    assert_trap("print !(1/0 == 0);", "error: division by zero");
+}
+
+#[test]
+fn trap_in_repeat_length_with_type() {
+    assert_trap("let a: [int] = [0; -1];", "error: array length out of range");
+}
+
+#[test]
+fn trap_in_substr_with_type() {
+    assert_trap("let s: str = substr(\"a\", 5, 1);", "error: index out of bounds");
+    assert_trap("let s = \"\"; s = substr(\"a\", 5, 1);", "error: index out of bounds");
+}
+
+#[test]
+fn trap_in_singular_inverse() {
+    assert_trap("let m: [[float; 2]; 2] = inv([[1, 2], [2, 4]]);", "error: singular matrix");
+    assert_trap("print inv([[0.0]]);", "error: singular matrix");
+}
+
+#[test]
+fn every_trap_kind() {
+    let cases = [
+        ("let x = 0; print 1 / x;", "error: division by zero"),
+        ("let x = 9223372036854775807; print x + 1;", "error: integer overflow"),
+        ("let x = 1e30; print x as int;", "error: integer overflow"),
+        ("let s = 64; print 1 << s;", "error: shift amount out of range"),
+        ("let n = -1; print n!;", "error: factorial of a negative number"),
+        ("let r = 9223372036854775807r; print r * 2r;", "error: rational overflow"),
+        ("let d = 99999999999999999.0; print d as dec;", "error: decimal overflow"),
+        ("let a = [1, 2]; let i = 2; print a[i];", "error: index out of bounds"),
+        ("print parse_int(\"x\");", "error: invalid integer"),
+        ("let n = -1; print len([0; n]);", "error: array length out of range"),
+        ("print det(inv([[0.0]]));", "error: singular matrix"),
+    ];
+    for (src, message) in cases {
+        assert_trap(src, message);
+    }
+}
+
+#[test]
+fn trap_skips_the_rest_of_the_statement() {
+    assert_trap("let x = 0; let y: float = -(1 / x) + sqrt(2.0);", "error: division by zero");
+    assert_trap("let x = 0; print(\"{} {}\", 1 / x, parse_int(\"x\"));", "error: division by zero");
+    assert_trap("let s: str = substr(\"a\", 5, 1) + \"b\";", "error: index out of bounds");
+    assert_trap("let n = -1; let b = !(n! > 0);", "error: factorial of a negative number");
+}
+
+#[test]
+fn trap_in_float_to_int() {
+    assert_trap("print 9223372036854775808.0 as int;", "error: integer overflow");
+    assert_trap("let z = 0.0; print (z / z) as int;", "error: integer overflow");
+    assert_trap("let z = 0.0; print (-1 / z) as int;", "error: integer overflow");
+    assert_trap("let x = 9.3e18; print -x as int;", "error: integer overflow");
+}